@@ -0,0 +1,76 @@
+import json
+import subprocess
+import time
+
+import pytest
+import requests
+from config import BASE_URL
+
+MAX_BODY_SIZE = 1024
+TOO_LARGE_MESSAGE = f"Request body is larger than {MAX_BODY_SIZE} bytes"
+
+
+@pytest.fixture(scope="module")
+def server_with_body_limit():
+    proc = subprocess.Popen(
+        ["cargo", "run", "--", "--max-body-size", str(MAX_BODY_SIZE)]
+    )
+
+    time.sleep(1)
+
+    yield proc
+
+    proc.terminate()
+    proc.wait()
+
+
+def test_body_over_limit_is_rejected(server_with_body_limit):
+    columns = [{"name": f"col{i}", "type": "INT64"} for i in range(100)]
+    data = {"name": "test_body_over_limit_is_rejected", "columns": columns}
+    resp = requests.put(f"{BASE_URL}/table", json=data)
+    assert resp.status_code == 413
+    assert resp.json()["message"] == TOO_LARGE_MESSAGE
+
+    resp = requests.get(f"{BASE_URL}/tables")
+    assert resp.status_code == 200
+    assert "test_body_over_limit_is_rejected" not in [
+        table["name"] for table in resp.json()
+    ]
+
+
+def test_body_under_limit_is_accepted(server_with_body_limit):
+    data = {
+        "name": "test_body_under_limit_is_accepted",
+        "columns": [{"name": "col1", "type": "INT64"}],
+    }
+    resp = requests.put(f"{BASE_URL}/table", json=data)
+    assert resp.status_code == 200
+
+
+def test_chunked_body_over_limit_is_rejected(server_with_body_limit):
+    columns = [{"name": f"col{i}", "type": "INT64"} for i in range(100)]
+    data = {"name": "test_chunked_body_over_limit_is_rejected", "columns": columns}
+    body = json.dumps(data).encode()
+    # A generator body is sent chunked, without Content-Length.
+    chunks = (body[i : i + 100] for i in range(0, len(body), 100))
+    resp = requests.put(
+        f"{BASE_URL}/table",
+        data=chunks,
+        headers={"Content-Type": "application/json"},
+    )
+    assert resp.status_code == 413
+    assert resp.json()["message"] == TOO_LARGE_MESSAGE
+
+
+def test_chunked_body_under_limit_is_accepted(server_with_body_limit):
+    data = {
+        "name": "test_chunked_body_under_limit_is_accepted",
+        "columns": [{"name": "col1", "type": "INT64"}],
+    }
+    body = json.dumps(data).encode()
+    resp = requests.put(
+        f"{BASE_URL}/table",
+        data=iter([body]),
+        headers={"Content-Type": "application/json"},
+    )
+    assert resp.status_code == 200
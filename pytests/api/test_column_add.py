@@ -0,0 +1,103 @@
+import csv
+import os
+
+import requests
+from config import BASE_URL
+from utils import create_dummy_table, wait_for_final_status
+
+
+def _copy_rows(table_name, rows):
+    csv_path = os.path.join(os.getcwd(), "data", f"{table_name}.csv")
+    os.makedirs(os.path.dirname(csv_path), exist_ok=True)
+    with open(csv_path, "w", newline="") as f:
+        csv.writer(f).writerows(rows)
+
+    data = {
+        "queryDefinition": {
+            "sourceFilepath": csv_path,
+            "destinationTableName": table_name,
+        }
+    }
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert wait_for_final_status(resp.json()) == "COMPLETED"
+
+
+def _select_all_columns(table_name):
+    resp = requests.post(
+        f"{BASE_URL}/query", json={"queryDefinition": {"tableName": table_name}}
+    )
+    select_id = resp.json()
+    assert wait_for_final_status(select_id) == "COMPLETED"
+    return requests.get(f"{BASE_URL}/result/{select_id}").json()[0]["columns"]
+
+
+def test_add_column_with_default(server):
+    table_name = "test_add_column_with_default"
+    table_id, _ = create_dummy_table(table_name)
+    _copy_rows(table_name, [["1", "a"], ["2", "b"]])
+
+    column = {"name": "col3", "type": "INT64", "defaultValue": 7}
+    resp = requests.post(f"{BASE_URL}/table/{table_id}/column", json=column)
+    assert resp.status_code == 200
+
+    resp = requests.get(f"{BASE_URL}/table/{table_id}")
+    assert resp.json()["columns"][2] == column
+    assert _select_all_columns(table_name) == [[1, 2], ["a", "b"], [7, 7]]
+
+
+def test_add_column_without_default(server):
+    table_name = "test_add_column_without_default"
+    table_id, _ = create_dummy_table(table_name)
+    _copy_rows(table_name, [["1", "a"]])
+
+    resp = requests.post(
+        f"{BASE_URL}/table/{table_id}/column", json={"name": "col3", "type": "VARCHAR"}
+    )
+    assert resp.status_code == 200
+    assert _select_all_columns(table_name) == [[1], ["a"], [""]]
+
+
+def test_add_column_default_of_wrong_type(server):
+    table_id, _ = create_dummy_table("test_add_column_default_of_wrong_type")
+
+    column = {"name": "col3", "type": "INT64", "defaultValue": "seven"}
+    resp = requests.post(f"{BASE_URL}/table/{table_id}/column", json=column)
+    assert resp.status_code == 400
+    assert resp.json() == {
+        "message": "Default value doesn't match the type of the column",
+        "context": "col3",
+    }
+
+
+def test_add_existing_column(server):
+    table_id, _ = create_dummy_table("test_add_existing_column")
+
+    resp = requests.post(
+        f"{BASE_URL}/table/{table_id}/column", json={"name": "col1", "type": "INT64"}
+    )
+    assert resp.status_code == 400
+    assert resp.json() == {"message": "Column 'col1' already exists"}
+
+
+def test_add_column_to_missing_table(server):
+    resp = requests.post(
+        f"{BASE_URL}/table/test_add_column_to_missing_table/column",
+        json={"name": "col1", "type": "INT64"},
+    )
+    assert resp.status_code == 404
+
+
+
+def test_create_table_default_of_wrong_type(server):
+    data = {
+        "name": "test_create_table_default_of_wrong_type",
+        "columns": [{"name": "col1", "type": "VARCHAR", "defaultValue": 1}],
+    }
+    resp = requests.put(f"{BASE_URL}/table", json=data)
+    assert resp.status_code == 400
+    assert resp.json()["problems"] == [
+        {
+            "error": "Default value doesn't match the type of the column",
+            "context": "col1",
+        }
+    ]
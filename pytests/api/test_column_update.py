@@ -0,0 +1,72 @@
+import requests
+from config import BASE_URL
+from utils import create_dummy_table
+
+
+def test_rename_column(server):
+    table_id, _ = create_dummy_table("test_rename_column")
+
+    resp = requests.patch(
+        f"{BASE_URL}/table/{table_id}/column/col1", json={"newName": "id"}
+    )
+    assert resp.status_code == 200
+
+    resp = requests.get(f"{BASE_URL}/table/{table_id}")
+    assert resp.status_code == 200
+    assert [column["name"] for column in resp.json()["columns"]] == ["id", "col2"]
+
+
+def test_rename_column_to_existing_name(server):
+    table_id, _ = create_dummy_table("test_rename_column_to_existing_name")
+
+    resp = requests.patch(
+        f"{BASE_URL}/table/{table_id}/column/col1", json={"newName": "col2"}
+    )
+    assert resp.status_code == 400
+    assert resp.json() == {"message": "Column 'col2' already exists"}
+
+
+def test_rename_missing_column(server):
+    table_id, _ = create_dummy_table("test_rename_missing_column")
+
+    resp = requests.patch(
+        f"{BASE_URL}/table/{table_id}/column/missing", json={"newName": "id"}
+    )
+    assert resp.status_code == 400
+    assert resp.json() == {"message": "Column 'missing' not found"}
+
+
+def test_rename_column_of_missing_table(server):
+    resp = requests.patch(
+        f"{BASE_URL}/table/test_rename_column_of_missing_table/column/col1",
+        json={"newName": "id"},
+    )
+    assert resp.status_code == 404
+
+
+def test_change_column_type_to_varchar(server):
+    table_id, _ = create_dummy_table("test_change_column_type_to_varchar")
+
+    resp = requests.patch(
+        f"{BASE_URL}/table/{table_id}/column/col1", json={"newType": "VARCHAR"}
+    )
+    assert resp.status_code == 200
+
+    resp = requests.get(f"{BASE_URL}/table/{table_id}")
+    assert resp.status_code == 200
+    assert [column["type"] for column in resp.json()["columns"]] == [
+        "VARCHAR",
+        "VARCHAR",
+    ]
+
+
+def test_change_column_type_from_varchar_is_rejected(server):
+    table_id, _ = create_dummy_table("test_change_column_type_from_varchar_is_rejected")
+
+    resp = requests.patch(
+        f"{BASE_URL}/table/{table_id}/column/col2", json={"newType": "INT64"}
+    )
+    assert resp.status_code == 400
+    assert resp.json() == {
+        "message": "Column of type VARCHAR can't be converted to INT64"
+    }
@@ -0,0 +1,163 @@
+import csv
+import os
+import shutil
+import subprocess
+
+
+def test_convert_creates_missing_directories():
+    directory = os.path.join(
+        os.getcwd(), "data", "test_convert_creates_missing_directories"
+    )
+    shutil.rmtree(directory, ignore_errors=True)
+    os.makedirs(directory)
+
+    csv_path = os.path.join(directory, "input.csv")
+    with open(csv_path, "w", newline="") as f:
+        csv.writer(f).writerows([["1", "a"], ["2", "b"]])
+
+    isdb_path = os.path.join(directory, "missing", "nested", "output.isdb")
+    subprocess.run(
+        ["cargo", "run", "--", "convert", csv_path, isdb_path],
+        check=True,
+        capture_output=True,
+    )
+    assert os.path.getsize(isdb_path) > 0
+
+
+def test_export_reads_version_1_file():
+    # Written by the first file format version, must keep loading as the format evolves.
+    golden_path = os.path.join(os.path.dirname(__file__), "..", "golden", "v1.isdb")
+    directory = os.path.join(os.getcwd(), "data", "test_export_reads_version_1_file")
+    os.makedirs(directory, exist_ok=True)
+
+    csv_path = os.path.join(directory, "output.csv")
+    subprocess.run(
+        ["cargo", "run", "--", "export", golden_path, csv_path, "--has-headers"],
+        check=True,
+        capture_output=True,
+    )
+    with open(csv_path, newline="") as f:
+        assert list(csv.reader(f)) == [
+            ["id", "name"],
+            ["1", "a"],
+            ["2", "bc"],
+            ["3", ""],
+        ]
+
+
+def _convert(directory, rows, *flags):
+    csv_path = os.path.join(directory, "input.csv")
+    with open(csv_path, "w", newline="") as f:
+        csv.writer(f).writerows(rows)
+
+    isdb_path = os.path.join(directory, "output.isdb")
+    subprocess.run(
+        ["cargo", "run", "--", "convert", csv_path, isdb_path, *flags],
+        check=True,
+        capture_output=True,
+    )
+    return isdb_path
+
+
+def test_inspect_prints_schema():
+    directory = os.path.join(os.getcwd(), "data", "test_inspect_prints_schema")
+    os.makedirs(directory, exist_ok=True)
+    isdb_path = _convert(
+        directory, [["id", "name"], ["1", "a"], ["2", "b"]], "--has-headers"
+    )
+
+    result = subprocess.run(
+        ["cargo", "run", "--", "inspect", isdb_path],
+        check=True,
+        capture_output=True,
+        text=True,
+    )
+    lines = result.stdout.splitlines()
+    assert lines[0] == f"File: {isdb_path}"
+    assert "Rows: 2" in lines
+    assert "Columns: 2" in lines
+    columns = [line.split()[:3] for line in lines if line.startswith("  [")]
+    assert columns == [["[0]", "id", "INT64"], ["[1]", "name", "VARCHAR"]]
+
+
+def test_convert_round_trips_through_export():
+    directory = os.path.join(os.getcwd(), "data", "test_convert_round_trips")
+    os.makedirs(directory, exist_ok=True)
+    rows = [["id", "name"], ["1", "a"], ["-2", ""], ["3", "zaż"]]
+    isdb_path = _convert(directory, rows, "--has-headers", "--no-compression")
+
+    csv_path = os.path.join(directory, "exported.csv")
+    subprocess.run(
+        ["cargo", "run", "--", "export", isdb_path, csv_path, "--has-headers"],
+        check=True,
+        capture_output=True,
+    )
+    with open(csv_path, newline="") as f:
+        assert list(csv.reader(f)) == rows
+
+
+def test_export_streams_large_compressed_file():
+    directory = os.path.join(os.getcwd(), "data", "test_export_streams_large_file")
+    os.makedirs(directory, exist_ok=True)
+    rows = [
+        [str(i * 7919 % 100003 - 50000), f"name;{i}", f'"{i % 13}"']
+        for i in range(50000)
+    ]
+    isdb_path = _convert(directory, rows)
+
+    csv_path = os.path.join(directory, "exported.csv")
+    subprocess.run(
+        ["cargo", "run", "--", "export", isdb_path, csv_path, "--delimiter", ";"],
+        check=True,
+        capture_output=True,
+    )
+    with open(csv_path, newline="") as f:
+        assert list(csv.reader(f, delimiter=";")) == rows
+
+
+def test_convert_with_lz4_frame_records_the_codec():
+    directory = os.path.join(os.getcwd(), "data", "test_convert_with_lz4_frame")
+    os.makedirs(directory, exist_ok=True)
+    rows = [["id", "name"], ["1", "a"], ["-2", ""], ["3", "zaż"]]
+    isdb_path = _convert(directory, rows, "--has-headers", "--lz4-frame")
+
+    result = subprocess.run(
+        ["cargo", "run", "--", "inspect", isdb_path],
+        check=True,
+        capture_output=True,
+        text=True,
+    )
+    assert "String codec: LZ4_FRAME" in result.stdout.splitlines()
+
+    csv_path = os.path.join(directory, "exported.csv")
+    subprocess.run(
+        ["cargo", "run", "--", "export", isdb_path, csv_path, "--has-headers"],
+        check=True,
+        capture_output=True,
+    )
+    with open(csv_path, newline="") as f:
+        assert list(csv.reader(f)) == rows
+
+
+def test_convert_with_thousands_separator_infers_grouped_numbers_as_int():
+    directory = os.path.join(
+        os.getcwd(), "data", "test_convert_with_thousands_separator"
+    )
+    os.makedirs(directory, exist_ok=True)
+    isdb_path = _convert(
+        directory,
+        [["amount", "code"], ["1,234", "1,2,3"], ["12,345,678", "4,5,6"]],
+        "--has-headers",
+        "--thousands-separator",
+        ",",
+    )
+
+    result = subprocess.run(
+        ["cargo", "run", "--", "inspect", isdb_path],
+        check=True,
+        capture_output=True,
+        text=True,
+    )
+    columns = [line.split()[:3] for line in result.stdout.splitlines()]
+    assert ["[0]", "amount", "INT64"] in columns
+    assert ["[1]", "code", "VARCHAR"] in columns
@@ -46,6 +46,38 @@ def test_fail_column_count_mismatch_no_mapping(server, test_csv_path):
     )
 
 
+def test_fail_column_count_mismatch_names_columns(server):
+    table_name = "width_mismatch_with_header"
+    create_table(
+        table_name,
+        [{"name": "c1", "type": "INT64"}, {"name": "c2", "type": "VARCHAR"}],
+    )
+
+    file_path = os.path.join(os.getcwd(), "data", "test_copy_fails_header.csv")
+    os.makedirs(os.path.dirname(file_path), exist_ok=True)
+    with open(file_path, "w", newline="") as f:
+        csv.writer(f).writerows([["id", "name", "age"], ["10", "abc", "20"]])
+
+    data = {
+        "queryDefinition": {
+            "sourceFilepath": file_path,
+            "destinationTableName": table_name,
+            "doesCsvContainHeader": True,
+        }
+    }
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    query_id = resp.json()
+
+    status = wait_for_final_status(query_id)
+    assert status == "FAILED"
+
+    err = get_error_message(query_id)
+    assert (
+        err
+        == "Mismatch: Table has 2 columns (c1, c2), but CSV has 3 (id, name, age). Without mapping, counts must match exactly."
+    )
+
+
 def test_fail_mapping_bad_column_name(server, test_csv_path):
     table_name = "bad_map_name"
     create_table(
@@ -154,3 +186,123 @@ def test_fail_csv_too_narrow(server, test_csv_path):
 
     err = get_error_message(query_id)
     assert err == "CSV too narrow: Mapping requires 2 columns, but CSV only has 1."
+
+
+def test_fail_field_too_large(server):
+    table_name = "field_too_large"
+    create_table(
+        table_name,
+        [{"name": "c1", "type": "INT64"}, {"name": "c2", "type": "VARCHAR"}],
+    )
+
+    file_path = os.path.join(os.getcwd(), "data", "test_field_too_large.csv")
+    os.makedirs(os.path.dirname(file_path), exist_ok=True)
+    with open(file_path, "w", newline="") as f:
+        writer = csv.writer(f)
+        writer.writerows([["1", "short"], ["2", "x" * 100]])
+
+    data = {
+        "queryDefinition": {
+            "sourceFilepath": file_path,
+            "destinationTableName": table_name,
+            "maxFieldSize": 10,
+        }
+    }
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    query_id = resp.json()
+
+    status = wait_for_final_status(query_id)
+    assert status == "FAILED"
+
+    err = get_error_message(query_id)
+    assert (
+        err
+        == "Field too large at Row 2, Column 2: 100 bytes exceeds limit of 10 bytes"
+    )
+
+
+def test_fail_negative_max_field_size(server):
+    table_name = "negative_max_field_size"
+    create_table(table_name, [{"name": "c1", "type": "INT64"}])
+
+    file_path = os.path.join(os.getcwd(), "data", "test_negative_max_field_size.csv")
+    os.makedirs(os.path.dirname(file_path), exist_ok=True)
+    with open(file_path, "w", newline="") as f:
+        f.write("1\n")
+
+    data = {
+        "queryDefinition": {
+            "sourceFilepath": file_path,
+            "destinationTableName": table_name,
+            "maxFieldSize": -1,
+        }
+    }
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert resp.status_code == 400
+
+
+def _copy_with_expectations(table_name, csv_path, **expectations):
+    create_table(
+        table_name,
+        [
+            {"name": "c1", "type": "INT64"},
+            {"name": "c2", "type": "VARCHAR"},
+            {"name": "c3", "type": "INT64"},
+        ],
+    )
+    data = {
+        "queryDefinition": {
+            "sourceFilepath": csv_path,
+            "destinationTableName": table_name,
+            **expectations,
+        }
+    }
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert resp.status_code == 200
+    return resp.json()
+
+
+def test_fail_expected_column_count(server, test_csv_path):
+    query_id = _copy_with_expectations(
+        "expect_columns_fail", test_csv_path, expectedColumnCount=4
+    )
+    assert wait_for_final_status(query_id) == "FAILED"
+    assert get_error_message(query_id) == "Expected 4 columns in CSV, but file has 3"
+
+
+def test_fail_expected_min_rows(server, test_csv_path):
+    query_id = _copy_with_expectations(
+        "expect_min_rows_fail", test_csv_path, minRowCount=3
+    )
+    assert wait_for_final_status(query_id) == "FAILED"
+    assert (
+        get_error_message(query_id)
+        == "Expected at least 3 rows in CSV, but file has 2"
+    )
+
+
+def test_fail_expected_max_rows(server, test_csv_path):
+    query_id = _copy_with_expectations(
+        "expect_max_rows_fail", test_csv_path, maxRowCount=1
+    )
+    assert wait_for_final_status(query_id) == "FAILED"
+    assert (
+        get_error_message(query_id) == "Expected at most 1 rows in CSV, but file has 2"
+    )
+
+
+def test_expectations_matching_file(server, test_csv_path):
+    query_id = _copy_with_expectations(
+        "expect_ok",
+        test_csv_path,
+        expectedColumnCount=3,
+        minRowCount=2,
+        maxRowCount=2,
+    )
+    assert wait_for_final_status(query_id) == "COMPLETED"
+
+
+def test_get_error_non_existent_query(server):
+    resp = requests.get(f"{BASE_URL}/error/test_get_error_non_existent_query")
+    assert resp.status_code == 404
+    assert resp.json() == {"message": "Couldn't find a query of given ID"}
@@ -0,0 +1,181 @@
+import json
+import os
+import shutil
+import subprocess
+import time
+from contextlib import contextmanager
+
+import pytest
+import requests
+from config import BASE_URL
+
+METASTORE_FILE = "metastore.json"
+METASTORE_BACKUP_FILE = METASTORE_FILE + ".bak"
+QUERY_ID = "test_dangling_result_query"
+
+
+def _fnv1a(data):
+    hash = 0xCBF29CE484222325
+    for byte in data:
+        hash = ((hash ^ byte) * 0x100000001B3) % 2**64
+    return hash
+
+
+def _with_checksum(metastore):
+    content = json.dumps(metastore, indent=2)
+    return f"{content}\n#checksum:{_fnv1a(content.encode()):016x}\n"
+
+
+@contextmanager
+def _preserved_file(path, content):
+    saved = path + ".test_backup"
+    had_file = os.path.exists(path)
+    if had_file:
+        shutil.copyfile(path, saved)
+
+    if content is None:
+        if had_file:
+            os.remove(path)
+    else:
+        with open(path, "w") as f:
+            f.write(content)
+
+    try:
+        yield
+    finally:
+        if had_file:
+            shutil.move(saved, path)
+        elif os.path.exists(path):
+            os.remove(path)
+
+
+@contextmanager
+def _server_with_metastore_file(content, backup_content=None):
+    with _preserved_file(METASTORE_FILE, content), _preserved_file(
+        METASTORE_BACKUP_FILE, backup_content
+    ):
+        proc = subprocess.Popen(["cargo", "run"], stderr=subprocess.PIPE, text=True)
+        time.sleep(1)
+
+        try:
+            yield proc
+        finally:
+            if proc.poll() is None:
+                proc.terminate()
+                proc.wait()
+
+
+@pytest.fixture(scope="module")
+def server_with_dangling_result():
+    metastore = {
+        "scheduled_for_deletion": ["missing_result_table"],
+        "tables": {},
+        "tables_name_id": {"missing_result_table": "missing_result_table"},
+        "table_accesses": {"missing_result_table": [QUERY_ID]},
+        "queries": {
+            QUERY_ID: {
+                "status": "Completed",
+                "definition": {
+                    "SelectAll": {
+                        "table_id": "missing_table",
+                        "table_name": "missing_table",
+                    }
+                },
+                "result": [{"table_id": "missing_result_table"}],
+                "errors": None,
+            }
+        },
+    }
+    with _server_with_metastore_file(json.dumps(metastore)) as proc:
+        yield proc
+
+
+def test_dangling_result_reference_is_pruned(server_with_dangling_result):
+    resp = requests.get(f"{BASE_URL}/result/{QUERY_ID}")
+    assert resp.status_code == 400
+    assert resp.json() == {"message": "Result for this query is not available"}
+
+    resp = requests.get(f"{BASE_URL}/query/{QUERY_ID}")
+    assert resp.status_code == 200
+    assert resp.json()["status"] == "COMPLETED"
+    assert resp.json()["isResultAvailable"] is False
+
+
+def test_truncated_metastore_is_detected():
+    metastore = {
+        "scheduled_for_deletion": [],
+        "tables": {},
+        "tables_name_id": {},
+        "table_accesses": {},
+        "queries": {},
+    }
+    content = _with_checksum(metastore)
+    truncated = content[: len(content) // 2]
+
+    with _server_with_metastore_file(truncated) as proc:
+        _, log = proc.communicate(timeout=60)
+        assert proc.returncode != 0
+
+    assert "Metastore file metastore.json is corrupt" in log
+
+
+def test_corrupt_metastore_is_not_overwritten():
+    corrupt = '{"scheduled_for_deletion": [], "tables": {'
+
+    with _server_with_metastore_file(corrupt) as proc:
+        assert proc.wait(timeout=60) != 0
+
+        with open(METASTORE_FILE) as f:
+            assert f.read() == corrupt
+
+
+def test_corrupt_metastore_loads_backup():
+    query_id = "test_corrupt_metastore_loads_backup"
+    metastore = {
+        "scheduled_for_deletion": [],
+        "tables": {},
+        "tables_name_id": {},
+        "table_accesses": {},
+        "queries": {
+            query_id: {
+                "status": "Completed",
+                "definition": {
+                    "SelectAll": {
+                        "table_id": "missing_table",
+                        "table_name": "missing_table",
+                    }
+                },
+                "result": None,
+                "errors": None,
+            }
+        },
+    }
+    corrupt = '{"scheduled_for_deletion": [], "tables": {'
+
+    with _server_with_metastore_file(corrupt, _with_checksum(metastore)):
+        resp = requests.get(f"{BASE_URL}/query/{query_id}")
+        assert resp.status_code == 200
+        assert resp.json()["status"] == "COMPLETED"
+
+
+def test_backup_listing_deleted_table_file_loads():
+    table_id = "test_backup_listing_deleted_table_file_loads"
+    metastore = {
+        "scheduled_for_deletion": [],
+        "tables": {
+            table_id: {
+                "name": table_id,
+                "table_file": os.path.join("tables", f"{table_id}.isdb"),
+            }
+        },
+        "tables_name_id": {table_id: table_id},
+        "table_accesses": {},
+        "queries": {},
+    }
+    corrupt = '{"scheduled_for_deletion": [], "tables": {'
+
+    with _server_with_metastore_file(corrupt, _with_checksum(metastore)) as proc:
+        resp = requests.get(f"{BASE_URL}/tables")
+        assert resp.status_code == 200
+        assert table_id not in [table["name"] for table in resp.json()]
+        assert proc.poll() is None
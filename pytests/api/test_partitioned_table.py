@@ -0,0 +1,110 @@
+import csv
+import os
+import subprocess
+
+import requests
+from config import BASE_URL
+from utils import wait_for_final_status
+
+PARTITIONS = {
+    "2024-01-01": [["19723", "1"], ["19723", "2"]],
+    "2024-01-02": [["19724", "3"]],
+    "2024-01-03": [["19725", "4"], ["19725", "5"]],
+}
+
+
+def _write_partitions(directory):
+    os.makedirs(directory, exist_ok=True)
+    partitions = []
+    for key, rows in PARTITIONS.items():
+        csv_path = os.path.join(directory, f"{key}.csv")
+        isdb_path = os.path.join(directory, f"{key}.isdb")
+        with open(csv_path, "w", newline="") as f:
+            writer = csv.writer(f)
+            writer.writerow(["day", "value"])
+            writer.writerows(rows)
+        subprocess.run(
+            ["cargo", "run", "--", "convert", csv_path, isdb_path, "--has-headers"],
+            check=True,
+            capture_output=True,
+        )
+        partitions.append({"key": key, "filePath": isdb_path})
+    return partitions
+
+
+def _files_read():
+    resp = requests.get(f"{BASE_URL}/system/stats")
+    assert resp.status_code == 200
+    return resp.json()["partitionFilesRead"]
+
+
+def _day_filter(table_name, operator, day):
+    return {
+        "operator": operator,
+        "leftOperand": {"tableName": table_name, "columnName": "day"},
+        "rightOperand": {"value": day},
+    }
+
+
+def test_select_reads_only_overlapping_partitions(server):
+    table_name = "test_select_reads_only_overlapping_partitions"
+    directory = os.path.join(os.getcwd(), "data", table_name)
+    partitions = _write_partitions(directory)
+
+    data = {"name": table_name, "partitionColumn": "day", "partitions": partitions}
+    resp = requests.put(f"{BASE_URL}/partitioned-table", json=data)
+    assert resp.status_code == 200
+
+    # Never read again, so removing it proves the filter skipped it.
+    os.remove(partitions[0]["filePath"])
+
+    before = _files_read()
+    where = {
+        "operator": "AND",
+        "leftOperand": _day_filter(table_name, "GREATER_EQUAL", 19724),
+        "rightOperand": _day_filter(table_name, "LESS_THAN", 19726),
+    }
+    data = {
+        "queryDefinition": {
+            "columnClauses": [
+                {"tableName": table_name, "columnName": "day"},
+                {"tableName": table_name, "columnName": "value"},
+            ],
+            "whereClause": where,
+        }
+    }
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert resp.status_code == 200
+    query_id = resp.json()
+    assert wait_for_final_status(query_id) == "COMPLETED"
+
+    resp = requests.get(f"{BASE_URL}/result/{query_id}")
+    assert resp.status_code == 200
+    assert resp.json() == [
+        {"rowCount": 3, "columns": [[19724, 19725, 19725], [3, 4, 5]]}
+    ]
+    assert _files_read() == before + 2
+
+
+def test_partitions_with_different_schemas_are_rejected(server):
+    table_name = "test_partitions_with_different_schemas_are_rejected"
+    directory = os.path.join(os.getcwd(), "data", table_name)
+    partitions = _write_partitions(directory)
+
+    csv_path = os.path.join(directory, "other.csv")
+    isdb_path = os.path.join(directory, "other.isdb")
+    with open(csv_path, "w", newline="") as f:
+        csv.writer(f).writerows([["day"], ["19726"]])
+    subprocess.run(
+        ["cargo", "run", "--", "convert", csv_path, isdb_path, "--has-headers"],
+        check=True,
+        capture_output=True,
+    )
+    partitions.append({"key": "2024-01-04", "filePath": isdb_path})
+
+    data = {"name": table_name, "partitionColumn": "day", "partitions": partitions}
+    resp = requests.put(f"{BASE_URL}/partitioned-table", json=data)
+    assert resp.status_code == 400
+    assert resp.json()["problems"][0]["error"] == (
+        "Partition has a different schema than the first one"
+    )
@@ -0,0 +1,77 @@
+import csv
+import os
+
+import requests
+from config import BASE_URL
+from utils import create_dummy_table, wait_for_final_status
+
+METASTORE_FILE = "metastore.json"
+
+
+def _persist():
+    resp = requests.post(f"{BASE_URL}/system/persist")
+    assert resp.status_code == 200
+    return resp.json()
+
+
+def test_persist_writes_only_changed_tables(server):
+    # Flushes whatever earlier requests left unsaved.
+    _persist()
+
+    table_id, _ = create_dummy_table("test_persist_writes_only_changed_tables")
+    assert _persist() == 1
+    assert os.path.exists(os.path.join("tables", f"{table_id}.isdb"))
+    with open(METASTORE_FILE) as f:
+        assert table_id in f.read()
+
+    assert _persist() == 0
+
+
+def _copy_rows(table_name, rows):
+    csv_path = os.path.abspath(f"{table_name}.csv")
+    with open(csv_path, "w", newline="") as f:
+        csv.writer(f).writerows(rows)
+
+    data = {
+        "queryDefinition": {
+            "sourceFilepath": csv_path,
+            "destinationTableName": table_name,
+        }
+    }
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert resp.status_code == 200
+    assert wait_for_final_status(resp.json()) == "COMPLETED"
+    os.remove(csv_path)
+
+
+def _select_all(table_name):
+    data = {"queryDefinition": {"tableName": table_name}}
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert resp.status_code == 200
+    query_id = resp.json()
+    assert wait_for_final_status(query_id) == "COMPLETED"
+
+    resp = requests.get(f"{BASE_URL}/result/{query_id}")
+    assert resp.status_code == 200
+    return resp.json()[0]["columns"]
+
+
+def test_compact_keeps_table_data(server):
+    tables = {
+        "test_compact_keeps_table_data_1": [["1", "a"], ["2", "b"]],
+        "test_compact_keeps_table_data_2": [[str(i), f"value{i}"] for i in range(100)],
+    }
+    for table_name, rows in tables.items():
+        create_dummy_table(table_name)
+        _copy_rows(table_name, rows)
+    _persist()
+
+    resp = requests.post(f"{BASE_URL}/system/compact")
+    assert resp.status_code == 200
+    assert resp.json() >= 0
+
+    for table_name, rows in tables.items():
+        assert _select_all(table_name) == [
+            [int(row[0]) for row in rows],
+            [row[1] for row in rows],
+        ]
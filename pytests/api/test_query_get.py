@@ -1,6 +1,10 @@
+import csv
+import os
+import subprocess
+
 import requests
 from config import BASE_URL, QUERY_STATUSES
-from utils import create_dummy_table
+from utils import create_dummy_table, wait_for_final_status
 
 
 def test_get_query_select(server):
@@ -28,3 +32,118 @@ def test_get_non_existence_query(server):
 
     body = resp.json()
     assert body["message"] == "Couldn't find a query of given ID"
+
+
+def test_get_query_select_result_compression(server):
+    table_name = "test_get_query_select_result_compression"
+    create_dummy_table(table_name)
+
+    data = {
+        "queryDefinition": {
+            "columnClauses": [{"tableName": table_name, "columnName": "col1"}],
+            "resultCompression": "UNCOMPRESSED",
+        }
+    }
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert resp.status_code == 200
+    query_id = resp.json()
+    assert wait_for_final_status(query_id) == "COMPLETED"
+
+    resp = requests.get(f"{BASE_URL}/query/{query_id}")
+    assert resp.status_code == 200
+    assert resp.json()["queryDefinition"]["resultCompression"] == "UNCOMPRESSED"
+
+    resp = requests.get(f"{BASE_URL}/result/{query_id}")
+    assert resp.status_code == 200
+    assert resp.json()[0]["rowCount"] == 0
+
+
+def test_uncompressed_result_file_round_trips(server):
+    table_name = "test_uncompressed_result_file_round_trips"
+    create_dummy_table(table_name)
+    directory = os.path.join(os.getcwd(), "data", table_name)
+    os.makedirs(directory, exist_ok=True)
+    rows = [["1", "a"], ["2", "bc"], ["3", ""]]
+    csv_path = os.path.join(directory, "input.csv")
+    with open(csv_path, "w", newline="") as f:
+        csv.writer(f).writerows(rows)
+
+    data = {
+        "queryDefinition": {
+            "sourceFilepath": csv_path,
+            "destinationTableName": table_name,
+        }
+    }
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert wait_for_final_status(resp.json()) == "COMPLETED"
+
+    result_path = os.path.join(directory, "result.isdb")
+    data = {
+        "queryDefinition": {
+            "columnClauses": [
+                {"tableName": table_name, "columnName": "col1"},
+                {"tableName": table_name, "columnName": "col2"},
+            ],
+            "resultCompression": "UNCOMPRESSED",
+            "resultFilepath": result_path,
+            "overwriteResultFile": True,
+        }
+    }
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert resp.status_code == 200
+    query_id = resp.json()
+    assert wait_for_final_status(query_id) == "COMPLETED"
+
+    resp = requests.get(f"{BASE_URL}/result/{query_id}")
+    assert resp.status_code == 200
+    assert resp.json() == [{"rowCount": 3, "columns": [[1, 2, 3], ["a", "bc", ""]]}]
+
+    result = subprocess.run(
+        ["cargo", "run", "--", "inspect", result_path],
+        check=True,
+        capture_output=True,
+        text=True,
+    )
+    assert "String codec: NONE_STRING" in result.stdout.splitlines()
+
+    exported_path = os.path.join(directory, "result.csv")
+    subprocess.run(
+        ["cargo", "run", "--", "export", result_path, exported_path, "--has-headers"],
+        check=True,
+        capture_output=True,
+    )
+    with open(exported_path, newline="") as f:
+        assert list(csv.reader(f)) == [["col1", "col2"], *rows]
+
+
+def test_head_query_exists(server):
+    table_name = "test_head_query_exists"
+    create_dummy_table(table_name)
+
+    data = {"queryDefinition": {"tableName": table_name}}
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert resp.status_code == 200
+    query_id = resp.json()
+
+    resp = requests.head(f"{BASE_URL}/query/{query_id}")
+    assert resp.status_code == 200
+
+
+def test_head_non_existence_query(server):
+    resp = requests.head(f"{BASE_URL}/query/test_head_non_existence_query")
+    assert resp.status_code == 404
+
+
+def test_get_query_span_id(server):
+    table_name = "test_get_query_span_id"
+    create_dummy_table(table_name)
+
+    data = {"queryDefinition": {"tableName": table_name}}
+    headers = {"X-Span-ID": "test-get-query-span-id"}
+    resp = requests.post(f"{BASE_URL}/query", json=data, headers=headers)
+    assert resp.status_code == 200
+    query_id = resp.json()
+
+    resp = requests.get(f"{BASE_URL}/query/{query_id}")
+    assert resp.status_code == 200
+    assert resp.json()["spanId"] == "test-get-query-span-id"
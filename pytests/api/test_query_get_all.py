@@ -27,3 +27,35 @@ def test_list_queries(server):
     assert len(body) == 1
     assert body[0]["queryId"] == query_id
     assert body[0]["status"] in QUERY_STATUSES
+
+
+def test_list_queries_by_label_and_tag(server):
+    data = {"name": "test_list_queries_by_label", "columns": [
+        {"name": "c1", "type": "INT64"},
+    ]}
+    resp = requests.put(f"{BASE_URL}/table", json=data)
+    assert resp.status_code == 200
+
+    data = {
+        "label": "nightly report",
+        "tags": ["reports", "nightly"],
+        "queryDefinition": {"tableName": "test_list_queries_by_label"},
+    }
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert resp.status_code == 200
+    query_id = resp.json()
+
+    resp = requests.get(f"{BASE_URL}/queries", params={"label": "nightly report"})
+    assert resp.status_code == 200
+    body = resp.json()
+    assert [query["queryId"] for query in body] == [query_id]
+    assert body[0]["tags"] == ["reports", "nightly"]
+
+    resp = requests.get(f"{BASE_URL}/queries", params={"tag": "reports"})
+    assert [query["queryId"] for query in resp.json()] == [query_id]
+
+    resp = requests.get(f"{BASE_URL}/queries", params={"tag": "missing"})
+    assert resp.json() == []
+
+    resp = requests.get(f"{BASE_URL}/query/{query_id}")
+    assert resp.json()["label"] == "nightly report"
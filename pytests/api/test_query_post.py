@@ -1,10 +1,11 @@
 import csv
 import os
+import subprocess
 
 import pytest
 import requests
 from config import BASE_URL
-from utils import create_table, wait_for_final_status
+from utils import create_table, get_error_message, wait_for_final_status
 
 
 @pytest.fixture
@@ -61,3 +62,660 @@ def test_fail_file_not_found(server):
     assert len(body["problems"]) == 1
     assert body["problems"][0]["error"] == "File does not exist"
     assert body["problems"][0]["context"] == "ghost_file.csv"
+
+
+def test_copy_best_effort_skips_bad_rows(server):
+    table_name = "copy_best_effort"
+    create_table(
+        table_name,
+        [{"name": "c1", "type": "INT64"}, {"name": "c2", "type": "VARCHAR"}],
+    )
+
+    file_path = os.path.join(os.getcwd(), "data", "test_copy_best_effort.csv")
+    os.makedirs(os.path.dirname(file_path), exist_ok=True)
+    with open(file_path, "w", newline="") as f:
+        f.write("1,a\nbad,b\n3,c\n4\n5,e\n")
+
+    data = {
+        "queryDefinition": {
+            "sourceFilepath": file_path,
+            "destinationTableName": table_name,
+            "bestEffort": True,
+        }
+    }
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert resp.status_code == 200
+    query_id = resp.json()
+
+    status = wait_for_final_status(query_id)
+    assert status == "COMPLETED"
+
+    resp = requests.get(f"{BASE_URL}/query/{query_id}")
+    assert resp.json()["skippedRowCount"] == 2
+
+    resp = requests.get(f"{BASE_URL}/error/{query_id}")
+    assert resp.status_code == 200
+    problems = resp.json()["problems"]
+    assert [p["context"] for p in problems] == ["Skipped row 2", "Skipped row 4"]
+    assert (
+        problems[0]["error"]
+        == "Type Error at Row 2, Column 'c1': Expected INT64, got 'bad'"
+    )
+    assert problems[1]["error"] == "Row 4 length mismatch"
+
+    resp = requests.post(
+        f"{BASE_URL}/query", json={"queryDefinition": {"tableName": table_name}}
+    )
+    select_id = resp.json()
+    assert wait_for_final_status(select_id) == "COMPLETED"
+
+    body = requests.get(f"{BASE_URL}/result/{select_id}").json()
+    assert body[0]["rowCount"] == 3
+    assert body[0]["columns"] == [[1, 3, 5], ["a", "c", "e"]]
+
+
+def test_copy_best_effort_skips_malformed_first_row(server):
+    table_name = "copy_best_effort_first_row"
+    create_table(
+        table_name,
+        [{"name": "c1", "type": "INT64"}, {"name": "c2", "type": "VARCHAR"}],
+    )
+
+    file_path = os.path.join(os.getcwd(), "data", "test_copy_best_effort_first.csv")
+    os.makedirs(os.path.dirname(file_path), exist_ok=True)
+    with open(file_path, "w", newline="") as f:
+        f.write("1\n2,b\n3,c,extra\n4,d\n")
+
+    data = {
+        "queryDefinition": {
+            "sourceFilepath": file_path,
+            "destinationTableName": table_name,
+            "bestEffort": True,
+        }
+    }
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert resp.status_code == 200
+    query_id = resp.json()
+    assert wait_for_final_status(query_id) == "COMPLETED"
+
+    problems = requests.get(f"{BASE_URL}/error/{query_id}").json()["problems"]
+    assert [p["context"] for p in problems] == ["Skipped row 1", "Skipped row 3"]
+
+    resp = requests.post(
+        f"{BASE_URL}/query", json={"queryDefinition": {"tableName": table_name}}
+    )
+    select_id = resp.json()
+    assert wait_for_final_status(select_id) == "COMPLETED"
+    body = requests.get(f"{BASE_URL}/result/{select_id}").json()
+    assert body[0]["columns"] == [[2, 4], ["b", "d"]]
+
+
+def test_copy_bom_and_crlf(server):
+    table_name = "copy_bom_crlf"
+    create_table(
+        table_name,
+        [{"name": "id", "type": "INT64"}, {"name": "name", "type": "VARCHAR"}],
+    )
+
+    file_path = os.path.join(os.getcwd(), "data", "test_copy_bom_crlf.csv")
+    os.makedirs(os.path.dirname(file_path), exist_ok=True)
+    with open(file_path, "wb") as f:
+        f.write(b"\xef\xbb\xbf1,first\r\n2,second\n3,third\r\n")
+
+    data = {
+        "queryDefinition": {
+            "sourceFilepath": file_path,
+            "destinationTableName": table_name,
+        }
+    }
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert resp.status_code == 200
+    assert wait_for_final_status(resp.json()) == "COMPLETED"
+
+    resp = requests.post(
+        f"{BASE_URL}/query", json={"queryDefinition": {"tableName": table_name}}
+    )
+    select_id = resp.json()
+    assert wait_for_final_status(select_id) == "COMPLETED"
+
+    body = requests.get(f"{BASE_URL}/result/{select_id}").json()
+    assert body[0]["columns"] == [[1, 2, 3], ["first", "second", "third"]]
+
+
+def _copy_rows(table_name, file_name, rows, **options):
+    file_path = os.path.join(os.getcwd(), "data", file_name)
+    os.makedirs(os.path.dirname(file_path), exist_ok=True)
+    with open(file_path, "w", newline="") as f:
+        csv.writer(f).writerows(rows)
+
+    data = {
+        "queryDefinition": {
+            "sourceFilepath": file_path,
+            "destinationTableName": table_name,
+        }
+    }
+    data["queryDefinition"].update(options)
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert resp.status_code == 200
+    return resp.json()
+
+
+def _select_all_columns(table_name):
+    resp = requests.post(
+        f"{BASE_URL}/query", json={"queryDefinition": {"tableName": table_name}}
+    )
+    select_id = resp.json()
+    assert wait_for_final_status(select_id) == "COMPLETED"
+    return requests.get(f"{BASE_URL}/result/{select_id}").json()[0]["columns"]
+
+
+def _create_upsert_table(table_name):
+    create_table(
+        table_name,
+        [{"name": "id", "type": "INT64"}, {"name": "name", "type": "VARCHAR"}],
+    )
+    query_id = _copy_rows(table_name, f"{table_name}_base.csv", [["1", "a"], ["2", "b"]])
+    assert wait_for_final_status(query_id) == "COMPLETED"
+
+
+def test_copy_upsert_update_only(server):
+    table_name = "copy_upsert_update"
+    _create_upsert_table(table_name)
+
+    query_id = _copy_rows(
+        table_name, f"{table_name}.csv", [["2", "bb"], ["1", "aa"]], keyColumn="id"
+    )
+    assert wait_for_final_status(query_id) == "COMPLETED"
+    assert _select_all_columns(table_name) == [[1, 2], ["aa", "bb"]]
+
+
+def test_copy_upsert_insert_only(server):
+    table_name = "copy_upsert_insert"
+    _create_upsert_table(table_name)
+
+    query_id = _copy_rows(
+        table_name, f"{table_name}.csv", [["3", "c"], ["4", "d"]], keyColumn="id"
+    )
+    assert wait_for_final_status(query_id) == "COMPLETED"
+    assert _select_all_columns(table_name) == [[1, 2, 3, 4], ["a", "b", "c", "d"]]
+
+
+def test_copy_upsert_mixed(server):
+    table_name = "copy_upsert_mixed"
+    _create_upsert_table(table_name)
+
+    query_id = _copy_rows(
+        table_name, f"{table_name}.csv", [["3", "c"], ["1", "aa"]], keyColumn="id"
+    )
+    assert wait_for_final_status(query_id) == "COMPLETED"
+    assert _select_all_columns(table_name) == [[1, 2, 3], ["aa", "b", "c"]]
+
+
+def test_copy_upsert_fails_on_duplicate_keys(server):
+    table_name = "copy_upsert_duplicates"
+    _create_upsert_table(table_name)
+    query_id = _copy_rows(table_name, f"{table_name}_dup.csv", [["1", "again"]])
+    assert wait_for_final_status(query_id) == "COMPLETED"
+
+    query_id = _copy_rows(
+        table_name, f"{table_name}.csv", [["1", "x"]], keyColumn="id"
+    )
+    assert wait_for_final_status(query_id) == "FAILED"
+    assert get_error_message(query_id) == "Key column 'id' is not unique in table"
+    assert _select_all_columns(table_name) == [[1, 2, 1], ["a", "b", "again"]]
+
+
+def test_copy_upsert_repeated_incoming_key(server):
+    table_name = "copy_upsert_repeated_incoming_key"
+    _create_upsert_table(table_name)
+
+    rows = [["3", "c"], ["1", "aa"], ["3", "cc"]]
+    query_id = _copy_rows(table_name, f"{table_name}.csv", rows, keyColumn="id")
+    assert wait_for_final_status(query_id) == "COMPLETED"
+    assert _select_all_columns(table_name) == [[1, 2, 3], ["aa", "b", "cc"]]
+
+
+def test_copy_mapping_repeats_column(server):
+    table_name = "copy_mapping_repeats_column"
+    _create_upsert_table(table_name)
+
+    query_id = _copy_rows(
+        table_name,
+        f"{table_name}.csv",
+        [["3", "4"]],
+        keyColumn="id",
+        destinationColumns=["id", "id"],
+    )
+    assert wait_for_final_status(query_id) == "FAILED"
+    assert (
+        get_error_message(query_id)
+        == "Mapping references column 'id' more than once"
+    )
+    assert _select_all_columns(table_name) == [[1, 2], ["a", "b"]]
+
+
+def _create_keyed_table(table_name):
+    data = {
+        "name": table_name,
+        "columns": [
+            {"name": "id", "type": "INT64"},
+            {"name": "name", "type": "VARCHAR"},
+        ],
+        "keyColumn": "id",
+    }
+    resp = requests.put(f"{BASE_URL}/table", json=data)
+    assert resp.status_code == 200
+    table_id = resp.json()
+
+    resp = requests.get(f"{BASE_URL}/table/{table_id}")
+    assert resp.json()["keyColumn"] == "id"
+
+    query_id = _copy_rows(
+        table_name, f"{table_name}_base.csv", [["1", "a"], ["2", "b"]]
+    )
+    assert wait_for_final_status(query_id) == "COMPLETED"
+
+
+def test_copy_duplicate_key_into_keyed_table(server):
+    table_name = "copy_duplicate_key_into_keyed_table"
+    _create_keyed_table(table_name)
+
+    query_id = _copy_rows(table_name, f"{table_name}.csv", [["3", "c"], ["1", "x"]])
+    assert wait_for_final_status(query_id) == "FAILED"
+    assert get_error_message(query_id) == "Value '1' already exists in key column 'id'"
+    assert _select_all_columns(table_name) == [[1, 2], ["a", "b"]]
+
+
+def test_copy_upsert_by_table_key(server):
+    table_name = "copy_upsert_by_table_key"
+    _create_keyed_table(table_name)
+
+    query_id = _copy_rows(
+        table_name, f"{table_name}.csv", [["3", "c"], ["1", "aa"]], upsert=True
+    )
+    assert wait_for_final_status(query_id) == "COMPLETED"
+    assert _select_all_columns(table_name) == [[1, 2, 3], ["aa", "b", "c"]]
+
+
+def test_copy_upsert_without_key_column(server):
+    table_name = "copy_upsert_without_key_column"
+    _create_upsert_table(table_name)
+
+    query_id = _copy_rows(table_name, f"{table_name}.csv", [["1", "aa"]], upsert=True)
+    assert wait_for_final_status(query_id) == "FAILED"
+
+
+def test_copy_null_sentinel(server):
+    table_name = "copy_null_sentinel"
+    create_table(
+        table_name,
+        [{"name": "id", "type": "INT64"}, {"name": "name", "type": "VARCHAR"}],
+    )
+    rows = [["0", "zero"], ["\\N", "missing"], ["5", "\\N"]]
+
+    query_id = _copy_rows(
+        table_name, f"{table_name}.csv", rows, nullSentinel="\\N", nullIntValue=-1
+    )
+    assert wait_for_final_status(query_id) == "COMPLETED"
+    assert _select_all_columns(table_name) == [
+        [0, -1, 5],
+        ["zero", "missing", "\\N"],
+    ]
+
+
+def test_copy_null_sentinel_not_set(server):
+    table_name = "copy_null_sentinel_not_set"
+    create_table(table_name, [{"name": "id", "type": "INT64"}])
+
+    query_id = _copy_rows(table_name, f"{table_name}.csv", [["0"], ["\\N"]])
+    assert wait_for_final_status(query_id) == "FAILED"
+    assert get_error_message(query_id) == (
+        "Type Error at Row 2, Column 'id': Expected INT64, got '\\N'"
+    )
+
+
+def test_copy_validate_only(server):
+    table_name = "copy_validate_only"
+    _create_upsert_table(table_name)
+
+    query_id = _copy_rows(
+        table_name, f"{table_name}.csv", [["3", "c"], ["4", "d"]], validateOnly=True
+    )
+    assert wait_for_final_status(query_id) == "COMPLETED"
+    resp = requests.get(f"{BASE_URL}/query/{query_id}")
+    assert resp.json()["validatedRowCount"] == 2
+    assert _select_all_columns(table_name) == [[1, 2], ["a", "b"]]
+
+
+def test_copy_validate_only_bad_file(server):
+    table_name = "copy_validate_only_bad_file"
+    _create_upsert_table(table_name)
+
+    query_id = _copy_rows(
+        table_name, f"{table_name}.csv", [["3", "c"], ["x", "d"]], validateOnly=True
+    )
+    assert wait_for_final_status(query_id) == "FAILED"
+    assert get_error_message(query_id) == (
+        "Type Error at Row 2, Column 'id': Expected INT64, got 'x'"
+    )
+    assert _select_all_columns(table_name) == [[1, 2], ["a", "b"]]
+
+
+def _union(table_names, **options):
+    data = {"queryDefinition": {"tableNames": table_names}}
+    data["queryDefinition"].update(options)
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert resp.status_code == 200
+    return resp.json()
+
+
+def _create_union_tables(prefix):
+    first, second = f"{prefix}_first", f"{prefix}_second"
+    _create_upsert_table(first)
+    _create_upsert_table(second)
+    query_id = _copy_rows(second, f"{second}_extra.csv", [["3", "c"]])
+    assert wait_for_final_status(query_id) == "COMPLETED"
+    return [first, second]
+
+
+def test_union_all_keeps_duplicates(server):
+    table_names = _create_union_tables("union_all")
+
+    query_id = _union(table_names, all=True)
+    assert wait_for_final_status(query_id) == "COMPLETED"
+    resp = requests.get(f"{BASE_URL}/result/{query_id}")
+    assert resp.json()[0]["rowCount"] == 5
+    assert resp.json()[0]["columns"] == [[1, 2, 1, 2, 3], ["a", "b", "a", "b", "c"]]
+
+
+def test_union_removes_duplicates(server):
+    table_names = _create_union_tables("union_distinct")
+
+    query_id = _union(table_names)
+    assert wait_for_final_status(query_id) == "COMPLETED"
+    resp = requests.get(f"{BASE_URL}/result/{query_id}")
+    assert resp.json()[0]["rowCount"] == 3
+    assert resp.json()[0]["columns"] == [[1, 2, 3], ["a", "b", "c"]]
+
+
+def test_union_schema_mismatch(server):
+    first = "union_mismatch_first"
+    second = "union_mismatch_second"
+    _create_upsert_table(first)
+    create_table(second, [{"name": "id", "type": "INT64"}])
+
+    query_id = _union([first, second])
+    assert wait_for_final_status(query_id) == "FAILED"
+    assert get_error_message(query_id).startswith(f"Table '{second}' has schema")
+
+
+def test_union_single_table(server):
+    table_name = "union_single_table"
+    _create_upsert_table(table_name)
+
+    resp = requests.post(
+        f"{BASE_URL}/query", json={"queryDefinition": {"tableNames": [table_name]}}
+    )
+    assert resp.status_code == 400
+
+
+def test_select_order_by_multiple_columns(server):
+    table_name = "select_order_by_multiple_columns"
+    create_table(
+        table_name,
+        [{"name": "name", "type": "VARCHAR"}, {"name": "score", "type": "INT64"}],
+    )
+    rows = [["b", "1"], ["a", "1"], ["b", "3"], ["a", "2"], ["b", "2"]]
+    query_id = _copy_rows(table_name, f"{table_name}.csv", rows)
+    assert wait_for_final_status(query_id) == "COMPLETED"
+
+    data = {
+        "queryDefinition": {
+            "columnClauses": [
+                {"tableName": table_name, "columnName": "name"},
+                {"tableName": table_name, "columnName": "score"},
+            ],
+            "orderByClause": [
+                {"columnIndex": 0, "ascending": True},
+                {"columnIndex": 1, "ascending": False},
+            ],
+        }
+    }
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert resp.status_code == 200
+    query_id = resp.json()
+    assert wait_for_final_status(query_id) == "COMPLETED"
+
+    resp = requests.get(f"{BASE_URL}/result/{query_id}")
+    assert resp.json()[0]["columns"] == [
+        ["a", "a", "b", "b", "b"],
+        [2, 1, 3, 2, 1],
+    ]
+
+
+def _copy_ndjson(table_name, lines, **options):
+    file_path = os.path.join(os.getcwd(), "data", f"{table_name}.ndjson")
+    os.makedirs(os.path.dirname(file_path), exist_ok=True)
+    with open(file_path, "w") as f:
+        f.write("\n".join(lines) + "\n")
+
+    data = {
+        "queryDefinition": {
+            "sourceFilepath": file_path,
+            "destinationTableName": table_name,
+            "sourceFormat": "NDJSON",
+        }
+    }
+    data["queryDefinition"].update(options)
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert resp.status_code == 200
+    return resp.json()
+
+
+def test_copy_ndjson(server):
+    table_name = "copy_ndjson"
+    create_table(
+        table_name,
+        [
+            {"name": "id", "type": "INT64"},
+            {"name": "name", "type": "VARCHAR"},
+            {"name": "tags", "type": "VARCHAR"},
+        ],
+    )
+
+    lines = [
+        '{"name": "a", "id": 1, "tags": ["x", "y"]}',
+        '{"id": 2, "name": "zaż"}',
+        '{"id": null, "name": 3}',
+    ]
+    query_id = _copy_ndjson(table_name, lines, stringifyNestedValues=True)
+    assert wait_for_final_status(query_id) == "COMPLETED"
+    assert _select_all_columns(table_name) == [
+        [1, 2, 0],
+        ["a", "zaż", "3"],
+        ['["x","y"]', "", ""],
+    ]
+
+
+def test_copy_ndjson_nested_value(server):
+    table_name = "copy_ndjson_nested_value"
+    _create_upsert_table(table_name)
+
+    query_id = _copy_ndjson(table_name, ['{"id": 3, "name": {"first": "c"}}'])
+    assert wait_for_final_status(query_id) == "FAILED"
+    assert get_error_message(query_id) == (
+        "NDJSON Parse Error: Invalid row: Line 1, key 'name' holds a nested value"
+    )
+
+
+def test_copy_ndjson_unknown_key(server):
+    table_name = "copy_ndjson_unknown_key"
+    _create_upsert_table(table_name)
+
+    query_id = _copy_ndjson(table_name, ['{"id": 3, "name": "c", "age": 30}'])
+    assert wait_for_final_status(query_id) == "FAILED"
+    assert get_error_message(query_id) == (
+        "NDJSON key 'age' does not match any column in table"
+    )
+    assert _select_all_columns(table_name) == [[1, 2], ["a", "b"]]
+
+
+def test_copy_ndjson_line_too_long(server):
+    table_name = "copy_ndjson_line_too_long"
+    _create_upsert_table(table_name)
+
+    line = '{"id": 3, "name": "%s"}' % ("x" * 1000)
+    query_id = _copy_ndjson(table_name, [line], maxFieldSize=10)
+    assert wait_for_final_status(query_id) == "FAILED"
+    assert get_error_message(query_id) == (
+        "NDJSON Parse Error: Invalid row: Line 1 is longer than 562 bytes"
+    )
+    assert _select_all_columns(table_name) == [[1, 2], ["a", "b"]]
+
+
+def _select_into_file(table_name, result_path, **options):
+    data = {
+        "queryDefinition": {
+            "columnClauses": [
+                {"tableName": table_name, "columnName": "name"},
+                {"tableName": table_name, "columnName": "id"},
+            ],
+            "whereClause": {
+                "operator": "GREATER_THAN",
+                "leftOperand": {"tableName": table_name, "columnName": "id"},
+                "rightOperand": {"value": 1},
+            },
+            "resultFilepath": result_path,
+        }
+    }
+    data["queryDefinition"].update(options)
+    return requests.post(f"{BASE_URL}/query", json=data)
+
+
+def test_select_result_written_to_file(server):
+    table_name = "select_result_written_to_file"
+    _create_upsert_table(table_name)
+    directory = os.path.join(os.getcwd(), "data", table_name)
+    os.makedirs(directory, exist_ok=True)
+    result_path = os.path.join(directory, "result.isdb")
+    if os.path.exists(result_path):
+        os.remove(result_path)
+
+    resp = _select_into_file(table_name, result_path)
+    assert resp.status_code == 200
+    assert wait_for_final_status(resp.json()) == "COMPLETED"
+
+    csv_path = os.path.join(directory, "result.csv")
+    subprocess.run(
+        ["cargo", "run", "--", "export", result_path, csv_path, "--has-headers"],
+        check=True,
+        capture_output=True,
+    )
+    with open(csv_path, newline="") as f:
+        assert list(csv.reader(f)) == [["name", "id"], ["b", "2"]]
+
+    resp = _select_into_file(table_name, result_path)
+    assert resp.status_code == 400
+    assert resp.json()["problems"][0]["error"] == "Result file already exists"
+
+    resp = _select_into_file(table_name, result_path, overwriteResultFile=True)
+    assert resp.status_code == 200
+    assert wait_for_final_status(resp.json()) == "COMPLETED"
+
+
+@pytest.mark.parametrize("compression", ["SMALLEST", "LZ4_FRAME"])
+def test_select_result_file_with_compression(server, compression):
+    table_name = f"select_result_file_{compression.lower()}"
+    _create_upsert_table(table_name)
+    directory = os.path.join(os.getcwd(), "data", table_name)
+    os.makedirs(directory, exist_ok=True)
+    result_path = os.path.join(directory, "result.isdb")
+
+    resp = _select_into_file(
+        table_name,
+        result_path,
+        resultCompression=compression,
+        overwriteResultFile=True,
+    )
+    assert resp.status_code == 200
+    query_id = resp.json()
+    assert wait_for_final_status(query_id) == "COMPLETED"
+    resp = requests.get(f"{BASE_URL}/query/{query_id}")
+    assert resp.json()["queryDefinition"]["resultCompression"] == compression
+
+    csv_path = os.path.join(directory, "result.csv")
+    subprocess.run(
+        ["cargo", "run", "--", "export", result_path, csv_path, "--has-headers"],
+        check=True,
+        capture_output=True,
+    )
+    with open(csv_path, newline="") as f:
+        assert list(csv.reader(f)) == [["name", "id"], ["b", "2"]]
+
+
+def test_select_result_file_in_missing_directory(server):
+    table_name = "select_result_file_in_missing_directory"
+    _create_upsert_table(table_name)
+    result_path = os.path.join(os.getcwd(), "data", "missing", table_name, "r.isdb")
+
+    resp = _select_into_file(table_name, result_path)
+    assert resp.status_code == 400
+    assert resp.json()["problems"][0] == {
+        "error": "Directory of result file does not exist",
+        "context": result_path,
+    }
+
+
+def test_select_result_file_never_replaces_database_files(server):
+    table_name = "select_result_file_never_replaces_database_files"
+    _create_upsert_table(table_name)
+
+    for result_path in ["metastore.json", os.path.join(os.getcwd(), "metastore.json")]:
+        resp = _select_into_file(table_name, result_path, overwriteResultFile=True)
+        assert resp.status_code == 400
+        assert resp.json()["problems"][0] == {
+            "error": "Result file would replace a database file",
+            "context": result_path,
+        }
+
+
+def _copy_table(source_table_name, destination_table_name):
+    data = {
+        "queryDefinition": {
+            "sourceTableName": source_table_name,
+            "destinationTableName": destination_table_name,
+        }
+    }
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert resp.status_code == 200
+    return resp.json()
+
+
+def test_copy_between_tables(server):
+    source_name = "copy_between_tables_source"
+    destination_name = "copy_between_tables_destination"
+    _create_upsert_table(source_name)
+    _create_upsert_table(destination_name)
+
+    query_id = _copy_table(source_name, destination_name)
+    assert wait_for_final_status(query_id) == "COMPLETED"
+    assert _select_all_columns(destination_name) == [[1, 2, 1, 2], ["a", "b", "a", "b"]]
+    assert _select_all_columns(source_name) == [[1, 2], ["a", "b"]]
+
+    resp = requests.get(f"{BASE_URL}/query/{query_id}")
+    assert resp.json()["queryDefinition"] == {
+        "sourceTableName": source_name,
+        "destinationTableName": destination_name,
+        "isolationLevel": "SNAPSHOT",
+    }
+
+
+def test_copy_between_tables_schema_mismatch(server):
+    source_name = "copy_between_tables_mismatch_source"
+    destination_name = "copy_between_tables_mismatch_destination"
+    _create_upsert_table(source_name)
+    create_table(destination_name, [{"name": "id", "type": "INT64"}])
+
+    query_id = _copy_table(source_name, destination_name)
+    assert wait_for_final_status(query_id) == "FAILED"
+    assert get_error_message(query_id).startswith(f"Table '{source_name}' has schema")
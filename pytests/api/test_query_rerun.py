@@ -0,0 +1,79 @@
+import os
+
+import requests
+from config import BASE_URL
+from utils import create_table, wait_for_final_status
+
+
+def _write_csv(name, content):
+    file_path = os.path.join(os.getcwd(), "data", name)
+    os.makedirs(os.path.dirname(file_path), exist_ok=True)
+    with open(file_path, "w", newline="") as f:
+        f.write(content)
+    return file_path
+
+
+def test_rerun_failed_copy_after_fixing_file(server):
+    table_name = "test_rerun_failed_copy"
+    create_table(
+        table_name,
+        [{"name": "c1", "type": "INT64"}, {"name": "c2", "type": "VARCHAR"}],
+    )
+    csv_path = _write_csv("test_rerun_failed_copy.csv", "1,a\nbad,b\n")
+
+    data = {
+        "queryDefinition": {
+            "sourceFilepath": csv_path,
+            "destinationTableName": table_name,
+        }
+    }
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert resp.status_code == 200
+    failed_id = resp.json()
+    assert wait_for_final_status(failed_id) == "FAILED"
+
+    _write_csv("test_rerun_failed_copy.csv", "1,a\n2,b\n")
+    resp = requests.post(f"{BASE_URL}/query/{failed_id}/rerun")
+    assert resp.status_code == 200
+    rerun_id = resp.json()
+    assert rerun_id != failed_id
+    assert wait_for_final_status(rerun_id) == "COMPLETED"
+
+    resp = requests.get(f"{BASE_URL}/query/{failed_id}")
+    assert resp.json()["status"] == "FAILED"
+    resp = requests.get(f"{BASE_URL}/query/{rerun_id}")
+    assert resp.json()["queryDefinition"]["sourceFilepath"] == csv_path
+
+    resp = requests.post(
+        f"{BASE_URL}/query", json={"queryDefinition": {"tableName": table_name}}
+    )
+    query_id = resp.json()
+    assert wait_for_final_status(query_id) == "COMPLETED"
+    resp = requests.get(f"{BASE_URL}/result/{query_id}")
+    assert resp.json()[0]["rowCount"] == 2
+
+
+def test_rerun_query_of_deleted_table(server):
+    table_name = "test_rerun_query_of_deleted_table"
+    table_id = create_table(table_name, [{"name": "c1", "type": "INT64"}])
+
+    resp = requests.post(
+        f"{BASE_URL}/query", json={"queryDefinition": {"tableName": table_name}}
+    )
+    query_id = resp.json()
+    assert wait_for_final_status(query_id) == "COMPLETED"
+
+    resp = requests.delete(f"{BASE_URL}/table/{table_id}")
+    assert resp.status_code == 200
+
+    resp = requests.post(f"{BASE_URL}/query/{query_id}/rerun")
+    assert resp.status_code == 400
+    assert resp.json()["problems"] == [
+        {"error": "Table used by the query no longer exists", "context": table_id}
+    ]
+
+
+def test_rerun_non_existent_query(server):
+    resp = requests.post(f"{BASE_URL}/query/test_rerun_non_existent_query/rerun")
+    assert resp.status_code == 404
+    assert resp.json() == {"message": "Couldn't find a query of given ID"}
@@ -0,0 +1,112 @@
+import csv
+import os
+import subprocess
+import time
+
+import pytest
+import requests
+from config import BASE_URL
+from utils import create_dummy_table, create_table, wait_for_final_status
+
+
+def _start_server(*args):
+    proc = subprocess.Popen(["cargo", "run", "--", *args])
+
+    time.sleep(1)
+
+    return proc
+
+
+@pytest.fixture
+def server_with_retention():
+    proc = _start_server("--result-retention-secs", "1")
+
+    yield proc
+
+    proc.terminate()
+    proc.wait()
+
+
+@pytest.fixture
+def server_with_max_retained_queries():
+    proc = _start_server("--max-retained-queries", "3")
+
+    yield proc
+
+    proc.terminate()
+    proc.wait()
+
+
+def _wait_for_removal(query_id):
+    deadline = time.time() + 10
+    while time.time() < deadline:
+        resp = requests.get(f"{BASE_URL}/query/{query_id}")
+        if resp.status_code == 404:
+            break
+        time.sleep(0.5)
+    return resp.status_code
+
+
+def _submit(query_definition):
+    data = {"queryDefinition": query_definition}
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert resp.status_code == 200
+    query_id = resp.json()
+    assert wait_for_final_status(query_id) == "COMPLETED"
+    return query_id
+
+
+def test_finished_query_removed_after_retention(server_with_retention):
+    table_name = "test_finished_query_removed_after_retention"
+    create_dummy_table(table_name)
+
+    data = {"queryDefinition": {"tableName": table_name}}
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    query_id = resp.json()
+    assert wait_for_final_status(query_id) == "COMPLETED"
+
+    assert _wait_for_removal(query_id) == 404
+    resp = requests.get(f"{BASE_URL}/result/{query_id}")
+    assert resp.status_code == 404
+
+    resp = requests.get(f"{BASE_URL}/tables")
+    assert table_name in [table["name"] for table in resp.json()]
+
+
+def test_removed_queries_release_result_and_snapshot_tables(
+    server_with_max_retained_queries,
+):
+    table_name = "test_removed_queries_release_result_and_snapshot_tables"
+    table_id = create_table(table_name, [{"name": "c1", "type": "INT64"}])
+    csv_path = os.path.join(os.getcwd(), "data", f"{table_name}.csv")
+    os.makedirs(os.path.dirname(csv_path), exist_ok=True)
+    with open(csv_path, "w", newline="") as f:
+        csv.writer(f).writerows([["1"], ["2"]])
+
+    # Reader keeps the table version it saw, so the copy leaves a snapshot behind.
+    select_all_id = _submit({"tableName": table_name})
+    copy_id = _submit(
+        {
+            "sourceFilepath": csv_path,
+            "destinationTableName": table_name,
+            "isolationLevel": "SNAPSHOT",
+        }
+    )
+    select_id = _submit(
+        {"columnClauses": [{"tableName": table_name, "columnName": "c1"}]}
+    )
+    stats = requests.get(f"{BASE_URL}/system/stats").json()
+    assert stats["snapshotTableCount"] >= 1
+    assert stats["scheduledForDeletionCount"] >= 2
+
+    # Newer queries push the three above out.
+    for _ in range(3):
+        _submit({"tableName": table_name})
+    for query_id in [select_all_id, copy_id, select_id]:
+        assert _wait_for_removal(query_id) == 404
+
+    stats = requests.get(f"{BASE_URL}/system/stats").json()
+    assert stats["snapshotTableCount"] == 0
+    assert stats["scheduledForDeletionCount"] == 0
+    resp = requests.get(f"{BASE_URL}/table/{table_id}")
+    assert resp.status_code == 200
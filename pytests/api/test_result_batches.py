@@ -0,0 +1,76 @@
+import csv
+import os
+import subprocess
+import time
+
+import pytest
+import requests
+from config import BASE_URL
+from utils import create_table, wait_for_final_status
+
+RESULT_BATCH_ROWS = 4
+
+
+@pytest.fixture(scope="module")
+def server_with_result_batches():
+    proc = subprocess.Popen(
+        ["cargo", "run", "--", "--result-batch-rows", str(RESULT_BATCH_ROWS)]
+    )
+
+    time.sleep(1)
+
+    yield proc
+
+    proc.terminate()
+    proc.wait()
+
+
+def _table_with_rows(table_name, row_count):
+    create_table(table_name, [{"name": "c1", "type": "INT64"}])
+    csv_path = os.path.abspath(f"{table_name}.csv")
+    with open(csv_path, "w", newline="") as f:
+        csv.writer(f).writerows([[str(i)] for i in range(row_count)])
+
+    data = {
+        "queryDefinition": {
+            "sourceFilepath": csv_path,
+            "destinationTableName": table_name,
+        }
+    }
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert resp.status_code == 200
+    assert wait_for_final_status(resp.json()) == "COMPLETED"
+    os.remove(csv_path)
+
+
+def _select_all(table_name):
+    data = {"queryDefinition": {"tableName": table_name}}
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert resp.status_code == 200
+    query_id = resp.json()
+    assert wait_for_final_status(query_id) == "COMPLETED"
+    return query_id
+
+
+def test_result_split_into_batches(server_with_result_batches):
+    table_name = "test_result_split_into_batches"
+    _table_with_rows(table_name, 10)
+    query_id = _select_all(table_name)
+
+    resp = requests.get(f"{BASE_URL}/result/{query_id}")
+    assert resp.status_code == 200
+
+    body = resp.json()
+    assert [part["rowCount"] for part in body] == [4, 4, 2]
+    values = [value for part in body for value in part["columns"][0]]
+    assert values == list(range(10))
+
+
+def test_result_within_batch_is_single_part(server_with_result_batches):
+    table_name = "test_result_within_batch_is_single_part"
+    _table_with_rows(table_name, 3)
+    query_id = _select_all(table_name)
+
+    resp = requests.get(f"{BASE_URL}/result/{query_id}")
+    assert resp.status_code == 200
+    assert [part["rowCount"] for part in resp.json()] == [3]
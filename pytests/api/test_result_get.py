@@ -1,6 +1,14 @@
+import csv
+import os
+
 import requests
 from config import BASE_URL
-from utils import create_dummy_table, wait_for_status
+from utils import (
+    create_dummy_table,
+    create_table,
+    wait_for_final_status,
+    wait_for_status,
+)
 
 
 def test_get_query_result_success(server):
@@ -27,3 +35,149 @@ def test_get_result_non_existent(server):
     id = "test_get_result_non_existent"
     resp = requests.get(f"{BASE_URL}/result/{id}")
     assert resp.status_code == 404
+
+
+def _copy_after_completed_select_all(table_name, csv_path, isolation_level):
+    create_table(table_name, [{"name": "c1", "type": "INT64"}])
+    with open(csv_path, "w", newline="") as f:
+        csv.writer(f).writerows([["1"], ["2"]])
+
+    resp = requests.post(
+        f"{BASE_URL}/query", json={"queryDefinition": {"tableName": table_name}}
+    )
+    select_id = resp.json()
+    assert wait_for_status(select_id, ["COMPLETED"]) == "COMPLETED"
+
+    data = {
+        "queryDefinition": {
+            "sourceFilepath": csv_path,
+            "destinationTableName": table_name,
+            "isolationLevel": isolation_level,
+        }
+    }
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert resp.status_code == 200
+    assert wait_for_final_status(resp.json()) == "COMPLETED"
+
+    resp = requests.get(f"{BASE_URL}/result/{select_id}")
+    assert resp.status_code == 200
+    return resp.json()
+
+
+def test_snapshot_reader_does_not_see_copy(server):
+    csv_path = os.path.join(os.getcwd(), "data", "test_isolation_snapshot.csv")
+    os.makedirs(os.path.dirname(csv_path), exist_ok=True)
+
+    body = _copy_after_completed_select_all("isolation_snapshot", csv_path, "SNAPSHOT")
+    assert body[0]["rowCount"] == 0
+
+
+def test_read_committed_reader_sees_copy(server):
+    csv_path = os.path.join(os.getcwd(), "data", "test_isolation_read_committed.csv")
+    os.makedirs(os.path.dirname(csv_path), exist_ok=True)
+
+    body = _copy_after_completed_select_all(
+        "isolation_read_committed", csv_path, "READ_COMMITTED"
+    )
+    assert body[0]["rowCount"] == 2
+    assert body[0]["columns"] == [[1, 2]]
+
+
+def test_get_query_result_after_flush(server):
+    table_name = "test_get_query_result_after_flush"
+    create_dummy_table(table_name)
+
+    data = {"queryDefinition": {"tableName": table_name}}
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    query_id = resp.json()
+    assert wait_for_final_status(query_id) == "COMPLETED"
+
+    resp = requests.get(f"{BASE_URL}/result/{query_id}", json={"flushResult": True})
+    assert resp.status_code == 200
+
+    resp = requests.get(f"{BASE_URL}/result/{query_id}")
+    assert resp.status_code == 400
+    assert resp.json() == {"message": "Result for this query is no longer available"}
+
+
+def _select_all_with_rows(table_name, rows):
+    create_dummy_table(table_name)
+    csv_path = os.path.join(os.getcwd(), "data", f"{table_name}.csv")
+    os.makedirs(os.path.dirname(csv_path), exist_ok=True)
+    with open(csv_path, "w", newline="") as f:
+        csv.writer(f).writerows(rows)
+
+    data = {
+        "queryDefinition": {"sourceFilepath": csv_path, "destinationTableName": table_name}
+    }
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert wait_for_final_status(resp.json()) == "COMPLETED"
+
+    resp = requests.post(
+        f"{BASE_URL}/query", json={"queryDefinition": {"tableName": table_name}}
+    )
+    query_id = resp.json()
+    assert wait_for_final_status(query_id) == "COMPLETED"
+    return query_id
+
+
+def test_get_query_result_column(server):
+    query_id = _select_all_with_rows(
+        "test_get_query_result_column", [["1", "a"], ["2", "b"], ["3", "c"]]
+    )
+
+    resp = requests.get(f"{BASE_URL}/result/{query_id}/column/col2")
+    assert resp.status_code == 200
+    assert resp.json() == [{"rowCount": 3, "columns": [["a", "b", "c"]]}]
+
+    resp = requests.get(
+        f"{BASE_URL}/result/{query_id}/column/col1", params={"rowLimit": 2}
+    )
+    assert resp.status_code == 200
+    assert resp.json() == [{"rowCount": 2, "columns": [[1, 2]]}]
+
+
+def test_get_query_result_unknown_column(server):
+    query_id = _select_all_with_rows("test_get_query_result_unknown_column", [["1", "a"]])
+
+    resp = requests.get(f"{BASE_URL}/result/{query_id}/column/missing")
+    assert resp.status_code == 400
+    assert resp.json() == {
+        "message": "Column 'missing' doesn't exist in result of this query"
+    }
+
+
+def test_get_query_result_with_predicate(server):
+    query_id = _select_all_with_rows(
+        "test_get_query_result_with_predicate",
+        [["1", "active"], ["2", "inactive"], ["3", "active"], ["4", "active"]],
+    )
+
+    predicate = {"columnName": "col2", "operator": "EQUAL", "value": "active"}
+    resp = requests.get(
+        f"{BASE_URL}/result/{query_id}", json={"predicate": predicate, "rowLimit": 2}
+    )
+    assert resp.status_code == 200
+    assert resp.json() == [{"rowCount": 2, "columns": [[1, 3], ["active", "active"]]}]
+
+    predicate = {"columnName": "col1", "operator": "GREATER_THAN", "value": 2}
+    resp = requests.get(f"{BASE_URL}/result/{query_id}", json={"predicate": predicate})
+    assert resp.status_code == 200
+    assert resp.json() == [{"rowCount": 2, "columns": [[3, 4], ["active", "active"]]}]
+
+    resp = requests.get(f"{BASE_URL}/result/{query_id}")
+    assert resp.status_code == 200
+    assert resp.json()[0]["rowCount"] == 4
+
+
+def test_get_query_result_predicate_type_mismatch(server):
+    query_id = _select_all_with_rows(
+        "test_get_query_result_predicate_type_mismatch", [["1", "a"]]
+    )
+
+    predicate = {"columnName": "col1", "operator": "EQUAL", "value": "a"}
+    resp = requests.get(f"{BASE_URL}/result/{query_id}", json={"predicate": predicate})
+    assert resp.status_code == 400
+    assert resp.json() == {
+        "message": "Predicate value has different type than column 'col1'"
+    }
@@ -0,0 +1,96 @@
+import shutil
+import subprocess
+import time
+
+import pytest
+import requests
+import urllib3
+from config import BASE_URL
+from utils import create_dummy_table
+
+HTTPS_URL = "https://127.0.0.1:8443"
+
+
+@pytest.fixture(scope="module")
+def tls_files(tmp_path_factory):
+    if shutil.which("openssl") is None:
+        pytest.skip("openssl is needed to create a test certificate")
+
+    tmp = tmp_path_factory.mktemp("tls")
+    key_file, cert_file = str(tmp / "key.pem"), str(tmp / "cert.pem")
+    subprocess.run(
+        [
+            "openssl", "req", "-x509", "-newkey", "rsa:2048", "-nodes",
+            "-keyout", key_file, "-out", cert_file,
+            "-days", "1", "-subj", "/CN=127.0.0.1",
+        ],
+        check=True,
+        capture_output=True,
+    )
+    return cert_file, key_file
+
+
+@pytest.fixture(scope="module")
+def server_with_both_listeners(tls_files):
+    cert_file, key_file = tls_files
+    proc = subprocess.Popen(
+        [
+            "cargo", "run", "--",
+            "--https-addr", "0.0.0.0:8443",
+            "--tls-cert", cert_file,
+            "--tls-key", key_file,
+        ]
+    )
+    time.sleep(1)
+
+    yield proc
+
+    proc.terminate()
+    proc.wait()
+
+
+def test_http_and_https_share_metastore(server_with_both_listeners):
+    urllib3.disable_warnings(urllib3.exceptions.InsecureRequestWarning)
+    table_id, _ = create_dummy_table("test_http_and_https_share_metastore")
+
+    resp = requests.get(f"{HTTPS_URL}/table/{table_id}", verify=False)
+    assert resp.status_code == 200
+    assert resp.json()["name"] == "test_http_and_https_share_metastore"
+
+    resp = requests.put(
+        f"{HTTPS_URL}/table",
+        json={
+            "name": "test_http_and_https_share_metastore_tls",
+            "columns": [{"name": "col1", "type": "INT64"}],
+        },
+        verify=False,
+    )
+    assert resp.status_code == 200
+
+    resp = requests.get(f"{BASE_URL}/table/{resp.json()}")
+    assert resp.status_code == 200
+
+
+def test_missing_tls_certificate_is_reported(tls_files):
+    _, key_file = tls_files
+    proc = subprocess.run(
+        [
+            "cargo", "run", "--",
+            "--https-addr", "0.0.0.0:8443",
+            "--tls-cert", "missing-cert.pem",
+            "--tls-key", key_file,
+        ],
+        capture_output=True,
+        text=True,
+        timeout=60,
+    )
+    assert proc.returncode == 1
+    assert "Can't read TLS file missing-cert.pem" in proc.stderr
+    assert "panicked" not in proc.stderr
+
+
+def test_port_in_use_is_reported(server_with_both_listeners):
+    proc = subprocess.run(["cargo", "run"], capture_output=True, text=True, timeout=60)
+    assert proc.returncode == 1
+    assert "Failed to start server: Failed to bind 0.0.0.0:8080" in proc.stderr
+    assert "panicked" not in proc.stderr
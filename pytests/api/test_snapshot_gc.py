@@ -0,0 +1,57 @@
+import csv
+import os
+
+import requests
+from config import BASE_URL
+from utils import create_table, wait_for_final_status
+
+TABLES_DIR = "tables"
+
+
+def _table_files():
+    return set(os.listdir(TABLES_DIR)) if os.path.exists(TABLES_DIR) else set()
+
+
+def test_flushing_last_reader_removes_snapshot(server):
+    table_name = "test_flushing_last_reader_removes_snapshot"
+    table_id = create_table(table_name, [{"name": "c1", "type": "INT64"}])
+
+    csv_path = os.path.join(os.getcwd(), "data", f"{table_name}.csv")
+    os.makedirs(os.path.dirname(csv_path), exist_ok=True)
+    with open(csv_path, "w", newline="") as f:
+        csv.writer(f).writerows([["1"], ["2"]])
+
+    resp = requests.post(
+        f"{BASE_URL}/query", json={"queryDefinition": {"tableName": table_name}}
+    )
+    select_id = resp.json()
+    assert wait_for_final_status(select_id) == "COMPLETED"
+
+    data = {
+        "queryDefinition": {
+            "sourceFilepath": csv_path,
+            "destinationTableName": table_name,
+            "isolationLevel": "SNAPSHOT",
+        }
+    }
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert wait_for_final_status(resp.json()) == "COMPLETED"
+
+    # Snapshot gets a file of its own once saved.
+    files_before = _table_files()
+    resp = requests.post(f"{BASE_URL}/system/persist")
+    assert resp.status_code == 200
+    snapshot_files = _table_files() - files_before - {f"{table_id}.isdb"}
+    assert len(snapshot_files) == 1
+    snapshot_file = os.path.join(TABLES_DIR, snapshot_files.pop())
+
+    resp = requests.get(f"{BASE_URL}/result/{select_id}", json={"flushResult": True})
+    assert resp.status_code == 200
+    assert resp.json()[0]["rowCount"] == 0
+
+    assert not os.path.exists(snapshot_file)
+    resp = requests.get(f"{BASE_URL}/table/{table_id}")
+    assert resp.status_code == 200
+    assert resp.json()["name"] == table_name
+    resp = requests.get(f"{BASE_URL}/system/stats")
+    assert resp.json()["snapshotTableCount"] == 0
@@ -0,0 +1,82 @@
+import os
+
+import requests
+from config import BASE_URL
+from utils import create_dummy_table, wait_for_final_status
+
+
+def _stats():
+    resp = requests.get(f"{BASE_URL}/system/stats")
+    assert resp.status_code == 200
+    body = resp.json()
+    body["queryCounts"] = {
+        count["status"]: count["count"] for count in body["queryCounts"]
+    }
+    return body
+
+
+def test_system_stats_reflect_tables_and_queries(server):
+    before = _stats()
+    assert set(before["queryCounts"]) == {
+        "CREATED",
+        "PLANNING",
+        "RUNNING",
+        "COMPLETED",
+        "FAILED",
+    }
+
+    kept_name = "test_system_stats_kept"
+    create_dummy_table(kept_name)
+    deleted_id, _ = create_dummy_table("test_system_stats_deleted")
+    resp = requests.delete(f"{BASE_URL}/table/{deleted_id}")
+    assert resp.status_code == 200
+
+    data = {"queryDefinition": {"tableName": kept_name}}
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert resp.status_code == 200
+    assert wait_for_final_status(resp.json()) == "COMPLETED"
+
+    after = _stats()
+    assert after["tableCount"] == before["tableCount"] + 1
+    assert after["queryCounts"]["COMPLETED"] == before["queryCounts"]["COMPLETED"] + 1
+    assert after["snapshotTableCount"] == before["snapshotTableCount"]
+    assert after["totalRows"] == before["totalRows"]
+
+
+def test_system_stats_count_compressed_bytes_per_codec(server):
+    table_name = "test_system_stats_codecs"
+    create_dummy_table(table_name)
+    rows = [(i, f"value{i}") for i in range(100)]
+    csv_path = os.path.join(os.getcwd(), "data", "test_system_stats_codecs.csv")
+    os.makedirs(os.path.dirname(csv_path), exist_ok=True)
+    with open(csv_path, "w") as f:
+        f.writelines(f"{number},{text}\n" for number, text in rows)
+
+    data = {
+        "queryDefinition": {
+            "sourceFilepath": csv_path,
+            "destinationTableName": table_name,
+        }
+    }
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert wait_for_final_status(resp.json()) == "COMPLETED"
+
+    codecs = {codec["codec"]: codec for codec in _stats()["codecStatistics"]}
+    assert set(codecs) == {
+        "VLE_DELTA",
+        "BIT_PACK",
+        "NONE_INT",
+        "LZ4",
+        "LZ4_FRAME",
+        "FRONT_CODED",
+        "NONE_STRING",
+    }
+
+    resp = requests.post(f"{BASE_URL}/system/persist")
+    assert resp.status_code == 200
+
+    after = {codec["codec"]: codec for codec in _stats()["codecStatistics"]}
+    string_bytes = sum(len(text) for _, text in rows)
+    assert after["VLE_DELTA"]["bytesIn"] >= codecs["VLE_DELTA"]["bytesIn"] + 8 * 100
+    assert after["LZ4"]["bytesIn"] >= codecs["LZ4"]["bytesIn"] + string_bytes
+    assert after["LZ4"]["bytesOut"] > codecs["LZ4"]["bytesOut"]
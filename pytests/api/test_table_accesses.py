@@ -0,0 +1,34 @@
+import requests
+from config import BASE_URL
+from utils import create_dummy_table, wait_for_final_status
+
+
+def test_select_all_holds_table_until_result_flush(server):
+    table_id, _ = create_dummy_table("test_select_all_holds_table_until_result_flush")
+
+    resp = requests.get(f"{BASE_URL}/table/{table_id}/accesses")
+    assert resp.status_code == 200
+    assert resp.json() == []
+
+    data = {
+        "queryDefinition": {"tableName": "test_select_all_holds_table_until_result_flush"}
+    }
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    query_id = resp.json()
+    assert wait_for_final_status(query_id) == "COMPLETED"
+
+    resp = requests.get(f"{BASE_URL}/table/{table_id}/accesses")
+    assert resp.status_code == 200
+    assert resp.json() == [query_id]
+
+    resp = requests.get(f"{BASE_URL}/result/{query_id}", json={"flushResult": True})
+    assert resp.status_code == 200
+
+    resp = requests.get(f"{BASE_URL}/table/{table_id}/accesses")
+    assert resp.status_code == 200
+    assert resp.json() == []
+
+
+def test_accesses_of_missing_table(server):
+    resp = requests.get(f"{BASE_URL}/table/test_accesses_of_missing_table/accesses")
+    assert resp.status_code == 404
@@ -0,0 +1,79 @@
+import csv
+import os
+
+import requests
+from config import BASE_URL
+from utils import create_dummy_table, wait_for_final_status
+
+
+def _copy_rows(table_name, rows):
+    csv_path = os.path.join(os.getcwd(), "data", f"{table_name}.csv")
+    os.makedirs(os.path.dirname(csv_path), exist_ok=True)
+    with open(csv_path, "w", newline="") as f:
+        csv.writer(f).writerows(rows)
+
+    data = {
+        "queryDefinition": {
+            "sourceFilepath": csv_path,
+            "destinationTableName": table_name,
+        }
+    }
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert wait_for_final_status(resp.json()) == "COMPLETED"
+
+
+def _select_all_columns(table_name):
+    resp = requests.post(
+        f"{BASE_URL}/query", json={"queryDefinition": {"tableName": table_name}}
+    )
+    select_id = resp.json()
+    assert wait_for_final_status(select_id) == "COMPLETED"
+    return requests.get(f"{BASE_URL}/result/{select_id}").json()[0]["columns"]
+
+
+def test_duplicate_is_independent(server):
+    source_name = "test_duplicate_is_independent"
+    copy_name = f"{source_name}_copy"
+    source_id, _ = create_dummy_table(source_name)
+    _copy_rows(source_name, [["1", "a"], ["2", "b"]])
+
+    resp = requests.post(
+        f"{BASE_URL}/table/{source_id}/duplicate", json={"name": copy_name}
+    )
+    assert resp.status_code == 200
+    copy_id = resp.json()
+    assert copy_id != source_id
+    assert _select_all_columns(copy_name) == [[1, 2], ["a", "b"]]
+
+    _copy_rows(copy_name, [["3", "c"]])
+    resp = requests.patch(
+        f"{BASE_URL}/table/{copy_id}/column/col1", json={"newName": "id"}
+    )
+    assert resp.status_code == 200
+
+    assert _select_all_columns(copy_name) == [[1, 2, 3], ["a", "b", "c"]]
+    assert _select_all_columns(source_name) == [[1, 2], ["a", "b"]]
+    resp = requests.get(f"{BASE_URL}/table/{source_id}")
+    assert [column["name"] for column in resp.json()["columns"]] == ["col1", "col2"]
+
+
+def test_duplicate_with_taken_name(server):
+    source_id, _ = create_dummy_table("test_duplicate_with_taken_name")
+    create_dummy_table("test_duplicate_with_taken_name_other")
+
+    resp = requests.post(
+        f"{BASE_URL}/table/{source_id}/duplicate",
+        json={"name": "test_duplicate_with_taken_name_other"},
+    )
+    assert resp.status_code == 400
+    assert resp.json()["problems"] == [
+        {"error": "Table with given name already exists"}
+    ]
+
+
+def test_duplicate_missing_table(server):
+    resp = requests.post(
+        f"{BASE_URL}/table/test_duplicate_missing_table/duplicate",
+        json={"name": "test_duplicate_missing_table_copy"},
+    )
+    assert resp.status_code == 404
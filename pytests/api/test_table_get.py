@@ -28,7 +28,10 @@ def test_get_non_existence_table(server):
     id = "test_get_non_existence_table"
     resp = requests.get(f"{BASE_URL}/table/{id}")
     assert resp.status_code == 404
-    assert resp.json() == {"message": "Couldn't find a table of given ID"}
+    assert resp.json() == {
+        "message": "Couldn't find a table of given ID",
+        "context": id,
+    }
 
 
 def test_get_table_after_delete(server):
@@ -44,4 +47,50 @@ def test_get_table_after_delete(server):
 
     resp = requests.get(f"{BASE_URL}/table/{id}")
     assert resp.status_code == 404
-    assert resp.json() == {"message": "Couldn't find a table of given ID"}
+    assert resp.json() == {
+        "message": "Couldn't find a table of given ID",
+        "context": id,
+    }
+
+
+def test_get_table_column_metadata(server):
+    data = {
+        "name": "test_get_table_column_metadata",
+        "columns": [
+            {"name": "price", "type": "INT64", "metadata": "price in cents"},
+            {"name": "label", "type": "VARCHAR"},
+        ],
+    }
+    resp = requests.put(f"{BASE_URL}/table", json=data)
+    assert resp.status_code == 200
+    id = resp.json()
+
+    resp = requests.get(f"{BASE_URL}/table/{id}")
+    assert resp.status_code == 200
+
+    columns = {column["name"]: column for column in resp.json()["columns"]}
+    assert columns["price"]["metadata"] == "price in cents"
+    assert "metadata" not in columns["label"]
+
+
+def test_head_table_exists(server):
+    data = {"name": "test_head_table_exists", "columns": [
+        {"name": "col1", "type": "VARCHAR"},
+    ]}
+    resp = requests.put(f"{BASE_URL}/table", json=data)
+    assert resp.status_code == 200
+    id = resp.json()
+
+    resp = requests.head(f"{BASE_URL}/table/{id}")
+    assert resp.status_code == 200
+
+    resp = requests.delete(f"{BASE_URL}/table/{id}")
+    assert resp.status_code == 200
+
+    resp = requests.head(f"{BASE_URL}/table/{id}")
+    assert resp.status_code == 404
+
+
+def test_head_non_existence_table(server):
+    resp = requests.head(f"{BASE_URL}/table/test_head_non_existence_table")
+    assert resp.status_code == 404
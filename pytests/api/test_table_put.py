@@ -81,3 +81,36 @@ def test_put_table_with_multiple_errors(server):
         ],
         key=lambda x: (x["error"], x.get("context", "")),
     )
+
+
+def test_put_table_without_columns(server):
+    data = {"name": "test_put_table_without_columns", "columns": []}
+    resp = requests.put(f"{BASE_URL}/table", json=data)
+    assert resp.status_code == 400
+
+    body = resp.json()
+    assert body["problems"] == [{"error": "Table has no columns"}]
+
+
+def test_put_table_with_too_long_column_name(server):
+    long_name = "c" * 256
+    data = {
+        "name": "test_put_table_with_too_long_column_name",
+        "columns": [
+            {"name": "col1", "type": "INT64"},
+            {"name": long_name, "type": "VARCHAR"},
+        ],
+    }
+    resp = requests.put(f"{BASE_URL}/table", json=data)
+    assert resp.status_code == 400
+
+    body = resp.json()
+    assert body["problems"] == [
+        {"error": "Column name is longer than 255 bytes", "context": long_name}
+    ]
+
+    resp = requests.get(f"{BASE_URL}/tables")
+    assert resp.status_code == 200
+    assert "test_put_table_with_too_long_column_name" not in [
+        table["name"] for table in resp.json()
+    ]
@@ -0,0 +1,70 @@
+import csv
+import os
+
+import requests
+from config import BASE_URL
+from utils import create_dummy_table, wait_for_final_status
+
+
+def _select_all(table_name):
+    data = {"queryDefinition": {"tableName": table_name}}
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert resp.status_code == 200
+    query_id = resp.json()
+    assert wait_for_final_status(query_id) == "COMPLETED"
+    return query_id
+
+
+def test_unloaded_table_is_reloaded_on_access(server):
+    table_name = "test_unloaded_table_is_reloaded_on_access"
+    table_id, _ = create_dummy_table(table_name)
+
+    csv_path = os.path.join(os.getcwd(), "data", f"{table_name}.csv")
+    os.makedirs(os.path.dirname(csv_path), exist_ok=True)
+    with open(csv_path, "w", newline="") as f:
+        csv.writer(f).writerows([["1", "a"], ["2", "bc"], ["3", ""]])
+
+    data = {
+        "queryDefinition": {
+            "sourceFilepath": csv_path,
+            "destinationTableName": table_name,
+        }
+    }
+    resp = requests.post(f"{BASE_URL}/query", json=data)
+    assert wait_for_final_status(resp.json()) == "COMPLETED"
+
+    resp = requests.post(f"{BASE_URL}/table/{table_id}/unload")
+    assert resp.status_code == 200
+    assert os.path.exists(os.path.join("tables", f"{table_id}.isdb"))
+
+    resp = requests.get(f"{BASE_URL}/table/{table_id}")
+    assert resp.status_code == 200
+    assert [column["name"] for column in resp.json()["columns"]] == ["col1", "col2"]
+
+    query_id = _select_all(table_name)
+    resp = requests.get(f"{BASE_URL}/result/{query_id}", json={"flushResult": True})
+    assert resp.status_code == 200
+    assert resp.json() == [{"rowCount": 3, "columns": [[1, 2, 3], ["a", "bc", ""]]}]
+
+
+def test_unload_table_accessed_by_query(server):
+    table_name = "test_unload_table_accessed_by_query"
+    table_id, _ = create_dummy_table(table_name)
+    query_id = _select_all(table_name)
+
+    resp = requests.post(f"{BASE_URL}/table/{table_id}/unload")
+    assert resp.status_code == 409
+    assert resp.json() == {
+        "message": "Table is accessed by a query",
+        "context": table_id,
+    }
+
+    resp = requests.get(f"{BASE_URL}/result/{query_id}", json={"flushResult": True})
+    assert resp.status_code == 200
+    resp = requests.post(f"{BASE_URL}/table/{table_id}/unload")
+    assert resp.status_code == 200
+
+
+def test_unload_missing_table(server):
+    resp = requests.post(f"{BASE_URL}/table/test_unload_missing_table/unload")
+    assert resp.status_code == 404
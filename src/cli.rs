@@ -0,0 +1,149 @@
+use std::{fs::File, io::BufWriter, path::Path};
+
+use csv::WriterBuilder;
+use lib::{
+    ColumnData, CsvOptions, Serializer, SerializerError, Table,
+    compress::{LZ4FrameStringCompressor, StringCompressors},
+};
+
+fn type_name(data: &ColumnData) -> &'static str {
+    match data {
+        ColumnData::INT64(_) => "INT64",
+        ColumnData::STR(_) => "VARCHAR",
+        ColumnData::BOOL(_) => "BOOL",
+    }
+}
+
+pub fn inspect(path: &str) -> Result<(), SerializerError> {
+    let header = Serializer::new().read_schema(Path::new(path))?;
+
+    println!("File: {}", path);
+    println!("Version: {}", header.version);
+    println!("Rows: {}", header.num_rows);
+    if let Some(string_compressor) = &header.string_compressor {
+        println!("String codec: {}", string_compressor.codec().name());
+    }
+    println!("Columns: {}", header.columns.len());
+    for (i, column) in header.columns.iter().enumerate() {
+        println!(
+            "  [{}] {} {} offset={} length={} lengths_length={}",
+            i,
+            column.name,
+            type_name(&column.data),
+            column.offset,
+            column.length,
+            column.length2
+        );
+        if column.rank_length > 0 {
+            println!("      sorted, rank_length={}", column.rank_length);
+        }
+        if let Some(metadata) = &column.metadata {
+            println!("      metadata: {}", metadata);
+        }
+        if let Some(checksum) = column.checksum {
+            println!("      checksum: {:016x}", checksum);
+        }
+        if let Some(stats) = column.stats {
+            println!("      min={} max={}", stats.min, stats.max);
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_delimiter(delimiter: &str) -> Result<u8, String> {
+    match delimiter.as_bytes() {
+        [byte] => Ok(*byte),
+        _ => Err(format!(
+            "Delimiter must be a single byte, got '{}'",
+            delimiter
+        )),
+    }
+}
+
+pub fn convert(
+    csv_path: &str,
+    isdb_path: &str,
+    has_headers: bool,
+    delimiter: &str,
+    thousands_separator: Option<&str>,
+    no_compression: bool,
+    lz4_frame: bool,
+) -> Result<(), String> {
+    let delimiter = parse_delimiter(delimiter)?;
+    let thousands_separator = thousands_separator
+        .map(|separator| match separator.as_bytes() {
+            [byte] => Ok(char::from(*byte)),
+            _ => Err(format!(
+                "Thousands separator must be a single byte, got '{}'",
+                separator
+            )),
+        })
+        .transpose()?;
+
+    let file = File::open(csv_path).map_err(|e| e.to_string())?;
+    let options = CsvOptions {
+        has_headers,
+        delimiter,
+        thousands_separator,
+    };
+    let table = Table::from_csv_reader(file, &options).map_err(|e| e.to_string())?;
+
+    let serializer = if no_compression {
+        Serializer::no_compression()
+    } else if lz4_frame {
+        Serializer::builder()
+            .string_compressor(StringCompressors::Lz4Frame(LZ4FrameStringCompressor))
+            .build()
+    } else {
+        Serializer::new()
+    };
+    serializer
+        .serialize(Path::new(isdb_path), &table)
+        .map_err(|e| e.to_string())?;
+
+    println!(
+        "Converted {} rows and {} columns into {}",
+        table.get_num_rows(),
+        table.get_num_cols(),
+        isdb_path
+    );
+    Ok(())
+}
+
+pub fn export(
+    isdb_path: &str,
+    csv_path: &str,
+    has_headers: bool,
+    delimiter: &str,
+) -> Result<(), String> {
+    let delimiter = parse_delimiter(delimiter)?;
+
+    let serializer = Serializer::new();
+    let path = Path::new(isdb_path);
+    let header = serializer.read_schema(path).map_err(|e| e.to_string())?;
+    let rows = serializer.stream_rows(path).map_err(|e| e.to_string())?;
+
+    let file = File::create(csv_path).map_err(|e| e.to_string())?;
+    let mut wtr = WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(BufWriter::new(file));
+    if has_headers {
+        wtr.write_record(header.columns.iter().map(|column| column.name.as_str()))
+            .map_err(|e| e.to_string())?;
+    }
+    for row in rows {
+        let row = row.map_err(|e| e.to_string())?;
+        wtr.write_record(row.iter().map(|value| value.to_string()))
+            .map_err(|e| e.to_string())?;
+    }
+    wtr.flush().map_err(|e| e.to_string())?;
+
+    println!(
+        "Exported {} rows and {} columns into {}",
+        header.num_rows,
+        header.columns.len(),
+        csv_path
+    );
+    Ok(())
+}
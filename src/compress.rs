@@ -1,68 +1,383 @@
-use std::string::FromUtf8Error;
+use std::{
+    borrow::Cow,
+    fmt::Display,
+    io::{Read, Write},
+    string::FromUtf8Error,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 use integer_encoding::VarInt;
-use lz4_flex::block::{DecompressError, compress_prepend_size, decompress_size_prepended};
+use lz4_flex::{
+    block::{
+        DecompressError, compress_prepend_size, decompress_size_prepended, get_maximum_output_size,
+    },
+    frame::{FrameDecoder, FrameEncoder},
+};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum StringCompressors {
     Lz4(LZ4StringCompressor),
+    Lz4Frame(LZ4FrameStringCompressor),
+    FrontCoded(FrontCodedStringCompressor),
     None(NoStringCompressor),
 }
 
 impl StringCompressors {
-    pub fn compress(&self, data: &[String]) -> Result<CompressedStringColumn, CompressorError> {
+    const NONE_MODE: u8 = 0;
+    const LZ4_MODE: u8 = 1;
+    const LZ4_FRAME_MODE: u8 = 2;
+    const FRONT_CODED_MODE: u8 = 3;
+
+    /// Id of the codec stored in file headers.
+    pub fn mode(&self) -> u8 {
         match self {
-            StringCompressors::Lz4(c) => c.compress(data),
-            StringCompressors::None(c) => c.compress(data),
+            StringCompressors::Lz4(_) => Self::LZ4_MODE,
+            StringCompressors::Lz4Frame(_) => Self::LZ4_FRAME_MODE,
+            StringCompressors::FrontCoded(_) => Self::FRONT_CODED_MODE,
+            StringCompressors::None(_) => Self::NONE_MODE,
         }
     }
 
+    pub fn from_mode(mode: u8) -> Result<Self, CompressorError> {
+        match mode {
+            Self::LZ4_MODE => Ok(StringCompressors::Lz4(LZ4StringCompressor)),
+            Self::LZ4_FRAME_MODE => Ok(StringCompressors::Lz4Frame(LZ4FrameStringCompressor)),
+            Self::FRONT_CODED_MODE => Ok(StringCompressors::FrontCoded(FrontCodedStringCompressor)),
+            Self::NONE_MODE => Ok(StringCompressors::None(NoStringCompressor)),
+            _ => Err(CompressorError::UnknownCodec(format!(
+                "Unknown string codec mode: {}",
+                mode
+            ))),
+        }
+    }
+
+    pub fn codec(&self) -> Codec {
+        match self {
+            StringCompressors::Lz4(_) => Codec::Lz4,
+            StringCompressors::Lz4Frame(_) => Codec::Lz4Frame,
+            StringCompressors::FrontCoded(_) => Codec::FrontCoded,
+            StringCompressors::None(_) => Codec::NoString,
+        }
+    }
+
+    /// Counted in the codec counters as string bytes in and compressed bytes out, lengths are
+    /// counted by the int codec storing them.
+    pub fn compress(&self, data: &[String]) -> Result<CompressedStringColumn, CompressorError> {
+        let compressed = match self {
+            StringCompressors::Lz4(c) => c.compress(data)?,
+            StringCompressors::Lz4Frame(c) => c.compress(data)?,
+            StringCompressors::FrontCoded(c) => c.compress(data)?,
+            StringCompressors::None(c) => c.compress(data)?,
+        };
+
+        self.codec()
+            .record(data.iter().map(String::len).sum(), compressed.data.len());
+        Ok(compressed)
+    }
+
     pub fn decompress(
         &self,
         data: &CompressedStringColumn,
     ) -> Result<Vec<String>, CompressorError> {
         match self {
             StringCompressors::Lz4(c) => c.decompress(data),
+            StringCompressors::Lz4Frame(c) => c.decompress(data),
+            StringCompressors::FrontCoded(c) => c.decompress(data),
             StringCompressors::None(c) => c.decompress(data),
         }
     }
+
+    /// Size of compressed string bytes, without lengths (those go through an int compressor).
+    pub fn estimate_compressed_size(&self, data: &[String]) -> usize {
+        match self {
+            StringCompressors::Lz4(c) => c.estimate_compressed_size(data),
+            StringCompressors::Lz4Frame(c) => c.estimate_compressed_size(data),
+            StringCompressors::FrontCoded(c) => c.estimate_compressed_size(data),
+            StringCompressors::None(c) => c.estimate_compressed_size(data),
+        }
+    }
+
+    /// Bytes of all strings one after another, together with lengths of the strings.
+    /// Uncompressed data is borrowed, other codecs decompress into a single buffer instead of
+    /// allocating every string separately (except front coding which needs whole strings).
+    pub fn decompress_concatenated<'a>(
+        &self,
+        data: &'a [u8],
+        lengths: Vec<i64>,
+    ) -> Result<(Cow<'a, [u8]>, Vec<i64>), CompressorError> {
+        match self {
+            StringCompressors::Lz4(_) => {
+                Ok((Cow::Owned(decompress_size_prepended(data)?), lengths))
+            }
+            StringCompressors::Lz4Frame(_) => Ok((Cow::Owned(decompress_frame(data)?), lengths)),
+            StringCompressors::None(_) => Ok((Cow::Borrowed(data), lengths)),
+            StringCompressors::FrontCoded(c) => {
+                let strings =
+                    c.decompress(&CompressedStringColumn::new(data.to_vec(), lengths)?)?;
+                let lengths = strings.iter().map(|s| s.len() as i64).collect();
+                Ok((Cow::Owned(strings.concat().into_bytes()), lengths))
+            }
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum IntCompressors {
     VleDelta(VleDeltaIntCompressor),
+    BitPack(BitPackIntCompressor),
     None(NoIntCompressor),
 }
 
+/// Compressed int data starts with a mode byte naming the codec that wrote it, so data can be
+/// decompressed by any `IntCompressors` regardless of the variant it was created with.
 impl IntCompressors {
+    const NONE_MODE: u8 = 0;
+    const VLE_DELTA_MODE: u8 = 1;
+    const BIT_PACK_MODE: u8 = 2;
+
+    fn mode(&self) -> u8 {
+        match self {
+            IntCompressors::VleDelta(_) => Self::VLE_DELTA_MODE,
+            IntCompressors::BitPack(_) => Self::BIT_PACK_MODE,
+            IntCompressors::None(_) => Self::NONE_MODE,
+        }
+    }
+
+    pub fn codec(&self) -> Codec {
+        match self {
+            IntCompressors::VleDelta(_) => Codec::VleDelta,
+            IntCompressors::BitPack(_) => Codec::BitPack,
+            IntCompressors::None(_) => Codec::NoInt,
+        }
+    }
+
+    /// Counted in the codec counters as 8 bytes per value in and codec output (without the
+    /// mode byte) out.
     pub fn compress(&self, data: &[i64]) -> Result<Vec<u8>, CompressorError> {
+        let compressed = match self {
+            IntCompressors::VleDelta(c) => c.compress(data)?,
+            IntCompressors::BitPack(c) => c.compress(data)?,
+            IntCompressors::None(c) => c.compress(data)?,
+        };
+        self.codec().record(data.len() * 8, compressed.len());
+
+        let mut res = Vec::with_capacity(compressed.len() + 1);
+        res.push(self.mode());
+        res.extend(compressed);
+        Ok(res)
+    }
+
+    /// Fails before decoding when the data claims more than `max_len` values, the row count
+    /// known to the caller. Bit packed equal values take no bits each, so the data alone can't
+    /// tell a huge claimed count from a real one.
+    pub fn decompress(&self, data: &[u8], max_len: usize) -> Result<Vec<i64>, CompressorError> {
+        let (&mode, rest) = data.split_first().ok_or(CompressorError::WrongDataLength(
+            "Missing int codec mode byte".to_string(),
+        ))?;
+
+        match mode {
+            Self::VLE_DELTA_MODE => VleDeltaIntCompressor.decode(rest),
+            Self::BIT_PACK_MODE => BitPackIntCompressor.decode(rest, max_len),
+            Self::NONE_MODE => NoIntCompressor.decode(rest),
+            _ => Err(CompressorError::UnknownCodec(format!(
+                "Unknown int codec mode: {}",
+                mode
+            ))),
+        }
+    }
+
+    /// Codec with the smallest `estimate_compressed_size` for `data`, earlier variants win ties.
+    /// Every int estimate is exact, so this is the codec producing the fewest bytes.
+    pub fn smallest_for(data: &[i64]) -> Self {
+        [
+            IntCompressors::VleDelta(VleDeltaIntCompressor),
+            IntCompressors::BitPack(BitPackIntCompressor),
+            IntCompressors::None(NoIntCompressor),
+        ]
+        .into_iter()
+        .min_by_key(|compressor| compressor.estimate_compressed_size(data))
+        .unwrap()
+    }
+
+    /// Position of value `index` in `compress` output whose first byte is `mode`, for codecs
+    /// storing every value at a fixed position. `None` when the data has to be decoded from the
+    /// start.
+    pub fn value_position(mode: u8, index: usize) -> Option<usize> {
+        (mode == Self::NONE_MODE).then(|| 1 + index * 8)
+    }
+
+    /// Size of `compress` output, mode byte included.
+    pub fn estimate_compressed_size(&self, data: &[i64]) -> usize {
+        1 + match self {
+            IntCompressors::VleDelta(c) => c.estimate_compressed_size(data),
+            IntCompressors::BitPack(c) => c.estimate_compressed_size(data),
+            IntCompressors::None(c) => c.estimate_compressed_size(data),
+        }
+    }
+
+    /// Decompresses data written before mode bytes existed, trusting the codec of `self`.
+    /// `max_len` bounds the values like in `decompress`.
+    pub fn decompress_unprefixed(
+        &self,
+        data: &[u8],
+        max_len: usize,
+    ) -> Result<Vec<i64>, CompressorError> {
         match self {
-            IntCompressors::VleDelta(c) => c.compress(data),
-            IntCompressors::None(c) => c.compress(data),
+            IntCompressors::VleDelta(c) => c.decode(data),
+            IntCompressors::BitPack(c) => c.decode(data, max_len),
+            IntCompressors::None(c) => c.decode(data),
         }
     }
+}
+
+/// Every codec a column can be compressed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    VleDelta,
+    BitPack,
+    NoInt,
+    Lz4,
+    Lz4Frame,
+    FrontCoded,
+    NoString,
+}
+
+/// Totals of all `compress` calls of a codec in this process.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CodecCounters {
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+}
+
+struct AtomicCodecCounters {
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+}
+
+/// Indexed by `Codec as usize`, shared by all compressors.
+static CODEC_COUNTERS: [AtomicCodecCounters; Codec::ALL.len()] = [const {
+    AtomicCodecCounters {
+        bytes_in: AtomicU64::new(0),
+        bytes_out: AtomicU64::new(0),
+    }
+}; Codec::ALL.len()];
+
+impl Codec {
+    pub const ALL: [Codec; 7] = [
+        Codec::VleDelta,
+        Codec::BitPack,
+        Codec::NoInt,
+        Codec::Lz4,
+        Codec::Lz4Frame,
+        Codec::FrontCoded,
+        Codec::NoString,
+    ];
 
-    pub fn decompress(&self, data: &Vec<u8>) -> Result<Vec<i64>, CompressorError> {
+    pub fn name(&self) -> &'static str {
         match self {
-            IntCompressors::VleDelta(c) => c.decompress(data),
-            IntCompressors::None(c) => c.decompress(data),
+            Codec::VleDelta => "VLE_DELTA",
+            Codec::BitPack => "BIT_PACK",
+            Codec::NoInt => "NONE_INT",
+            Codec::Lz4 => "LZ4",
+            Codec::Lz4Frame => "LZ4_FRAME",
+            Codec::FrontCoded => "FRONT_CODED",
+            Codec::NoString => "NONE_STRING",
+        }
+    }
+
+    pub fn counters(&self) -> CodecCounters {
+        let counters = &CODEC_COUNTERS[*self as usize];
+        CodecCounters {
+            bytes_in: counters.bytes_in.load(Ordering::Relaxed),
+            bytes_out: counters.bytes_out.load(Ordering::Relaxed),
         }
     }
+
+    fn record(&self, bytes_in: usize, bytes_out: usize) {
+        let counters = &CODEC_COUNTERS[*self as usize];
+        counters
+            .bytes_in
+            .fetch_add(bytes_in as u64, Ordering::Relaxed);
+        counters
+            .bytes_out
+            .fetch_add(bytes_out as u64, Ordering::Relaxed);
+    }
 }
 
+/// Compressed string bytes with the (uncompressed) length of every string. Fields are private so
+/// lengths read from a file are always checked by `new` before a codec gets to them.
 #[derive(Debug)]
 pub struct CompressedStringColumn {
-    pub data: Vec<u8>,
-    pub lengths: Vec<i64>,
+    data: Vec<u8>,
+    lengths: Vec<i64>,
+}
+
+impl CompressedStringColumn {
+    /// Fails when a length is negative or all of them together don't fit in memory.
+    pub fn new(data: Vec<u8>, lengths: Vec<i64>) -> Result<Self, CompressorError> {
+        let mut total = 0usize;
+        for &len in &lengths {
+            let len = usize::try_from(len).map_err(|_| {
+                CompressorError::NegativeStringLength(format!("{} was passed", len))
+            })?;
+            total = total
+                .checked_add(len)
+                .ok_or(CompressorError::WrongDataLength(
+                    "String lengths add up to more than fits in memory".to_string(),
+                ))?;
+        }
+
+        Ok(Self { data, lengths })
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn lengths(&self) -> &[i64] {
+        &self.lengths
+    }
+
+    pub fn into_parts(self) -> (Vec<u8>, Vec<i64>) {
+        (self.data, self.lengths)
+    }
 }
 
 #[derive(Debug)]
 pub enum CompressorError {
     Lz4Decompression(DecompressError),
+    Lz4Frame(std::io::Error),
     Utf8Decoding(FromUtf8Error),
     VleDecoding(String),
     WrongDataLength(String),
     NegativeStringLength(String),
+    UnknownCodec(String),
+}
+
+impl Display for CompressorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Lz4Decompression(e) => write!(f, "LZ4 decompression failed: {}", e),
+            Self::Lz4Frame(e) => write!(f, "LZ4 frame coding failed: {}", e),
+            Self::Utf8Decoding(e) => write!(f, "String is not valid UTF-8: {}", e),
+            Self::VleDecoding(msg) => write!(f, "VLE decoding failed: {}", msg),
+            Self::WrongDataLength(msg) => write!(f, "Wrong data length: {}", msg),
+            Self::NegativeStringLength(msg) => write!(f, "Negative string length: {}", msg),
+            // Message already names the codec and the mode byte found.
+            Self::UnknownCodec(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for CompressorError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Lz4Decompression(e) => Some(e),
+            Self::Lz4Frame(e) => Some(e),
+            Self::Utf8Decoding(e) => Some(e),
+            _ => None,
+        }
+    }
 }
 
 impl From<DecompressError> for CompressorError {
@@ -84,7 +399,12 @@ pub trait Compressor<T> {
     fn decompress(&self, compressed: &Self::Compressed) -> Result<Vec<T>, CompressorError>;
 }
 
-#[derive(Debug)]
+/// Stores differences between consecutive values as variable length integers.
+///
+/// Deltas are computed with `wrapping_sub` and summed back with `wrapping_add`, so a jump like
+/// `i64::MIN` -> `i64::MAX` is stored as a small wrapped delta and every input round-trips
+/// exactly. Both sides have to keep wrapping, changing one of them breaks existing files.
+#[derive(Debug, Clone)]
 pub struct VleDeltaIntCompressor;
 
 impl Compressor<i64> for VleDeltaIntCompressor {
@@ -95,7 +415,7 @@ impl Compressor<i64> for VleDeltaIntCompressor {
         let mut last = 0i64;
 
         for &d in data {
-            deltas.push(d - last);
+            deltas.push(d.wrapping_sub(last));
             last = d;
         }
 
@@ -103,29 +423,162 @@ impl Compressor<i64> for VleDeltaIntCompressor {
     }
 
     fn decompress(&self, compressed: &Self::Compressed) -> Result<Vec<i64>, CompressorError> {
-        let mut cursor = &compressed[..];
-        let mut deltas = Vec::<i64>::new();
+        self.decode(compressed)
+    }
+}
+
+impl VleDeltaIntCompressor {
+    /// Decodes straight from the slice, summing deltas as they are read.
+    fn decode(&self, compressed: &[u8]) -> Result<Vec<i64>, CompressorError> {
+        let mut cursor = compressed;
+        let mut data = Vec::<i64>::new();
+        let mut last = 0i64;
         while !cursor.is_empty() {
-            let (d, n) = i64::decode_var(&cursor).ok_or(CompressorError::VleDecoding(
+            let (delta, n) = i64::decode_var(cursor).ok_or(CompressorError::VleDecoding(
                 "Decoder stopped before going through all data".to_string(),
             ))?;
-            deltas.push(d);
+            last = last.wrapping_add(delta);
+            data.push(last);
             cursor = &cursor[n..];
         }
 
-        let mut data = Vec::<i64>::with_capacity(deltas.len());
+        Ok(data)
+    }
+
+    /// Exact, sums varint lengths of the deltas.
+    pub fn estimate_compressed_size(&self, data: &[i64]) -> usize {
         let mut last = 0i64;
+        data.iter()
+            .map(|&d| {
+                let delta = d.wrapping_sub(last);
+                last = d;
+                delta.required_space()
+            })
+            .sum()
+    }
+}
+
+/// Stores every value as its offset from the column minimum, packed LSB first into as many
+/// bits as the largest offset needs.
+///
+/// Layout is a VarInt row count, the VarInt minimum and one byte with the bit width, followed
+/// by the bitstream. A column of equal values has width 0 and stores no bitstream at all.
+/// Offsets are computed with `wrapping_sub` and read as unsigned, so negative minimums and
+/// ranges spanning all of `i64` round-trip exactly.
+#[derive(Debug, Clone)]
+pub struct BitPackIntCompressor;
+
+impl BitPackIntCompressor {
+    fn frame(data: &[i64]) -> (i64, u8) {
+        let min = data.iter().copied().min().unwrap_or(0);
+        let max = data.iter().copied().max().unwrap_or(0);
+        let range = max.wrapping_sub(min) as u64;
+        (min, (u64::BITS - range.leading_zeros()) as u8)
+    }
 
-        for delta in deltas {
-            data.push(delta + last);
-            last += delta;
+    /// Exact, header plus `width` bits per value rounded up to whole bytes.
+    pub fn estimate_compressed_size(&self, data: &[i64]) -> usize {
+        let (min, width) = Self::frame(data);
+        (data.len() as u64).required_space()
+            + min.required_space()
+            + 1
+            + (data.len() * width as usize).div_ceil(8)
+    }
+
+    /// Decodes at most `max_len` values, checked before anything is allocated.
+    fn decode(&self, compressed: &[u8], max_len: usize) -> Result<Vec<i64>, CompressorError> {
+        let (len, n) = u64::decode_var(compressed).ok_or(CompressorError::VleDecoding(
+            "Missing bit packed row count".to_string(),
+        ))?;
+        let len = usize::try_from(len)
+            .ok()
+            .filter(|&len| len <= max_len)
+            .ok_or_else(|| {
+                CompressorError::WrongDataLength(format!(
+                    "Bit packed row count {} is larger than {}",
+                    len, max_len
+                ))
+            })?;
+        let cursor = &compressed[n..];
+        let (min, n) = i64::decode_var(cursor).ok_or(CompressorError::VleDecoding(
+            "Missing bit packed minimum".to_string(),
+        ))?;
+        let (&width, packed) =
+            cursor[n..]
+                .split_first()
+                .ok_or(CompressorError::WrongDataLength(
+                    "Missing bit packed width".to_string(),
+                ))?;
+
+        if width > 64 {
+            return Err(CompressorError::WrongDataLength(format!(
+                "Bit width {} is wider than 64 bits",
+                width
+            )));
+        }
+        let packed_len = len.checked_mul(width as usize).map(|bits| bits.div_ceil(8));
+        if packed_len != Some(packed.len()) {
+            return Err(CompressorError::WrongDataLength(
+                "Bit packed data length does not match row count and width".to_string(),
+            ));
+        }
+
+        let mut data = Vec::new();
+        data.try_reserve_exact(len).map_err(|_| {
+            CompressorError::WrongDataLength(format!("Bit packed row count {} is too large", len))
+        })?;
+        let mut bit = 0usize;
+        for _ in 0..len {
+            let mut offset = 0u64;
+            for i in 0..width as usize {
+                if packed[(bit + i) / 8] >> ((bit + i) % 8) & 1 == 1 {
+                    offset |= 1 << i;
+                }
+            }
+            data.push(min.wrapping_add(offset as i64));
+            bit += width as usize;
         }
 
         Ok(data)
     }
 }
 
-#[derive(Debug)]
+impl Compressor<i64> for BitPackIntCompressor {
+    type Compressed = Vec<u8>;
+
+    fn compress(&self, data: &[i64]) -> Result<Self::Compressed, CompressorError> {
+        let (min, width) = Self::frame(data);
+        let mut res = (data.len() as u64).encode_var_vec();
+        res.extend(min.encode_var_vec());
+        res.push(width);
+
+        let packed_len = (data.len() * width as usize).div_ceil(8);
+        let header_len = res.len();
+        res.resize(header_len + packed_len, 0);
+        let packed = &mut res[header_len..];
+
+        let mut bit = 0usize;
+        for &d in data {
+            let offset = d.wrapping_sub(min) as u64;
+            for i in 0..width as usize {
+                if offset >> i & 1 == 1 {
+                    packed[(bit + i) / 8] |= 1 << ((bit + i) % 8);
+                }
+            }
+            bit += width as usize;
+        }
+
+        Ok(res)
+    }
+
+    /// Without a known row count a forged count of equal values is limited only by what can be
+    /// allocated, `IntCompressors::decompress` takes the count from the caller instead.
+    fn decompress(&self, compressed: &Self::Compressed) -> Result<Vec<i64>, CompressorError> {
+        self.decode(compressed, usize::MAX)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct LZ4StringCompressor;
 
 impl Compressor<String> for LZ4StringCompressor {
@@ -147,10 +600,133 @@ impl Compressor<String> for LZ4StringCompressor {
         })
     }
 
+    fn decompress(&self, compressed: &Self::Compressed) -> Result<Vec<String>, CompressorError> {
+        let raw = decompress_size_prepended(&compressed.data)?;
+        split_strings(&raw, &compressed.lengths)
+    }
+}
+
+/// Cuts concatenated string bytes back into strings of given lengths.
+fn split_strings(raw: &[u8], lengths: &[i64]) -> Result<Vec<String>, CompressorError> {
+    let mut res = Vec::with_capacity(lengths.len());
+    let mut offset = 0;
+
+    for &len in lengths {
+        if len < 0 {
+            return Err(CompressorError::NegativeStringLength(
+                "Negative string length was passed".to_string(),
+            ));
+        }
+
+        let slice =
+            raw.get(offset..offset + len as usize)
+                .ok_or(CompressorError::WrongDataLength(
+                    "Data length is shorter then declared strings lengths".to_string(),
+                ))?;
+        res.push(String::from_utf8(slice.to_vec())?);
+        offset += len as usize;
+    }
+
+    Ok(res)
+}
+
+impl LZ4StringCompressor {
+    /// Upper bound, LZ4 output size is only known after compressing. Includes the 4 byte
+    /// size prefix.
+    pub fn estimate_compressed_size(&self, data: &[String]) -> usize {
+        let raw_len = data.iter().map(String::len).sum();
+        4 + get_maximum_output_size(raw_len)
+    }
+}
+
+/// Same as `LZ4StringCompressor`, but writes the standard LZ4 frame format instead of a raw
+/// block with a size prefix, so column data can be read by other LZ4 tools (e.g. `lz4 -d`).
+/// Frame header and block headers make it a few bytes bigger and it is slower.
+#[derive(Debug, Clone)]
+pub struct LZ4FrameStringCompressor;
+
+impl Compressor<String> for LZ4FrameStringCompressor {
+    type Compressed = CompressedStringColumn;
+
+    fn compress(&self, data: &[String]) -> Result<Self::Compressed, CompressorError> {
+        let mut encoder = FrameEncoder::new(Vec::new());
+        for d in data {
+            encoder
+                .write_all(d.as_bytes())
+                .map_err(CompressorError::Lz4Frame)?;
+        }
+        let compressed_data = encoder
+            .finish()
+            .map_err(|e| CompressorError::Lz4Frame(e.into()))?;
+
+        Ok(Self::Compressed {
+            data: compressed_data,
+            lengths: data.iter().map(|d| d.len() as i64).collect(),
+        })
+    }
+
+    fn decompress(&self, compressed: &Self::Compressed) -> Result<Vec<String>, CompressorError> {
+        let raw = decompress_frame(&compressed.data)?;
+        split_strings(&raw, &compressed.lengths)
+    }
+}
+
+impl LZ4FrameStringCompressor {
+    /// Block bound plus frame header (at most 19 bytes), end mark and a 4 byte header of every
+    /// 64 KiB block.
+    pub fn estimate_compressed_size(&self, data: &[String]) -> usize {
+        let raw_len = data.iter().map(String::len).sum::<usize>();
+        get_maximum_output_size(raw_len) + 23 + 4 * raw_len.div_ceil(64 * 1024)
+    }
+}
+
+fn decompress_frame(data: &[u8]) -> Result<Vec<u8>, CompressorError> {
+    let mut raw = Vec::new();
+    FrameDecoder::new(data)
+        .read_to_end(&mut raw)
+        .map_err(CompressorError::Lz4Frame)?;
+    Ok(raw)
+}
+
+/// Stores every string as length of prefix shared with the previous string followed by the
+/// rest of its bytes, then compresses everything with LZ4. Works best on sorted columns with
+/// long common prefixes (paths, URLs). `lengths` hold lengths of the stored suffixes.
+#[derive(Debug, Clone)]
+pub struct FrontCodedStringCompressor;
+
+impl Compressor<String> for FrontCodedStringCompressor {
+    type Compressed = CompressedStringColumn;
+
+    fn compress(&self, data: &[String]) -> Result<Self::Compressed, CompressorError> {
+        let mut raw = Vec::<u8>::new();
+        let mut lengths = Vec::<i64>::with_capacity(data.len());
+        let mut previous: &[u8] = &[];
+
+        for d in data {
+            let bytes = d.as_bytes();
+            let shared = previous
+                .iter()
+                .zip(bytes)
+                .take_while(|(a, b)| a == b)
+                .count();
+
+            raw.extend(shared.encode_var_vec());
+            raw.extend_from_slice(&bytes[shared..]);
+            lengths.push((bytes.len() - shared) as i64);
+            previous = bytes;
+        }
+
+        Ok(Self::Compressed {
+            data: compress_prepend_size(&raw),
+            lengths,
+        })
+    }
+
     fn decompress(&self, compressed: &Self::Compressed) -> Result<Vec<String>, CompressorError> {
         let raw = decompress_size_prepended(&compressed.data)?;
         let mut res = Vec::with_capacity(compressed.lengths.len());
-        let mut offset = 0;
+        let mut cursor = &raw[..];
+        let mut previous = Vec::<u8>::new();
 
         for &len in &compressed.lengths {
             if len < 0 {
@@ -159,20 +735,55 @@ impl Compressor<String> for LZ4StringCompressor {
                 ));
             }
 
-            let slice =
-                raw.get(offset..offset + len as usize)
-                    .ok_or(CompressorError::WrongDataLength(
-                        "Data length is shorter then declared strings lengths".to_string(),
-                    ))?;
-            res.push(String::from_utf8(slice.to_vec())?);
-            offset += len as usize;
+            let (shared, n) = usize::decode_var(cursor).ok_or(CompressorError::VleDecoding(
+                "Decoder stopped before reading shared prefix length".to_string(),
+            ))?;
+            cursor = &cursor[n..];
+            if shared > previous.len() {
+                return Err(CompressorError::WrongDataLength(
+                    "Shared prefix is longer than previous string".to_string(),
+                ));
+            }
+
+            let suffix = cursor
+                .get(..len as usize)
+                .ok_or(CompressorError::WrongDataLength(
+                    "Data length is shorter then declared strings lengths".to_string(),
+                ))?;
+            cursor = &cursor[len as usize..];
+
+            previous.truncate(shared);
+            previous.extend_from_slice(suffix);
+            res.push(String::from_utf8(previous.clone())?);
         }
 
         Ok(res)
     }
 }
 
-#[derive(Debug)]
+impl FrontCodedStringCompressor {
+    /// Upper bound, front coding is computed exactly but the LZ4 pass on top of it is not.
+    pub fn estimate_compressed_size(&self, data: &[String]) -> usize {
+        let mut raw_len = 0usize;
+        let mut previous: &[u8] = &[];
+
+        for d in data {
+            let bytes = d.as_bytes();
+            let shared = previous
+                .iter()
+                .zip(bytes)
+                .take_while(|(a, b)| a == b)
+                .count();
+
+            raw_len += shared.required_space() + bytes.len() - shared;
+            previous = bytes;
+        }
+
+        4 + get_maximum_output_size(raw_len)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct NoIntCompressor;
 
 impl Compressor<i64> for NoIntCompressor {
@@ -183,6 +794,12 @@ impl Compressor<i64> for NoIntCompressor {
     }
 
     fn decompress(&self, compressed: &Self::Compressed) -> Result<Vec<i64>, CompressorError> {
+        self.decode(compressed)
+    }
+}
+
+impl NoIntCompressor {
+    fn decode(&self, compressed: &[u8]) -> Result<Vec<i64>, CompressorError> {
         if compressed.len() % 8 != 0 {
             return Err(CompressorError::WrongDataLength(
                 "Data length must be divisable by 8".to_string(),
@@ -199,9 +816,14 @@ impl Compressor<i64> for NoIntCompressor {
             })
             .collect())
     }
+
+    /// Exact, 8 bytes per value.
+    pub fn estimate_compressed_size(&self, data: &[i64]) -> usize {
+        data.len() * 8
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct NoStringCompressor;
 
 impl Compressor<String> for NoStringCompressor {
@@ -241,3 +863,306 @@ impl Compressor<String> for NoStringCompressor {
         Ok(res)
     }
 }
+
+impl NoStringCompressor {
+    /// Exact, strings are stored as they are.
+    pub fn estimate_compressed_size(&self, data: &[String]) -> usize {
+        data.iter().map(String::len).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    use super::*;
+
+    /// Bytes the column takes in a file, with lengths stored the default way.
+    fn stored_size(column: &CompressedStringColumn) -> usize {
+        column.data().len()
+            + VleDeltaIntCompressor
+                .compress(column.lengths())
+                .unwrap()
+                .len()
+    }
+
+    #[test]
+    fn front_coding_beats_lz4_on_sorted_paths() {
+        let mut paths = (0..5_000)
+            .map(|i| {
+                format!(
+                    "/srv/data/warehouse/events/year=2024/month={:02}/day={:02}/part-{:05}.isdb",
+                    i % 12 + 1,
+                    i % 28 + 1,
+                    i
+                )
+            })
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        let front_coded = FrontCodedStringCompressor.compress(&paths).unwrap();
+        let lz4 = LZ4StringCompressor.compress(&paths).unwrap();
+
+        assert_eq!(
+            FrontCodedStringCompressor.decompress(&front_coded).unwrap(),
+            paths
+        );
+        assert!(stored_size(&front_coded) < stored_size(&lz4));
+    }
+
+    #[test]
+    fn front_coding_round_trips_unsorted_data() {
+        let data = ["", "abc", "abd", "", "zaż", "za", "abc"]
+            .map(String::from)
+            .to_vec();
+        let compressed = FrontCodedStringCompressor.compress(&data).unwrap();
+        assert_eq!(
+            FrontCodedStringCompressor.decompress(&compressed).unwrap(),
+            data
+        );
+    }
+
+    fn int_compressors() -> [IntCompressors; 3] {
+        [
+            IntCompressors::VleDelta(VleDeltaIntCompressor),
+            IntCompressors::BitPack(BitPackIntCompressor),
+            IntCompressors::None(NoIntCompressor),
+        ]
+    }
+
+    /// Values drawn mostly from the edges of the range, with runs of repeated values.
+    fn random_ints(rng: &mut StdRng, len: usize) -> Vec<i64> {
+        let mut data = Vec::with_capacity(len);
+        while data.len() < len {
+            let value = match rng.random_range(0..5) {
+                0 => i64::MIN,
+                1 => i64::MAX,
+                2 => 0,
+                3 => rng.random_range(-1000..1000),
+                _ => rng.random(),
+            };
+            let run = rng.random_range(1..=8usize).min(len - data.len());
+            data.extend(std::iter::repeat_n(value, run));
+        }
+        data
+    }
+
+    #[test]
+    fn int_codecs_round_trip_random_data() {
+        let mut rng = StdRng::seed_from_u64(1922);
+        for _ in 0..200 {
+            let len = rng.random_range(0..300);
+            let data = random_ints(&mut rng, len);
+            for compressor in int_compressors() {
+                let compressed = compressor.compress(&data).unwrap();
+                assert_eq!(
+                    compressor.decompress(&compressed, data.len()).unwrap(),
+                    data,
+                    "{:?}",
+                    compressor
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn int_codecs_round_trip_extreme_neighbours() {
+        let data = vec![i64::MIN, i64::MAX];
+        for compressor in int_compressors() {
+            let compressed = compressor.compress(&data).unwrap();
+            assert_eq!(
+                compressor.decompress(&compressed, data.len()).unwrap(),
+                data
+            );
+        }
+    }
+
+    #[test]
+    fn vle_delta_wraps_on_extreme_transitions() {
+        let data = vec![0, i64::MAX, i64::MIN, 0];
+        let compressed = VleDeltaIntCompressor.compress(&data).unwrap();
+        assert_eq!(VleDeltaIntCompressor.decompress(&compressed).unwrap(), data);
+    }
+
+    #[test]
+    fn int_data_is_read_by_any_codec() {
+        let data = vec![5, -3, 1 << 40, 0];
+        for writer in int_compressors() {
+            let compressed = writer.compress(&data).unwrap();
+            for reader in int_compressors() {
+                assert_eq!(reader.decompress(&compressed, data.len()).unwrap(), data);
+            }
+        }
+        assert!(matches!(
+            IntCompressors::None(NoIntCompressor).decompress(&[9, 0], 1),
+            Err(CompressorError::UnknownCodec(_))
+        ));
+    }
+
+    #[test]
+    fn int_estimates_are_exact() {
+        let mut rng = StdRng::seed_from_u64(1932);
+        for len in [0, 1, 7, 300] {
+            let data = random_ints(&mut rng, len);
+            for compressor in int_compressors() {
+                assert_eq!(
+                    compressor.estimate_compressed_size(&data),
+                    compressor.compress(&data).unwrap().len(),
+                    "{:?}",
+                    compressor
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn smallest_codec_is_chosen_by_estimate() {
+        let mut rng = StdRng::seed_from_u64(1932);
+        let narrow = (0..1000)
+            .map(|_| rng.random_range(0..16))
+            .collect::<Vec<i64>>();
+        assert!(matches!(
+            IntCompressors::smallest_for(&narrow),
+            IntCompressors::BitPack(_)
+        ));
+        let ascending = (0..1000).collect::<Vec<i64>>();
+        assert!(matches!(
+            IntCompressors::smallest_for(&ascending),
+            IntCompressors::VleDelta(_)
+        ));
+    }
+
+    #[test]
+    fn wrong_data_length_message_says_what_is_missing() {
+        let error = IntCompressors::None(NoIntCompressor)
+            .decompress(&[], 0)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Wrong data length: Missing int codec mode byte"
+        );
+    }
+
+    #[test]
+    fn bit_pack_stores_equal_values_without_a_bitstream() {
+        let data = vec![-7; 10_000];
+        let compressed = BitPackIntCompressor.compress(&data).unwrap();
+
+        assert_eq!(compressed.len(), 2 + 1 + 1);
+        assert_eq!(BitPackIntCompressor.decompress(&compressed).unwrap(), data);
+    }
+
+    #[test]
+    fn bit_pack_rejects_row_counts_over_the_limit() {
+        let codec = IntCompressors::BitPack(BitPackIntCompressor);
+        let mut forged = vec![IntCompressors::BIT_PACK_MODE];
+        forged.extend((1u64 << 40).encode_var_vec());
+        forged.extend(0i64.encode_var_vec());
+        forged.push(0);
+
+        let error = codec.decompress(&forged, 3).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Wrong data length: Bit packed row count {} is larger than 3",
+                1u64 << 40
+            )
+        );
+
+        let round_trip = codec.compress(&[5, 5, 5]).unwrap();
+        assert_eq!(codec.decompress(&round_trip, 3).unwrap(), [5, 5, 5]);
+        assert!(codec.decompress(&round_trip, 2).is_err());
+    }
+
+    #[test]
+    fn bit_pack_rejects_overflowing_bit_counts() {
+        let mut forged = (u64::MAX / 2).encode_var_vec();
+        forged.extend(0i64.encode_var_vec());
+        forged.push(64);
+
+        assert!(BitPackIntCompressor.decompress(&forged).is_err());
+    }
+
+    #[test]
+    fn bit_pack_reports_unallocatable_row_counts() {
+        let mut forged = (u64::MAX / 2).encode_var_vec();
+        forged.extend(0i64.encode_var_vec());
+        forged.push(0);
+
+        let error = BitPackIntCompressor.decompress(&forged).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Wrong data length: Bit packed row count {} is too large",
+                u64::MAX / 2
+            )
+        );
+    }
+
+    #[test]
+    fn bit_pack_halves_small_values_compared_to_vle_delta() {
+        let mut rng = StdRng::seed_from_u64(1957);
+        let data = (0..10_000)
+            .map(|_| rng.random_range(0..16))
+            .collect::<Vec<i64>>();
+
+        // 4 bits per value against at least a byte per delta.
+        let bit_pack = BitPackIntCompressor.compress(&data).unwrap();
+        let vle_delta = VleDeltaIntCompressor.compress(&data).unwrap();
+        assert!(bit_pack.len() <= 5_010, "{}", bit_pack.len());
+        assert!(vle_delta.len() >= 10_000, "{}", vle_delta.len());
+        assert_eq!(BitPackIntCompressor.decompress(&bit_pack).unwrap(), data);
+    }
+
+    #[test]
+    fn string_codec_modes_round_trip() {
+        for compressor in [
+            StringCompressors::Lz4(LZ4StringCompressor),
+            StringCompressors::Lz4Frame(LZ4FrameStringCompressor),
+            StringCompressors::FrontCoded(FrontCodedStringCompressor),
+            StringCompressors::None(NoStringCompressor),
+        ] {
+            let parsed = StringCompressors::from_mode(compressor.mode()).unwrap();
+            assert_eq!(parsed.codec(), compressor.codec());
+        }
+        assert!(matches!(
+            StringCompressors::from_mode(4),
+            Err(CompressorError::UnknownCodec(_))
+        ));
+    }
+
+    #[test]
+    fn compressed_string_column_rejects_negative_lengths() {
+        let column = CompressedStringColumn::new(b"abc".to_vec(), vec![1, 2]).unwrap();
+        assert_eq!(column.lengths(), [1, 2]);
+
+        let error = CompressedStringColumn::new(b"abc".to_vec(), vec![1, -2]).unwrap_err();
+        assert_eq!(error.to_string(), "Negative string length: -2 was passed");
+        assert!(matches!(
+            CompressedStringColumn::new(Vec::new(), vec![i64::MAX, i64::MAX, 2]),
+            Err(CompressorError::WrongDataLength(_))
+        ));
+    }
+
+    #[test]
+    fn codec_counters_grow_by_compressed_sizes() {
+        // Counters are shared with tests running in parallel, which can only add to them.
+        let ints: Vec<i64> = (0..1000).map(|i| i % 7).collect();
+        let before = Codec::BitPack.counters();
+        let compressed = IntCompressors::BitPack(BitPackIntCompressor)
+            .compress(&ints)
+            .unwrap();
+        let after = Codec::BitPack.counters();
+        assert!(after.bytes_in - before.bytes_in >= 8000);
+        assert!(after.bytes_out - before.bytes_out >= compressed.len() as u64 - 1);
+
+        let strings: Vec<String> = (0..100).map(|i| format!("/data/{:03}", i)).collect();
+        let before = Codec::FrontCoded.counters();
+        let compressed = StringCompressors::FrontCoded(FrontCodedStringCompressor)
+            .compress(&strings)
+            .unwrap();
+        let after = Codec::FrontCoded.counters();
+        assert!(after.bytes_in - before.bytes_in >= 900);
+        assert!(after.bytes_out - before.bytes_out >= compressed.data().len() as u64);
+    }
+}
@@ -1,8 +1,30 @@
 pub const METASTORE_FILE: &str = "metastore.json";
+/// Last line of the metastore file, followed by `lib::checksum` of everything before that line.
+pub const METASTORE_CHECKSUM_PREFIX: &str = "#checksum:";
 
 pub const TABLES_DIR: &str = "tables";
 pub const FILE_EXTENSION: &str = "isdb";
 
+pub const MAX_CSV_FIELD_SIZE: usize = 16 * 1024 * 1024;
+/// Quotes, colon, comma and whitespace around a single key and value on an NDJSON line.
+pub const NDJSON_FIELD_OVERHEAD: usize = 16;
+
+/// Column names listed by the error reported when CSV width doesn't match the table.
+pub const MISMATCH_NAMES_SHOWN: usize = 5;
+
+/// Largest request body accepted by the server unless configured otherwise.
+pub const DEFAULT_MAX_BODY_SIZE: u64 = 16 * 1024 * 1024;
+
+pub const RETENTION_CHECK_INTERVAL_SECS: u64 = 1;
+
+/// SELECTs run one after another while a COPY is waiting, before the COPY gets its turn.
+pub const MAX_INTERACTIVE_STREAK: usize = 8;
+
+/// Attempts at changing status of a query after transient failures, kept low so the engine
+/// worker never waits long on a single query.
+pub const STATUS_CHANGE_ATTEMPTS: usize = 3;
+pub const STATUS_CHANGE_RETRY_DELAY_MS: u64 = 10;
+
 pub const SERVER_VERSION: &str = "1.0.0";
 pub const INTERFACE_VERSION: &str = "1.0.0";
 pub const AUTHOR: &str = "Jakub Kłos";
@@ -1,10 +1,27 @@
 use lib::ColumnData;
-use log::{error, info};
-use std::{cmp::Ordering, collections::HashMap, fs::File, rc::Rc};
+use log::{error, info, warn};
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::BufReader,
+    path::Path,
+    rc::Rc,
+    sync::Arc,
+    time::Duration,
+};
 
 use csv::ReaderBuilder;
 
-use crate::{metastore, planner, query, utils::convert_to_table_file_table};
+use crate::{
+    consts::{
+        MISMATCH_NAMES_SHOWN, NDJSON_FIELD_OVERHEAD, STATUS_CHANGE_ATTEMPTS,
+        STATUS_CHANGE_RETRY_DELAY_MS,
+    },
+    metastore, planner, query,
+    utils::unix_timestamp,
+};
 
 use query::BinOperator as BinOp;
 use query::Operator as Op;
@@ -13,13 +30,23 @@ type ExecutorError = String;
 
 type EvaluationResult = Result<Rc<ColumnData>, ExecutorError>;
 type ExecutionResult = Result<Option<Vec<query::QueryResult>>, ExecutorError>;
+/// Header and records of a COPY source file, as raw strings.
+type RawRecords = (Option<Vec<String>>, Vec<Vec<String>>);
+/// Called with the query ID before every attempt at changing its status.
+type StatusHook = Arc<dyn Fn(&String) -> Result<(), StatusError> + Send + Sync>;
 
 #[derive(Clone)]
-pub struct Executor {}
+pub struct Executor {
+    /// Fails with `StatusError::Busy` while something other than the metastore lock keeps the
+    /// status of a query from changing. Nothing does so far, tests inject failures here.
+    status_hook: StatusHook,
+}
 
 impl Executor {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            status_hook: Arc::new(|_| Ok(())),
+        }
     }
 
     pub async fn execute(
@@ -28,18 +55,21 @@ impl Executor {
         plan: planner::PhysicalPlan,
         metastore: &metastore::SharedMetastore,
     ) {
-        if let Err(e) = self
-            .set_status(query_id, query::QueryStatus::Running, metastore)
-            .await
-        {
-            error!("Failed to start query {}: {:?}", query_id, e);
-            self.fail_query(
-                query_id,
-                "Query was deleted before execution".to_string(),
-                metastore,
-            )
-            .await;
-            return;
+        match self.start_query(query_id, metastore).await {
+            Ok(true) => {}
+            Ok(false) => {
+                info!("Query {} was already started, skipping", query_id);
+                return;
+            }
+            Err(StatusError::QueryNotFound) => {
+                info!("Query {} was deleted before execution, skipping", query_id);
+                return;
+            }
+            Err(e) => {
+                self.fail_query(query_id, format!("Failed to start query: {}", e), metastore)
+                    .await;
+                return;
+            }
         }
 
         let result = match plan {
@@ -61,6 +91,17 @@ impl Executor {
                 }
                 res
             }
+            planner::PhysicalPlan::Union(union) => self.union(query_id, &union, metastore).await,
+            planner::PhysicalPlan::CopyFromTable(copy) => {
+                let res = self.copy_from_table(query_id, &copy, metastore).await;
+                let mut metastore_guard = metastore.write().await;
+                for table_id in [&copy.source_table_id, &copy.table_id] {
+                    if let Some(access_set) = metastore_guard.table_accesses.get_mut(table_id) {
+                        access_set.remove(query_id);
+                    }
+                }
+                res
+            }
         };
 
         match result {
@@ -90,7 +131,24 @@ impl Executor {
         select_plan: &planner::SelectPlan,
         metastore: &metastore::SharedMetastore,
     ) -> ExecutionResult {
-        let (result_columns, current_row_count) = self.execude_plan(select_plan, metastore).await?;
+        let (mut result_columns, current_row_count) =
+            self.execude_plan(select_plan, metastore).await?;
+        if let Some(result_file) = &select_plan.result_file {
+            // Tables or partitions registered since the query was submitted are checked too.
+            if metastore
+                .read()
+                .await
+                .is_database_file(Path::new(result_file))
+            {
+                return Err(format!(
+                    "Result file '{}' would replace a database file",
+                    result_file
+                ));
+            }
+            result_columns = self
+                .write_result_file(select_plan, result_file, result_columns, current_row_count)
+                .await?;
+        }
 
         let result_table_id = {
             let mut metastore_guard = metastore.write().await;
@@ -98,11 +156,131 @@ impl Executor {
                 query_id,
                 result_columns,
                 current_row_count,
+                select_plan.result_compression,
             );
             metastore_guard
                 .scheduled_for_deletion
                 .insert(table_id.clone());
+            // Also drops the temporary table a partitioned table was read into.
             if let Some(id) = &select_plan.table_id {
+                metastore_guard.flush_table_reference(id, Some(query_id));
+            }
+            table_id
+        };
+
+        Ok(Some(vec![query::QueryResult {
+            table_id: result_table_id,
+        }]))
+    }
+
+    /// Writes result columns into an ISDB file outside of the metastore and hands them back.
+    async fn write_result_file(
+        &self,
+        select_plan: &planner::SelectPlan,
+        result_file: &str,
+        result_columns: Vec<ColumnData>,
+        row_count: usize,
+    ) -> Result<Vec<ColumnData>, ExecutorError> {
+        let path = Path::new(result_file);
+        if path.exists() && !select_plan.overwrite_result_file {
+            return Err(format!("Result file '{}' already exists", result_file));
+        }
+
+        let columns = select_plan
+            .result_names
+            .iter()
+            .zip(result_columns)
+            .map(|(name, data)| lib::Column {
+                name: name.clone(),
+                data,
+                metadata: None,
+            })
+            .collect();
+        let table = lib::Table::new(row_count as u64, columns);
+        let serializer = select_plan
+            .result_compression
+            .serializer()
+            .unwrap_or_else(lib::Serializer::new);
+        let path = path.to_path_buf();
+        // Compressing and writing a large result would otherwise hold up other queries.
+        let table = tokio::task::spawn_blocking(move || {
+            serializer.serialize(&path, &table).map(|()| table)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("Failed to write result file '{}': {}", result_file, e))?;
+        info!("Result written to {}", result_file);
+
+        Ok(table
+            .columns
+            .into_iter()
+            .map(|column| column.data)
+            .collect())
+    }
+
+    async fn union(
+        &self,
+        query_id: &String,
+        union_plan: &planner::UnionPlan,
+        metastore: &metastore::SharedMetastore,
+    ) -> ExecutionResult {
+        let mut result = {
+            let metastore_guard = metastore.read().await;
+            let get_table = |table_id: &String| {
+                metastore_guard
+                    .get_table_internal(table_id)
+                    .map_err(|e| e.to_string())?
+                    .cloned()
+                    .ok_or(format!("Table {} not found during execution", table_id))
+            };
+
+            let (first, rest) = union_plan
+                .tables
+                .split_first()
+                .ok_or("UNION without tables".to_string())?;
+            let mut result = get_table(first)?;
+            for table_id in rest {
+                result
+                    .append_table(get_table(table_id)?)
+                    .map_err(|e| e.to_string())?;
+            }
+            result
+        };
+
+        if !union_plan.all {
+            let mut seen = HashSet::new();
+            let mask = (0..result.get_num_rows() as usize)
+                .map(|row| {
+                    seen.insert(
+                        result
+                            .iter_columns()
+                            .map(|col| col.data.value(row))
+                            .collect::<Vec<_>>(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            for col in result.iter_columns_mut() {
+                match &mut col.data {
+                    ColumnData::STR(raw) => self.apply_mask(raw, &mask),
+                    ColumnData::INT64(raw) => self.apply_mask(raw, &mask),
+                    ColumnData::BOOL(raw) => self.apply_mask(raw, &mask),
+                }
+            }
+            result.num_rows = seen.len() as u64;
+        }
+
+        let result_table_id = {
+            let mut metastore_guard = metastore.write().await;
+            let table_id = metastore_guard.create_query_result_table(
+                query_id,
+                result.columns.into_iter().map(|col| col.data).collect(),
+                result.num_rows as usize,
+                metastore::CompressionProfile::default(),
+            );
+            metastore_guard
+                .scheduled_for_deletion
+                .insert(table_id.clone());
+            for id in &union_plan.tables {
                 if let Some(access_set) = metastore_guard.table_accesses.get_mut(id) {
                     access_set.remove(query_id);
                 }
@@ -125,6 +303,7 @@ impl Executor {
                 let metastore_guard = metastore.read().await;
                 let table = metastore_guard
                     .get_table_internal(table_id)
+                    .map_err(|e| e.to_string())?
                     .ok_or(format!("Table {} not found during execution", table_id))?;
 
                 let mut working_columns_innter: HashMap<String, Rc<ColumnData>> = HashMap::new();
@@ -481,60 +660,83 @@ impl Executor {
 
     async fn copy_from_csv(
         &self,
-        _: &String,
+        query_id: &String,
         copy_plan: &planner::CopyFromCsvPlan,
         metastore: &metastore::SharedMetastore,
     ) -> ExecutionResult {
-        let file = File::open(&copy_plan.file_path)
-            .map_err(|e| format!("Failed to open file '{}': {}", copy_plan.file_path, e))?;
-        let mut rdr = ReaderBuilder::new()
-            .has_headers(copy_plan.has_headers)
-            .from_reader(file);
-        let records = rdr
-            .records()
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|e| format!("CSV Parse Error: {}", e))?
-            .into_iter()
-            .map(|r| r.iter().map(|s| s.to_string()).collect::<Vec<_>>())
-            .collect::<Vec<_>>();
-
-        let (mut shadow_columns, original_column_names) = {
+        let schema = {
             let metastore_guard = metastore.read().await;
-            let table = metastore_guard
+            metastore_guard
                 .get_table_internal(&copy_plan.table_id)
-                .ok_or_else(|| {
-                    format!("Table {} not found during execution", copy_plan.table_id)
-                })?;
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("Table {} not found during execution", copy_plan.table_id))?
+                .schema()
+        };
 
-            (
-                table
-                    .iter_columns()
-                    .map(|column| match column.data {
-                        ColumnData::STR(_) => (
-                            column.name.clone(),
-                            ColumnData::STR(Vec::with_capacity(records.len())),
-                        ),
-                        ColumnData::INT64(_) => (
-                            column.name.clone(),
-                            ColumnData::INT64(Vec::with_capacity(records.len())),
-                        ),
-                        ColumnData::BOOL(_) => (
-                            column.name.clone(),
-                            ColumnData::BOOL(Vec::with_capacity(records.len())),
-                        ),
-                    })
-                    .collect::<HashMap<_, _>>(),
-                table
-                    .iter_columns()
-                    .map(|column| column.name.clone())
-                    .collect(),
-            )
+        let (headers, records) = match copy_plan.source_format {
+            query::SourceFormat::Csv => self.read_csv(copy_plan)?,
+            query::SourceFormat::Ndjson => self.read_ndjson(copy_plan, schema.columns.len())?,
         };
 
-        let csv_width = records[0].len();
-        let num_rows = records.len() as u64;
+        let mut shadow_columns = lib::Table::with_capacity(records.len(), &schema)
+            .columns
+            .into_iter()
+            .map(|column| (column.name, column.data))
+            .collect::<HashMap<_, _>>();
+        let original_column_names = schema
+            .columns
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+
+        if let Some(min_rows) = copy_plan.min_row_count
+            && records.len() < min_rows
+        {
+            return Err(format!(
+                "Expected at least {} rows in CSV, but file has {}",
+                min_rows,
+                records.len()
+            ));
+        }
+        if let Some(max_rows) = copy_plan.max_row_count
+            && records.len() > max_rows
+        {
+            return Err(format!(
+                "Expected at most {} rows in CSV, but file has {}",
+                max_rows,
+                records.len()
+            ));
+        }
+
+        // Width of the file, from its header or else its first row. Skipped rows of a best-effort
+        // COPY may include the first one, so without a header rows are only checked one by one.
+        let csv_width = match &headers {
+            Some(headers) => Some(headers.len()),
+            None if copy_plan.best_effort => None,
+            None => Some(records.first().map_or(0, Vec::len)),
+        };
 
         let csv_to_table_map: Vec<String> = match &copy_plan.mapping {
+            // NDJSON values are named by their keys, which have to match the table columns.
+            _ if copy_plan.source_format == query::SourceFormat::Ndjson => {
+                let keys = headers.unwrap_or_default();
+                if let Some(key) = keys.iter().find(|key| !shadow_columns.contains_key(*key)) {
+                    return Err(format!(
+                        "NDJSON key '{}' does not match any column in table",
+                        key
+                    ));
+                }
+                if let Some(name) = original_column_names
+                    .iter()
+                    .find(|name| !keys.contains(name))
+                {
+                    return Err(format!(
+                        "Column '{}' is missing from every NDJSON object",
+                        name
+                    ));
+                }
+                keys
+            }
             Some(map_names) => {
                 if map_names.len() != shadow_columns.len() {
                     return Err(format!(
@@ -543,7 +745,9 @@ impl Executor {
                         shadow_columns.len()
                     ));
                 }
-                if csv_width < map_names.len() {
+                if let Some(csv_width) = csv_width
+                    && csv_width < map_names.len()
+                {
                     return Err(format!(
                         "CSV too narrow: Mapping requires {} columns, but CSV only has {}.",
                         map_names.len(),
@@ -551,6 +755,7 @@ impl Executor {
                     ));
                 }
 
+                let mut mapped = HashSet::with_capacity(map_names.len());
                 for name in map_names {
                     if !shadow_columns.contains_key(name) {
                         return Err(format!(
@@ -558,15 +763,33 @@ impl Executor {
                             name
                         ));
                     }
+                    if !mapped.insert(name) {
+                        return Err(format!(
+                            "Mapping references column '{}' more than once",
+                            name
+                        ));
+                    }
                 }
                 map_names.clone()
             }
             None => {
-                if csv_width != shadow_columns.len() {
+                if let Some(csv_width) = csv_width
+                    && csv_width != shadow_columns.len()
+                {
+                    // Names are shown only when the CSV has them to compare against.
+                    let (table_names, csv_names) = match &headers {
+                        Some(headers) => (
+                            format!(" ({})", shown_names(&original_column_names)),
+                            format!(" ({})", shown_names(headers)),
+                        ),
+                        None => (String::new(), String::new()),
+                    };
                     return Err(format!(
-                        "Mismatch: Table has {} columns, but CSV has {}. Without mapping, counts must match exactly.",
+                        "Mismatch: Table has {} columns{}, but CSV has {}{}. Without mapping, counts must match exactly.",
                         shadow_columns.len(),
-                        csv_width
+                        table_names,
+                        csv_width,
+                        csv_names
                     ));
                 }
 
@@ -574,143 +797,108 @@ impl Executor {
             }
         };
 
+        let mut loaded_rows = 0usize;
+        let mut skipped_rows = Vec::new();
         for (row_idx, record) in records.iter().enumerate() {
-            if record.len() != csv_width {
-                return Err(format!("Row {} length mismatch", row_idx + 1));
-            }
-
-            for (i, col_name) in csv_to_table_map.iter().enumerate() {
-                let raw_val = &record[i];
-
-                // We use unwrap() safely because we validated keys exist above
-                let column_data = shadow_columns.get_mut(col_name).unwrap();
-
-                match column_data {
-                    ColumnData::INT64(vec) => {
-                        let val = raw_val.trim().parse::<i64>().map_err(|_| {
-                            format!(
-                                "Type Error at Row {}, Column '{}': Expected INT64, got '{}'",
-                                row_idx + 1,
-                                col_name,
-                                raw_val
-                            )
-                        })?;
-                        vec.push(val);
-                    }
-                    ColumnData::STR(vec) => {
-                        vec.push(raw_val.clone());
-                    }
-                    ColumnData::BOOL(vec) => {
-                        let val = raw_val.trim().parse::<bool>().map_err(|_| {
-                            format!(
-                                "Type Error at Row {}, Column '{}': Expected INT64, got '{}'",
-                                row_idx + 1,
-                                col_name,
-                                raw_val
-                            )
-                        })?;
-                        vec.push(val);
+            match self.push_record(
+                row_idx,
+                record,
+                &csv_to_table_map,
+                &mut shadow_columns,
+                copy_plan,
+            ) {
+                Ok(()) => loaded_rows += 1,
+                Err(e) if copy_plan.best_effort => {
+                    for column_data in shadow_columns.values_mut() {
+                        column_data.truncate(loaded_rows);
                     }
+                    skipped_rows.push(query::QueryError {
+                        message: e,
+                        context: Some(format!("Skipped row {}", row_idx + 1)),
+                        kind: query::QueryErrorKind::Skipped,
+                    });
                 }
+                Err(e) => return Err(e),
             }
         }
+        let num_rows = loaded_rows as u64;
 
-        {
-            let mut metastore_guard = metastore.write().await;
-            let all_active_readers = metastore_guard
-                .table_accesses
-                .entry(copy_plan.table_id.clone())
-                .or_default()
-                .clone();
-            let active_readers = all_active_readers
-                .into_iter()
-                .filter(|id| {
-                    let query = metastore_guard.queries.get(id).unwrap();
-                    match query.definition {
-                        query::QueryDefinition::Copy(_) => false,
-                        _ => true,
-                    }
-                })
-                .collect::<Vec<_>>();
-
-            if !active_readers.is_empty() {
-                info!(
-                    "COPY: Table {} has {} active readers. Creating snapshot.",
-                    copy_plan.table_id,
-                    active_readers.len()
-                );
-
-                let current_table = metastore_guard
-                    .get_table_internal(&copy_plan.table_id)
-                    .ok_or(format!("Table {} not found", copy_plan.table_id))?;
-
-                let snapshot_id = uuid::Uuid::new_v4().to_string();
-                let snapshot_metadata = metastore::TableMetaData {
-                    name: copy_plan.table_name.clone(),
-                    table: current_table.clone(),
-                    table_file: convert_to_table_file_table(&snapshot_id),
-                };
-
-                metastore_guard
-                    .tables
-                    .insert(snapshot_id.clone(), snapshot_metadata);
-
-                for reader_query_id in active_readers {
-                    if let Some(query) = metastore_guard.queries.get_mut(&reader_query_id) {
-                        if let Some(results) = &mut query.result {
-                            for res in results {
-                                if res.table_id == copy_plan.table_id {
-                                    res.table_id = snapshot_id.clone();
-                                }
-                            }
-                        }
-
-                        match &mut query.definition {
-                            query::QueryDefinition::SelectAll(select_all) => {
-                                if select_all.table_id == copy_plan.table_id {
-                                    select_all.table_id = snapshot_id.clone();
-                                }
-                            }
-                            query::QueryDefinition::Select(select) => {
-                                if let Some(current_table_id) = &select.table_id {
-                                    if *current_table_id == copy_plan.table_id {
-                                        select.table_id = Some(snapshot_id.clone());
-                                    }
-                                }
-                            }
-                            query::QueryDefinition::Copy(copy) => {
-                                if copy.table_id == copy_plan.table_id {
-                                    copy.table_id = snapshot_id.clone();
-                                }
-                            }
-                        }
-                    }
-
-                    metastore_guard
-                        .table_accesses
-                        .entry(snapshot_id.clone())
-                        .or_default()
-                        .insert(reader_query_id);
-                }
+        if !skipped_rows.is_empty() {
+            warn!(
+                "COPY: Query {} skipped {} invalid rows",
+                query_id,
+                skipped_rows.len()
+            );
+            if let Some(q) = metastore.write().await.get_query_internal_mut(query_id) {
+                q.errors = Some(skipped_rows);
+            }
+        }
 
-                metastore_guard.table_accesses.remove(&copy_plan.table_id);
-                metastore_guard
-                    .scheduled_for_deletion
-                    .insert(snapshot_id.clone());
+        if copy_plan.validate_only {
+            info!(
+                "COPY: Query {} validated {} rows, table {} left unchanged",
+                query_id, loaded_rows, copy_plan.table_id
+            );
+            if let Some(q) = metastore.write().await.get_query_internal_mut(query_id) {
+                q.validated_row_count = Some(loaded_rows);
             }
+            return Ok(None);
         }
 
+        self.isolate_readers(&copy_plan.table_id, copy_plan.isolation_level, metastore)
+            .await?;
+
         {
             let mut metastore_guard = metastore.write().await;
+            let (column_defaults, table_key) = metastore_guard
+                .tables
+                .get(&copy_plan.table_id)
+                .map(|metadata| {
+                    (
+                        metadata.column_defaults.clone(),
+                        metadata.key_column.clone(),
+                    )
+                })
+                .unwrap_or_default();
             let table = metastore_guard
                 .get_table_internal_mut(&copy_plan.table_id)
+                .map_err(|e| e.to_string())?
                 .ok_or_else(|| format!("Table {} deleted during copy", copy_plan.table_id))?;
 
+            if let Some(key_column) = &copy_plan.key_column {
+                self.upsert_rows(
+                    table,
+                    &shadow_columns,
+                    &column_defaults,
+                    num_rows as usize,
+                    key_column,
+                    table_key.as_deref(),
+                )?;
+                return Ok(None);
+            }
+
+            if let Some(key_column) = &table_key {
+                let key_data = table.column(key_column).ok_or_else(|| {
+                    format!("Key column '{}' does not exist in table", key_column)
+                })?;
+                let new_keys = match shadow_columns.get(key_column) {
+                    Some(new_keys) => Cow::Borrowed(new_keys),
+                    None => Cow::Owned(metastore::backfilled_column(
+                        key_data.data.logical_type(),
+                        column_defaults.get(key_column),
+                        num_rows as usize,
+                    )),
+                };
+                check_unique_keys(key_column, &key_data.data, &new_keys)?;
+            }
+
             for col in &mut table.columns {
-                let mut new_data = shadow_columns.remove(&col.name).unwrap_or(match col.data {
-                    ColumnData::INT64(_) => ColumnData::INT64(vec![0i64; num_rows as usize]),
-                    ColumnData::STR(_) => ColumnData::STR(vec!["".to_string(); num_rows as usize]),
-                    ColumnData::BOOL(_) => ColumnData::BOOL(vec![false; num_rows as usize]),
+                let mut new_data = shadow_columns.remove(&col.name).unwrap_or_else(|| {
+                    metastore::backfilled_column(
+                        col.data.logical_type(),
+                        column_defaults.get(&col.name),
+                        num_rows as usize,
+                    )
                 });
 
                 match (&mut col.data, &mut new_data) {
@@ -735,19 +923,357 @@ impl Executor {
         Ok(None)
     }
 
-    async fn set_status(
+    /// Appends all rows of the source table to the destination. Source rows are cloned under a
+    /// read lock, so a table can be copied into itself.
+    async fn copy_from_table(
         &self,
         query_id: &String,
-        status: query::QueryStatus,
+        copy_plan: &planner::CopyFromTablePlan,
         metastore: &metastore::SharedMetastore,
-    ) -> Result<(), ()> {
+    ) -> ExecutionResult {
+        let source = metastore
+            .read()
+            .await
+            .get_table_internal(&copy_plan.source_table_id)
+            .map_err(|e| e.to_string())?
+            .cloned()
+            .ok_or_else(|| {
+                format!(
+                    "Table {} not found during execution",
+                    copy_plan.source_table_id
+                )
+            })?;
+        let num_rows = source.num_rows;
+
+        self.isolate_readers(&copy_plan.table_id, copy_plan.isolation_level, metastore)
+            .await?;
+
         let mut metastore_guard = metastore.write().await;
-        if let Some(q) = metastore_guard.get_query_internal_mut(query_id) {
-            q.status = status;
-            Ok(())
+        let table_key = metastore_guard
+            .tables
+            .get(&copy_plan.table_id)
+            .and_then(|metadata| metadata.key_column.clone());
+        let table = metastore_guard
+            .get_table_internal_mut(&copy_plan.table_id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Table {} deleted during copy", copy_plan.table_id))?;
+
+        if let Some(key_column) = &table_key {
+            let key_data = table
+                .column(key_column)
+                .ok_or_else(|| format!("Key column '{}' does not exist in table", key_column))?;
+            let new_keys = source.column(key_column).ok_or_else(|| {
+                format!("Key column '{}' does not exist in source table", key_column)
+            })?;
+            check_unique_keys(key_column, &key_data.data, &new_keys.data)?;
+        }
+
+        table.append_table(source).map_err(|e| e.to_string())?;
+
+        info!(
+            "COPY: Query {} copied {} rows from table {} into table {}",
+            query_id, num_rows, copy_plan.source_table_id, copy_plan.table_id
+        );
+        Ok(None)
+    }
+
+    /// With snapshot isolation, moves queries reading the table about to be appended to onto a
+    /// copy of its current content, so their results don't change.
+    async fn isolate_readers(
+        &self,
+        table_id: &String,
+        isolation_level: query::IsolationLevel,
+        metastore: &metastore::SharedMetastore,
+    ) -> Result<(), ExecutorError> {
+        if isolation_level == query::IsolationLevel::Snapshot {
+            metastore
+                .write()
+                .await
+                .isolate_readers(table_id)
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Header (when the file has one) and records of the CSV file, as raw strings.
+    fn read_csv(&self, copy_plan: &planner::CopyFromCsvPlan) -> Result<RawRecords, ExecutorError> {
+        let file = File::open(&copy_plan.file_path)
+            .map_err(|e| format!("Failed to open file '{}': {}", copy_plan.file_path, e))?;
+        let mut file = BufReader::new(file);
+        lib::skip_utf8_bom(&mut file)
+            .map_err(|e| format!("Failed to read file '{}': {}", copy_plan.file_path, e))?;
+        let mut rdr = ReaderBuilder::new()
+            .has_headers(copy_plan.has_headers)
+            .flexible(true)
+            .from_reader(file);
+        let headers = if copy_plan.has_headers {
+            let headers = rdr
+                .headers()
+                .map_err(|e| format!("CSV Parse Error: {}", e))?;
+            Some(headers.iter().map(|s| s.to_string()).collect::<Vec<_>>())
         } else {
-            Err(())
+            None
+        };
+        let mut records = Vec::new();
+        for (row_idx, record) in rdr.records().enumerate() {
+            let record = record.map_err(|e| format!("CSV Parse Error: {}", e))?;
+            let record = record.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+            check_field_sizes(row_idx, &record, copy_plan.max_field_size)?;
+            records.push(record);
+        }
+        Ok((headers, records))
+    }
+
+    /// Keys and records of the NDJSON file. Values are turned back into strings, so they go
+    /// through the same parsing against the table as CSV fields.
+    /// A line may hold every column of the table, each named and at most `max_field_size` long,
+    /// anything longer is rejected while reading.
+    fn read_ndjson(
+        &self,
+        copy_plan: &planner::CopyFromCsvPlan,
+        num_columns: usize,
+    ) -> Result<RawRecords, ExecutorError> {
+        let file = File::open(&copy_plan.file_path)
+            .map_err(|e| format!("Failed to open file '{}': {}", copy_plan.file_path, e))?;
+        let max_line_len = copy_plan
+            .max_field_size
+            .saturating_add(lib::MAX_COLUMN_NAME_LEN + NDJSON_FIELD_OVERHEAD)
+            .saturating_mul(num_columns);
+        let options = lib::NdjsonOptions {
+            stringify_nested: copy_plan.stringify_nested,
+            max_line_len: Some(max_line_len),
+        };
+        let table = lib::Table::from_ndjson_reader(file, &options)
+            .map_err(|e| format!("NDJSON Parse Error: {}", e))?;
+
+        let records: Vec<Vec<String>> = (0..table.get_num_rows() as usize)
+            .map(|row| {
+                table
+                    .iter_columns()
+                    .map(|column| match &column.data {
+                        ColumnData::INT64(data) => data[row].to_string(),
+                        ColumnData::STR(data) => data[row].clone(),
+                        ColumnData::BOOL(data) => data[row].to_string(),
+                    })
+                    .collect()
+            })
+            .collect();
+        for (row_idx, record) in records.iter().enumerate() {
+            check_field_sizes(row_idx, record, copy_plan.max_field_size)?;
+        }
+        let keys = table.iter_columns().map(|column| column.name.clone());
+        Ok((Some(keys.collect()), records))
+    }
+
+    /// Overwrites rows whose key already exists in the table and appends the rest. Incoming rows
+    /// repeating a key update the row inserted earlier in the same COPY. Columns not loaded from
+    /// the file keep their values in updated rows and get their default in appended ones.
+    /// Values of `table_key`, the key column of the table, have to stay unique. Everything is
+    /// checked before the table is touched, so a failed upsert leaves it unchanged.
+    fn upsert_rows(
+        &self,
+        table: &mut lib::Table,
+        shadow_columns: &HashMap<String, ColumnData>,
+        column_defaults: &HashMap<String, query::Literal>,
+        num_rows: usize,
+        key_column: &str,
+        table_key: Option<&str>,
+    ) -> Result<(), ExecutorError> {
+        let key_idx = table
+            .column_index(key_column)
+            .ok_or_else(|| format!("Key column '{}' does not exist in table", key_column))?;
+        let new_keys = shadow_columns
+            .get(key_column)
+            .ok_or_else(|| format!("Key column '{}' is not loaded from CSV", key_column))?;
+        // Resolved once, in table column order, instead of by name for every row.
+        let new_columns = table
+            .iter_columns()
+            .map(|col| {
+                let new_data = match shadow_columns.get(&col.name) {
+                    Some(new_data) => Cow::Borrowed(new_data),
+                    None => Cow::Owned(metastore::backfilled_column(
+                        col.data.logical_type(),
+                        column_defaults.get(&col.name),
+                        num_rows,
+                    )),
+                };
+                if new_data.logical_type() != col.data.logical_type() || new_data.len() != num_rows
+                {
+                    return Err("Columns types mismatched".to_string());
+                }
+                Ok((new_data, shadow_columns.contains_key(&col.name)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut key_rows = HashMap::new();
+        let key_data = &table.column_at(key_idx).unwrap().data;
+        for row in 0..table.num_rows as usize {
+            if key_rows.insert(key_data.value(row), row).is_some() {
+                return Err(format!(
+                    "Key column '{}' is not unique in table",
+                    key_column
+                ));
+            }
+        }
+
+        let mut target_rows = Vec::with_capacity(num_rows);
+        let mut total_rows = table.num_rows as usize;
+        for new_row in 0..num_rows {
+            let target_row = *key_rows.entry(new_keys.value(new_row)).or_insert_with(|| {
+                total_rows += 1;
+                total_rows - 1
+            });
+            target_rows.push(target_row);
+        }
+
+        // Upsert by another column may still repeat a value of the key column of the table.
+        if let Some(table_key) = table_key.filter(|&table_key| table_key != key_column) {
+            let table_key_idx = table
+                .column_index(table_key)
+                .ok_or_else(|| format!("Key column '{}' does not exist in table", table_key))?;
+            let existing = &table.column_at(table_key_idx).unwrap().data;
+            let (new_data, loaded) = &new_columns[table_key_idx];
+            let mut keys = (0..existing.len())
+                .map(|row| existing.value(row))
+                .collect::<Vec<_>>();
+            for (new_row, &target_row) in target_rows.iter().enumerate() {
+                if target_row == keys.len() {
+                    keys.push(new_data.value(new_row));
+                } else if *loaded {
+                    keys[target_row] = new_data.value(new_row);
+                }
+            }
+            let mut seen = HashSet::with_capacity(keys.len());
+            if let Some(key) = keys.into_iter().find(|key| !seen.insert(key.clone())) {
+                return Err(format!(
+                    "Value '{}' already exists in key column '{}'",
+                    key, table_key
+                ));
+            }
+        }
+
+        for (col, (new_data, loaded)) in table.columns.iter_mut().zip(&new_columns) {
+            match (&mut col.data, new_data.as_ref()) {
+                (ColumnData::INT64(existing), ColumnData::INT64(new)) => {
+                    upsert_values(existing, new, &target_rows, *loaded)
+                }
+                (ColumnData::STR(existing), ColumnData::STR(new)) => {
+                    upsert_values(existing, new, &target_rows, *loaded)
+                }
+                (ColumnData::BOOL(existing), ColumnData::BOOL(new)) => {
+                    upsert_values(existing, new, &target_rows, *loaded)
+                }
+                _ => unreachable!("types were compared above"),
+            }
+        }
+        table.num_rows = total_rows as u64;
+
+        Ok(())
+    }
+
+    fn push_record(
+        &self,
+        row_idx: usize,
+        record: &[String],
+        csv_to_table_map: &[String],
+        shadow_columns: &mut HashMap<String, ColumnData>,
+        copy_plan: &planner::CopyFromCsvPlan,
+    ) -> Result<(), ExecutorError> {
+        // Columns past the mapped ones are ignored, without a mapping every column is loaded.
+        let width = csv_to_table_map.len();
+        if record.len() < width || (copy_plan.mapping.is_none() && record.len() > width) {
+            return Err(format!("Row {} length mismatch", row_idx + 1));
+        }
+
+        for (i, col_name) in csv_to_table_map.iter().enumerate() {
+            let raw_val = &record[i];
+
+            // We use unwrap() safely because we validated keys exist before
+            let column_data = shadow_columns.get_mut(col_name).unwrap();
+
+            match column_data {
+                ColumnData::INT64(vec)
+                    if copy_plan.null_sentinel.as_deref() == Some(raw_val.trim()) =>
+                {
+                    // No nulls in storage yet, so missing values get the configured placeholder.
+                    vec.push(copy_plan.null_int_value);
+                }
+                ColumnData::INT64(vec) => {
+                    let val = raw_val.trim().parse::<i64>().map_err(|_| {
+                        format!(
+                            "Type Error at Row {}, Column '{}': Expected INT64, got '{}'",
+                            row_idx + 1,
+                            col_name,
+                            raw_val
+                        )
+                    })?;
+                    vec.push(val);
+                }
+                ColumnData::STR(vec) => {
+                    vec.push(raw_val.clone());
+                }
+                ColumnData::BOOL(vec) => {
+                    let val = raw_val.trim().parse::<bool>().map_err(|_| {
+                        format!(
+                            "Type Error at Row {}, Column '{}': Expected INT64, got '{}'",
+                            row_idx + 1,
+                            col_name,
+                            raw_val
+                        )
+                    })?;
+                    vec.push(val);
+                }
+            }
         }
+
+        Ok(())
+    }
+
+    /// Applies `change` to query `query_id` under the metastore lock, waiting for the lock as
+    /// long as it takes. Transient failures are tried again up to `STATUS_CHANGE_ATTEMPTS` times
+    /// with a short pause in between. A deleted query fails at once, retrying can't bring it back.
+    async fn change_status<T>(
+        &self,
+        query_id: &String,
+        metastore: &metastore::SharedMetastore,
+        mut change: impl FnMut(&mut metastore::Metastore) -> Option<T>,
+    ) -> Result<T, StatusError> {
+        let mut attempt = 1;
+        loop {
+            let result = match (self.status_hook)(query_id) {
+                Ok(()) => change(&mut *metastore.write().await).ok_or(StatusError::QueryNotFound),
+                Err(e) => Err(e),
+            };
+            match result {
+                Err(e) if e.is_transient() && attempt < STATUS_CHANGE_ATTEMPTS => {
+                    warn!(
+                        "Failed to change status of query {} (attempt {}): {}, retrying",
+                        query_id, attempt, e
+                    );
+                    attempt += 1;
+                    tokio::time::sleep(Duration::from_millis(STATUS_CHANGE_RETRY_DELAY_MS)).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Moves a planned query to RUNNING. `false` when it is no longer planning, so a query
+    /// delivered twice is executed only once.
+    async fn start_query(
+        &self,
+        query_id: &String,
+        metastore: &metastore::SharedMetastore,
+    ) -> Result<bool, StatusError> {
+        self.change_status(query_id, metastore, |metastore| {
+            let q = metastore.get_query_internal_mut(query_id)?;
+            if !matches!(q.status, query::QueryStatus::Planning) {
+                return Some(false);
+            }
+            q.status = query::QueryStatus::Running;
+            Some(true)
+        })
+        .await
     }
 
     async fn complete_query(
@@ -756,11 +1282,19 @@ impl Executor {
         result: Option<Vec<query::QueryResult>>,
         metastore: &metastore::SharedMetastore,
     ) {
-        let mut metastore_guard = metastore.write().await;
-        if let Some(q) = metastore_guard.get_query_internal_mut(query_id) {
-            q.status = query::QueryStatus::Completed;
-            q.result = result;
-            info!("Query {} completed successfully", query_id);
+        let mut result = Some(result);
+        let changed = self
+            .change_status(query_id, metastore, |metastore| {
+                let q = metastore.get_query_internal_mut(query_id)?;
+                q.status = query::QueryStatus::Completed;
+                q.result = result.take().flatten();
+                q.finished_at = Some(unix_timestamp());
+                Some(())
+            })
+            .await;
+        match changed {
+            Ok(()) => info!("Query {} completed successfully", query_id),
+            Err(e) => error!("Failed to complete query {}: {}", query_id, e),
         }
     }
 
@@ -770,24 +1304,355 @@ impl Executor {
         error: ExecutorError,
         metastore: &metastore::SharedMetastore,
     ) {
-        let mut metastore_guard = metastore.write().await;
-        if let Some(q) = metastore_guard.get_query_internal_mut(query_id) {
-            q.status = query::QueryStatus::Failed;
-            q.errors = Some(vec![query::QueryError {
-                message: error.clone(),
-                context: None,
-            }]);
-            let maybe_table_id = match &q.definition {
-                query::QueryDefinition::SelectAll(select_all) => Some(select_all.table_id.clone()),
-                query::QueryDefinition::Select(select) => select.table_id.clone(),
-                query::QueryDefinition::Copy(copy) => Some(copy.table_id.clone()),
-            };
-            if let Some(id) = maybe_table_id {
-                if let Some(access_set) = metastore_guard.table_accesses.get_mut(&id) {
-                    access_set.remove(query_id);
+        let changed = self
+            .change_status(query_id, metastore, |metastore| {
+                let q = metastore.get_query_internal_mut(query_id)?;
+                q.status = query::QueryStatus::Failed;
+                q.finished_at = Some(unix_timestamp());
+                q.errors = Some(vec![query::QueryError {
+                    message: error.clone(),
+                    context: None,
+                    kind: query::QueryErrorKind::Error,
+                }]);
+                for id in q.definition.table_ids() {
+                    if let Some(access_set) = metastore.table_accesses.get_mut(&id) {
+                        access_set.remove(query_id);
+                    }
                 }
-            }
-            error!("Query {} failed: {}", query_id, error);
+                Some(())
+            })
+            .await;
+        match changed {
+            Ok(()) => error!("Query {} failed: {}", query_id, error),
+            Err(e) => error!(
+                "Failed to mark query {} as failed ({}): {}",
+                query_id, error, e
+            ),
+        }
+    }
+}
+
+/// Why the status of a query couldn't be changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StatusError {
+    /// Query was deleted, retrying can't help.
+    QueryNotFound,
+    /// Status is kept from changing for now, a later attempt may succeed. Only returned by
+    /// `status_hook`, which nothing but tests sets yet.
+    #[allow(dead_code)]
+    Busy,
+}
+
+impl StatusError {
+    fn is_transient(&self) -> bool {
+        matches!(self, Self::Busy)
+    }
+}
+
+impl std::fmt::Display for StatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::QueryNotFound => write!(f, "Query not found"),
+            Self::Busy => write!(f, "Query status is busy"),
+        }
+    }
+}
+
+/// Writes `new[i]` into row `target_rows[i]` of `existing`, appending rows past its end.
+/// Existing rows are only overwritten when `overwrite` is set.
+fn upsert_values<T: Clone>(
+    existing: &mut Vec<T>,
+    new: &[T],
+    target_rows: &[usize],
+    overwrite: bool,
+) {
+    for (value, &row) in new.iter().zip(target_rows) {
+        if row == existing.len() {
+            existing.push(value.clone());
+        } else if overwrite {
+            existing[row] = value.clone();
+        }
+    }
+}
+
+/// Fails when a value of `new_keys` is already in `existing` or repeats among `new_keys`.
+fn check_unique_keys(
+    key_column: &str,
+    existing: &ColumnData,
+    new_keys: &ColumnData,
+) -> Result<(), ExecutorError> {
+    let mut seen = HashSet::with_capacity(existing.len() + new_keys.len());
+    seen.extend((0..existing.len()).map(|row| existing.value(row)));
+    for row in 0..new_keys.len() {
+        let key = new_keys.value(row);
+        if seen.contains(&key) {
+            return Err(format!(
+                "Value '{}' already exists in key column '{}'",
+                key, key_column
+            ));
         }
+        seen.insert(key);
+    }
+    Ok(())
+}
+
+fn check_field_sizes(
+    row_idx: usize,
+    record: &[String],
+    max_field_size: usize,
+) -> Result<(), ExecutorError> {
+    match record
+        .iter()
+        .enumerate()
+        .find(|(_, field)| field.len() > max_field_size)
+    {
+        Some((col_idx, field)) => Err(format!(
+            "Field too large at Row {}, Column {}: {} bytes exceeds limit of {} bytes",
+            row_idx + 1,
+            col_idx + 1,
+            field.len(),
+            max_field_size
+        )),
+        None => Ok(()),
+    }
+}
+
+/// First few names joined by commas, with `...` when some were left out.
+fn shown_names(names: &[String]) -> String {
+    let mut shown = names
+        .iter()
+        .take(MISMATCH_NAMES_SHOWN)
+        .cloned()
+        .collect::<Vec<_>>();
+    if names.len() > MISMATCH_NAMES_SHOWN {
+        shown.push("...".to_string());
+    }
+    shown.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env, fs,
+        sync::atomic::{AtomicUsize, Ordering as AtomicOrdering},
+    };
+
+    use openapi_client::models;
+    use tokio::sync::RwLock;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn copy_delivered_twice_is_applied_once() {
+        let source = env::temp_dir().join(format!("isdb_{}_copy_twice.csv", std::process::id()));
+        fs::write(&source, "1,a\n2,b\n").unwrap();
+        let metastore: metastore::SharedMetastore =
+            Arc::new(RwLock::new(metastore::Metastore::new()));
+        let query_id = {
+            let mut guard = metastore.write().await;
+            guard
+                .create_table(models::TableSchema::new(
+                    "t".to_string(),
+                    vec![
+                        models::Column::new("id".to_string(), models::LogicalColumnType::Int64),
+                        models::Column::new("name".to_string(), models::LogicalColumnType::Varchar),
+                    ],
+                ))
+                .unwrap();
+            guard
+                .create_copy_query(&models::CopyQuery::new(
+                    source.display().to_string(),
+                    "t".to_string(),
+                ))
+                .unwrap()
+        };
+
+        // Both deliveries are planned before either runs, then the id arrives once more after
+        // the query completed.
+        let planner = planner::Planner::new();
+        let executor = Executor::new();
+        let first = planner.plan(&query_id, &metastore).await.unwrap();
+        let second = planner.plan(&query_id, &metastore).await.unwrap();
+        executor.execute(&query_id, first, &metastore).await;
+        executor.execute(&query_id, second, &metastore).await;
+        assert!(planner.plan(&query_id, &metastore).await.is_none());
+        fs::remove_file(&source).unwrap();
+
+        let mut guard = metastore.write().await;
+        let table_id = guard.tables_name_id["t"].clone();
+        assert_eq!(
+            guard
+                .get_table_internal(&table_id)
+                .unwrap()
+                .unwrap()
+                .num_rows,
+            2
+        );
+        assert_eq!(
+            guard.get_query_internal_mut(&query_id).unwrap().status,
+            query::QueryStatus::Completed
+        );
+    }
+
+    #[tokio::test]
+    async fn upsert_by_another_column_keeps_table_key_unique() {
+        let source = env::temp_dir().join(format!("isdb_{}_upsert_key.csv", std::process::id()));
+        fs::write(&source, "1,a\n1,b\n").unwrap();
+        let metastore: metastore::SharedMetastore =
+            Arc::new(RwLock::new(metastore::Metastore::new()));
+        let query_id = {
+            let mut guard = metastore.write().await;
+            let mut schema = models::TableSchema::new(
+                "keyed".to_string(),
+                vec![
+                    models::Column::new("id".to_string(), models::LogicalColumnType::Int64),
+                    models::Column::new("name".to_string(), models::LogicalColumnType::Varchar),
+                ],
+            );
+            schema.key_column = Some("id".to_string());
+            guard.create_table(schema).unwrap();
+            let mut copy =
+                models::CopyQuery::new(source.display().to_string(), "keyed".to_string());
+            copy.key_column = Some("name".to_string());
+            copy.upsert = Some(true);
+            guard.create_copy_query(&copy).unwrap()
+        };
+
+        let plan = planner::Planner::new()
+            .plan(&query_id, &metastore)
+            .await
+            .unwrap();
+        Executor::new().execute(&query_id, plan, &metastore).await;
+        fs::remove_file(&source).unwrap();
+
+        let mut guard = metastore.write().await;
+        let table_id = guard.tables_name_id["keyed"].clone();
+        assert_eq!(
+            guard
+                .get_table_internal(&table_id)
+                .unwrap()
+                .unwrap()
+                .num_rows,
+            0
+        );
+        assert_eq!(
+            guard.get_query_internal_mut(&query_id).unwrap().status,
+            query::QueryStatus::Failed
+        );
+    }
+
+    fn planning_query(metastore: &mut metastore::Metastore) -> String {
+        metastore
+            .create_table(models::TableSchema::new(
+                "busy".to_string(),
+                vec![models::Column::new(
+                    "id".to_string(),
+                    models::LogicalColumnType::Int64,
+                )],
+            ))
+            .unwrap();
+        let query_id = metastore
+            .create_select_all_query(&models::SelectAllQuery::new("busy".to_string()))
+            .unwrap();
+        metastore.get_query_internal_mut(&query_id).unwrap().status = query::QueryStatus::Planning;
+        query_id
+    }
+
+    /// Executor whose status changes fail as busy `failures` times, and the count of attempts.
+    fn flaky_executor(failures: usize) -> (Executor, Arc<AtomicUsize>) {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = attempts.clone();
+        let executor = Executor {
+            status_hook: Arc::new(move |_| {
+                if counter.fetch_add(1, AtomicOrdering::SeqCst) < failures {
+                    Err(StatusError::Busy)
+                } else {
+                    Ok(())
+                }
+            }),
+        };
+        (executor, attempts)
+    }
+
+    #[tokio::test]
+    async fn transient_status_failure_is_retried() {
+        let mut guard = metastore::Metastore::new();
+        let query_id = planning_query(&mut guard);
+        let metastore: metastore::SharedMetastore = Arc::new(RwLock::new(guard));
+        let (executor, attempts) = flaky_executor(STATUS_CHANGE_ATTEMPTS - 1);
+
+        assert_eq!(executor.start_query(&query_id, &metastore).await, Ok(true));
+        assert_eq!(
+            attempts.load(AtomicOrdering::SeqCst),
+            STATUS_CHANGE_ATTEMPTS
+        );
+        assert_eq!(
+            metastore
+                .write()
+                .await
+                .get_query_internal_mut(&query_id)
+                .unwrap()
+                .status,
+            query::QueryStatus::Running
+        );
+    }
+
+    #[tokio::test]
+    async fn status_change_gives_up_after_the_last_attempt() {
+        let mut guard = metastore::Metastore::new();
+        let query_id = planning_query(&mut guard);
+        let metastore: metastore::SharedMetastore = Arc::new(RwLock::new(guard));
+        let (executor, attempts) = flaky_executor(usize::MAX);
+
+        assert_eq!(
+            executor.start_query(&query_id, &metastore).await,
+            Err(StatusError::Busy)
+        );
+        assert_eq!(
+            attempts.load(AtomicOrdering::SeqCst),
+            STATUS_CHANGE_ATTEMPTS
+        );
+        assert_eq!(
+            metastore
+                .write()
+                .await
+                .get_query_internal_mut(&query_id)
+                .unwrap()
+                .status,
+            query::QueryStatus::Planning
+        );
+    }
+
+    #[tokio::test]
+    async fn status_change_waits_for_a_busy_lock() {
+        let mut guard = metastore::Metastore::new();
+        let query_id = planning_query(&mut guard);
+        let metastore: metastore::SharedMetastore = Arc::new(RwLock::new(guard));
+        let (executor, attempts) = flaky_executor(0);
+
+        // However long the lock is held, the change waits for it in a single attempt.
+        let guard = metastore.read().await;
+        let (started, ()) = tokio::join!(executor.start_query(&query_id, &metastore), async move {
+            tokio::task::yield_now().await;
+            drop(guard);
+        });
+
+        assert_eq!(started, Ok(true));
+        assert_eq!(attempts.load(AtomicOrdering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn deleted_query_is_not_retried() {
+        let metastore: metastore::SharedMetastore =
+            Arc::new(RwLock::new(metastore::Metastore::new()));
+        let (executor, attempts) = flaky_executor(0);
+
+        assert_eq!(
+            executor
+                .start_query(&"deleted".to_string(), &metastore)
+                .await,
+            Err(StatusError::QueryNotFound)
+        );
+        assert_eq!(attempts.load(AtomicOrdering::SeqCst), 1);
     }
 }
@@ -1,16 +1,27 @@
 use std::{
-    fmt::Debug,
-    fs::File,
-    io::{Error, Read, Seek, SeekFrom, Write},
-    path::Path,
+    borrow::Cow,
+    collections::{BTreeMap, VecDeque},
+    fmt::{Debug, Display},
+    io::{BufRead, BufReader, BufWriter, Cursor, Error, ErrorKind, Read, Seek, SeekFrom, Write},
+    num::ParseIntError,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
 };
 
+use csv::ReaderBuilder;
+use rayon::{ThreadPool, ThreadPoolBuilder, prelude::*};
+
 use crate::compress::{
     CompressedStringColumn, CompressorError, IntCompressors, LZ4StringCompressor, NoIntCompressor,
     NoStringCompressor, StringCompressors, VleDeltaIntCompressor,
 };
+use crate::storage::{FileStamp, LocalFs, ReadSeek, StorageBackend};
 
 pub mod compress;
+pub mod storage;
 
 /*
 * [HEADER]
@@ -18,16 +29,29 @@ pub mod compress;
 * 1 byte for version number
 * 2 bytes for number of columns u16
 * 8 bytes for number of rows u64
+* 1 byte for flags (1 - checksums, 2 - stats, 4 - string offsets, 8 - sorted columns)
+*   (since version 4)
+* 1 byte for string codec (0 - none, 1 - LZ4, 2 - LZ4 frame, 3 - front coding)
+*   (since version 5)
 * For every colum its data:
 *   1 byte for name length
 *   name bytes
 *   1 byte for type (0 - INT64, 1 - STRING)
+*   1 byte metadata flag (0 - absent, 1 - present) (since version 2)
+*   4 bytes for metadata length u32 and UTF-8 metadata bytes (only when flag is 1)
 *   8 bytes for data offset
 *   8 bytes for data length
 *   8 bytes for lengths data offset (for STRING only)
 *   8 bytes for lengths data length (for STRING only)
+*   8 bytes for rank data length, 0 for unsorted columns (only with sorted columns flag)
+*   8 bytes for checksum of data, lengths data and rank data (only with checksums flag)
+*   8 bytes for min and 8 bytes for max i64 (only with stats flag, min > max when empty)
 *
 * [DATA SECTION] columns data at each offset
+*   int data (also string lengths) starts with 1 byte int codec mode (since version 3)
+*   with string offsets flag string lengths are stored as cumulative end offsets instead
+*   sorted column stores its values sorted, followed by int data holding for every
+*   original row the position of its value in sorted data
 *
 * [FOOTER]
 * 4 bytes for magic: b"ENDC"
@@ -35,7 +59,54 @@ pub mod compress;
 
 const MAGIC: &[u8; 4] = b"ISBD";
 const FOOTER: &[u8; 4] = b"ENDC";
-const VERSION: u8 = 1;
+const VERSION: u8 = 5;
+
+const CHECKSUMS_FLAG: u8 = 1;
+const STATS_FLAG: u8 = 2;
+const STRING_OFFSETS_FLAG: u8 = 4;
+const SORTED_FLAG: u8 = 8;
+
+/// Size of the write buffer used by `serialize` unless configured otherwise.
+pub const DEFAULT_BLOCK_SIZE: usize = 8 * 1024;
+
+/// Longest column name in bytes a file can hold, its length is stored in a single byte.
+pub const MAX_COLUMN_NAME_LEN: usize = u8::MAX as usize;
+
+/// Most columns a file can hold, their count is stored in two bytes.
+pub const MAX_COLUMNS: usize = u16::MAX as usize;
+
+/// Pool shared by serializers without their own thread limit. Uses half of the cores, so
+/// compression running next to a server leaves room for request handling.
+/// `None` when the pool can't be built, serializers then run on the global rayon pool.
+fn default_thread_pool() -> Option<Arc<ThreadPool>> {
+    static POOL: OnceLock<Option<Arc<ThreadPool>>> = OnceLock::new();
+    POOL.get_or_init(|| {
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        build_thread_pool((cores / 2).max(1))
+            .inspect_err(|e| log::warn!("{}, using the global thread pool", e))
+            .ok()
+    })
+    .clone()
+}
+
+/// `threads` has to be positive, rayon would treat 0 as one thread per core.
+fn build_thread_pool(threads: usize) -> Result<Arc<ThreadPool>, SerializerError> {
+    ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .map(Arc::new)
+        .map_err(SerializerError::ThreadPool)
+}
+
+/// 64-bit FNV-1a hash of `parts` one after another, only meant to detect corrupted data.
+pub fn checksum(parts: &[&[u8]]) -> u64 {
+    parts
+        .iter()
+        .flat_map(|part| part.iter())
+        .fold(0xcbf29ce484222325u64, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+}
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum ColumnData {
@@ -44,10 +115,165 @@ pub enum ColumnData {
     BOOL(Vec<bool>),
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LogicalColumnType {
+    Int64,
+    Varchar,
+    Bool,
+}
+
+impl Display for LogicalColumnType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Int64 => write!(f, "INT64"),
+            Self::Varchar => write!(f, "VARCHAR"),
+            Self::Bool => write!(f, "BOOL"),
+        }
+    }
+}
+
+impl ColumnData {
+    /// No values, but room for `capacity` of them without reallocating.
+    pub fn with_capacity(column_type: LogicalColumnType, capacity: usize) -> Self {
+        match column_type {
+            LogicalColumnType::Int64 => Self::INT64(Vec::with_capacity(capacity)),
+            LogicalColumnType::Varchar => Self::STR(Vec::with_capacity(capacity)),
+            LogicalColumnType::Bool => Self::BOOL(Vec::with_capacity(capacity)),
+        }
+    }
+
+    pub fn logical_type(&self) -> LogicalColumnType {
+        match self {
+            Self::INT64(_) => LogicalColumnType::Int64,
+            Self::STR(_) => LogicalColumnType::Varchar,
+            Self::BOOL(_) => LogicalColumnType::Bool,
+        }
+    }
+
+    /// Value at `row`, panics when out of bounds like slice indexing does.
+    pub fn value(&self, row: usize) -> RowValue {
+        match self {
+            Self::INT64(data) => RowValue::Int(data[row]),
+            Self::STR(data) => RowValue::Str(data[row].clone()),
+            Self::BOOL(data) => RowValue::Bool(data[row]),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::INT64(data) => data.len(),
+            Self::STR(data) => data.len(),
+            Self::BOOL(data) => data.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Size of the values in memory, not counting allocation overhead.
+    pub fn byte_size(&self) -> usize {
+        match self {
+            Self::INT64(data) => data.len() * size_of::<i64>(),
+            Self::STR(data) => data.iter().map(String::len).sum(),
+            Self::BOOL(data) => data.len(),
+        }
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        match self {
+            Self::INT64(data) => data.truncate(len),
+            Self::STR(data) => data.truncate(len),
+            Self::BOOL(data) => data.truncate(len),
+        }
+    }
+
+    /// Keeps the first `head` and the last `tail` values, dropping everything between them.
+    pub fn retain_ends(&mut self, head: usize, tail: usize) {
+        fn retain<T>(data: &mut Vec<T>, head: usize, tail: usize) {
+            let head = head.min(data.len());
+            let tail_start = data.len().saturating_sub(tail).max(head);
+            data.drain(head..tail_start);
+        }
+
+        match self {
+            Self::INT64(data) => retain(data, head, tail),
+            Self::STR(data) => retain(data, head, tail),
+            Self::BOOL(data) => retain(data, head, tail),
+        }
+    }
+
+    /// Removes values at `sorted_indices`, which have to be ascending and without repeats.
+    /// Indices past the end are ignored.
+    pub fn drop_rows(&mut self, sorted_indices: &[usize]) {
+        fn drop<T>(data: &mut Vec<T>, sorted_indices: &[usize]) {
+            let mut to_drop = sorted_indices.iter().peekable();
+            let mut row = 0;
+            data.retain(|_| {
+                let dropped = to_drop.next_if_eq(&&row).is_some();
+                row += 1;
+                !dropped
+            });
+        }
+
+        match self {
+            Self::INT64(data) => drop(data, sorted_indices),
+            Self::STR(data) => drop(data, sorted_indices),
+            Self::BOOL(data) => drop(data, sorted_indices),
+        }
+    }
+
+    /// Converts the values to type `to` in place. Only changes keeping every value are allowed,
+    /// INT64 values become their decimal representation in VARCHAR.
+    pub fn convert(&mut self, to: LogicalColumnType) -> Result<(), SerializerError> {
+        self.check_convert(to)?;
+        if let (Self::INT64(data), LogicalColumnType::Varchar) = (&*self, to) {
+            *self = Self::STR(data.iter().map(i64::to_string).collect());
+        }
+        Ok(())
+    }
+
+    /// Fails like `convert` to `to` would, without touching the data.
+    pub fn check_convert(&self, to: LogicalColumnType) -> Result<(), SerializerError> {
+        match (self, to) {
+            (data, to) if data.logical_type() == to => Ok(()),
+            (Self::INT64(_), LogicalColumnType::Varchar) => Ok(()),
+            (data, to) => Err(SerializerError::UnsupportedConversion {
+                from: data.logical_type(),
+                to,
+            }),
+        }
+    }
+
+    /// Whether `value` has the type of this column.
+    pub fn accepts(&self, value: &RowValue) -> bool {
+        self.logical_type() == value.logical_type()
+    }
+
+    /// Appends `value`, failing with `InvalidRow` when its type doesn't match the column.
+    pub fn push(&mut self, value: RowValue) -> Result<(), SerializerError> {
+        match (self, value) {
+            (Self::INT64(data), RowValue::Int(v)) => data.push(v),
+            (Self::STR(data), RowValue::Str(v)) => data.push(v),
+            (Self::BOOL(data), RowValue::Bool(v)) => data.push(v),
+            (column, value) => {
+                return Err(SerializerError::InvalidRow(format!(
+                    "Expected {} value, got {}",
+                    column.logical_type(),
+                    value.logical_type()
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Column {
     pub name: String,
     pub data: ColumnData,
+    /// Free-form description attached by the user, e.g. unit of the values.
+    pub metadata: Option<String>,
 }
 
 impl Column {
@@ -55,6 +281,7 @@ impl Column {
         Self {
             name,
             data: ColumnData::INT64(int_data),
+            metadata: None,
         }
     }
 
@@ -62,7 +289,40 @@ impl Column {
         Self {
             name,
             data: ColumnData::STR(str_data),
+            metadata: None,
+        }
+    }
+
+    pub fn empty(name: String, column_type: LogicalColumnType) -> Self {
+        Self {
+            name,
+            data: ColumnData::with_capacity(column_type, 0),
+            metadata: None,
+        }
+    }
+
+    pub fn with_metadata(mut self, metadata: Option<String>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+}
+
+/// Ordered column names and types, compared instead of whole tables when only shape matters.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Schema {
+    pub columns: Vec<(String, LogicalColumnType)>,
+}
+
+impl Display for Schema {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(")?;
+        for (i, (name, column_type)) in self.columns.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{} {}", name, column_type)?;
         }
+        write!(f, ")")
     }
 }
 
@@ -77,6 +337,21 @@ impl Table {
         Self { num_rows, columns }
     }
 
+    /// Empty table of `schema`, every column has room for `num_rows_hint` values, so a table
+    /// filled row by row doesn't reallocate until it grows past the hint.
+    pub fn with_capacity(num_rows_hint: usize, schema: &Schema) -> Self {
+        let columns = schema
+            .columns
+            .iter()
+            .map(|(name, column_type)| Column {
+                name: name.clone(),
+                data: ColumnData::with_capacity(*column_type, num_rows_hint),
+                metadata: None,
+            })
+            .collect();
+        Self::new(0, columns)
+    }
+
     pub fn iter_columns(&self) -> impl Iterator<Item = &Column> {
         self.columns.iter()
     }
@@ -92,288 +367,4358 @@ impl Table {
     pub fn get_num_cols(&self) -> usize {
         self.columns.len()
     }
-}
 
-#[derive(Debug)]
-pub enum SerializerError {
-    Compressor(CompressorError),
-    IO(Error),
-    InvalidFileFormat(String),
-}
-
-impl From<CompressorError> for SerializerError {
-    fn from(value: CompressorError) -> Self {
-        Self::Compressor(value)
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.columns.iter().find(|column| column.name == name)
     }
-}
 
-impl From<Error> for SerializerError {
-    fn from(value: Error) -> Self {
-        Self::IO(value)
+    /// Position of column `name`, so loops can resolve it once and use `column_at` afterwards.
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|column| column.name == name)
     }
-}
-
-#[derive(Debug)]
-pub struct Serializer {
-    int_compressor: IntCompressors,
-    string_compressor: StringCompressors,
-}
 
-impl Serializer {
-    pub fn new() -> Self {
-        Self {
-            int_compressor: IntCompressors::VleDelta(VleDeltaIntCompressor),
-            string_compressor: StringCompressors::Lz4(LZ4StringCompressor),
-        }
+    /// Column at position `index`, `None` when the table has fewer columns.
+    pub fn column_at(&self, index: usize) -> Option<&Column> {
+        self.columns.get(index)
     }
 
-    pub fn no_compression() -> Self {
-        Self {
-            int_compressor: IntCompressors::None(NoIntCompressor),
-            string_compressor: StringCompressors::None(NoStringCompressor),
+    /// Data of INT64 column `name`, `None` when there is no such column or it has other type.
+    pub fn int_column(&self, name: &str) -> Option<&[i64]> {
+        match &self.column(name)?.data {
+            ColumnData::INT64(data) => Some(data),
+            _ => None,
         }
     }
 
-    pub fn with_compressors(
-        int_compressor: IntCompressors,
-        string_compressor: StringCompressors,
-    ) -> Self {
-        Self {
-            int_compressor,
-            string_compressor,
+    /// Data of VARCHAR column `name`, `None` when there is no such column or it has other type.
+    pub fn str_column(&self, name: &str) -> Option<&[String]> {
+        match &self.column(name)?.data {
+            ColumnData::STR(data) => Some(data),
+            _ => None,
         }
     }
 
-    pub fn serialize(&self, path: &Path, table: &Table) -> Result<(), SerializerError> {
-        let mut f = File::create(path)?;
-
-        f.write_all(MAGIC)?;
-        f.write_all(&[VERSION])?;
-        f.write_all(&(table.columns.len() as u16).to_le_bytes())?;
-        f.write_all(&table.num_rows.to_le_bytes())?;
-
-        let mut placeholders_offsets = Vec::<u64>::new();
-
-        for column in &table.columns {
-            f.write_all(&(column.name.len() as u8).to_le_bytes())?;
-            f.write_all(column.name.as_bytes())?;
-
-            let type_byte = match column.data {
-                ColumnData::BOOL(_) | ColumnData::INT64(_) => 0u8,
-                ColumnData::STR(_) => 1u8,
-            };
-            f.write_all(&[type_byte])?;
+    /// Name and data of every INT64 column in table order, other columns are skipped.
+    pub fn int_columns(&self) -> impl Iterator<Item = (&str, &[i64])> {
+        self.columns.iter().filter_map(|column| match &column.data {
+            ColumnData::INT64(data) => Some((column.name.as_str(), data.as_slice())),
+            _ => None,
+        })
+    }
 
-            placeholders_offsets.push(f.stream_position()? as u64);
+    /// Name and data of every VARCHAR column in table order, other columns are skipped.
+    pub fn str_columns(&self) -> impl Iterator<Item = (&str, &[String])> {
+        self.columns.iter().filter_map(|column| match &column.data {
+            ColumnData::STR(data) => Some((column.name.as_str(), data.as_slice())),
+            _ => None,
+        })
+    }
 
-            f.write_all(&0u64.to_le_bytes())?; // placeholder
-            f.write_all(&0u64.to_le_bytes())?; // placeholder
+    /// Narrowest integer type holding every value, for each INT64 column in order.
+    pub fn int_widths(&self) -> Vec<(String, IntWidth)> {
+        self.int_columns()
+            .map(|(name, data)| {
+                let width = match (data.iter().min(), data.iter().max()) {
+                    (Some(&min), Some(&max)) => ColumnStats { min, max }.int_width(),
+                    _ => IntWidth::I8,
+                };
+                (name.to_string(), width)
+            })
+            .collect()
+    }
 
-            if matches!(column.data, ColumnData::STR(_)) {
-                f.write_all(&0u64.to_le_bytes())?; // placeholder
-            }
+    pub fn schema(&self) -> Schema {
+        Schema {
+            columns: self
+                .columns
+                .iter()
+                .map(|column| (column.name.clone(), column.data.logical_type()))
+                .collect(),
         }
+    }
 
-        #[derive(Debug)]
-        enum Location {
-            INT {
-                offset: u64,
-                length: u64,
-            },
-            STR {
-                offset: u64,
-                length: u64,
-                length2: u64,
-            },
+    /// Appends rows of `other`, which has to have exactly the same schema.
+    pub fn append_table(&mut self, other: Table) -> Result<(), SerializerError> {
+        if !other.matches_schema(&self.schema()) {
+            return Err(SerializerError::SchemaMismatch {
+                expected: self.schema(),
+                found: other.schema(),
+            });
         }
 
-        let mut offsets_and_lengths = Vec::<Location>::new();
-        for column in &table.columns {
-            match &column.data {
-                ColumnData::INT64(data) => {
-                    let compressed_data = self.int_compressor.compress(data.as_slice())?;
-                    let offset = f.stream_position()?;
-                    let length = compressed_data.len() as u64;
-                    offsets_and_lengths.push(Location::INT { offset, length });
-                    f.write_all(&compressed_data)?;
+        for (column, other_column) in self.columns.iter_mut().zip(other.columns) {
+            match (&mut column.data, other_column.data) {
+                (ColumnData::INT64(existing), ColumnData::INT64(mut new)) => {
+                    existing.append(&mut new)
                 }
-                ColumnData::BOOL(data) => {
-                    let int_data = data.iter().map(|&v| v as i64).collect::<Vec<_>>();
-                    let compressed_data = self.int_compressor.compress(int_data.as_slice())?;
-                    let offset = f.stream_position()?;
-                    let length = compressed_data.len() as u64;
-                    offsets_and_lengths.push(Location::INT { offset, length });
-                    f.write_all(&compressed_data)?;
-                }
-                ColumnData::STR(data) => {
-                    let compressed_data = self.string_compressor.compress(data.as_slice())?;
-                    let compressed_str_data = compressed_data.data;
-                    let lengths = compressed_data.lengths;
-
-                    let offset = f.stream_position()?;
-                    let length = compressed_str_data.len() as u64;
-                    f.write_all(&compressed_str_data)?;
-
-                    let compressed_int_data = self.int_compressor.compress(lengths.as_slice())?;
-                    let length2 = compressed_int_data.len() as u64;
-                    f.write_all(&compressed_int_data)?;
-
-                    offsets_and_lengths.push(Location::STR {
-                        offset,
-                        length,
-                        length2,
-                    });
+                (ColumnData::STR(existing), ColumnData::STR(mut new)) => existing.append(&mut new),
+                (ColumnData::BOOL(existing), ColumnData::BOOL(mut new)) => {
+                    existing.append(&mut new)
                 }
+                _ => unreachable!("schemas were compared above"),
             }
         }
+        self.num_rows += other.num_rows;
 
-        f.write_all(FOOTER)?;
+        Ok(())
+    }
 
-        for (i, &offset) in placeholders_offsets.iter().enumerate() {
-            f.seek(SeekFrom::Start(offset))?;
-            match offsets_and_lengths[i] {
-                Location::INT { offset, length } => {
-                    f.write_all(&offset.to_le_bytes())?;
-                    f.write_all(&length.to_le_bytes())?;
-                }
-                Location::STR {
-                    offset,
-                    length,
-                    length2,
-                } => {
-                    f.write_all(&offset.to_le_bytes())?;
-                    f.write_all(&length.to_le_bytes())?;
-                    f.write_all(&length2.to_le_bytes())?;
-                }
-            }
+    /// Removes rows at `sorted_indices` from every column in a single pass over each. Indices
+    /// have to be ascending, without repeats and in range, otherwise nothing is removed and
+    /// `InvalidRow` is returned.
+    pub fn drop_rows(&mut self, sorted_indices: &[usize]) -> Result<(), SerializerError> {
+        if let Some(pair) = sorted_indices.windows(2).find(|pair| pair[0] >= pair[1]) {
+            return Err(SerializerError::InvalidRow(format!(
+                "Row indices have to be ascending, {} is followed by {}",
+                pair[0], pair[1]
+            )));
         }
+        if let Some(&last) = sorted_indices.last()
+            && last as u64 >= self.num_rows
+        {
+            return Err(SerializerError::InvalidRow(format!(
+                "Row {} out of range, table has {} rows",
+                last, self.num_rows
+            )));
+        }
+
+        for column in &mut self.columns {
+            column.data.drop_rows(sorted_indices);
+        }
+        self.num_rows -= sorted_indices.len() as u64;
 
         Ok(())
     }
 
-    pub fn deserialize(&self, path: &Path) -> Result<Table, SerializerError> {
-        let mut f = File::open(path)?;
+    /// Changes only the name, column data and metadata are kept as they are.
+    pub fn rename_column(&mut self, old: &str, new: &str) -> Result<(), SerializerError> {
+        if old != new && self.column(new).is_some() {
+            return Err(SerializerError::DuplicateColumn(new.to_string()));
+        }
+        let column = self
+            .columns
+            .iter_mut()
+            .find(|column| column.name == old)
+            .ok_or_else(|| SerializerError::ColumnNotFound(old.to_string()))?;
+        column.name = new.to_string();
 
-        let mut magic = [0u8; 4];
-        f.read_exact(&mut magic)?;
-        if &magic != MAGIC {
-            return Err(SerializerError::InvalidFileFormat(
-                "Invalid file indicator".to_string(),
-            ));
+        Ok(())
+    }
+
+    /// Changes type of column `name`, see `ColumnData::convert` for the allowed changes.
+    pub fn change_column_type(
+        &mut self,
+        name: &str,
+        to: LogicalColumnType,
+    ) -> Result<(), SerializerError> {
+        self.columns
+            .iter_mut()
+            .find(|column| column.name == name)
+            .ok_or_else(|| SerializerError::ColumnNotFound(name.to_string()))?
+            .data
+            .convert(to)
+    }
+
+    /// Appends `column`, which has to hold a value for every row and have a name not used yet.
+    pub fn add_column(&mut self, column: Column) -> Result<(), SerializerError> {
+        if self.column(&column.name).is_some() {
+            return Err(SerializerError::DuplicateColumn(column.name));
         }
+        if column.data.len() as u64 != self.num_rows {
+            return Err(SerializerError::InvalidRow(format!(
+                "Column '{}' has {} rows, table has {}",
+                column.name,
+                column.data.len(),
+                self.num_rows
+            )));
+        }
+        self.columns.push(column);
 
-        let mut v = [0u8; 1];
-        f.read_exact(&mut v)?;
-        let _version = v[0];
+        Ok(())
+    }
 
-        let mut tmp2 = [0u8; 2];
-        f.read_exact(&mut tmp2)?;
-        let num_cols = u16::from_le_bytes(tmp2) as usize;
+    pub fn matches_schema(&self, schema: &Schema) -> bool {
+        self.columns.len() == schema.columns.len()
+            && self
+                .columns
+                .iter()
+                .zip(&schema.columns)
+                .all(|(column, (name, column_type))| {
+                    column.name == *name && column.data.logical_type() == *column_type
+                })
+    }
 
-        let mut tmp8 = [0u8; 8];
-        f.read_exact(&mut tmp8)?;
-        let num_rows = u64::from_le_bytes(tmp8);
+    /// Reads whole CSV into a table. A column becomes INT64 when every value in it parses as
+    /// an integer, otherwise it is kept as strings. Without headers columns are named col1, col2...
+    pub fn from_csv_reader<R: Read>(
+        reader: R,
+        options: &CsvOptions,
+    ) -> Result<Self, SerializerError> {
+        let mut reader = BufReader::new(reader);
+        skip_utf8_bom(&mut reader)?;
+        let mut rdr = ReaderBuilder::new()
+            .has_headers(options.has_headers)
+            .delimiter(options.delimiter)
+            .from_reader(reader);
 
-        #[derive(Debug)]
-        struct ColumnDescription {
-            name: String,
-            data: ColumnData,
-            offset: u64,
-            length: u64,
-            length2: u64,
+        let mut names = if options.has_headers {
+            rdr.headers()?.iter().map(|s| s.to_string()).collect()
+        } else {
+            Vec::new()
+        };
+
+        let mut raw_columns: Vec<Vec<String>> = vec![Vec::new(); names.len()];
+        let mut num_rows = 0u64;
+        for record in rdr.records() {
+            let record = record?;
+            if raw_columns.is_empty() {
+                raw_columns = vec![Vec::new(); record.len()];
+            }
+            if record.len() != raw_columns.len() {
+                return Err(SerializerError::InvalidRow(format!(
+                    "Row {} has {} values, but CSV has {} columns",
+                    num_rows + 1,
+                    record.len(),
+                    raw_columns.len()
+                )));
+            }
+            for (column, value) in raw_columns.iter_mut().zip(record.iter()) {
+                column.push(value.to_string());
+            }
+            num_rows += 1;
         }
-        let mut descriptions = Vec::<ColumnDescription>::with_capacity(num_cols);
-        for col_idx in 0..num_cols {
-            let mut nl = [0u8; 1];
-            f.read_exact(&mut nl)?;
-            let name_length = nl[0];
 
-            let mut name_bytes = vec![0u8; name_length as usize];
-            f.read_exact(&mut name_bytes)?;
-            let name = String::from_utf8_lossy(&name_bytes).into_owned();
+        if names.is_empty() {
+            names = (1..=raw_columns.len())
+                .map(|i| format!("col{}", i))
+                .collect();
+        }
 
-            let mut t = [0u8; 1];
-            f.read_exact(&mut t)?;
-            let data = match t[0] {
-                0u8 => ColumnData::INT64(Vec::new()),
-                1u8 => ColumnData::STR(Vec::new()),
-                _ => {
-                    return Err(SerializerError::InvalidFileFormat(format!(
-                        "Invalid column type at column: {}",
-                        col_idx
+        let columns = names
+            .into_iter()
+            .zip(raw_columns)
+            .map(|(name, values)| {
+                let ints = values
+                    .iter()
+                    .map(|v| parse_int(v.trim(), options.thousands_separator))
+                    .collect::<Result<Vec<_>, _>>();
+                match ints {
+                    Ok(ints) if !values.is_empty() => Column::new_int_col(name, ints),
+                    _ => Column::new_str_col(name, values),
+                }
+            })
+            .collect();
+
+        Ok(Self::new(num_rows, columns))
+    }
+
+    /// Reads one flat JSON object per line, blank lines are skipped. Columns are the union of
+    /// keys of all objects, ordered by name. A column is INT64 when all its values are integers,
+    /// BOOL when all are booleans and STR otherwise, with non-string values kept as JSON text.
+    /// There are no nulls, so `null` and missing keys get the default value of the column type.
+    pub fn from_ndjson_reader<R: Read>(
+        reader: R,
+        options: &NdjsonOptions,
+    ) -> Result<Self, SerializerError> {
+        let mut reader = BufReader::new(reader);
+        skip_utf8_bom(&mut reader)?;
+
+        let mut raw_columns = BTreeMap::<String, Vec<Option<serde_json::Value>>>::new();
+        let mut num_rows = 0usize;
+        // One byte over the limit is enough to tell that a line is too long.
+        let read_limit = options.max_line_len.map_or(u64::MAX, |len| len as u64 + 1);
+        let mut buf = Vec::new();
+        for line_idx in 0.. {
+            buf.clear();
+            if (&mut reader).take(read_limit).read_until(b'\n', &mut buf)? == 0 {
+                break;
+            }
+            if buf.last() == Some(&b'\n') {
+                buf.pop();
+                if buf.last() == Some(&b'\r') {
+                    buf.pop();
+                }
+            } else if let Some(max) = options.max_line_len
+                && buf.len() > max
+            {
+                return Err(SerializerError::InvalidRow(format!(
+                    "Line {} is longer than {} bytes",
+                    line_idx + 1,
+                    max
+                )));
+            }
+            let Ok(line) = std::str::from_utf8(&buf) else {
+                return Err(SerializerError::InvalidRow(format!(
+                    "Line {} is not valid UTF-8",
+                    line_idx + 1
+                )));
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let object = match serde_json::from_str(line) {
+                Ok(serde_json::Value::Object(object)) => object,
+                Ok(_) => {
+                    return Err(SerializerError::InvalidRow(format!(
+                        "Line {} is not a JSON object",
+                        line_idx + 1
+                    )));
+                }
+                Err(e) => {
+                    return Err(SerializerError::InvalidRow(format!(
+                        "Line {} is not valid JSON: {}",
+                        line_idx + 1,
+                        e
                     )));
                 }
             };
 
-            let mut off = [0u8; 8];
-            f.read_exact(&mut off)?;
-            let offset = u64::from_le_bytes(off);
+            for (key, value) in object {
+                if matches!(
+                    value,
+                    serde_json::Value::Array(_) | serde_json::Value::Object(_)
+                ) && !options.stringify_nested
+                {
+                    return Err(SerializerError::InvalidRow(format!(
+                        "Line {}, key '{}' holds a nested value",
+                        line_idx + 1,
+                        key
+                    )));
+                }
 
-            let mut len = [0u8; 8];
-            f.read_exact(&mut len)?;
-            let length = u64::from_le_bytes(len);
+                let column = raw_columns.entry(key).or_default();
+                column.resize(num_rows, None);
+                column.push((!value.is_null()).then_some(value));
+            }
+            num_rows += 1;
+        }
 
-            let description = match data {
-                ColumnData::BOOL(_) | ColumnData::INT64(_) => ColumnDescription {
-                    name,
-                    data,
-                    offset,
-                    length,
-                    length2: 0u64,
-                },
-                ColumnData::STR(_) => {
-                    let mut len2 = [0u8; 8];
-                    f.read_exact(&mut len2)?;
-                    let length2 = u64::from_le_bytes(len2);
-                    ColumnDescription {
+        let columns = raw_columns
+            .into_iter()
+            .map(|(name, mut values)| {
+                values.resize(num_rows, None);
+                let mut present = values.iter().flatten().peekable();
+                if present.peek().is_none() {
+                    Column::new_str_col(name, vec![String::new(); num_rows])
+                } else if present.clone().all(|value| value.as_i64().is_some()) {
+                    let ints = values
+                        .iter()
+                        .map(|value| value.as_ref().and_then(|v| v.as_i64()).unwrap_or(0))
+                        .collect();
+                    Column::new_int_col(name, ints)
+                } else if present.all(|value| value.as_bool().is_some()) {
+                    let bools = values
+                        .iter()
+                        .map(|value| value.as_ref().and_then(|v| v.as_bool()).unwrap_or(false))
+                        .collect();
+                    Column {
                         name,
-                        data,
-                        offset,
-                        length,
-                        length2,
+                        data: ColumnData::BOOL(bools),
+                        metadata: None,
                     }
+                } else {
+                    let strings = values
+                        .into_iter()
+                        .map(|value| match value {
+                            None => String::new(),
+                            Some(serde_json::Value::String(s)) => s,
+                            Some(other) => other.to_string(),
+                        })
+                        .collect();
+                    Column::new_str_col(name, strings)
                 }
-            };
+            })
+            .collect();
 
-            descriptions.push(description);
+        Ok(Self::new(num_rows as u64, columns))
+    }
+}
+
+/// Table read from a byte slice by `Serializer::view`. String columns keep all their values in
+/// one buffer, borrowed from the slice when it is not compressed, and hand out `&str` per row.
+#[derive(Debug)]
+pub struct TableView<'a> {
+    pub num_rows: u64,
+    pub columns: Vec<ColumnView<'a>>,
+}
+
+impl<'a> TableView<'a> {
+    pub fn column(&self, name: &str) -> Option<&ColumnView<'a>> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+
+    pub fn int_column(&self, name: &str) -> Option<&[i64]> {
+        match &self.column(name)?.data {
+            ColumnDataView::INT64(data) => Some(data),
+            _ => None,
         }
+    }
 
-        let mut columns = Vec::<Column>::with_capacity(num_cols);
-        for desc in descriptions {
-            f.seek(SeekFrom::Start(desc.offset))?;
-            let mut buf = vec![0u8; desc.length as usize];
-            f.read_exact(&mut buf)?;
+    pub fn str_column(&self, name: &str) -> Option<&StrColumnView<'a>> {
+        match &self.column(name)?.data {
+            ColumnDataView::STR(data) => Some(data),
+            _ => None,
+        }
+    }
 
-            match desc.data {
-                ColumnData::BOOL(_) | ColumnData::INT64(_) => {
-                    let mut int_data = self.int_compressor.decompress(&buf)?;
-                    int_data.resize(num_rows as usize, 0i64);
-                    columns.push(Column::new_int_col(desc.name, int_data));
-                }
-                ColumnData::STR(_) => {
-                    let mut buf2 = vec![0u8; desc.length2 as usize];
-                    f.read_exact(&mut buf2)?;
-
-                    let lengths_data = self.int_compressor.decompress(&buf2)?;
-                    let mut str_data =
-                        self.string_compressor.decompress(&CompressedStringColumn {
-                            data: buf,
-                            lengths: lengths_data,
-                        })?;
-                    str_data.resize(num_rows as usize, "".to_string());
-                    columns.push(Column::new_str_col(desc.name, str_data));
-                }
-            }
+    /// Copies every value into an owned `Table`.
+    pub fn to_table(&self) -> Table {
+        Table {
+            num_rows: self.num_rows,
+            columns: self
+                .columns
+                .iter()
+                .map(|column| Column {
+                    name: column.name.clone(),
+                    metadata: column.metadata.clone(),
+                    data: match &column.data {
+                        ColumnDataView::INT64(data) => ColumnData::INT64(data.clone()),
+                        ColumnDataView::STR(data) => {
+                            ColumnData::STR(data.iter().map(str::to_string).collect())
+                        }
+                    },
+                })
+                .collect(),
         }
+    }
+}
 
-        let mut footer = [0u8; 4];
-        f.read_exact(&mut footer)?;
-        if &footer != FOOTER {
-            return Err(SerializerError::InvalidFileFormat(
-                "Invalid file footer".to_string(),
-            ));
+#[derive(Debug)]
+pub struct ColumnView<'a> {
+    pub name: String,
+    pub metadata: Option<String>,
+    pub data: ColumnDataView<'a>,
+}
+
+#[derive(Debug)]
+pub enum ColumnDataView<'a> {
+    INT64(Vec<i64>),
+    STR(StrColumnView<'a>),
+}
+
+/// Strings of a column stored back to back. `offsets` has one entry more than there are rows,
+/// row `i` spans `offsets[i]..offsets[i + 1]`.
+#[derive(Debug)]
+pub struct StrColumnView<'a> {
+    data: Cow<'a, str>,
+    offsets: Vec<usize>,
+}
+
+impl<'a> StrColumnView<'a> {
+    /// Validates UTF-8 of the whole buffer once and then only checks that every string starts
+    /// and ends on a character boundary.
+    fn new(data: Cow<'a, [u8]>, lengths: &[i64], num_rows: usize) -> Result<Self, SerializerError> {
+        let invalid =
+            || SerializerError::InvalidFileFormat("Invalid string column data".to_string());
+        let data = match data {
+            Cow::Borrowed(bytes) => {
+                Cow::Borrowed(std::str::from_utf8(bytes).map_err(|_| invalid())?)
+            }
+            Cow::Owned(bytes) => Cow::Owned(String::from_utf8(bytes).map_err(|_| invalid())?),
+        };
+
+        let mut offsets = Vec::with_capacity(num_rows + 1);
+        let mut end = 0usize;
+        offsets.push(end);
+        for &length in lengths.iter().take(num_rows) {
+            end = usize::try_from(length)
+                .ok()
+                .and_then(|length| end.checked_add(length))
+                .ok_or_else(invalid)?;
+            if !data.is_char_boundary(end) {
+                return Err(invalid());
+            }
+            offsets.push(end);
         }
+        offsets.resize(num_rows + 1, end);
+
+        Ok(Self { data, offsets })
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
 
-        Ok(Table { num_rows, columns })
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether the strings point into the viewed slice rather than a decompressed buffer.
+    pub fn is_borrowed(&self) -> bool {
+        matches!(self.data, Cow::Borrowed(_))
+    }
+
+    pub fn get(&self, row: usize) -> Option<&str> {
+        let start = *self.offsets.get(row)?;
+        let end = *self.offsets.get(row + 1)?;
+        Some(&self.data[start..end])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.offsets
+            .windows(2)
+            .map(|bounds| &self.data[bounds[0]..bounds[1]])
+    }
+}
+
+const UTF8_BOM: &[u8; 3] = b"\xEF\xBB\xBF";
+
+/// Consumes a leading UTF-8 BOM, so it doesn't end up in the first header name or value.
+/// CRLF and mixed line endings need no handling, csv reader accepts `\r\n`, `\n` and `\r`.
+pub fn skip_utf8_bom<R: BufRead>(reader: &mut R) -> Result<(), Error> {
+    if reader.fill_buf()?.starts_with(UTF8_BOM) {
+        reader.consume(UTF8_BOM.len());
+    }
+    Ok(())
+}
+
+/// With a separator, only values grouped like "12,345,678" have it removed; anything else, e.g.
+/// "1,2,3", is parsed as it is and fails.
+fn parse_int(value: &str, thousands_separator: Option<char>) -> Result<i64, ParseIntError> {
+    if let Some(separator) = thousands_separator
+        && value.contains(separator)
+    {
+        let digits = value.strip_prefix(['+', '-']).unwrap_or(value);
+        let mut groups = digits.split(separator);
+        let first_len = groups.next().map_or(0, str::len);
+        if (1..=3).contains(&first_len) && groups.all(|group| group.len() == 3) {
+            return value.replace(separator, "").parse();
+        }
+    }
+    value.parse()
+}
+
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    pub has_headers: bool,
+    pub delimiter: u8,
+    /// Grouping separator removed from values before trying to infer them as INT64, e.g. `,`
+    /// for `"1,234"`. Off by default, as it would turn strings like "1,2" into numbers.
+    pub thousands_separator: Option<char>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            has_headers: true,
+            delimiter: b',',
+            thousands_separator: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NdjsonOptions {
+    /// Nested objects and arrays are stored as their JSON text instead of failing the import.
+    pub stringify_nested: bool,
+    /// Lines longer than this many bytes fail the import before they are fully read.
+    pub max_line_len: Option<usize>,
+}
+
+/// Values of the same type are ordered naturally. Order between types is unspecified.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+pub enum RowValue {
+    Int(i64),
+    Str(String),
+    Bool(bool),
+}
+
+impl RowValue {
+    pub fn logical_type(&self) -> LogicalColumnType {
+        match self {
+            Self::Int(_) => LogicalColumnType::Int64,
+            Self::Str(_) => LogicalColumnType::Varchar,
+            Self::Bool(_) => LogicalColumnType::Bool,
+        }
+    }
+}
+
+/// Same text as the value has in a CSV file.
+impl Display for RowValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Int(value) => write!(f, "{}", value),
+            Self::Str(value) => write!(f, "{}", value),
+            Self::Bool(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl From<i64> for RowValue {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<String> for RowValue {
+    fn from(value: String) -> Self {
+        Self::Str(value)
+    }
+}
+
+impl From<&str> for RowValue {
+    fn from(value: &str) -> Self {
+        Self::Str(value.to_string())
+    }
+}
+
+impl From<bool> for RowValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+/// Conversion back fails with the original value when it holds a different type.
+impl TryFrom<RowValue> for i64 {
+    type Error = RowValue;
+
+    fn try_from(value: RowValue) -> Result<Self, Self::Error> {
+        match value {
+            RowValue::Int(v) => Ok(v),
+            other => Err(other),
+        }
+    }
+}
+
+impl TryFrom<RowValue> for String {
+    type Error = RowValue;
+
+    fn try_from(value: RowValue) -> Result<Self, Self::Error> {
+        match value {
+            RowValue::Str(v) => Ok(v),
+            other => Err(other),
+        }
+    }
+}
+
+impl TryFrom<RowValue> for bool {
+    type Error = RowValue;
+
+    fn try_from(value: RowValue) -> Result<Self, Self::Error> {
+        match value {
+            RowValue::Bool(v) => Ok(v),
+            other => Err(other),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SerializerError {
+    Compressor(CompressorError),
+    IO(Error),
+    InvalidFileFormat(String),
+    ColumnNotFound(String),
+    DuplicateColumn(String),
+    SchemaMismatch {
+        expected: Schema,
+        found: Schema,
+    },
+    InvalidRow(String),
+    Csv(csv::Error),
+    Cancelled,
+    UnsupportedConversion {
+        from: LogicalColumnType,
+        to: LogicalColumnType,
+    },
+    UnsortableColumn {
+        name: String,
+        column_type: LogicalColumnType,
+    },
+    NoThreads,
+    ThreadPool(rayon::ThreadPoolBuildError),
+    TooManyColumns(usize),
+    ColumnNameTooLong(String),
+}
+
+impl Display for SerializerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Compressor(e) => write!(f, "Compression error: {}", e),
+            Self::IO(e) => write!(f, "IO error: {}", e),
+            Self::InvalidFileFormat(msg) => write!(f, "Invalid file format: {}", msg),
+            Self::ColumnNotFound(name) => write!(f, "Column '{}' not found", name),
+            Self::DuplicateColumn(name) => write!(f, "Column '{}' already exists", name),
+            Self::SchemaMismatch { expected, found } => {
+                write!(f, "Schema mismatch: expected {}, found {}", expected, found)
+            }
+            Self::InvalidRow(msg) => write!(f, "Invalid row: {}", msg),
+            Self::Csv(e) => write!(f, "CSV error: {}", e),
+            Self::Cancelled => write!(f, "Operation was cancelled"),
+            Self::UnsupportedConversion { from, to } => {
+                write!(f, "Column of type {} can't be converted to {}", from, to)
+            }
+            Self::UnsortableColumn { name, column_type } => write!(
+                f,
+                "Only VARCHAR columns can be stored sorted, '{}' is {}",
+                name, column_type
+            ),
+            Self::NoThreads => write!(f, "Compression thread pool needs at least one thread"),
+            Self::ThreadPool(e) => write!(f, "Failed to build compression thread pool: {}", e),
+            Self::TooManyColumns(count) => {
+                write!(f, "Table has {} columns, more than {}", count, MAX_COLUMNS)
+            }
+            Self::ColumnNameTooLong(name) => write!(
+                f,
+                "Column name is longer than {} bytes: {}",
+                MAX_COLUMN_NAME_LEN, name
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SerializerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Compressor(e) => Some(e),
+            Self::IO(e) => Some(e),
+            Self::Csv(e) => Some(e),
+            Self::ThreadPool(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<csv::Error> for SerializerError {
+    fn from(value: csv::Error) -> Self {
+        Self::Csv(value)
+    }
+}
+
+impl From<CompressorError> for SerializerError {
+    fn from(value: CompressorError) -> Self {
+        Self::Compressor(value)
+    }
+}
+
+impl From<Error> for SerializerError {
+    fn from(value: Error) -> Self {
+        Self::IO(value)
+    }
+}
+
+/// Value range of a column, for strings the range of their byte lengths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColumnStats {
+    pub min: i64,
+    pub max: i64,
+}
+
+impl ColumnStats {
+    /// `None` for an empty column.
+    pub fn of(data: &ColumnData) -> Option<Self> {
+        let values: Box<dyn Iterator<Item = i64>> = match data {
+            ColumnData::INT64(data) => Box::new(data.iter().copied()),
+            ColumnData::STR(data) => Box::new(data.iter().map(|s| s.len() as i64)),
+            ColumnData::BOOL(data) => Box::new(data.iter().map(|&b| b as i64)),
+        };
+        values.fold(None, |stats, value| match stats {
+            None => Some(Self {
+                min: value,
+                max: value,
+            }),
+            Some(Self { min, max }) => Some(Self {
+                min: min.min(value),
+                max: max.max(value),
+            }),
+        })
+    }
+
+    fn encode(stats: Option<Self>) -> [u8; 16] {
+        let (min, max) = stats.map_or((i64::MAX, i64::MIN), |stats| (stats.min, stats.max));
+        let mut buf = [0u8; 16];
+        buf[..8].copy_from_slice(&min.to_le_bytes());
+        buf[8..].copy_from_slice(&max.to_le_bytes());
+        buf
+    }
+
+    fn decode(buf: [u8; 16]) -> Option<Self> {
+        let min = i64::from_le_bytes(buf[..8].try_into().unwrap());
+        let max = i64::from_le_bytes(buf[8..].try_into().unwrap());
+        (min <= max).then_some(Self { min, max })
+    }
+}
+
+/// Integer type narrow enough for a range of values. Ordered from the narrowest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IntWidth {
+    I8,
+    I16,
+    I32,
+    I64,
+}
+
+impl IntWidth {
+    pub fn bits(&self) -> u32 {
+        match self {
+            Self::I8 => 8,
+            Self::I16 => 16,
+            Self::I32 => 32,
+            Self::I64 => 64,
+        }
+    }
+}
+
+impl ColumnStats {
+    /// Known from the header alone for files written with stats.
+    pub fn int_width(&self) -> IntWidth {
+        let fits = |min: i64, max: i64| self.min >= min && self.max <= max;
+        if fits(i8::MIN as i64, i8::MAX as i64) {
+            IntWidth::I8
+        } else if fits(i16::MIN as i64, i16::MAX as i64) {
+            IntWidth::I16
+        } else if fits(i32::MIN as i64, i32::MAX as i64) {
+            IntWidth::I32
+        } else {
+            IntWidth::I64
+        }
+    }
+}
+
+/// INT64 values kept in the narrowest type holding all of them, for consumers scanning many
+/// rows that don't need a full `i64` per value. Values are widened back without loss.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NarrowIntColumn {
+    I8(Vec<i8>),
+    I16(Vec<i16>),
+    I32(Vec<i32>),
+    I64(Vec<i64>),
+}
+
+impl NarrowIntColumn {
+    pub fn new(data: &[i64]) -> Self {
+        let width = match (data.iter().min(), data.iter().max()) {
+            (Some(&min), Some(&max)) => ColumnStats { min, max }.int_width(),
+            _ => IntWidth::I8,
+        };
+        match width {
+            IntWidth::I8 => Self::I8(data.iter().map(|&v| v as i8).collect()),
+            IntWidth::I16 => Self::I16(data.iter().map(|&v| v as i16).collect()),
+            IntWidth::I32 => Self::I32(data.iter().map(|&v| v as i32).collect()),
+            IntWidth::I64 => Self::I64(data.to_vec()),
+        }
+    }
+
+    pub fn width(&self) -> IntWidth {
+        match self {
+            Self::I8(_) => IntWidth::I8,
+            Self::I16(_) => IntWidth::I16,
+            Self::I32(_) => IntWidth::I32,
+            Self::I64(_) => IntWidth::I64,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::I8(data) => data.len(),
+            Self::I16(data) => data.len(),
+            Self::I32(data) => data.len(),
+            Self::I64(data) => data.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, row: usize) -> Option<i64> {
+        match self {
+            Self::I8(data) => data.get(row).map(|&v| v as i64),
+            Self::I16(data) => data.get(row).map(|&v| v as i64),
+            Self::I32(data) => data.get(row).map(|&v| v as i64),
+            Self::I64(data) => data.get(row).copied(),
+        }
+    }
+
+    pub fn to_i64(&self) -> Vec<i64> {
+        (0..self.len()).filter_map(|row| self.get(row)).collect()
+    }
+}
+
+/// Column entry of a file header. `data` is always empty and only marks the column type,
+/// `length2` is the size of compressed string lengths (0 for non-string columns) and
+/// `rank_length` the size of the compressed rank of a sorted column (0 for other columns).
+/// `checksum` and `stats` are present only in files written with them enabled.
+#[derive(Debug, Clone)]
+pub struct ColumnDescription {
+    pub name: String,
+    pub data: ColumnData,
+    pub metadata: Option<String>,
+    pub offset: u64,
+    pub length: u64,
+    pub length2: u64,
+    pub rank_length: u64,
+    pub checksum: Option<u64>,
+    pub stats: Option<ColumnStats>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileHeader {
+    pub version: u8,
+    pub num_rows: u64,
+    pub checksums: bool,
+    pub stats: bool,
+    pub string_offsets: bool,
+    pub sorted: bool,
+    /// None for files older than version 5, which are read with the configured codec.
+    pub string_compressor: Option<StringCompressors>,
+    pub columns: Vec<ColumnDescription>,
+    pub data_end: u64,
+}
+
+impl FileHeader {
+    fn flags(&self) -> u8 {
+        encode_flags(self.checksums, self.stats, self.string_offsets, self.sorted)
+    }
+}
+
+fn encode_flags(checksums: bool, stats: bool, string_offsets: bool, sorted: bool) -> u8 {
+    let mut flags = 0u8;
+    if checksums {
+        flags |= CHECKSUMS_FLAG;
+    }
+    if stats {
+        flags |= STATS_FLAG;
+    }
+    if string_offsets {
+        flags |= STRING_OFFSETS_FLAG;
+    }
+    if sorted {
+        flags |= SORTED_FLAG;
+    }
+    flags
+}
+
+impl FileHeader {
+    pub fn schema(&self) -> Schema {
+        Schema {
+            columns: self
+                .columns
+                .iter()
+                .map(|desc| (desc.name.clone(), desc.data.logical_type()))
+                .collect(),
+        }
+    }
+}
+
+/// Bounded LRU of parsed file headers keyed by (path, mtime, size). A header whose file was
+/// modified since it was cached is treated as a miss and replaced on the next insert.
+#[derive(Debug, Clone)]
+struct HeaderCache {
+    capacity: usize,
+    entries: VecDeque<(PathBuf, FileStamp, Arc<FileHeader>)>,
+}
+
+impl HeaderCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    fn get(&mut self, path: &Path, stamp: FileStamp) -> Option<Arc<FileHeader>> {
+        let idx = self.entries.iter().position(|(p, _, _)| p == path)?;
+        let entry = self.entries.remove(idx)?;
+        if entry.1 != stamp {
+            return None;
+        }
+        let header = entry.2.clone();
+        self.entries.push_back(entry);
+        Some(header)
+    }
+
+    fn insert(&mut self, path: PathBuf, stamp: FileStamp, header: Arc<FileHeader>) {
+        self.entries.retain(|(p, _, _)| *p != path);
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((path, stamp, header));
+    }
+}
+
+/// `rows_read` is the number of rows every column actually stored, at most the number of
+/// rows in the header. When it is lower, missing values were padded by `deserialize`.
+/// `bytes_read` counts compressed column data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeserializeStats {
+    pub rows_read: u64,
+    pub bytes_read: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompactionReport {
+    pub size_before: u64,
+    pub size_after: u64,
+}
+
+/// Configuration of a `Serializer`. Defaults match `Serializer::new`.
+#[derive(Debug, Clone)]
+pub struct SerializerBuilder {
+    int_compressor: IntCompressors,
+    string_compressor: StringCompressors,
+    checksums: bool,
+    stats: bool,
+    string_offsets: bool,
+    sorted_column: Option<String>,
+    block_size: usize,
+    thread_pool: Option<Arc<ThreadPool>>,
+    lenient_footer: bool,
+    overwrite_protection: bool,
+    auto_codec: bool,
+    backend: Arc<dyn StorageBackend>,
+}
+
+impl Default for SerializerBuilder {
+    fn default() -> Self {
+        Self {
+            int_compressor: IntCompressors::VleDelta(VleDeltaIntCompressor),
+            string_compressor: StringCompressors::Lz4(LZ4StringCompressor),
+            checksums: false,
+            stats: false,
+            string_offsets: false,
+            sorted_column: None,
+            block_size: DEFAULT_BLOCK_SIZE,
+            thread_pool: None,
+            lenient_footer: false,
+            overwrite_protection: false,
+            auto_codec: false,
+            backend: Arc::new(LocalFs),
+        }
+    }
+}
+
+impl SerializerBuilder {
+    pub fn int_compressor(mut self, int_compressor: IntCompressors) -> Self {
+        self.int_compressor = int_compressor;
+        self
+    }
+
+    pub fn string_compressor(mut self, string_compressor: StringCompressors) -> Self {
+        self.string_compressor = string_compressor;
+        self
+    }
+
+    /// Stores a checksum of every column, verified whenever the column is read.
+    pub fn checksums(mut self, checksums: bool) -> Self {
+        self.checksums = checksums;
+        self
+    }
+
+    /// Stores min and max of every column in the header.
+    pub fn stats(mut self, stats: bool) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    /// Stores string lengths as cumulative end offsets, so a single value can be located without
+    /// summing all lengths before it.
+    pub fn string_offsets(mut self, string_offsets: bool) -> Self {
+        self.string_offsets = string_offsets;
+        self
+    }
+
+    /// Stores the named VARCHAR column sorted, together with the rank of every row restoring
+    /// the original order on read. The rank costs 2-3 bytes per row, so this pays off only for
+    /// few distinct long values in random order, with a codec that stores repeated neighbours
+    /// almost for free (front coding). INT64 and BOOL codecs already store such columns in less
+    /// than the rank takes, so those columns are rejected on serialization.
+    pub fn sorted_column(mut self, name: impl Into<String>) -> Self {
+        self.sorted_column = Some(name.into());
+        self
+    }
+
+    /// Size of the buffer files are written through.
+    pub fn block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// Compresses and decompresses columns on a dedicated pool of `threads` threads instead of
+    /// the pool shared by all serializers. The pool is started right away, 0 threads is an error.
+    pub fn threads(mut self, threads: usize) -> Result<Self, SerializerError> {
+        if threads == 0 {
+            return Err(SerializerError::NoThreads);
+        }
+        self.thread_pool = Some(build_thread_pool(threads)?);
+        Ok(self)
+    }
+
+    /// Reads files whose footer is missing or damaged, as written by early versions that could
+    /// stop before the footer. Column data still has to be fully inside the file. A warning is
+    /// logged for every such file. Off by default.
+    pub fn lenient_footer(mut self, lenient_footer: bool) -> Self {
+        self.lenient_footer = lenient_footer;
+        self
+    }
+
+    /// Refuses to write over an existing file, failing with an `AlreadyExists` IO error and
+    /// leaving the file untouched. Off by default, files are silently replaced.
+    pub fn overwrite_protection(mut self, overwrite_protection: bool) -> Self {
+        self.overwrite_protection = overwrite_protection;
+        self
+    }
+
+    /// Compresses every int column, string lengths and ranks included, with the codec estimated
+    /// to produce the fewest bytes instead of the configured one. Int data names its codec, so
+    /// such files read back with any configuration. Off by default.
+    pub fn auto_codec(mut self, auto_codec: bool) -> Self {
+        self.auto_codec = auto_codec;
+        self
+    }
+
+    /// Keeps files in `backend` instead of the local filesystem.
+    pub fn storage_backend(mut self, backend: impl StorageBackend + 'static) -> Self {
+        self.backend = Arc::new(backend);
+        self
+    }
+
+    pub fn build(self) -> Serializer {
+        Serializer {
+            int_compressor: self.int_compressor,
+            string_compressor: self.string_compressor,
+            checksums: self.checksums,
+            stats: self.stats,
+            string_offsets: self.string_offsets,
+            sorted_column: self.sorted_column,
+            block_size: self.block_size,
+            thread_pool: self.thread_pool.or_else(default_thread_pool),
+            header_cache: None,
+            header_parses: AtomicUsize::new(0),
+            lenient_footer: self.lenient_footer,
+            overwrite_protection: self.overwrite_protection,
+            auto_codec: self.auto_codec,
+            backend: self.backend,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Serializer {
+    int_compressor: IntCompressors,
+    string_compressor: StringCompressors,
+    checksums: bool,
+    stats: bool,
+    string_offsets: bool,
+    sorted_column: Option<String>,
+    block_size: usize,
+    thread_pool: Option<Arc<ThreadPool>>,
+    header_cache: Option<Mutex<HeaderCache>>,
+    header_parses: AtomicUsize,
+    lenient_footer: bool,
+    overwrite_protection: bool,
+    auto_codec: bool,
+    backend: Arc<dyn StorageBackend>,
+}
+
+/// Clone shares the thread pool, but starts with its own copy of the header cache and a zero
+/// header parse count. A single serializer can also be shared between threads as it is.
+impl Clone for Serializer {
+    fn clone(&self) -> Self {
+        Self {
+            int_compressor: self.int_compressor.clone(),
+            string_compressor: self.string_compressor.clone(),
+            checksums: self.checksums,
+            stats: self.stats,
+            string_offsets: self.string_offsets,
+            sorted_column: self.sorted_column.clone(),
+            block_size: self.block_size,
+            thread_pool: self.thread_pool.clone(),
+            header_cache: self
+                .header_cache
+                .as_ref()
+                .map(|cache| Mutex::new(cache.lock().unwrap().clone())),
+            header_parses: AtomicUsize::new(0),
+            lenient_footer: self.lenient_footer,
+            overwrite_protection: self.overwrite_protection,
+            auto_codec: self.auto_codec,
+            backend: self.backend.clone(),
+        }
+    }
+}
+
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Serializer>();
+};
+
+impl Serializer {
+    pub fn builder() -> SerializerBuilder {
+        SerializerBuilder::default()
+    }
+
+    pub fn new() -> Self {
+        Self::builder().build()
+    }
+
+    pub fn no_compression() -> Self {
+        Self::builder()
+            .int_compressor(IntCompressors::None(NoIntCompressor))
+            .string_compressor(StringCompressors::None(NoStringCompressor))
+            .build()
+    }
+
+    pub fn with_compressors(
+        int_compressor: IntCompressors,
+        string_compressor: StringCompressors,
+    ) -> Self {
+        Self::builder()
+            .int_compressor(int_compressor)
+            .string_compressor(string_compressor)
+            .build()
+    }
+
+    /// Enables caching of up to `capacity` parsed file headers for this serializer.
+    pub fn with_header_cache(mut self, capacity: usize) -> Self {
+        self.header_cache = Some(Mutex::new(HeaderCache::new(capacity)));
+        self
+    }
+
+    pub fn serialize(&self, path: &Path, table: &Table) -> Result<(), SerializerError> {
+        self.serialize_cancellable(path, table, &AtomicBool::new(false))
+    }
+
+    /// Same as `serialize`, but gives up with `SerializerError::Cancelled` once `cancel` is set.
+    /// The flag is checked before each column is compressed and written. Data goes to a file
+    /// next to `path`, renamed over it only once complete, so a cancelled or failed write
+    /// leaves whatever was at `path` before untouched.
+    pub fn serialize_cancellable(
+        &self,
+        path: &Path,
+        table: &Table,
+        cancel: &AtomicBool,
+    ) -> Result<(), SerializerError> {
+        if self.overwrite_protection && self.backend.exists(path) {
+            return Err(SerializerError::IO(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("{}: File already exists", path.display()),
+            )));
+        }
+
+        self.replace_file(path, table, cancel)
+    }
+
+    /// Writes the table next to `path` and renames it over `path` once complete.
+    fn replace_file(
+        &self,
+        path: &Path,
+        table: &Table,
+        cancel: &AtomicBool,
+    ) -> Result<(), SerializerError> {
+        let partial_path = partial_path(path);
+        let result = self
+            .write_table(&partial_path, table, cancel)
+            .and_then(|()| Ok(self.backend.rename(&partial_path, path)?));
+        if result.is_err() {
+            let _ = self.backend.remove(&partial_path);
+        }
+        result
+    }
+
+    fn write_table(
+        &self,
+        path: &Path,
+        table: &Table,
+        cancel: &AtomicBool,
+    ) -> Result<(), SerializerError> {
+        let check_cancel = || {
+            if cancel.load(Ordering::Relaxed) {
+                Err(SerializerError::Cancelled)
+            } else {
+                Ok(())
+            }
+        };
+
+        if let Some(name) = &self.sorted_column {
+            let column = table
+                .column(name)
+                .ok_or_else(|| SerializerError::ColumnNotFound(name.clone()))?;
+            let column_type = column.data.logical_type();
+            if column_type != LogicalColumnType::Varchar {
+                return Err(SerializerError::UnsortableColumn {
+                    name: name.clone(),
+                    column_type,
+                });
+            }
+        }
+
+        let mut f =
+            BufWriter::with_capacity(self.block_size, self.backend.create_write(path, true)?);
+
+        let flags = encode_flags(
+            self.checksums,
+            self.stats,
+            self.string_offsets,
+            self.sorted_column.is_some(),
+        );
+        // Locations are filled in once the data is written, the header is then written again over
+        // the first one, which has the same size.
+        let mut descs = table
+            .columns
+            .iter()
+            .map(|column| ColumnDescription {
+                name: column.name.clone(),
+                data: ColumnData::with_capacity(column.data.logical_type(), 0),
+                metadata: column.metadata.clone(),
+                offset: 0,
+                length: 0,
+                length2: 0,
+                rank_length: 0,
+                checksum: None,
+                stats: if self.stats {
+                    ColumnStats::of(&column.data)
+                } else {
+                    None
+                },
+            })
+            .collect::<Vec<_>>();
+        f.write_all(&encode_header(
+            VERSION,
+            table.num_rows,
+            flags,
+            Some(&self.string_compressor),
+            descs.iter(),
+        )?)?;
+
+        // Columns are compressed in parallel on the serializer's pool, only writing is sequential.
+        let compressed_columns = self.install(|| {
+            table
+                .columns
+                .par_iter()
+                .map(|column| {
+                    check_cancel()?;
+                    if self.sorted_column.as_ref() != Some(&column.name) {
+                        let (data, lengths) = self.compress_column(&column.data)?;
+                        return Ok((data, lengths, Vec::new()));
+                    }
+                    let (sorted, rank) = sort_with_rank(&column.data);
+                    let (data, lengths) = self.compress_column(&sorted)?;
+                    Ok((data, lengths, self.compress_ints(&rank)?))
+                })
+                .collect::<Result<Vec<_>, SerializerError>>()
+        })?;
+
+        for (desc, (compressed_data, compressed_lengths, compressed_rank)) in
+            descs.iter_mut().zip(&compressed_columns)
+        {
+            check_cancel()?;
+            desc.offset = f.stream_position()?;
+            desc.length = compressed_data.len() as u64;
+            desc.length2 = compressed_lengths.len() as u64;
+            desc.rank_length = compressed_rank.len() as u64;
+            f.write_all(compressed_data)?;
+            f.write_all(compressed_lengths)?;
+            f.write_all(compressed_rank)?;
+            if self.checksums {
+                desc.checksum = Some(checksum(&[
+                    compressed_data,
+                    compressed_lengths,
+                    compressed_rank,
+                ]));
+            }
+        }
+
+        f.write_all(FOOTER)?;
+
+        f.seek(SeekFrom::Start(0))?;
+        f.write_all(&encode_header(
+            VERSION,
+            table.num_rows,
+            flags,
+            Some(&self.string_compressor),
+            descs.iter(),
+        )?)?;
+        f.flush()?;
+
+        Ok(())
+    }
+
+    pub fn deserialize(&self, path: &Path) -> Result<Table, SerializerError> {
+        Ok(self.deserialize_with_stats(path)?.0)
+    }
+
+    /// Same as `deserialize`, additionally reporting how much was actually stored in the file.
+    pub fn deserialize_with_stats(
+        &self,
+        path: &Path,
+    ) -> Result<(Table, DeserializeStats), SerializerError> {
+        let mut f = self.backend.open_read(path)?;
+        let header = self.header(path, &mut f)?;
+
+        let read = match header.version {
+            1 => self.deserialize_v1(&mut f, &header)?,
+            _ => self.deserialize_current(&mut f, &header)?,
+        };
+        self.read_footer(&mut f, &header, path)?;
+
+        Ok(read)
+    }
+
+    /// Version 1 files, the first format: INT64 and STR columns without metadata, flags or int
+    /// codec mode bytes, with plain string lengths. Nothing in them names the codecs, so they are
+    /// read with the ones of this serializer. Kept apart from the reader of later versions, so
+    /// format changes since can't break loading them.
+    fn deserialize_v1(
+        &self,
+        f: &mut dyn ReadSeek,
+        header: &FileHeader,
+    ) -> Result<(Table, DeserializeStats), SerializerError> {
+        let num_rows = header.num_rows as usize;
+        let mut stats = DeserializeStats {
+            rows_read: header.num_rows,
+            bytes_read: 0,
+        };
+
+        let mut columns = Vec::with_capacity(header.columns.len());
+        for desc in &header.columns {
+            let (buf, buf2, _) = self.read_column_bytes(f, desc)?;
+            stats.bytes_read += desc.length + desc.length2;
+
+            let column = match desc.data {
+                ColumnData::BOOL(_) | ColumnData::INT64(_) => {
+                    let mut int_data = self.int_compressor.decompress_unprefixed(&buf, num_rows)?;
+                    stats.rows_read = stats.rows_read.min(int_data.len() as u64);
+                    int_data.resize(num_rows, 0i64);
+                    Column::new_int_col(desc.name.clone(), int_data)
+                }
+                ColumnData::STR(_) => {
+                    let lengths = self.int_compressor.decompress_unprefixed(&buf2, num_rows)?;
+                    let mut str_data = self
+                        .string_compressor
+                        .decompress(&CompressedStringColumn::new(buf, lengths)?)?;
+                    stats.rows_read = stats.rows_read.min(str_data.len() as u64);
+                    str_data.resize(num_rows, "".to_string());
+                    Column::new_str_col(desc.name.clone(), str_data)
+                }
+            };
+            columns.push(column);
+        }
+
+        Ok((
+            Table {
+                num_rows: header.num_rows,
+                columns,
+            },
+            stats,
+        ))
+    }
+
+    /// Files of version 2 and later, whose columns are decompressed in parallel.
+    fn deserialize_current(
+        &self,
+        f: &mut dyn ReadSeek,
+        header: &FileHeader,
+    ) -> Result<(Table, DeserializeStats), SerializerError> {
+        // Single file handle can't seek concurrently, so raw bytes are read one column after
+        // another and only decompression runs in parallel.
+        let mut raw_columns = Vec::with_capacity(header.columns.len());
+        let mut bytes_read = 0u64;
+        for desc in &header.columns {
+            raw_columns.push((desc, self.read_column_bytes(f, desc)?));
+            bytes_read += desc.length + desc.length2 + desc.rank_length;
+        }
+
+        let (columns, decoded): (Vec<_>, Vec<_>) = self
+            .install(|| {
+                raw_columns
+                    .into_par_iter()
+                    .map(|(desc, bytes)| self.decompress_column(desc, bytes, header))
+                    .collect::<Result<Vec<_>, _>>()
+            })?
+            .into_iter()
+            .unzip();
+
+        let stats = DeserializeStats {
+            rows_read: decoded
+                .into_iter()
+                .map(|decoded| decoded as u64)
+                .min()
+                .unwrap_or(header.num_rows)
+                .min(header.num_rows),
+            bytes_read,
+        };
+
+        Ok((
+            Table {
+                num_rows: header.num_rows,
+                columns,
+            },
+            stats,
+        ))
+    }
+
+    /// Rows of the file one by one, each with a value per column in file order. Only the header
+    /// is read up front. Files have no row blocks, so all columns are decompressed when the
+    /// first row is requested and rows are handed out from them afterwards. Memory use is that
+    /// of the decompressed columns, the stream only saves building a `Table` of them.
+    pub fn stream_rows(&self, path: &Path) -> Result<RowStream<'_>, SerializerError> {
+        let mut file = self.backend.open_read(path)?;
+        let header = self.header(path, &mut file)?;
+
+        Ok(RowStream {
+            serializer: self,
+            path: path.to_path_buf(),
+            file,
+            header,
+            columns: None,
+            row: 0,
+        })
+    }
+
+    /// First `head` rows followed by the last `tail` rows, for a quick look at a file. Rows are
+    /// never repeated, a file shorter than `head + tail` is returned whole. Files have no row
+    /// blocks, so columns are still decompressed whole and sliced afterwards.
+    pub fn preview(&self, path: &Path, head: usize, tail: usize) -> Result<Table, SerializerError> {
+        let mut table = self.deserialize(path)?;
+        for column in &mut table.columns {
+            column.data.retain_ends(head, tail);
+        }
+        table.num_rows = table.num_rows.min(head.saturating_add(tail) as u64);
+
+        Ok(table)
+    }
+
+    /// Reads only the requested columns, in the requested order. Header is taken from the
+    /// cache when one is enabled, so repeated projections over the same file parse it once.
+    pub fn deserialize_columns(
+        &self,
+        path: &Path,
+        names: &[&str],
+    ) -> Result<Table, SerializerError> {
+        let mut f = self.backend.open_read(path)?;
+        let header = self.header(path, &mut f)?;
+
+        let mut columns = Vec::<Column>::with_capacity(names.len());
+        for &name in names {
+            let desc = header
+                .columns
+                .iter()
+                .find(|desc| desc.name == name)
+                .ok_or_else(|| SerializerError::ColumnNotFound(name.to_string()))?;
+            columns.push(self.read_column(&mut f, desc, &header)?);
+        }
+
+        Ok(Table {
+            num_rows: header.num_rows,
+            columns,
+        })
+    }
+
+    /// Reads a single value of the named column. With string offsets and both strings and
+    /// offsets stored uncompressed, only the bytes of the value are read. Otherwise the column is
+    /// read and decompressed whole, and the string sliced out of it by its offsets or by lengths
+    /// summed up to it.
+    pub fn read_value(
+        &self,
+        path: &Path,
+        column_name: &str,
+        row: u64,
+    ) -> Result<RowValue, SerializerError> {
+        let mut f = self.backend.open_read(path)?;
+        let header = self.header(path, &mut f)?;
+        check_row_index(&header, row)?;
+
+        let desc = header
+            .columns
+            .iter()
+            .find(|desc| desc.name == column_name)
+            .ok_or_else(|| SerializerError::ColumnNotFound(column_name.to_string()))?;
+        self.read_column_value(&mut f, desc, &header, row)
+    }
+
+    /// Reads a single row, one value per column in file order. Every column is still read and
+    /// decompressed to get at its value, there is no per-row index to seek with.
+    pub fn read_row(&self, path: &Path, row_index: u64) -> Result<Vec<RowValue>, SerializerError> {
+        let mut f = self.backend.open_read(path)?;
+        let header = self.header(path, &mut f)?;
+        check_row_index(&header, row_index)?;
+
+        header
+            .columns
+            .iter()
+            .map(|desc| self.read_column_value(&mut f, desc, &header, row_index))
+            .collect()
+    }
+
+    fn read_column_value(
+        &self,
+        f: &mut dyn ReadSeek,
+        desc: &ColumnDescription,
+        header: &FileHeader,
+        row: u64,
+    ) -> Result<RowValue, SerializerError> {
+        if let Some(value) = self.read_string_range(f, desc, header, row)? {
+            return Ok(value);
+        }
+
+        let (buf, buf2, buf3) = self.read_column_bytes(f, desc)?;
+        let mut row = row as usize;
+        if desc.rank_length > 0 {
+            let rank = self.decompress_ints(&buf3, header)?;
+            match rank.get(row).map(|&position| usize::try_from(position)) {
+                Some(Ok(position)) => row = position,
+                // Rows past the stored data are padding.
+                None => row = usize::MAX,
+                Some(Err(_)) => return Err(invalid_rank()),
+            }
+        }
+
+        match desc.data {
+            ColumnData::BOOL(_) | ColumnData::INT64(_) => {
+                let int_data = self.decompress_ints(&buf, header)?;
+                Ok(RowValue::Int(int_data.get(row).copied().unwrap_or(0)))
+            }
+            ColumnData::STR(_) => {
+                // Front coding stores suffix lengths, so its offsets don't point into the
+                // decompressed data.
+                let direct = header.string_offsets
+                    && !matches!(
+                        self.string_compressor_for(header),
+                        StringCompressors::FrontCoded(_)
+                    );
+                let (data, bounds) = if direct {
+                    let ends = self.decompress_ints(&buf2, header)?;
+                    let (data, _) = self
+                        .string_compressor_for(header)
+                        .decompress_concatenated(&buf, Vec::new())?;
+                    let bounds = ends
+                        .get(row)
+                        .map(|&end| (if row == 0 { 0 } else { ends[row - 1] }, end));
+                    (data, bounds)
+                } else {
+                    let lengths = self.decompress_lengths(&buf2, header)?;
+                    let (data, lengths) = self
+                        .string_compressor_for(header)
+                        .decompress_concatenated(&buf, lengths)?;
+                    let bounds = lengths.get(row).map(|&length| {
+                        let start = lengths[..row].iter().sum::<i64>();
+                        (start, start + length)
+                    });
+                    (data, bounds)
+                };
+
+                // Rows past the stored data are padding.
+                let Some((start, end)) = bounds else {
+                    return Ok(RowValue::Str(String::new()));
+                };
+                let bytes = usize::try_from(start)
+                    .ok()
+                    .zip(usize::try_from(end).ok())
+                    .and_then(|(start, end)| data.get(start..end))
+                    .ok_or_else(|| {
+                        CompressorError::WrongDataLength(
+                            "Data length is shorter then declared strings lengths".to_string(),
+                        )
+                    })?;
+                Ok(RowValue::Str(
+                    String::from_utf8(bytes.to_vec()).map_err(CompressorError::from)?,
+                ))
+            }
+        }
+    }
+
+    /// Reads only the bytes of a single string, `None` when the column doesn't allow that.
+    /// Columns with a checksum are never read partially, the checksum covers the whole column.
+    fn read_string_range(
+        &self,
+        f: &mut dyn ReadSeek,
+        desc: &ColumnDescription,
+        header: &FileHeader,
+        row: u64,
+    ) -> Result<Option<RowValue>, SerializerError> {
+        if !matches!(desc.data, ColumnData::STR(_))
+            || !header.string_offsets
+            || header.version < 3
+            || desc.checksum.is_some()
+            || desc.rank_length > 0
+            || !matches!(
+                self.string_compressor_for(header),
+                StringCompressors::None(_)
+            )
+            || desc.length2 == 0
+        {
+            return Ok(None);
+        }
+
+        let ends_offset = desc.offset + desc.length;
+        let mut mode = [0u8; 1];
+        f.seek(SeekFrom::Start(ends_offset))?;
+        f.read_exact(&mut mode)?;
+        let Some(end_position) = IntCompressors::value_position(mode[0], row as usize) else {
+            return Ok(None);
+        };
+        // Rows past the stored data are padding.
+        if end_position as u64 + 8 > desc.length2 {
+            return Ok(Some(RowValue::Str(String::new())));
+        }
+
+        let mut read_end = |position: usize| -> Result<i64, SerializerError> {
+            let mut bytes = [0u8; 8];
+            f.seek(SeekFrom::Start(ends_offset + position as u64))?;
+            f.read_exact(&mut bytes)?;
+            Ok(i64::from_le_bytes(bytes))
+        };
+        let end = read_end(end_position)?;
+        let start = match row {
+            0 => 0,
+            _ => read_end(end_position - 8)?,
+        };
+
+        let invalid = || {
+            CompressorError::WrongDataLength(
+                "Data length is shorter then declared strings lengths".to_string(),
+            )
+        };
+        let (start, end) = u64::try_from(start)
+            .ok()
+            .zip(u64::try_from(end).ok())
+            .filter(|&(start, end)| start <= end && end <= desc.length)
+            .ok_or_else(invalid)?;
+        let mut bytes = vec![0u8; (end - start) as usize];
+        f.seek(SeekFrom::Start(desc.offset + start))?;
+        f.read_exact(&mut bytes)?;
+
+        Ok(Some(RowValue::Str(
+            String::from_utf8(bytes).map_err(CompressorError::from)?,
+        )))
+    }
+
+    /// Reads a whole file already present in memory (e.g. mmapped) without allocating a `String`
+    /// per value. Uncompressed string data is borrowed straight from `bytes`.
+    pub fn view<'a>(&self, bytes: &'a [u8]) -> Result<TableView<'a>, SerializerError> {
+        let header = self.parse_header(&mut Cursor::new(bytes))?;
+        let num_rows = header.num_rows as usize;
+
+        let section = |offset: u64, length: u64| {
+            usize::try_from(offset)
+                .ok()
+                .zip(usize::try_from(length).ok())
+                .and_then(|(offset, length)| bytes.get(offset..offset.checked_add(length)?))
+                .ok_or_else(|| {
+                    SerializerError::InvalidFileFormat("Column data out of bounds".to_string())
+                })
+        };
+
+        let mut columns = Vec::with_capacity(header.columns.len());
+        for desc in &header.columns {
+            let buf = section(desc.offset, desc.length)?;
+            let buf2 = section(desc.offset + desc.length, desc.length2)?;
+            let buf3 = section(desc.offset + desc.length + desc.length2, desc.rank_length)?;
+            verify_checksum(desc, buf, buf2, buf3)?;
+            let rank = if desc.rank_length > 0 {
+                Some(self.decompress_ints(buf3, &header)?)
+            } else {
+                None
+            };
+
+            let data = match desc.data {
+                ColumnData::BOOL(_) | ColumnData::INT64(_) => {
+                    let mut int_data = self.decompress_ints(buf, &header)?;
+                    if let Some(rank) = &rank {
+                        int_data = unsort(int_data, rank)?;
+                    }
+                    int_data.resize(num_rows, 0i64);
+                    ColumnDataView::INT64(int_data)
+                }
+                ColumnData::STR(_) => {
+                    let lengths = self.decompress_lengths(buf2, &header)?;
+                    let (data, lengths) = self
+                        .string_compressor_for(&header)
+                        .decompress_concatenated(buf, lengths)?;
+                    let view = StrColumnView::new(data, &lengths, num_rows)?;
+                    match &rank {
+                        // Sorted strings can't be borrowed in original order, they are copied
+                        // into a new buffer instead.
+                        Some(rank) => {
+                            let sorted = view.iter().take(lengths.len()).collect::<Vec<_>>();
+                            let strings = unsort(sorted, rank)?;
+                            let lengths =
+                                strings.iter().map(|s| s.len() as i64).collect::<Vec<_>>();
+                            let data = strings.concat().into_bytes();
+                            ColumnDataView::STR(StrColumnView::new(
+                                Cow::Owned(data),
+                                &lengths,
+                                num_rows,
+                            )?)
+                        }
+                        None => ColumnDataView::STR(view),
+                    }
+                }
+            };
+            columns.push(ColumnView {
+                name: desc.name.clone(),
+                metadata: desc.metadata.clone(),
+                data,
+            });
+        }
+
+        self.check_footer(
+            section(header.data_end, FOOTER.len() as u64).ok(),
+            &"buffer",
+        )?;
+
+        Ok(TableView {
+            num_rows: header.num_rows,
+            columns,
+        })
+    }
+
+    /// Merges files sharing the same schema into a single file at `out`, rows in the order of
+    /// `paths`. Schemas are compared on headers only, before any column data is decompressed.
+    pub fn concat(&self, paths: &[&Path], out: &Path) -> Result<(), SerializerError> {
+        let mut headers = Vec::with_capacity(paths.len());
+        for &path in paths {
+            let mut f = self.backend.open_read(path)?;
+            headers.push(self.header(path, &mut f)?);
+        }
+
+        if let Some(first) = headers.first() {
+            let expected = first.schema();
+            for header in headers.iter().skip(1) {
+                let found = header.schema();
+                if found != expected {
+                    return Err(SerializerError::SchemaMismatch { expected, found });
+                }
+            }
+        }
+
+        let mut result = Table::default();
+        for (i, &path) in paths.iter().enumerate() {
+            let table = self.deserialize(path)?;
+            if i == 0 {
+                result = table;
+            } else {
+                result.append_table(table)?;
+            }
+        }
+
+        self.serialize(out, &result)
+    }
+
+    /// Rewrites the file from its logical content, dropping anything not reachable from the
+    /// header. New file is written next to the old one and renamed over it when complete, also
+    /// with overwrite protection on.
+    pub fn compact(&self, path: &Path) -> Result<CompactionReport, SerializerError> {
+        let size_before = self.file_size(path)?;
+        let table = self.deserialize(path)?;
+        self.replace_file(path, &table, &AtomicBool::new(false))?;
+
+        Ok(CompactionReport {
+            size_before,
+            size_after: self.file_size(path)?,
+        })
+    }
+
+    fn file_size(&self, path: &Path) -> Result<u64, SerializerError> {
+        Ok(self.backend.open_read(path)?.seek(SeekFrom::End(0))?)
+    }
+
+    /// Writes a file at `out` holding only the named columns of `path`, in the given order.
+    /// Compressed column bytes are copied as they are, only the header is rebuilt.
+    pub fn clone_file_with_schema_subset(
+        &self,
+        path: &Path,
+        out: &Path,
+        names: &[&str],
+    ) -> Result<(), SerializerError> {
+        let mut f = self.backend.open_read(path)?;
+        let header = self.header(path, &mut f)?;
+
+        let mut columns = Vec::<(ColumnDescription, RawColumn)>::new();
+        for &name in names {
+            let desc = header
+                .columns
+                .iter()
+                .find(|desc| desc.name == name)
+                .ok_or_else(|| SerializerError::ColumnNotFound(name.to_string()))?;
+            columns.push((desc.clone(), self.read_column_bytes(&mut f, desc)?));
+        }
+
+        // Copied bytes keep the int codec framing of the source version. Version 1 differs from
+        // version 2 only in the header, so it is upgraded.
+        let version = header.version.max(2);
+
+        // Header has fixed size for given columns, so data offsets are known before writing it.
+        let mut offset = encode_header(
+            version,
+            header.num_rows,
+            header.flags(),
+            header.string_compressor.as_ref(),
+            columns.iter().map(|(desc, _)| desc),
+        )?
+        .len() as u64;
+        for (desc, (buf, buf2, buf3)) in columns.iter_mut() {
+            desc.offset = offset;
+            desc.length = buf.len() as u64;
+            desc.length2 = buf2.len() as u64;
+            desc.rank_length = buf3.len() as u64;
+            offset += desc.length + desc.length2 + desc.rank_length;
+        }
+
+        let mut out_file = self.backend.create_write(out, !self.overwrite_protection)?;
+        out_file.write_all(&encode_header(
+            version,
+            header.num_rows,
+            header.flags(),
+            header.string_compressor.as_ref(),
+            columns.iter().map(|(desc, _)| desc),
+        )?)?;
+        for (_, (buf, buf2, buf3)) in &columns {
+            out_file.write_all(buf)?;
+            out_file.write_all(buf2)?;
+            out_file.write_all(buf3)?;
+        }
+        out_file.write_all(FOOTER)?;
+        out_file.flush()?;
+
+        Ok(())
+    }
+
+    /// Reads only the header of a file, no column data is decompressed.
+    pub fn read_schema(&self, path: &Path) -> Result<FileHeader, SerializerError> {
+        let mut f = self.backend.open_read(path)?;
+        Ok(self.header(path, &mut f)?.as_ref().clone())
+    }
+
+    /// Whether the file has exactly `schema`: same column names and types in the same order,
+    /// and `num_rows` rows when given. Only the header is read, so it is cheap to call before
+    /// appending to the file.
+    pub fn schema_compatible(
+        &self,
+        path: &Path,
+        schema: &Schema,
+        num_rows: Option<u64>,
+    ) -> Result<bool, SerializerError> {
+        let mut f = self.backend.open_read(path)?;
+        let header = self.header(path, &mut f)?;
+        Ok(header.schema() == *schema && num_rows.is_none_or(|rows| rows == header.num_rows))
+    }
+
+    /// `footer` is `None` when the data ends before it. `source` names the file in the warning.
+    fn read_footer(
+        &self,
+        f: &mut dyn ReadSeek,
+        header: &FileHeader,
+        path: &Path,
+    ) -> Result<(), SerializerError> {
+        f.seek(SeekFrom::Start(header.data_end))?;
+        let mut footer = [0u8; 4];
+        let footer = match f.read_exact(&mut footer) {
+            Ok(()) => Some(&footer[..]),
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => None,
+            Err(e) => return Err(e.into()),
+        };
+        self.check_footer(footer, &path.display())
+    }
+
+    fn check_footer(
+        &self,
+        footer: Option<&[u8]>,
+        source: &dyn Display,
+    ) -> Result<(), SerializerError> {
+        if footer == Some(FOOTER) {
+            return Ok(());
+        }
+        if !self.lenient_footer {
+            return Err(SerializerError::InvalidFileFormat(
+                "Invalid file footer".to_string(),
+            ));
+        }
+        log::warn!("{} has no valid footer, reading it anyway", source);
+        Ok(())
+    }
+
+    /// Number of times a file header was actually parsed from disk by this serializer.
+    pub fn header_parse_count(&self) -> usize {
+        self.header_parses.load(Ordering::Relaxed)
+    }
+
+    fn header(
+        &self,
+        path: &Path,
+        f: &mut dyn ReadSeek,
+    ) -> Result<Arc<FileHeader>, SerializerError> {
+        let Some(cache) = &self.header_cache else {
+            return Ok(Arc::new(self.parse_header(f)?));
+        };
+
+        let Ok(stamp) = self.backend.stamp(path) else {
+            return Ok(Arc::new(self.parse_header(f)?));
+        };
+        if let Some(header) = cache.lock().unwrap().get(path, stamp) {
+            return Ok(header);
+        }
+
+        let header = Arc::new(self.parse_header(f)?);
+        cache
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), stamp, header.clone());
+        Ok(header)
+    }
+
+    fn parse_header<R: Read + Seek + ?Sized>(
+        &self,
+        f: &mut R,
+    ) -> Result<FileHeader, SerializerError> {
+        self.header_parses.fetch_add(1, Ordering::Relaxed);
+        f.seek(SeekFrom::Start(0))?;
+
+        let mut magic = [0u8; 4];
+        f.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(SerializerError::InvalidFileFormat(
+                "Invalid file indicator".to_string(),
+            ));
+        }
+
+        let mut v = [0u8; 1];
+        f.read_exact(&mut v)?;
+        let version = v[0];
+        if version == 0 || version > VERSION {
+            return Err(SerializerError::InvalidFileFormat(format!(
+                "Unsupported file version: {}",
+                version
+            )));
+        }
+
+        let mut tmp2 = [0u8; 2];
+        f.read_exact(&mut tmp2)?;
+        let num_cols = u16::from_le_bytes(tmp2) as usize;
+
+        let mut tmp8 = [0u8; 8];
+        f.read_exact(&mut tmp8)?;
+        let num_rows = u64::from_le_bytes(tmp8);
+
+        let flags = if version >= 4 {
+            let mut fl = [0u8; 1];
+            f.read_exact(&mut fl)?;
+            fl[0]
+        } else {
+            0u8
+        };
+        if flags & !(CHECKSUMS_FLAG | STATS_FLAG | STRING_OFFSETS_FLAG | SORTED_FLAG) != 0 {
+            return Err(SerializerError::InvalidFileFormat(format!(
+                "Unknown file flags: {}",
+                flags
+            )));
+        }
+
+        let string_compressor = if version >= 5 {
+            let mut mode = [0u8; 1];
+            f.read_exact(&mut mode)?;
+            Some(StringCompressors::from_mode(mode[0])?)
+        } else {
+            None
+        };
+
+        let mut descriptions = Vec::<ColumnDescription>::with_capacity(num_cols);
+        for col_idx in 0..num_cols {
+            let mut nl = [0u8; 1];
+            f.read_exact(&mut nl)?;
+            let name_length = nl[0];
+
+            let mut name_bytes = vec![0u8; name_length as usize];
+            f.read_exact(&mut name_bytes)?;
+            let name = String::from_utf8_lossy(&name_bytes).into_owned();
+
+            let mut t = [0u8; 1];
+            f.read_exact(&mut t)?;
+            let data = match t[0] {
+                0u8 => ColumnData::INT64(Vec::new()),
+                1u8 => ColumnData::STR(Vec::new()),
+                _ => {
+                    return Err(SerializerError::InvalidFileFormat(format!(
+                        "Invalid column type at column: {}",
+                        col_idx
+                    )));
+                }
+            };
+
+            let metadata = if version >= 2 {
+                let mut flag = [0u8; 1];
+                f.read_exact(&mut flag)?;
+                match flag[0] {
+                    0u8 => None,
+                    1u8 => {
+                        let mut ml = [0u8; 4];
+                        f.read_exact(&mut ml)?;
+                        let mut metadata_bytes = vec![0u8; u32::from_le_bytes(ml) as usize];
+                        f.read_exact(&mut metadata_bytes)?;
+                        Some(String::from_utf8(metadata_bytes).map_err(|_| {
+                            SerializerError::InvalidFileFormat(format!(
+                                "Invalid metadata at column: {}",
+                                col_idx
+                            ))
+                        })?)
+                    }
+                    _ => {
+                        return Err(SerializerError::InvalidFileFormat(format!(
+                            "Invalid metadata flag at column: {}",
+                            col_idx
+                        )));
+                    }
+                }
+            } else {
+                None
+            };
+
+            let mut off = [0u8; 8];
+            f.read_exact(&mut off)?;
+            let offset = u64::from_le_bytes(off);
+
+            let mut len = [0u8; 8];
+            f.read_exact(&mut len)?;
+            let length = u64::from_le_bytes(len);
+
+            let length2 = match data {
+                ColumnData::BOOL(_) | ColumnData::INT64(_) => 0u64,
+                ColumnData::STR(_) => {
+                    let mut len2 = [0u8; 8];
+                    f.read_exact(&mut len2)?;
+                    u64::from_le_bytes(len2)
+                }
+            };
+
+            let rank_length = if flags & SORTED_FLAG != 0 {
+                let mut len3 = [0u8; 8];
+                f.read_exact(&mut len3)?;
+                u64::from_le_bytes(len3)
+            } else {
+                0u64
+            };
+
+            let checksum = if flags & CHECKSUMS_FLAG != 0 {
+                let mut sum = [0u8; 8];
+                f.read_exact(&mut sum)?;
+                Some(u64::from_le_bytes(sum))
+            } else {
+                None
+            };
+
+            let stats = if flags & STATS_FLAG != 0 {
+                let mut st = [0u8; 16];
+                f.read_exact(&mut st)?;
+                ColumnStats::decode(st)
+            } else {
+                None
+            };
+
+            // Later reads add these up unchecked, a corrupt header must not overflow them.
+            [length, length2, rank_length]
+                .into_iter()
+                .try_fold(offset, u64::checked_add)
+                .ok_or_else(|| {
+                    SerializerError::InvalidFileFormat(format!(
+                        "Location of column {} is out of range",
+                        name
+                    ))
+                })?;
+
+            let description = ColumnDescription {
+                name,
+                data,
+                metadata,
+                offset,
+                length,
+                length2,
+                rank_length,
+                checksum,
+                stats,
+            };
+
+            descriptions.push(description);
+        }
+
+        let data_end = match descriptions.last() {
+            Some(desc) => desc.offset + desc.length + desc.length2 + desc.rank_length,
+            None => f.stream_position()?,
+        };
+
+        Ok(FileHeader {
+            version,
+            num_rows,
+            checksums: flags & CHECKSUMS_FLAG != 0,
+            stats: flags & STATS_FLAG != 0,
+            string_offsets: flags & STRING_OFFSETS_FLAG != 0,
+            sorted: flags & SORTED_FLAG != 0,
+            string_compressor,
+            columns: descriptions,
+            data_end,
+        })
+    }
+
+    fn read_column(
+        &self,
+        f: &mut dyn ReadSeek,
+        desc: &ColumnDescription,
+        header: &FileHeader,
+    ) -> Result<Column, SerializerError> {
+        let bytes = self.read_column_bytes(f, desc)?;
+        Ok(self.decompress_column(desc, bytes, header)?.0)
+    }
+
+    /// Returns compressed data, compressed lengths for string columns and compressed rank
+    /// for sorted columns.
+    fn read_column_bytes(
+        &self,
+        f: &mut dyn ReadSeek,
+        desc: &ColumnDescription,
+    ) -> Result<RawColumn, SerializerError> {
+        f.seek(SeekFrom::Start(desc.offset))?;
+        let mut buf = vec![0u8; desc.length as usize];
+        f.read_exact(&mut buf)?;
+
+        let mut buf2 = vec![0u8; desc.length2 as usize];
+        f.read_exact(&mut buf2)?;
+
+        let mut buf3 = vec![0u8; desc.rank_length as usize];
+        f.read_exact(&mut buf3)?;
+
+        verify_checksum(desc, &buf, &buf2, &buf3)?;
+        Ok((buf, buf2, buf3))
+    }
+
+    fn install<R: Send>(&self, op: impl FnOnce() -> R + Send) -> R {
+        match &self.thread_pool {
+            Some(pool) => pool.install(op),
+            None => op(),
+        }
+    }
+
+    fn compress_ints(&self, data: &[i64]) -> Result<Vec<u8>, CompressorError> {
+        if self.auto_codec {
+            IntCompressors::smallest_for(data).compress(data)
+        } else {
+            self.int_compressor.compress(data)
+        }
+    }
+
+    /// Returns compressed data and, for string columns, compressed lengths.
+    fn compress_column(&self, data: &ColumnData) -> Result<(Vec<u8>, Vec<u8>), SerializerError> {
+        match data {
+            ColumnData::INT64(data) => Ok((self.compress_ints(data)?, Vec::new())),
+            ColumnData::BOOL(data) => {
+                let int_data = data.iter().map(|&v| v as i64).collect::<Vec<_>>();
+                Ok((self.compress_ints(&int_data)?, Vec::new()))
+            }
+            ColumnData::STR(data) => {
+                let (compressed, mut lengths) = self.string_compressor.compress(data)?.into_parts();
+                if self.string_offsets {
+                    let mut end = 0i64;
+                    for length in lengths.iter_mut() {
+                        end += *length;
+                        *length = end;
+                    }
+                }
+                let compressed_lengths = self.compress_ints(&lengths)?;
+                Ok((compressed, compressed_lengths))
+            }
+        }
+    }
+
+    /// Also returns how many values were decoded before the column was resized to `num_rows`.
+    fn decompress_column(
+        &self,
+        desc: &ColumnDescription,
+        (buf, buf2, buf3): RawColumn,
+        header: &FileHeader,
+    ) -> Result<(Column, usize), SerializerError> {
+        let num_rows = header.num_rows;
+        let rank = if desc.rank_length > 0 {
+            Some(self.decompress_ints(&buf3, header)?)
+        } else {
+            None
+        };
+
+        match desc.data {
+            ColumnData::BOOL(_) | ColumnData::INT64(_) => {
+                let mut int_data = self.decompress_ints(&buf, header)?;
+                if let Some(rank) = &rank {
+                    int_data = unsort(int_data, rank)?;
+                }
+                let decoded = int_data.len();
+                int_data.resize(num_rows as usize, 0i64);
+                Ok((
+                    Column::new_int_col(desc.name.clone(), int_data)
+                        .with_metadata(desc.metadata.clone()),
+                    decoded,
+                ))
+            }
+            ColumnData::STR(_) => {
+                let lengths_data = self.decompress_lengths(&buf2, header)?;
+                let mut str_data = self
+                    .string_compressor_for(header)
+                    .decompress(&CompressedStringColumn::new(buf, lengths_data)?)?;
+                if let Some(rank) = &rank {
+                    str_data = unsort(str_data, rank)?;
+                }
+                let decoded = str_data.len();
+                str_data.resize(num_rows as usize, "".to_string());
+                Ok((
+                    Column::new_str_col(desc.name.clone(), str_data)
+                        .with_metadata(desc.metadata.clone()),
+                    decoded,
+                ))
+            }
+        }
+    }
+
+    /// Codec of string data in a file, files older than version 5 don't name it.
+    fn string_compressor_for<'a>(&'a self, header: &'a FileHeader) -> &'a StringCompressors {
+        header
+            .string_compressor
+            .as_ref()
+            .unwrap_or(&self.string_compressor)
+    }
+
+    /// Int data of a column, never more values than the file has rows. Int data of files older
+    /// than version 3 has no codec mode byte.
+    fn decompress_ints(
+        &self,
+        data: &[u8],
+        header: &FileHeader,
+    ) -> Result<Vec<i64>, CompressorError> {
+        let max_len = usize::try_from(header.num_rows).unwrap_or(usize::MAX);
+        if header.version >= 3 {
+            self.int_compressor.decompress(data, max_len)
+        } else {
+            self.int_compressor.decompress_unprefixed(data, max_len)
+        }
+    }
+
+    /// String lengths of a column, whichever way the file stores them.
+    fn decompress_lengths(
+        &self,
+        data: &[u8],
+        header: &FileHeader,
+    ) -> Result<Vec<i64>, CompressorError> {
+        let mut lengths = self.decompress_ints(data, header)?;
+        if header.string_offsets {
+            let mut start = 0i64;
+            for length in lengths.iter_mut() {
+                let end = *length;
+                *length = end - start;
+                start = end;
+            }
+        }
+        Ok(lengths)
+    }
+}
+
+/// File a table is written to before it replaces `path`.
+fn partial_path(path: &Path) -> PathBuf {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    PathBuf::from(partial)
+}
+
+/// Compressed data, lengths and rank of a column, as read from a file.
+type RawColumn = (Vec<u8>, Vec<u8>, Vec<u8>);
+
+fn verify_checksum(
+    desc: &ColumnDescription,
+    buf: &[u8],
+    buf2: &[u8],
+    buf3: &[u8],
+) -> Result<(), SerializerError> {
+    match desc.checksum {
+        Some(expected) if expected != checksum(&[buf, buf2, buf3]) => {
+            Err(SerializerError::InvalidFileFormat(format!(
+                "Checksum mismatch at column: {}",
+                desc.name
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Stable sort of the column together with its rank, the position in sorted data of every
+/// original row.
+fn sort_with_rank(data: &ColumnData) -> (ColumnData, Vec<i64>) {
+    fn sort<T: Ord + Clone>(data: &[T]) -> (Vec<T>, Vec<i64>) {
+        let mut order = (0..data.len()).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| data[a].cmp(&data[b]));
+
+        let mut rank = vec![0i64; data.len()];
+        for (position, &row) in order.iter().enumerate() {
+            rank[row] = position as i64;
+        }
+        (
+            order.into_iter().map(|row| data[row].clone()).collect(),
+            rank,
+        )
+    }
+
+    match data {
+        ColumnData::INT64(data) => {
+            let (sorted, rank) = sort(data);
+            (ColumnData::INT64(sorted), rank)
+        }
+        ColumnData::STR(data) => {
+            let (sorted, rank) = sort(data);
+            (ColumnData::STR(sorted), rank)
+        }
+        ColumnData::BOOL(data) => {
+            let (sorted, rank) = sort(data);
+            (ColumnData::BOOL(sorted), rank)
+        }
+    }
+}
+
+/// Restores the original order of values sorted by `sort_with_rank`. The rank has to be a
+/// permutation, a position used twice is as invalid as one out of range.
+fn unsort<T>(sorted: Vec<T>, rank: &[i64]) -> Result<Vec<T>, SerializerError> {
+    if sorted.len() != rank.len() {
+        return Err(invalid_rank());
+    }
+    let mut sorted = sorted.into_iter().map(Some).collect::<Vec<_>>();
+    rank.iter()
+        .map(|&position| {
+            usize::try_from(position)
+                .ok()
+                .and_then(|position| sorted.get_mut(position))
+                .and_then(Option::take)
+                .ok_or_else(invalid_rank)
+        })
+        .collect()
+}
+
+fn check_row_index(header: &FileHeader, row: u64) -> Result<(), SerializerError> {
+    if row >= header.num_rows {
+        return Err(SerializerError::InvalidRow(format!(
+            "Row {} out of range, file has {} rows",
+            row, header.num_rows
+        )));
+    }
+    Ok(())
+}
+
+fn invalid_rank() -> SerializerError {
+    SerializerError::InvalidFileFormat("Invalid rank of sorted column".to_string())
+}
+
+/// Header for already placed columns. `version` has to be at least 2, older headers have no
+/// metadata flag. `flags` are written only since version 4 and every column has to carry what
+/// they announce. `string_compressor` is written only since version 5 and is required there.
+/// Fails on more columns or longer names than the format can hold.
+fn encode_header<'a>(
+    version: u8,
+    num_rows: u64,
+    flags: u8,
+    string_compressor: Option<&StringCompressors>,
+    columns: impl ExactSizeIterator<Item = &'a ColumnDescription>,
+) -> Result<Vec<u8>, SerializerError> {
+    if columns.len() > MAX_COLUMNS {
+        return Err(SerializerError::TooManyColumns(columns.len()));
+    }
+
+    let mut buf = Vec::new();
+    buf.extend_from_slice(MAGIC);
+    buf.push(version);
+    buf.extend_from_slice(&(columns.len() as u16).to_le_bytes());
+    buf.extend_from_slice(&num_rows.to_le_bytes());
+    if version >= 4 {
+        buf.push(flags);
+    }
+    if version >= 5 {
+        buf.push(string_compressor.map_or(0, StringCompressors::mode));
+    }
+
+    for desc in columns {
+        let name_len = u8::try_from(desc.name.len())
+            .map_err(|_| SerializerError::ColumnNameTooLong(desc.name.clone()))?;
+        buf.push(name_len);
+        buf.extend_from_slice(desc.name.as_bytes());
+        buf.push(match desc.data {
+            ColumnData::BOOL(_) | ColumnData::INT64(_) => 0u8,
+            ColumnData::STR(_) => 1u8,
+        });
+
+        match &desc.metadata {
+            Some(metadata) => {
+                buf.push(1u8);
+                buf.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+                buf.extend_from_slice(metadata.as_bytes());
+            }
+            None => buf.push(0u8),
+        }
+
+        buf.extend_from_slice(&desc.offset.to_le_bytes());
+        buf.extend_from_slice(&desc.length.to_le_bytes());
+        if matches!(desc.data, ColumnData::STR(_)) {
+            buf.extend_from_slice(&desc.length2.to_le_bytes());
+        }
+        if version >= 4 && flags & SORTED_FLAG != 0 {
+            buf.extend_from_slice(&desc.rank_length.to_le_bytes());
+        }
+        if version >= 4 && flags & CHECKSUMS_FLAG != 0 {
+            buf.extend_from_slice(&desc.checksum.unwrap_or_default().to_le_bytes());
+        }
+        if version >= 4 && flags & STATS_FLAG != 0 {
+            buf.extend_from_slice(&ColumnStats::encode(desc.stats));
+        }
+    }
+
+    Ok(buf)
+}
+
+/// Lazy iterator over the rows of a file, created by `Serializer::stream_rows`. After an error
+/// no more rows are returned.
+pub struct RowStream<'a> {
+    serializer: &'a Serializer,
+    path: PathBuf,
+    file: Box<dyn ReadSeek>,
+    header: Arc<FileHeader>,
+    columns: Option<Vec<ColumnData>>,
+    row: u64,
+}
+
+impl RowStream<'_> {
+    fn read_columns(&mut self) -> Result<Vec<ColumnData>, SerializerError> {
+        let mut columns = Vec::with_capacity(self.header.columns.len());
+        for desc in &self.header.columns {
+            let column = self
+                .serializer
+                .read_column(&mut self.file, desc, &self.header)?;
+            columns.push(column.data);
+        }
+        self.serializer
+            .read_footer(&mut self.file, &self.header, &self.path)?;
+
+        Ok(columns)
+    }
+}
+
+impl Iterator for RowStream<'_> {
+    type Item = Result<Vec<RowValue>, SerializerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.row >= self.header.num_rows {
+            return None;
+        }
+        if self.columns.is_none() {
+            match self.read_columns() {
+                Ok(columns) => self.columns = Some(columns),
+                Err(e) => {
+                    self.row = self.header.num_rows;
+                    return Some(Err(e));
+                }
+            }
+        }
+
+        let row = self.row as usize;
+        self.row += 1;
+        // Every value is handed out once, so strings are moved out instead of cloned.
+        let columns = self.columns.as_mut()?;
+        Some(Ok(columns
+            .iter_mut()
+            .map(|data| match data {
+                ColumnData::INT64(data) => RowValue::Int(data[row]),
+                ColumnData::STR(data) => RowValue::Str(std::mem::take(&mut data[row])),
+                ColumnData::BOOL(data) => RowValue::Bool(data[row]),
+            })
+            .collect()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.header.num_rows - self.row) as usize;
+        (remaining, Some(remaining))
+    }
+}
+
+/// Builds a file row by row. Rows are buffered per column and compressed on `finish`, so the
+/// caller never has to assemble a `Table` by itself.
+pub struct TableWriter<'a> {
+    serializer: &'a Serializer,
+    path: PathBuf,
+    table: Table,
+}
+
+impl<'a> TableWriter<'a> {
+    /// `columns` describe the schema, their data is ignored.
+    pub fn open(serializer: &'a Serializer, path: &Path, columns: &[Column]) -> Self {
+        let columns = columns
+            .iter()
+            .map(|column| Column {
+                name: column.name.clone(),
+                data: match column.data {
+                    ColumnData::INT64(_) => ColumnData::INT64(Vec::new()),
+                    ColumnData::STR(_) => ColumnData::STR(Vec::new()),
+                    ColumnData::BOOL(_) => ColumnData::BOOL(Vec::new()),
+                },
+                metadata: column.metadata.clone(),
+            })
+            .collect();
+
+        Self {
+            serializer,
+            path: path.to_path_buf(),
+            table: Table::new(0, columns),
+        }
+    }
+
+    pub fn write_row(&mut self, row: Vec<RowValue>) -> Result<(), SerializerError> {
+        if row.len() != self.table.columns.len() {
+            return Err(SerializerError::InvalidRow(format!(
+                "Row {} has {} values, but table has {} columns",
+                self.table.num_rows + 1,
+                row.len(),
+                self.table.columns.len()
+            )));
+        }
+
+        for (column, value) in self.table.columns.iter().zip(&row) {
+            if !column.data.accepts(value) {
+                return Err(SerializerError::InvalidRow(format!(
+                    "Row {} has value of wrong type for column {}",
+                    self.table.num_rows + 1,
+                    column.name
+                )));
+            }
+        }
+
+        for (column, value) in self.table.columns.iter_mut().zip(row) {
+            column.data.push(value)?;
+        }
+        self.table.num_rows += 1;
+
+        Ok(())
+    }
+
+    pub fn write_batch(
+        &mut self,
+        rows: impl IntoIterator<Item = Vec<RowValue>>,
+    ) -> Result<(), SerializerError> {
+        for row in rows {
+            self.write_row(row)?;
+        }
+        Ok(())
+    }
+
+    /// Compresses buffered columns and writes the whole file, including footer and offsets.
+    pub fn finish(self) -> Result<(), SerializerError> {
+        self.serializer.serialize(&self.path, &self.table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    use super::*;
+    use crate::{
+        compress::{BitPackIntCompressor, FrontCodedStringCompressor, LZ4FrameStringCompressor},
+        storage::WriteSeek,
+    };
+
+    /// Path in the temp directory unique to this test process, removed when dropped.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            Self(std::env::temp_dir().join(format!("isdb_{}_{}", std::process::id(), name)))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn sample_table() -> Table {
+        Table::new(
+            3,
+            vec![
+                Column::new_int_col("id".to_string(), vec![1, -2, 3]),
+                Column::new_str_col(
+                    "name".to_string(),
+                    vec!["a".to_string(), "bb".to_string(), "ccc".to_string()],
+                ),
+            ],
+        )
+    }
+
+    #[test]
+    fn header_cache_parses_header_once_for_projections() {
+        let file = TempFile::new("header_cache_projections.isdb");
+        let serializer = Serializer::new().with_header_cache(4);
+        serializer.serialize(&file.0, &sample_table()).unwrap();
+
+        let ids = serializer.deserialize_columns(&file.0, &["id"]).unwrap();
+        let names = serializer.deserialize_columns(&file.0, &["name"]).unwrap();
+
+        assert_eq!(ids.int_column("id"), Some(&[1, -2, 3][..]));
+        assert_eq!(names.str_column("name").unwrap().len(), 3);
+        assert_eq!(serializer.header_parse_count(), 1);
+    }
+
+    #[test]
+    fn header_cache_misses_after_rewrite_with_same_mtime() {
+        let file = TempFile::new("header_cache_rewrite.isdb");
+        let serializer = Serializer::new().with_header_cache(4);
+        serializer.serialize(&file.0, &sample_table()).unwrap();
+        let mtime = fs::metadata(&file.0).unwrap().modified().unwrap();
+        serializer.deserialize_columns(&file.0, &["id"]).unwrap();
+
+        let mut wider = sample_table();
+        wider
+            .add_column(Column::new_int_col("extra".to_string(), vec![7, 8, 9]))
+            .unwrap();
+        serializer.serialize(&file.0, &wider).unwrap();
+        File::options()
+            .write(true)
+            .open(&file.0)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+
+        let extra = serializer.deserialize_columns(&file.0, &["extra"]).unwrap();
+        assert_eq!(extra.int_column("extra"), Some(&[7, 8, 9][..]));
+        assert_eq!(serializer.header_parse_count(), 2);
+    }
+
+    #[test]
+    fn ndjson_line_over_limit_is_rejected() {
+        let options = NdjsonOptions {
+            max_line_len: Some(16),
+            ..Default::default()
+        };
+        let input = "{\"a\": 1}\r\n{\"a\": \"0123456789\"}\n";
+
+        let err = Table::from_ndjson_reader(input.as_bytes(), &options).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid row: Line 2 is longer than 16 bytes"
+        );
+
+        let table = Table::from_ndjson_reader(&input.as_bytes()[..10], &options).unwrap();
+        assert_eq!(table.int_column("a"), Some(&[1][..]));
+    }
+
+    #[test]
+    fn concat_appends_rows_in_path_order() {
+        let (first, second, out) = (
+            TempFile::new("concat_first.isdb"),
+            TempFile::new("concat_second.isdb"),
+            TempFile::new("concat_out.isdb"),
+        );
+        let serializer = Serializer::new();
+        let mut expected = sample_table();
+        let tail = Table::new(
+            2,
+            vec![
+                Column::new_int_col("id".to_string(), vec![4, 5]),
+                Column::new_str_col("name".to_string(), vec!["d".to_string(), String::new()]),
+            ],
+        );
+        serializer.serialize(&first.0, &expected).unwrap();
+        serializer.serialize(&second.0, &tail).unwrap();
+
+        serializer.concat(&[&first.0, &second.0], &out.0).unwrap();
+
+        expected.append_table(tail).unwrap();
+        assert_eq!(serializer.deserialize(&out.0).unwrap(), expected);
+    }
+
+    #[test]
+    fn concat_rejects_mismatched_schemas() {
+        let (first, second, out) = (
+            TempFile::new("concat_mismatch_first.isdb"),
+            TempFile::new("concat_mismatch_second.isdb"),
+            TempFile::new("concat_mismatch_out.isdb"),
+        );
+        let serializer = Serializer::new();
+        serializer.serialize(&first.0, &sample_table()).unwrap();
+        let other = Table::new(1, vec![Column::new_int_col("id".to_string(), vec![4])]);
+        serializer.serialize(&second.0, &other).unwrap();
+
+        assert!(serializer.concat(&[&first.0, &second.0], &out.0).is_err());
+        assert!(!out.0.exists());
+    }
+
+    #[test]
+    fn table_writer_builds_same_file_as_table() {
+        let file = TempFile::new("table_writer.isdb");
+        let serializer = Serializer::new();
+        let expected = sample_table();
+
+        let mut writer = TableWriter::open(&serializer, &file.0, &expected.columns);
+        writer
+            .write_row(vec![RowValue::Int(1), RowValue::Str("a".to_string())])
+            .unwrap();
+        writer
+            .write_batch(vec![
+                vec![RowValue::Int(-2), RowValue::Str("bb".to_string())],
+                vec![RowValue::Int(3), RowValue::Str("ccc".to_string())],
+            ])
+            .unwrap();
+        assert!(writer.write_row(vec![RowValue::Bool(true)]).is_err());
+        assert!(
+            writer
+                .write_row(vec![RowValue::Str("4".to_string()), RowValue::Int(4)])
+                .is_err()
+        );
+        writer.finish().unwrap();
+
+        assert_eq!(serializer.deserialize(&file.0).unwrap(), expected);
+    }
+
+    #[test]
+    fn empty_column_matches_logical_type() {
+        for (column_type, data) in [
+            (LogicalColumnType::Int64, ColumnData::INT64(Vec::new())),
+            (LogicalColumnType::Varchar, ColumnData::STR(Vec::new())),
+            (LogicalColumnType::Bool, ColumnData::BOOL(Vec::new())),
+        ] {
+            let column = Column::empty("c".to_string(), column_type);
+            assert_eq!(column.data, data);
+            assert_eq!(column.data.logical_type(), column_type);
+        }
+    }
+
+    #[test]
+    fn tables_match_schema_by_names_and_types() {
+        let table = sample_table();
+        let same = Table::new(
+            1,
+            vec![
+                Column::new_int_col("id".to_string(), vec![7]),
+                Column::new_str_col("name".to_string(), vec!["x".to_string()]),
+            ],
+        );
+        assert_eq!(table.schema(), same.schema());
+        assert!(table.matches_schema(&same.schema()));
+
+        let renamed = Table::new(
+            1,
+            vec![
+                Column::new_int_col("key".to_string(), vec![7]),
+                Column::new_str_col("name".to_string(), vec!["x".to_string()]),
+            ],
+        );
+        let retyped = Table::new(
+            1,
+            vec![
+                Column::new_int_col("id".to_string(), vec![7]),
+                Column::new_int_col("name".to_string(), vec![8]),
+            ],
+        );
+        let narrower = Table::new(1, vec![Column::new_int_col("id".to_string(), vec![7])]);
+        for other in [renamed, retyped, narrower] {
+            assert!(!table.matches_schema(&other.schema()));
+        }
+    }
+
+    #[test]
+    fn compact_preserves_data_and_does_not_grow() {
+        let file = TempFile::new("compact.isdb");
+        let serializer = Serializer::new();
+        let table = sample_table();
+        serializer.serialize(&file.0, &table).unwrap();
+
+        let report = serializer.compact(&file.0).unwrap();
+
+        assert!(report.size_after <= report.size_before);
+        assert_eq!(report.size_after, fs::metadata(&file.0).unwrap().len());
+        assert_eq!(serializer.deserialize(&file.0).unwrap(), table);
+        assert!(!partial_path(&file.0).exists());
+    }
+
+    #[test]
+    fn compact_replaces_stale_partial_file_with_overwrite_protection() {
+        let file = TempFile::new("compact_protected.isdb");
+        let serializer = Serializer::builder().overwrite_protection(true).build();
+        let table = sample_table();
+        serializer.serialize(&file.0, &table).unwrap();
+        // Left behind by a write that crashed.
+        fs::write(partial_path(&file.0), b"stale").unwrap();
+
+        serializer.compact(&file.0).unwrap();
+
+        assert_eq!(serializer.deserialize(&file.0).unwrap(), table);
+        assert!(!partial_path(&file.0).exists());
+    }
+
+    fn wide_table(num_columns: usize, num_rows: usize) -> Table {
+        let columns = (0..num_columns)
+            .map(|i| match i % 2 {
+                0 => Column::new_int_col(
+                    format!("c{}", i),
+                    (0..num_rows as i64).map(|v| v * i as i64 - 7).collect(),
+                ),
+                _ => Column::new_str_col(
+                    format!("c{}", i),
+                    (0..num_rows)
+                        .map(|v| format!("value {} {}", i, v))
+                        .collect(),
+                ),
+            })
+            .collect();
+        Table::new(num_rows as u64, columns)
+    }
+
+    #[test]
+    fn parallel_deserialize_matches_sequential() {
+        let file = TempFile::new("parallel_deserialize.isdb");
+        let table = wide_table(64, 2_000);
+        Serializer::new().serialize(&file.0, &table).unwrap();
+
+        let sequential = Serializer::builder().threads(1).unwrap().build();
+        let parallel = Serializer::builder().threads(4).unwrap().build();
+
+        let started = std::time::Instant::now();
+        let sequential_table = sequential.deserialize(&file.0).unwrap();
+        let sequential_time = started.elapsed();
+        let started = std::time::Instant::now();
+        let parallel_table = parallel.deserialize(&file.0).unwrap();
+        let parallel_time = started.elapsed();
+        println!(
+            "64 columns: sequential {:?}, parallel {:?}",
+            sequential_time, parallel_time
+        );
+
+        assert_eq!(sequential_table, table);
+        assert_eq!(parallel_table, sequential_table);
+    }
+
+    #[test]
+    fn append_incompatible_table_is_schema_mismatch() {
+        let mut table = sample_table();
+        let other = Table::new(1, vec![Column::new_int_col("id".to_string(), vec![4])]);
+        let expected_schema = table.schema();
+
+        match table.append_table(other.clone()) {
+            Err(SerializerError::SchemaMismatch { expected, found }) => {
+                assert_eq!(expected, expected_schema);
+                assert_eq!(found, other.schema());
+            }
+            other => panic!("expected schema mismatch, got {:?}", other),
+        }
+        assert_eq!(table, sample_table());
+    }
+
+    #[test]
+    fn row_value_conversions() {
+        assert_eq!(RowValue::from(5), RowValue::Int(5));
+        assert_eq!(RowValue::from("a"), RowValue::Str("a".to_string()));
+        assert_eq!(
+            RowValue::from("b".to_string()),
+            RowValue::Str("b".to_string())
+        );
+        assert_eq!(RowValue::from(true), RowValue::Bool(true));
+
+        assert_eq!(i64::try_from(RowValue::Int(-3)), Ok(-3));
+        assert_eq!(String::try_from(RowValue::from("x")), Ok("x".to_string()));
+        assert_eq!(bool::try_from(RowValue::Bool(false)), Ok(false));
+        assert_eq!(
+            i64::try_from(RowValue::Bool(true)),
+            Err(RowValue::Bool(true))
+        );
+    }
+
+    #[test]
+    fn pushing_row_value_of_wrong_type_fails() {
+        let mut data = ColumnData::INT64(vec![1]);
+        assert!(data.accepts(&RowValue::Int(2)));
+        assert!(!data.accepts(&RowValue::from("2")));
+
+        data.push(RowValue::Int(2)).unwrap();
+        assert!(matches!(
+            data.push(RowValue::from("3")),
+            Err(SerializerError::InvalidRow(_))
+        ));
+        assert_eq!(data, ColumnData::INT64(vec![1, 2]));
+    }
+
+    #[test]
+    fn schema_subset_keeps_requested_columns() {
+        let (file, subset) = (
+            TempFile::new("subset_source.isdb"),
+            TempFile::new("subset_out.isdb"),
+        );
+        let serializer = Serializer::builder().checksums(true).stats(true).build();
+        let table = wide_table(4, 100);
+        serializer.serialize(&file.0, &table).unwrap();
+
+        serializer
+            .clone_file_with_schema_subset(&file.0, &subset.0, &["c3", "c0"])
+            .unwrap();
+
+        let expected = Table::new(
+            100,
+            vec![table.columns[3].clone(), table.columns[0].clone()],
+        );
+        assert_eq!(serializer.deserialize(&subset.0).unwrap(), expected);
+        assert!(fs::metadata(&subset.0).unwrap().len() < fs::metadata(&file.0).unwrap().len());
+    }
+
+    #[test]
+    fn header_with_every_flag_round_trips() {
+        let file = TempFile::new("every_flag.isdb");
+        let serializer = Serializer::builder()
+            .checksums(true)
+            .stats(true)
+            .string_offsets(true)
+            .sorted_column("name")
+            .build();
+        let mut table = sample_table();
+        table.columns[0].metadata = Some("unit: none".to_string());
+        serializer.serialize(&file.0, &table).unwrap();
+
+        let header = serializer.read_schema(&file.0).unwrap();
+        assert!(header.checksums && header.stats && header.string_offsets && header.sorted);
+        assert_eq!(header.columns[0].metadata.as_deref(), Some("unit: none"));
+        assert!(header.columns.iter().all(|desc| desc.checksum.is_some()));
+        assert_eq!(serializer.deserialize(&file.0).unwrap(), table);
+    }
+
+    #[test]
+    fn table_view_borrows_uncompressed_strings() {
+        let file = TempFile::new("table_view.isdb");
+        let serializer = Serializer::no_compression();
+        let table = sample_table();
+        serializer.serialize(&file.0, &table).unwrap();
+        let bytes = fs::read(&file.0).unwrap();
+
+        let view = serializer.view(&bytes).unwrap();
+        let names = view.str_column("name").unwrap();
+
+        assert!(names.is_borrowed());
+        assert_eq!(
+            names.iter().collect::<Vec<_>>(),
+            table.str_column("name").unwrap()
+        );
+        let bytes_range = bytes.as_ptr_range();
+        assert!(names.iter().all(|s| bytes_range.contains(&s.as_ptr())));
+        assert_eq!(view.int_column("id"), table.int_column("id"));
+        assert_eq!(view.to_table(), table);
+    }
+
+    #[test]
+    fn auto_codec_writes_smaller_file_that_reads_back() {
+        let (auto, fixed) = (
+            TempFile::new("auto_codec.isdb"),
+            TempFile::new("fixed_codec.isdb"),
+        );
+        let table = Table::new(
+            1000,
+            vec![Column::new_int_col(
+                "flags".to_string(),
+                (0..1000).map(|i| (i * 7919) % 4).collect(),
+            )],
+        );
+        Serializer::builder()
+            .auto_codec(true)
+            .build()
+            .serialize(&auto.0, &table)
+            .unwrap();
+        Serializer::new().serialize(&fixed.0, &table).unwrap();
+
+        assert!(fs::metadata(&auto.0).unwrap().len() < fs::metadata(&fixed.0).unwrap().len());
+        assert_eq!(Serializer::new().deserialize(&auto.0).unwrap(), table);
+    }
+
+    #[test]
+    fn builder_enables_checksums_and_stats() {
+        let file = TempFile::new("builder_flags.isdb");
+        let serializer = Serializer::builder().checksums(true).stats(true).build();
+        serializer.serialize(&file.0, &sample_table()).unwrap();
+
+        let header = serializer.read_schema(&file.0).unwrap();
+        assert!(header.checksums && header.stats);
+        assert!(header.columns.iter().all(|desc| desc.checksum.is_some()));
+        let id_stats = header.columns[0].stats.unwrap();
+        assert_eq!((id_stats.min, id_stats.max), (-2, 3));
+
+        let plain = Serializer::new();
+        let plain_file = TempFile::new("builder_plain.isdb");
+        plain.serialize(&plain_file.0, &sample_table()).unwrap();
+        let header = plain.read_schema(&plain_file.0).unwrap();
+        assert!(!header.checksums && !header.stats);
+    }
+
+    #[test]
+    fn deserialize_stats_report_every_row() {
+        let file = TempFile::new("deserialize_stats.isdb");
+        let serializer = Serializer::new();
+        let table = sample_table();
+        serializer.serialize(&file.0, &table).unwrap();
+
+        let (read, stats) = serializer.deserialize_with_stats(&file.0).unwrap();
+        let header = serializer.read_schema(&file.0).unwrap();
+
+        assert_eq!(read, table);
+        assert_eq!(stats.rows_read, table.num_rows);
+        let stored = header
+            .columns
+            .iter()
+            .map(|desc| desc.length + desc.length2 + desc.rank_length)
+            .sum::<u64>();
+        assert_eq!(stats.bytes_read, stored);
+    }
+
+    #[test]
+    fn cloned_serializer_writes_identical_bytes() {
+        let (original_file, cloned_file) = (
+            TempFile::new("clone_original.isdb"),
+            TempFile::new("clone_cloned.isdb"),
+        );
+        let original = Serializer::builder()
+            .checksums(true)
+            .sorted_column("name")
+            .build();
+        let cloned = original.clone();
+        let mut table = wide_table(6, 50);
+        table.columns.push(Column::new_str_col(
+            "name".to_string(),
+            (0..50).map(|i| format!("n{}", i % 3)).collect(),
+        ));
+
+        let writer = std::thread::spawn(move || {
+            cloned.serialize(&cloned_file.0, &table).unwrap();
+            (fs::read(&cloned_file.0).unwrap(), table)
+        });
+        let (cloned_bytes, table) = writer.join().unwrap();
+        original.serialize(&original_file.0, &table).unwrap();
+
+        assert_eq!(fs::read(&original_file.0).unwrap(), cloned_bytes);
+    }
+
+    #[test]
+    fn single_thread_pool_serializes_correctly() {
+        let file = TempFile::new("single_thread.isdb");
+        let serializer = Serializer::builder().threads(1).unwrap().build();
+        let table = wide_table(8, 100);
+
+        serializer.serialize(&file.0, &table).unwrap();
+
+        assert_eq!(Serializer::new().deserialize(&file.0).unwrap(), table);
+        assert!(matches!(
+            Serializer::builder().threads(0),
+            Err(SerializerError::NoThreads)
+        ));
+    }
+
+    /// Local files, counting bytes read through the backend.
+    #[derive(Debug, Default, Clone)]
+    struct CountingFs {
+        bytes_read: Arc<AtomicUsize>,
+    }
+
+    struct CountingReader {
+        file: File,
+        bytes_read: Arc<AtomicUsize>,
+    }
+
+    impl Read for CountingReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.file.read(buf)?;
+            self.bytes_read.fetch_add(n, Ordering::Relaxed);
+            Ok(n)
+        }
+    }
+
+    impl Seek for CountingReader {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.file.seek(pos)
+        }
+    }
+
+    impl StorageBackend for CountingFs {
+        fn open_read(&self, path: &Path) -> Result<Box<dyn ReadSeek>, Error> {
+            Ok(Box::new(CountingReader {
+                file: File::open(path)?,
+                bytes_read: self.bytes_read.clone(),
+            }))
+        }
+
+        fn create_write(&self, path: &Path, overwrite: bool) -> Result<Box<dyn WriteSeek>, Error> {
+            LocalFs.create_write(path, overwrite)
+        }
+
+        fn exists(&self, path: &Path) -> bool {
+            LocalFs.exists(path)
+        }
+
+        fn remove(&self, path: &Path) -> Result<(), Error> {
+            LocalFs.remove(path)
+        }
+
+        fn rename(&self, from: &Path, to: &Path) -> Result<(), Error> {
+            LocalFs.rename(from, to)
+        }
+    }
+
+    #[test]
+    fn string_offsets_read_only_the_requested_value() {
+        let file = TempFile::new("string_offsets_range.isdb");
+        let backend = CountingFs::default();
+        let serializer = Serializer::builder()
+            .int_compressor(IntCompressors::None(NoIntCompressor))
+            .string_compressor(StringCompressors::None(NoStringCompressor))
+            .string_offsets(true)
+            .storage_backend(backend.clone())
+            .build();
+        let values = (0..1000)
+            .map(|i| format!("{:0>100}", i * 37 % 1000))
+            .collect::<Vec<_>>();
+        let table = Table::new(1000, vec![Column::new_str_col("s".to_string(), values)]);
+        serializer.serialize(&file.0, &table).unwrap();
+        let full = serializer.deserialize(&file.0).unwrap();
+
+        for row in [0, 1, 499, 999] {
+            backend.bytes_read.store(0, Ordering::Relaxed);
+            let value = serializer.read_value(&file.0, "s", row).unwrap();
+            assert_eq!(value, full.columns[0].data.value(row as usize));
+            assert!(backend.bytes_read.load(Ordering::Relaxed) < 1000);
+        }
+    }
+
+    #[test]
+    fn preview_returns_head_and_tail_without_middle() {
+        let file = TempFile::new("preview.isdb");
+        let table = Table::new(
+            10,
+            vec![
+                Column::new_int_col("id".to_string(), (0..10).collect()),
+                Column::new_str_col("name".to_string(), (0..10).map(|i| i.to_string()).collect()),
+            ],
+        );
+        let serializer = Serializer::new();
+        serializer.serialize(&file.0, &table).unwrap();
+
+        let preview = serializer.preview(&file.0, 2, 3).unwrap();
+        assert_eq!(preview.num_rows, 5);
+        assert_eq!(preview.int_column("id").unwrap(), [0, 1, 7, 8, 9]);
+        assert_eq!(
+            preview.str_column("name").unwrap(),
+            ["0", "1", "7", "8", "9"]
+        );
+
+        // Overlapping ends return every row once.
+        let preview = serializer.preview(&file.0, 6, 6).unwrap();
+        assert_eq!(preview.num_rows, 10);
+        assert_eq!(
+            preview.int_column("id").unwrap(),
+            (0..10).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn checksum_mismatch_names_the_column() {
+        let file = TempFile::new("checksum_mismatch.isdb");
+        let serializer = Serializer::builder().checksums(true).build();
+        serializer.serialize(&file.0, &sample_table()).unwrap();
+
+        let offset = serializer.read_schema(&file.0).unwrap().columns[1].offset as usize;
+        let mut bytes = fs::read(&file.0).unwrap();
+        bytes[offset] ^= 0xff;
+        fs::write(&file.0, bytes).unwrap();
+
+        let error = serializer.deserialize(&file.0).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid file format: Checksum mismatch at column: name"
+        );
+    }
+
+    #[test]
+    fn sorted_string_column_saves_space_and_keeps_order() {
+        let mut rng = StdRng::seed_from_u64(3);
+        // Few long distinct values: LZ4 still pays a match per row on the unsorted column,
+        // front coding the sorted one leaves little more than the rank.
+        let values = (0..8)
+            .map(|_| {
+                (0..300)
+                    .map(|_| char::from(rng.random_range(b'a'..=b'z')))
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>();
+        let column = (0..20_000)
+            .map(|_| values[rng.random_range(0..values.len())].clone())
+            .collect();
+        let table = Table::new(20_000, vec![Column::new_str_col("s".to_string(), column)]);
+
+        let mut sizes = vec![];
+        for sorted in [false, true] {
+            let file = TempFile::new(&format!("sorted_strings_{}.isdb", sorted));
+            let mut builder = Serializer::builder()
+                .string_compressor(StringCompressors::FrontCoded(FrontCodedStringCompressor));
+            if sorted {
+                builder = builder.sorted_column("s");
+            }
+            let serializer = builder.build();
+            serializer.serialize(&file.0, &table).unwrap();
+            assert_eq!(serializer.deserialize(&file.0).unwrap(), table);
+            sizes.push(fs::metadata(&file.0).unwrap().len());
+        }
+        assert!(sizes[1] * 3 < sizes[0] * 2, "sizes {:?}", sizes);
+    }
+
+    #[test]
+    fn sorted_int_column_is_rejected() {
+        let file = TempFile::new("sorted_ints.isdb");
+        let serializer = Serializer::builder().sorted_column("id").build();
+        let error = serializer.serialize(&file.0, &sample_table()).unwrap_err();
+        assert!(matches!(
+            &error,
+            SerializerError::UnsortableColumn { name, column_type: LogicalColumnType::Int64 }
+                if name == "id"
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Only VARCHAR columns can be stored sorted, 'id' is INT64"
+        );
+    }
+
+    #[test]
+    fn unsort_rejects_ranks_that_are_no_permutation() {
+        assert_eq!(unsort(vec![1, 2, 3], &[2, 0, 1]).unwrap(), [3, 1, 2]);
+        assert!(unsort(vec![1, 2, 3], &[0, 0, 2]).is_err());
+        assert!(unsort(vec![1, 2, 3], &[0, 1, 3]).is_err());
+        assert!(unsort(vec![1, 2, 3], &[0, 1]).is_err());
+    }
+
+    #[test]
+    fn version_1_golden_file_is_read() {
+        let file = TempFile::new("golden_v1.isdb");
+        fs::write(&file.0, include_bytes!("../pytests/golden/v1.isdb")).unwrap();
+
+        let (table, stats) = Serializer::new().deserialize_with_stats(&file.0).unwrap();
+
+        assert_eq!(table.num_rows, 3);
+        assert_eq!(table.columns[0].name, "id");
+        assert_eq!(table.columns[0].data, ColumnData::INT64(vec![1, 2, 3]));
+        assert_eq!(table.columns[1].name, "name");
+        assert_eq!(
+            table.columns[1].data,
+            ColumnData::STR(vec!["a".to_string(), "bc".to_string(), "".to_string()])
+        );
+        assert_eq!(stats.rows_read, 3);
+        assert_eq!(stats.bytes_read, 3 + 8 + 3);
+    }
+
+    /// Table of up to 5 INT64, BOOL and STR columns and up to 40 rows, possibly none. Ints lean
+    /// towards the ends of the range and strings mix empty, ASCII and multibyte values.
+    fn random_table(rng: &mut StdRng) -> Table {
+        const PIECES: [&str; 6] = ["", "a", "zażółć", "日本", "🦀", "\u{0}\n,\""];
+        let num_rows = match rng.random_range(0..4) {
+            0 => 0,
+            _ => rng.random_range(1..40usize),
+        };
+        let columns = (0..rng.random_range(0..=5))
+            .map(|i| {
+                let name = format!("c{}", i);
+                match rng.random_range(0..3) {
+                    0 => Column::new_int_col(
+                        name,
+                        (0..num_rows)
+                            .map(|_| match rng.random_range(0..4) {
+                                0 => i64::MIN,
+                                1 => i64::MAX,
+                                2 => rng.random_range(-3..3),
+                                _ => rng.random(),
+                            })
+                            .collect(),
+                    ),
+                    1 => Column {
+                        name,
+                        data: ColumnData::BOOL((0..num_rows).map(|_| rng.random()).collect()),
+                        metadata: None,
+                    },
+                    _ => Column::new_str_col(
+                        name,
+                        (0..num_rows)
+                            .map(|_| {
+                                (0..rng.random_range(0..4))
+                                    .map(|_| PIECES[rng.random_range(0..PIECES.len())])
+                                    .collect()
+                            })
+                            .collect(),
+                    ),
+                }
+            })
+            .collect();
+        Table::new(num_rows as u64, columns)
+    }
+
+    /// BOOL columns are stored as ints and come back as INT64 of 0 and 1.
+    fn as_read_back(mut table: Table) -> Table {
+        for column in &mut table.columns {
+            if let ColumnData::BOOL(data) = &column.data {
+                column.data = ColumnData::INT64(data.iter().map(|&b| b as i64).collect());
+            }
+        }
+        table
+    }
+
+    #[test]
+    fn random_tables_round_trip_with_every_configuration() {
+        let int_compressors = [
+            IntCompressors::VleDelta(VleDeltaIntCompressor),
+            IntCompressors::BitPack(BitPackIntCompressor),
+            IntCompressors::None(NoIntCompressor),
+        ];
+        let string_compressors = [
+            StringCompressors::Lz4(LZ4StringCompressor),
+            StringCompressors::Lz4Frame(LZ4FrameStringCompressor),
+            StringCompressors::FrontCoded(FrontCodedStringCompressor),
+            StringCompressors::None(NoStringCompressor),
+        ];
+        let file = TempFile::new("fuzz.isdb");
+
+        let mut seed = 0u64;
+        for int_compressor in &int_compressors {
+            for string_compressor in &string_compressors {
+                for _ in 0..25 {
+                    seed += 1;
+                    let mut rng = StdRng::seed_from_u64(seed);
+                    let table = random_table(&mut rng);
+                    let mut builder = Serializer::builder()
+                        .int_compressor(int_compressor.clone())
+                        .string_compressor(string_compressor.clone())
+                        .checksums(rng.random())
+                        .stats(rng.random())
+                        .string_offsets(rng.random())
+                        .auto_codec(rng.random());
+                    if let Some(column) = table
+                        .columns
+                        .iter()
+                        .find(|column| matches!(column.data, ColumnData::STR(_)))
+                        && rng.random()
+                    {
+                        builder = builder.sorted_column(column.name.clone());
+                    }
+                    let serializer = builder.build();
+
+                    serializer
+                        .serialize(&file.0, &table)
+                        .unwrap_or_else(|e| panic!("seed {}: {}", seed, e));
+                    let read = serializer
+                        .deserialize(&file.0)
+                        .unwrap_or_else(|e| panic!("seed {}: {}", seed, e));
+                    assert_eq!(read, as_read_back(table), "seed {}", seed);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn schema_compatible_compares_names_types_and_order() {
+        let file = TempFile::new("schema_compatible.isdb");
+        let serializer = Serializer::new();
+        serializer.serialize(&file.0, &sample_table()).unwrap();
+        let schema = |columns: &[(&str, LogicalColumnType)]| Schema {
+            columns: columns
+                .iter()
+                .map(|(name, column_type)| (name.to_string(), *column_type))
+                .collect(),
+        };
+
+        let matching = schema(&[
+            ("id", LogicalColumnType::Int64),
+            ("name", LogicalColumnType::Varchar),
+        ]);
+        assert!(
+            serializer
+                .schema_compatible(&file.0, &matching, None)
+                .unwrap()
+        );
+
+        let reordered = schema(&[
+            ("name", LogicalColumnType::Varchar),
+            ("id", LogicalColumnType::Int64),
+        ]);
+        assert!(
+            !serializer
+                .schema_compatible(&file.0, &reordered, None)
+                .unwrap()
+        );
+
+        let mismatched = schema(&[
+            ("id", LogicalColumnType::Varchar),
+            ("name", LogicalColumnType::Varchar),
+        ]);
+        assert!(
+            !serializer
+                .schema_compatible(&file.0, &mismatched, None)
+                .unwrap()
+        );
+
+        assert!(
+            serializer
+                .schema_compatible(&file.0, &matching, Some(3))
+                .unwrap()
+        );
+        assert!(
+            !serializer
+                .schema_compatible(&file.0, &matching, Some(4))
+                .unwrap()
+        );
+    }
+
+    /// Local files whose writers set `cancel` once the header and one more write went through.
+    #[derive(Debug, Clone, Default)]
+    struct CancellingFs {
+        cancel: Arc<AtomicBool>,
+    }
+
+    struct CancellingWriter {
+        file: Box<dyn WriteSeek>,
+        writes: usize,
+        cancel: Arc<AtomicBool>,
+    }
+
+    impl Write for CancellingWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.writes += 1;
+            if self.writes >= 2 {
+                self.cancel.store(true, Ordering::Relaxed);
+            }
+            self.file.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.file.flush()
+        }
+    }
+
+    impl Seek for CancellingWriter {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.file.seek(pos)
+        }
+    }
+
+    impl StorageBackend for CancellingFs {
+        fn open_read(&self, path: &Path) -> Result<Box<dyn ReadSeek>, Error> {
+            LocalFs.open_read(path)
+        }
+
+        fn create_write(&self, path: &Path, overwrite: bool) -> Result<Box<dyn WriteSeek>, Error> {
+            Ok(Box::new(CancellingWriter {
+                file: LocalFs.create_write(path, overwrite)?,
+                writes: 0,
+                cancel: self.cancel.clone(),
+            }))
+        }
+
+        fn exists(&self, path: &Path) -> bool {
+            LocalFs.exists(path)
+        }
+
+        fn remove(&self, path: &Path) -> Result<(), Error> {
+            LocalFs.remove(path)
+        }
+
+        fn rename(&self, from: &Path, to: &Path) -> Result<(), Error> {
+            LocalFs.rename(from, to)
+        }
+    }
+
+    #[test]
+    fn cancelled_serialize_leaves_no_file_behind() {
+        let file = TempFile::new("cancelled.isdb");
+        let backend = CancellingFs::default();
+        let serializer = Serializer::builder()
+            .block_size(1)
+            .storage_backend(backend.clone())
+            .build();
+
+        let result =
+            serializer.serialize_cancellable(&file.0, &wide_table(8, 1000), &backend.cancel);
+
+        assert!(matches!(result, Err(SerializerError::Cancelled)));
+        assert!(!file.0.exists());
+        assert!(!partial_path(&file.0).exists());
+    }
+
+    #[test]
+    fn cancelled_serialize_keeps_the_previous_file() {
+        let file = TempFile::new("cancelled_overwrite.isdb");
+        Serializer::new()
+            .serialize(&file.0, &sample_table())
+            .unwrap();
+        let backend = CancellingFs::default();
+        let serializer = Serializer::builder()
+            .block_size(1)
+            .storage_backend(backend.clone())
+            .build();
+
+        let result =
+            serializer.serialize_cancellable(&file.0, &wide_table(8, 1000), &backend.cancel);
+
+        assert!(matches!(result, Err(SerializerError::Cancelled)));
+        assert_eq!(serializer.deserialize(&file.0).unwrap(), sample_table());
+        assert!(!partial_path(&file.0).exists());
+    }
+
+    #[test]
+    fn column_at_index_is_the_named_column() {
+        let table = sample_table();
+
+        for name in ["id", "name"] {
+            let index = table.column_index(name).unwrap();
+            assert_eq!(table.column_at(index), table.column(name));
+        }
+        assert_eq!(table.column_index("missing"), None);
+        assert_eq!(table.column_at(2), None);
+    }
+
+    #[test]
+    fn files_are_read_with_the_string_codec_they_were_written_with() {
+        let string_compressors = [
+            StringCompressors::Lz4(LZ4StringCompressor),
+            StringCompressors::Lz4Frame(LZ4FrameStringCompressor),
+            StringCompressors::FrontCoded(FrontCodedStringCompressor),
+            StringCompressors::None(NoStringCompressor),
+        ];
+        let table = wide_table(4, 500);
+        for string_compressor in string_compressors {
+            let file = TempFile::new(&format!("codec_{}.isdb", string_compressor.mode()));
+            Serializer::builder()
+                .string_compressor(string_compressor.clone())
+                .build()
+                .serialize(&file.0, &table)
+                .unwrap();
+
+            // Reader is configured with the default LZ4 codec.
+            let reader = Serializer::new();
+            let header = reader.read_schema(&file.0).unwrap();
+            assert_eq!(
+                header.string_compressor.map(|c| c.codec()),
+                Some(string_compressor.codec())
+            );
+            assert_eq!(reader.deserialize(&file.0).unwrap(), table);
+            assert!(matches!(
+                &reader.read_row(&file.0, 123).unwrap()[1],
+                RowValue::Str(value) if value == "value 1 123"
+            ));
+        }
+    }
+
+    #[test]
+    fn lz4_frame_costs_a_few_bytes_per_string_column() {
+        let table = wide_table(6, 2_000);
+        let mut sizes = vec![];
+        for string_compressor in [
+            StringCompressors::Lz4(LZ4StringCompressor),
+            StringCompressors::Lz4Frame(LZ4FrameStringCompressor),
+        ] {
+            let file = TempFile::new(&format!("frame_size_{}.isdb", string_compressor.mode()));
+            Serializer::builder()
+                .string_compressor(string_compressor)
+                .build()
+                .serialize(&file.0, &table)
+                .unwrap();
+            sizes.push(fs::metadata(&file.0).unwrap().len());
+        }
+        // Frame header, block header and end mark instead of the size prefix, for 3 columns.
+        assert!(sizes[0] < sizes[1], "sizes {:?}", sizes);
+        assert!(sizes[1] <= sizes[0] + 3 * 32, "sizes {:?}", sizes);
+    }
+
+    #[test]
+    fn footer_stripped_file_loads_only_in_lenient_mode() {
+        let file = TempFile::new("no_footer.isdb");
+        let table = sample_table();
+        Serializer::new().serialize(&file.0, &table).unwrap();
+        let len = fs::metadata(&file.0).unwrap().len();
+        File::options()
+            .write(true)
+            .open(&file.0)
+            .unwrap()
+            .set_len(len - FOOTER.len() as u64)
+            .unwrap();
+
+        let error = Serializer::new().deserialize(&file.0).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid file format: Invalid file footer"
+        );
+
+        let lenient = Serializer::builder().lenient_footer(true).build();
+        assert_eq!(lenient.deserialize(&file.0).unwrap(), table);
+
+        let bytes = fs::read(&file.0).unwrap();
+        assert!(Serializer::new().view(&bytes).is_err());
+        assert_eq!(lenient.view(&bytes).unwrap().to_table(), table);
+    }
+
+    #[test]
+    fn typed_column_iterators_yield_only_their_type_in_order() {
+        let table = Table::new(
+            2,
+            vec![
+                Column::new_str_col("s1".to_string(), vec!["a".to_string(), "b".to_string()]),
+                Column::new_int_col("i1".to_string(), vec![1, 2]),
+                Column {
+                    name: "flag".to_string(),
+                    data: ColumnData::BOOL(vec![true, false]),
+                    metadata: None,
+                },
+                Column::new_int_col("i2".to_string(), vec![3, 4]),
+                Column::new_str_col("s2".to_string(), vec!["c".to_string(), "d".to_string()]),
+            ],
+        );
+
+        let ints = table.int_columns().collect::<Vec<_>>();
+        assert_eq!(ints, [("i1", &[1, 2][..]), ("i2", &[3, 4][..])]);
+        let strs = table
+            .str_columns()
+            .map(|(name, data)| (name, data.join(",")))
+            .collect::<Vec<_>>();
+        assert_eq!(strs, [("s1", "a,b".to_string()), ("s2", "c,d".to_string())]);
+        assert_eq!(Table::new(0, vec![]).int_columns().count(), 0);
+    }
+
+    #[test]
+    fn table_with_capacity_has_empty_presized_columns() {
+        let schema = Schema {
+            columns: vec![
+                ("id".to_string(), LogicalColumnType::Int64),
+                ("name".to_string(), LogicalColumnType::Varchar),
+                ("flag".to_string(), LogicalColumnType::Bool),
+            ],
+        };
+        let table = Table::with_capacity(1000, &schema);
+
+        assert_eq!(table.get_num_rows(), 0);
+        assert!(table.matches_schema(&schema));
+        for column in table.iter_columns() {
+            let (len, capacity) = match &column.data {
+                ColumnData::INT64(data) => (data.len(), data.capacity()),
+                ColumnData::STR(data) => (data.len(), data.capacity()),
+                ColumnData::BOOL(data) => (data.len(), data.capacity()),
+            };
+            assert_eq!(len, 0, "column {}", column.name);
+            assert!(capacity >= 1000, "column {}", column.name);
+        }
+    }
+
+    #[test]
+    fn read_row_returns_first_middle_and_last_rows() {
+        let table = wide_table(4, 101);
+        let serializers = [
+            Serializer::new(),
+            Serializer::builder()
+                .string_compressor(StringCompressors::None(NoStringCompressor))
+                .string_offsets(true)
+                .build(),
+            Serializer::builder()
+                .string_offsets(true)
+                .sorted_column("c1")
+                .build(),
+        ];
+        for (i, serializer) in serializers.iter().enumerate() {
+            let file = TempFile::new(&format!("read_row_{}.isdb", i));
+            serializer.serialize(&file.0, &table).unwrap();
+
+            for row in [0, 50, 100] {
+                let expected = table
+                    .iter_columns()
+                    .map(|column| column.data.value(row))
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    serializer.read_row(&file.0, row as u64).unwrap(),
+                    expected,
+                    "serializer {} row {}",
+                    i,
+                    row
+                );
+            }
+            let error = serializer.read_row(&file.0, 101).unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                "Invalid row: Row 101 out of range, file has 101 rows"
+            );
+        }
+    }
+
+    #[test]
+    fn overwrite_protection_keeps_existing_files() {
+        let file = TempFile::new("protected.isdb");
+        let table = sample_table();
+        Serializer::new().serialize(&file.0, &table).unwrap();
+
+        let protected = Serializer::builder().overwrite_protection(true).build();
+        let error = protected.serialize(&file.0, &wide_table(2, 5)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("IO error: {}: File already exists", file.0.display())
+        );
+        assert_eq!(protected.deserialize(&file.0).unwrap(), table);
+
+        let copy = TempFile::new("protected_copy.isdb");
+        fs::copy(&file.0, &copy.0).unwrap();
+        assert!(
+            protected
+                .clone_file_with_schema_subset(&file.0, &copy.0, &["id"])
+                .is_err()
+        );
+
+        // Off by default, the file is replaced.
+        Serializer::new()
+            .serialize(&file.0, &wide_table(2, 5))
+            .unwrap();
+        assert_eq!(
+            Serializer::new().deserialize(&file.0).unwrap(),
+            wide_table(2, 5)
+        );
+    }
+
+    #[test]
+    fn overlong_column_name_is_not_truncated() {
+        let file = TempFile::new("long_name.isdb");
+        let name = "c".repeat(MAX_COLUMN_NAME_LEN + 1);
+        let table = Table::new(1, vec![Column::new_int_col(name.clone(), vec![1])]);
+
+        let error = Serializer::new().serialize(&file.0, &table).unwrap_err();
+        assert!(matches!(&error, SerializerError::ColumnNameTooLong(long) if *long == name));
+        assert_eq!(
+            error.to_string(),
+            format!("Column name is longer than 255 bytes: {}", name)
+        );
+        assert!(!file.0.exists());
+        assert!(!partial_path(&file.0).exists());
+    }
+
+    #[test]
+    fn streamed_rows_match_deserialized_table() {
+        let file = TempFile::new("stream_rows.isdb");
+        let table = wide_table(5, 300);
+        let serializer = Serializer::builder().sorted_column("c1").build();
+        serializer.serialize(&file.0, &table).unwrap();
+
+        let rows = serializer
+            .stream_rows(&file.0)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let deserialized = serializer.deserialize(&file.0).unwrap();
+        assert_eq!(rows.len(), 300);
+        for (row, values) in rows.iter().enumerate() {
+            let expected = deserialized
+                .iter_columns()
+                .map(|column| column.data.value(row))
+                .collect::<Vec<_>>();
+            assert_eq!(*values, expected, "row {}", row);
+        }
+
+        let empty = TempFile::new("stream_rows_empty.isdb");
+        serializer.serialize(&empty.0, &wide_table(5, 0)).unwrap();
+        assert_eq!(serializer.stream_rows(&empty.0).unwrap().count(), 0);
+    }
+
+    type MemoryFiles = Arc<Mutex<BTreeMap<PathBuf, Vec<u8>>>>;
+
+    /// Files kept in RAM; a writer stores its buffer on flush.
+    #[derive(Debug, Clone, Default)]
+    struct MemoryFs {
+        files: MemoryFiles,
+    }
+
+    struct MemoryWriter {
+        files: MemoryFiles,
+        path: PathBuf,
+        buf: Cursor<Vec<u8>>,
+    }
+
+    impl Write for MemoryWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.buf.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            let data = self.buf.get_ref().clone();
+            self.files.lock().unwrap().insert(self.path.clone(), data);
+            Ok(())
+        }
+    }
+
+    impl Seek for MemoryWriter {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.buf.seek(pos)
+        }
+    }
+
+    impl StorageBackend for MemoryFs {
+        fn open_read(&self, path: &Path) -> Result<Box<dyn ReadSeek>, Error> {
+            let files = self.files.lock().unwrap();
+            let data = files.get(path).cloned().ok_or(ErrorKind::NotFound)?;
+            Ok(Box::new(Cursor::new(data)))
+        }
+
+        fn create_write(&self, path: &Path, overwrite: bool) -> Result<Box<dyn WriteSeek>, Error> {
+            if !overwrite && self.exists(path) {
+                return Err(ErrorKind::AlreadyExists.into());
+            }
+            Ok(Box::new(MemoryWriter {
+                files: self.files.clone(),
+                path: path.to_path_buf(),
+                buf: Cursor::new(Vec::new()),
+            }))
+        }
+
+        fn exists(&self, path: &Path) -> bool {
+            self.files.lock().unwrap().contains_key(path)
+        }
+
+        fn remove(&self, path: &Path) -> Result<(), Error> {
+            let mut files = self.files.lock().unwrap();
+            files
+                .remove(path)
+                .map(|_| ())
+                .ok_or(ErrorKind::NotFound.into())
+        }
+
+        fn rename(&self, from: &Path, to: &Path) -> Result<(), Error> {
+            let mut files = self.files.lock().unwrap();
+            let data = files.remove(from).ok_or(ErrorKind::NotFound)?;
+            files.insert(to.to_path_buf(), data);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn memory_backend_round_trips_without_touching_disk() {
+        let memory = MemoryFs::default();
+        let serializer = Serializer::builder()
+            .storage_backend(memory.clone())
+            .checksums(true)
+            .build();
+        let table = sample_table();
+        let path = Path::new("/nonexistent/dir/table.isdb");
+
+        serializer.serialize(path, &table).unwrap();
+        assert!(!path.exists());
+        assert!(memory.exists(path));
+
+        let read_back = serializer.deserialize(path).unwrap();
+        assert_eq!(read_back.get_num_rows(), table.get_num_rows());
+        assert_eq!(
+            format!("{:?}", read_back.columns),
+            format!("{:?}", table.columns)
+        );
+
+        serializer.compact(path).unwrap();
+        assert_eq!(memory.files.lock().unwrap().len(), 1);
+        assert_eq!(
+            format!("{:?}", serializer.deserialize(path).unwrap().columns),
+            format!("{:?}", table.columns)
+        );
+    }
+
+    #[test]
+    fn dropping_scattered_rows_keeps_columns_aligned() {
+        let mut table = Table::new(
+            20,
+            vec![
+                Column::new_int_col("id".to_string(), (0..20).collect()),
+                Column::new_str_col(
+                    "name".to_string(),
+                    (0..20).map(|i| format!("r{i}")).collect(),
+                ),
+            ],
+        );
+        let dropped = [0, 3, 4, 11, 19];
+        table.drop_rows(&dropped).unwrap();
+
+        let kept: Vec<i64> = (0..20)
+            .filter(|i| !dropped.contains(&(*i as usize)))
+            .collect();
+        assert_eq!(table.get_num_rows(), 15);
+        assert_eq!(table.int_column("id").unwrap(), kept);
+        assert_eq!(
+            table.str_column("name").unwrap(),
+            kept.iter().map(|i| format!("r{i}")).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn drop_rows_rejects_unsorted_and_out_of_range_indices() {
+        let mut table = sample_table();
+        let num_rows = table.get_num_rows();
+        for indices in [&[2, 1][..], &[1, 1], &[0, num_rows as usize]] {
+            assert!(matches!(
+                table.drop_rows(indices),
+                Err(SerializerError::InvalidRow(_))
+            ));
+        }
+        assert_eq!(table.get_num_rows(), num_rows);
+
+        table.drop_rows(&[]).unwrap();
+        assert_eq!(table.get_num_rows(), num_rows);
+    }
+
+    fn inferred_types(csv: &str, thousands_separator: Option<char>) -> Vec<&'static str> {
+        let options = CsvOptions {
+            thousands_separator,
+            ..Default::default()
+        };
+        let table = Table::from_csv_reader(csv.as_bytes(), &options).unwrap();
+        table
+            .columns
+            .iter()
+            .map(|column| match column.data {
+                ColumnData::INT64(_) => "INT64",
+                ColumnData::STR(_) => "STR",
+                ColumnData::BOOL(_) => "BOOL",
+            })
+            .collect()
+    }
+
+    #[test]
+    fn grouped_numbers_infer_as_int_only_when_enabled() {
+        let csv = "a,b\n\"1,234\",\"-12,345,678\"\n\"999\",\"+1,000\"\n";
+        assert_eq!(inferred_types(csv, None), ["STR", "STR"]);
+        assert_eq!(inferred_types(csv, Some(',')), ["INT64", "INT64"]);
+
+        let options = CsvOptions {
+            thousands_separator: Some(','),
+            ..Default::default()
+        };
+        let table = Table::from_csv_reader(csv.as_bytes(), &options).unwrap();
+        assert_eq!(table.int_column("a").unwrap(), [1234, 999]);
+        assert_eq!(table.int_column("b").unwrap(), [-12345678, 1000]);
+    }
+
+    #[test]
+    fn malformed_grouping_falls_back_to_string() {
+        for value in ["1,2,3", "1234,567", "1,23", ",123", "1,234,", "-,123"] {
+            let csv = format!("a\n\"{}\"\n", value);
+            assert_eq!(inferred_types(&csv, Some(',')), ["STR"], "{}", value);
+        }
+    }
+
+    /// Sorted and repeating columns every codec should shrink a lot.
+    fn compressible_table(num_rows: usize) -> Table {
+        const STATUSES: [&str; 3] = ["active", "inactive", "pending"];
+        Table::new(
+            num_rows as u64,
+            vec![
+                Column::new_int_col("sorted".to_string(), (0..num_rows as i64).collect()),
+                Column::new_int_col(
+                    "repeated".to_string(),
+                    (0..num_rows as i64).map(|i| i % 4).collect(),
+                ),
+                Column::new_str_col(
+                    "status".to_string(),
+                    (0..num_rows).map(|i| STATUSES[i % 3].to_string()).collect(),
+                ),
+                Column::new_str_col(
+                    "path".to_string(),
+                    (0..num_rows)
+                        .map(|i| format!("/var/data/table/{:08}", i))
+                        .collect(),
+                ),
+            ],
+        )
+    }
+
+    /// Uniformly random ints and strings, which no codec here can shrink much.
+    fn incompressible_table(rng: &mut StdRng, num_rows: usize) -> Table {
+        Table::new(
+            num_rows as u64,
+            vec![
+                Column::new_int_col(
+                    "random".to_string(),
+                    (0..num_rows).map(|_| rng.random()).collect(),
+                ),
+                Column::new_str_col(
+                    "random_text".to_string(),
+                    (0..num_rows)
+                        .map(|_| {
+                            (0..16)
+                                .map(|_| char::from(rng.random_range(b'!'..=b'~')))
+                                .collect()
+                        })
+                        .collect(),
+                ),
+            ],
+        )
+    }
+
+    /// Extreme ints, empty, multibyte and long strings. Columns have no nulls, so empty
+    /// strings and zeros stand in for missing values.
+    fn edge_value_table() -> Table {
+        let ints = [0, i64::MIN, i64::MAX, -1, 1, i64::MIN + 1, i64::MAX - 1, 0];
+        let strings = [
+            "",
+            "zażółć",
+            "🦀",
+            "",
+            "x".repeat(70_000).leak(),
+            "\u{0}",
+            "\"\n,",
+            "",
+        ];
+        Table::new(
+            8,
+            vec![
+                Column::new_int_col("int".to_string(), ints.to_vec()),
+                Column::new_str_col(
+                    "str".to_string(),
+                    strings.iter().map(|s| s.to_string()).collect(),
+                ),
+                Column {
+                    name: "bool".to_string(),
+                    data: ColumnData::BOOL(vec![
+                        true, false, false, true, true, false, true, false,
+                    ]),
+                    metadata: None,
+                },
+            ],
+        )
+    }
+
+    fn int_codecs() -> [IntCompressors; 3] {
+        [
+            IntCompressors::VleDelta(VleDeltaIntCompressor),
+            IntCompressors::BitPack(BitPackIntCompressor),
+            IntCompressors::None(NoIntCompressor),
+        ]
+    }
+
+    fn string_codecs() -> [StringCompressors; 4] {
+        [
+            StringCompressors::Lz4(LZ4StringCompressor),
+            StringCompressors::Lz4Frame(LZ4FrameStringCompressor),
+            StringCompressors::FrontCoded(FrontCodedStringCompressor),
+            StringCompressors::None(NoStringCompressor),
+        ]
+    }
+
+    /// File size of the named columns of `table` written with the given codecs.
+    fn stored_size(
+        table: &Table,
+        names: &[&str],
+        int_compressor: IntCompressors,
+        string_compressor: StringCompressors,
+    ) -> u64 {
+        let file = TempFile::new(&format!("stored_size_{}.isdb", names.join("_")));
+        let columns = names
+            .iter()
+            .map(|name| table.column(name).unwrap().clone())
+            .collect();
+        Serializer::builder()
+            .int_compressor(int_compressor)
+            .string_compressor(string_compressor)
+            .build()
+            .serialize(&file.0, &Table::new(table.get_num_rows(), columns))
+            .unwrap();
+        fs::metadata(&file.0).unwrap().len()
+    }
+
+    fn int_size(table: &Table, name: &str, codec: IntCompressors) -> u64 {
+        stored_size(
+            table,
+            &[name],
+            codec,
+            StringCompressors::None(NoStringCompressor),
+        )
+    }
+
+    fn string_size(table: &Table, name: &str, codec: StringCompressors) -> u64 {
+        let lengths = IntCompressors::VleDelta(VleDeltaIntCompressor);
+        stored_size(table, &[name], lengths, codec)
+    }
+
+    #[test]
+    fn compressible_columns_shrink_with_every_codec() {
+        let table = compressible_table(10_000);
+        let [vle_delta, bit_pack, none] = int_codecs();
+
+        let raw = int_size(&table, "sorted", none.clone());
+        assert!(int_size(&table, "sorted", vle_delta.clone()) * 4 < raw);
+        assert!(int_size(&table, "sorted", bit_pack.clone()) * 4 < raw);
+        // Values in 0..4 take 2 bits packed, a byte each as deltas.
+        let raw = int_size(&table, "repeated", none);
+        let vle_delta = int_size(&table, "repeated", vle_delta);
+        let bit_pack = int_size(&table, "repeated", bit_pack);
+        assert!(vle_delta * 4 < raw);
+        assert!(bit_pack * 2 < vle_delta);
+
+        for name in ["status", "path"] {
+            let [lz4, lz4_frame, front_coded, none] =
+                string_codecs().map(|codec| string_size(&table, name, codec));
+            for size in [lz4, lz4_frame, front_coded] {
+                assert!(size * 2 < none, "{}: {} vs {}", name, size, none);
+            }
+        }
+    }
+
+    #[test]
+    fn incompressible_columns_barely_change_size() {
+        let mut rng = StdRng::seed_from_u64(1952);
+        let table = incompressible_table(&mut rng, 10_000);
+        let [vle_delta, bit_pack, none] = int_codecs();
+
+        // Random deltas need 9 or 10 varint bytes, full range values all 64 bits.
+        let raw = int_size(&table, "random", none);
+        assert!(int_size(&table, "random", vle_delta) > raw);
+        assert!(int_size(&table, "random", bit_pack) >= raw);
+
+        let [lz4, lz4_frame, front_coded, none] =
+            string_codecs().map(|codec| string_size(&table, "random_text", codec));
+        for size in [lz4, lz4_frame, front_coded] {
+            assert!(size * 10 >= none * 9, "{} vs {}", size, none);
+        }
+
+        // Same codec, far better ratio on compressible data of the same size.
+        let compressible = compressible_table(10_000);
+        let ratio = |table: &Table, name: &str| {
+            let [lz4, .., none] = string_codecs().map(|codec| string_size(table, name, codec));
+            lz4 as f64 / none as f64
+        };
+        assert!(ratio(&compressible, "path") * 2.0 < ratio(&table, "random_text"));
+    }
+
+    #[test]
+    fn edge_values_round_trip_with_every_codec() {
+        let table = edge_value_table();
+        let file = TempFile::new("edge_values.isdb");
+        for int_compressor in int_codecs() {
+            for string_compressor in string_codecs() {
+                let serializer = Serializer::builder()
+                    .int_compressor(int_compressor.clone())
+                    .string_compressor(string_compressor.clone())
+                    .build();
+                serializer.serialize(&file.0, &table).unwrap();
+                assert_eq!(
+                    serializer.deserialize(&file.0).unwrap(),
+                    as_read_back(table.clone())
+                );
+            }
+        }
+
+        // Range from i64::MIN to i64::MAX needs the full 64 bits per value.
+        let [_, bit_pack, none] = int_codecs();
+        assert!(int_size(&table, "int", bit_pack) >= int_size(&table, "int", none));
+    }
+
+    #[test]
+    fn overflowing_column_location_is_reported_as_corruption() {
+        let file = TempFile::new("overflowing_location.isdb");
+        let serializer = Serializer::new();
+        serializer.serialize(&file.0, &sample_table()).unwrap();
+
+        let mut bytes = fs::read(&file.0).unwrap();
+        let mut header = serializer.parse_header(&mut Cursor::new(&bytes)).unwrap();
+        header.columns[1].offset = u64::MAX - 1;
+        let encoded = encode_header(
+            header.version,
+            header.num_rows,
+            header.flags(),
+            header.string_compressor.as_ref(),
+            header.columns.iter(),
+        )
+        .unwrap();
+        bytes[..encoded.len()].copy_from_slice(&encoded);
+        fs::write(&file.0, bytes).unwrap();
+
+        let error = serializer.deserialize(&file.0).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid file format: Location of column name is out of range"
+        );
+    }
+
+    #[test]
+    fn too_many_columns_are_rejected() {
+        let file = TempFile::new("too_many_columns.isdb");
+        let table = wide_table(MAX_COLUMNS + 1, 0);
+
+        let error = Serializer::new().serialize(&file.0, &table).unwrap_err();
+        assert!(
+            matches!(error, SerializerError::TooManyColumns(count) if count == MAX_COLUMNS + 1)
+        );
+        assert_eq!(
+            error.to_string(),
+            "Table has 65536 columns, more than 65535"
+        );
+        assert!(!file.0.exists());
+    }
+
+    /// Value ranges at the edges of each width, with the narrowest width holding them.
+    const WIDTH_BOUNDARIES: [(i64, i64, IntWidth); 12] = [
+        (0, 0, IntWidth::I8),
+        (i8::MIN as i64, i8::MAX as i64, IntWidth::I8),
+        (i8::MIN as i64 - 1, 0, IntWidth::I16),
+        (0, i8::MAX as i64 + 1, IntWidth::I16),
+        (i16::MIN as i64, i16::MAX as i64, IntWidth::I16),
+        (i16::MIN as i64 - 1, 0, IntWidth::I32),
+        (0, i16::MAX as i64 + 1, IntWidth::I32),
+        (i32::MIN as i64, i32::MAX as i64, IntWidth::I32),
+        (i32::MIN as i64 - 1, 0, IntWidth::I64),
+        (0, i32::MAX as i64 + 1, IntWidth::I64),
+        (i64::MIN, i64::MAX, IntWidth::I64),
+        (-1, -1, IntWidth::I8),
+    ];
+
+    #[test]
+    fn narrowest_width_at_type_boundaries() {
+        for (min, max, width) in WIDTH_BOUNDARIES {
+            assert_eq!(
+                ColumnStats { min, max }.int_width(),
+                width,
+                "{}..={}",
+                min,
+                max
+            );
+            assert_eq!(
+                NarrowIntColumn::new(&[max, min]).width(),
+                width,
+                "{}..={}",
+                min,
+                max
+            );
+
+            let table = Table::new(
+                2,
+                vec![
+                    Column::new_int_col("values".to_string(), vec![min, max]),
+                    Column::new_str_col("skipped".to_string(), vec![String::new(); 2]),
+                ],
+            );
+            assert_eq!(table.int_widths(), vec![("values".to_string(), width)]);
+        }
+    }
+
+    #[test]
+    fn empty_int_column_is_narrowest() {
+        let column = NarrowIntColumn::new(&[]);
+        assert_eq!(column, NarrowIntColumn::I8(vec![]));
+        assert!(column.is_empty());
+        assert_eq!(column.get(0), None);
+        assert_eq!(column.to_i64(), Vec::<i64>::new());
+
+        let table = Table::new(0, vec![Column::new_int_col("empty".to_string(), vec![])]);
+        assert_eq!(
+            table.int_widths(),
+            vec![("empty".to_string(), IntWidth::I8)]
+        );
+    }
+
+    #[test]
+    fn narrowed_values_widen_back_unchanged() {
+        let mut rng = StdRng::seed_from_u64(1956);
+        for (min, max, width) in WIDTH_BOUNDARIES {
+            let mut data = vec![min, max, min / 2, max / 2];
+            data.extend((0..100).map(|_| rng.random_range(min..=max)));
+
+            let column = NarrowIntColumn::new(&data);
+            assert_eq!(column.width(), width);
+            assert_eq!(column.len(), data.len());
+            assert_eq!(column.to_i64(), data);
+            assert_eq!(column.get(1), Some(max));
+            assert_eq!(column.get(data.len()), None);
+        }
     }
 }
@@ -1,11 +1,13 @@
-use clap::{Arg, Command};
+use clap::{Arg, ArgAction, Command, value_parser};
 use lib::Serializer;
+use std::num::NonZeroUsize;
 use tokio::signal;
 
 use crate::{
-    consts::METASTORE_FILE,
-    metastore::{load_metastore, save_metastore},
+    consts::{DEFAULT_MAX_BODY_SIZE, METASTORE_FILE},
+    metastore::{RetentionPolicy, load_metastore, run_retention, save_metastore},
 };
+mod cli;
 mod consts;
 mod executor;
 mod metastore;
@@ -24,17 +26,200 @@ async fn main() {
                 .long("https")
                 .help("Whether to use HTTPS or not"),
         )
+        .arg(
+            Arg::new("https-addr")
+                .long("https-addr")
+                .conflicts_with("https")
+                .help("Additionally serve HTTPS on this address, next to plain HTTP"),
+        )
+        .arg(
+            Arg::new("tls-cert")
+                .long("tls-cert")
+                .default_value("examples/server-chain.pem")
+                .help("PEM certificate chain served over HTTPS"),
+        )
+        .arg(
+            Arg::new("tls-key")
+                .long("tls-key")
+                .default_value("examples/server-key.pem")
+                .help("PEM private key of the HTTPS certificate"),
+        )
+        .arg(
+            Arg::new("max-body-size")
+                .long("max-body-size")
+                .value_parser(value_parser!(u64))
+                .help(
+                    "Reject requests with a body larger than this many bytes (16 MiB by default)",
+                ),
+        )
+        .arg(
+            Arg::new("result-retention-secs")
+                .long("result-retention-secs")
+                .value_parser(value_parser!(u64))
+                .help("Remove finished queries and their results after this many seconds"),
+        )
+        .arg(
+            Arg::new("max-retained-queries")
+                .long("max-retained-queries")
+                .value_parser(value_parser!(usize))
+                .help("Keep at most this many finished queries, removing the oldest ones"),
+        )
+        .arg(
+            Arg::new("result-batch-rows")
+                .long("result-batch-rows")
+                .value_parser(value_parser!(NonZeroUsize))
+                .help("Return query results in parts of at most this many rows"),
+        )
+        .subcommand(
+            Command::new("inspect")
+                .about("Print header of an ISDB file without decompressing its data")
+                .arg(Arg::new("path").required(true)),
+        )
+        .subcommand(
+            Command::new("convert")
+                .about("Convert CSV file into ISDB file")
+                .arg(Arg::new("csv").required(true))
+                .arg(Arg::new("isdb").required(true))
+                .arg(
+                    Arg::new("has-headers")
+                        .long("has-headers")
+                        .action(ArgAction::SetTrue)
+                        .help("Whether first CSV row is a header"),
+                )
+                .arg(
+                    Arg::new("delimiter")
+                        .long("delimiter")
+                        .default_value(",")
+                        .help("Single byte CSV field delimiter"),
+                )
+                .arg(
+                    Arg::new("thousands-separator")
+                        .long("thousands-separator")
+                        .help(
+                            "Single byte digit grouping separator, e.g. ',' to read 1,234 as INT64",
+                        ),
+                )
+                .arg(
+                    Arg::new("no-compression")
+                        .long("no-compression")
+                        .action(ArgAction::SetTrue)
+                        .help("Store columns without compression"),
+                )
+                .arg(
+                    Arg::new("lz4-frame")
+                        .long("lz4-frame")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("no-compression")
+                        .help("Store strings in the standard LZ4 frame format"),
+                ),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Export ISDB file into CSV file")
+                .arg(Arg::new("isdb").required(true))
+                .arg(Arg::new("csv").required(true))
+                .arg(
+                    Arg::new("has-headers")
+                        .long("has-headers")
+                        .action(ArgAction::SetTrue)
+                        .help("Whether to write column names as the first CSV row"),
+                )
+                .arg(
+                    Arg::new("delimiter")
+                        .long("delimiter")
+                        .default_value(",")
+                        .help("Single byte CSV field delimiter"),
+                ),
+        )
         .get_matches();
 
+    if let Some(("inspect", sub_matches)) = matches.subcommand() {
+        let path = sub_matches.get_one::<String>("path").unwrap();
+        if let Err(e) = cli::inspect(path) {
+            eprintln!("Failed to inspect {}: {}", path, e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(("convert", sub_matches)) = matches.subcommand() {
+        let csv_path = sub_matches.get_one::<String>("csv").unwrap();
+        let isdb_path = sub_matches.get_one::<String>("isdb").unwrap();
+        if let Err(e) = cli::convert(
+            csv_path,
+            isdb_path,
+            sub_matches.get_flag("has-headers"),
+            sub_matches.get_one::<String>("delimiter").unwrap(),
+            sub_matches
+                .get_one::<String>("thousands-separator")
+                .map(String::as_str),
+            sub_matches.get_flag("no-compression"),
+            sub_matches.get_flag("lz4-frame"),
+        ) {
+            eprintln!("Failed to convert {}: {}", csv_path, e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(("export", sub_matches)) = matches.subcommand() {
+        let isdb_path = sub_matches.get_one::<String>("isdb").unwrap();
+        let csv_path = sub_matches.get_one::<String>("csv").unwrap();
+        if let Err(e) = cli::export(
+            isdb_path,
+            csv_path,
+            sub_matches.get_flag("has-headers"),
+            sub_matches.get_one::<String>("delimiter").unwrap(),
+        ) {
+            eprintln!("Failed to export {}: {}", isdb_path, e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let addr = "0.0.0.0:8080";
 
     let serializer = Serializer::new();
 
-    let metastore = load_metastore(METASTORE_FILE, &serializer).await;
+    let metastore = match load_metastore(METASTORE_FILE, &serializer).await {
+        Ok(metastore) => metastore,
+        Err(e) => {
+            eprintln!("Failed to load {}: {}", METASTORE_FILE, e);
+            std::process::exit(1);
+        }
+    };
+
+    let retention_policy = RetentionPolicy {
+        max_age_secs: matches.get_one::<u64>("result-retention-secs").copied(),
+        max_count: matches.get_one::<usize>("max-retained-queries").copied(),
+    };
+    if retention_policy.is_enabled() {
+        tokio::spawn(run_retention(metastore.clone(), retention_policy));
+    }
 
-    let server_handler = tokio::spawn(server::create(
-        addr,
-        matches.contains_id("https"),
+    let (http_addr, https_addr) = if matches.contains_id("https") {
+        (None, Some(addr.to_string()))
+    } else {
+        (
+            Some(addr.to_string()),
+            matches.get_one::<String>("https-addr").cloned(),
+        )
+    };
+    let tls = server::TlsConfig {
+        cert_path: matches.get_one::<String>("tls-cert").unwrap().clone(),
+        key_path: matches.get_one::<String>("tls-key").unwrap().clone(),
+    };
+    let mut server_handler = tokio::spawn(server::create(
+        http_addr,
+        https_addr,
+        tls,
+        matches
+            .get_one::<u64>("max-body-size")
+            .copied()
+            .unwrap_or(DEFAULT_MAX_BODY_SIZE),
+        matches
+            .get_one::<NonZeroUsize>("result-batch-rows")
+            .copied(),
         metastore.clone(),
     ));
 
@@ -58,11 +243,31 @@ async fn main() {
         }
     };
 
-    shutdown_signal.await;
+    // A panicked server task still saves the metastore, but the process exits with an error.
+    let mut server_failed = false;
+    tokio::select! {
+        result = &mut server_handler => match result {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                eprintln!("Failed to start server: {}", e);
+                std::process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("Server task failed: {}", e);
+                server_failed = true;
+            }
+        },
+        _ = shutdown_signal => {},
+    }
 
     println!("Shutting down server, saving metastore...");
-    save_metastore(metastore, METASTORE_FILE, &serializer).await;
+    if let Err(e) = save_metastore(metastore, METASTORE_FILE, &serializer).await {
+        eprintln!("Failed to save {}: {}", METASTORE_FILE, e);
+    }
 
     server_handler.abort();
     println!("Server Stopped.");
+    if server_failed {
+        std::process::exit(1);
+    }
 }
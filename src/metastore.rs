@@ -1,27 +1,421 @@
-use log::warn;
+use log::{error, info, warn};
 use std::{
-    cmp::min,
+    cmp::{max, min},
     collections::{HashMap, HashSet},
+    fmt::Display,
     fs,
-    path::Path,
-    sync::Arc,
+    num::NonZeroUsize,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+    time::Duration,
 };
 use uuid::Uuid;
 
-use lib;
+use lib::{
+    self,
+    compress::{LZ4FrameStringCompressor, StringCompressors},
+    storage::{FileStamp, LocalFs, StorageBackend},
+};
 use openapi_client::models;
 use serde::{Deserialize, Serialize};
-use swagger::OneOf3;
+use swagger::{OneOf3, OneOf5};
 use tokio::sync::RwLock;
 
-use crate::{query, utils::convert_to_table_file_table};
+use crate::{
+    consts::{
+        METASTORE_CHECKSUM_PREFIX, METASTORE_FILE, RETENTION_CHECK_INTERVAL_SECS, TABLES_DIR,
+    },
+    query,
+    utils::{convert_to_table_file_table, unix_timestamp},
+};
+
+/// Codecs used when a table is written to disk. Stored per table, so files written with
+/// a non-default profile are read back with the same codecs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CompressionProfile {
+    #[default]
+    Default,
+    Uncompressed,
+    Smallest,
+    Lz4Frame,
+}
+
+impl CompressionProfile {
+    pub(crate) fn serializer(&self) -> Option<lib::Serializer> {
+        match self {
+            Self::Default => None,
+            Self::Uncompressed => Some(lib::Serializer::no_compression()),
+            Self::Smallest => Some(lib::Serializer::builder().auto_codec(true).build()),
+            Self::Lz4Frame => Some(
+                lib::Serializer::builder()
+                    .string_compressor(StringCompressors::Lz4Frame(LZ4FrameStringCompressor))
+                    .build(),
+            ),
+        }
+    }
+}
+
+impl From<models::CompressionProfile> for CompressionProfile {
+    fn from(value: models::CompressionProfile) -> Self {
+        match value {
+            models::CompressionProfile::Default => Self::Default,
+            models::CompressionProfile::Uncompressed => Self::Uncompressed,
+            models::CompressionProfile::Smallest => Self::Smallest,
+            models::CompressionProfile::Lz4Frame => Self::Lz4Frame,
+        }
+    }
+}
+
+impl From<CompressionProfile> for models::CompressionProfile {
+    fn from(value: CompressionProfile) -> Self {
+        match value {
+            CompressionProfile::Default => Self::Default,
+            CompressionProfile::Uncompressed => Self::Uncompressed,
+            CompressionProfile::Smallest => Self::Smallest,
+            CompressionProfile::Lz4Frame => Self::Lz4Frame,
+        }
+    }
+}
+
+/// Limits on how long finished (COMPLETED or FAILED) queries are kept. Queries above the
+/// limits are removed together with their results and snapshots nobody else reads.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RetentionPolicy {
+    pub max_age_secs: Option<u64>,
+    pub max_count: Option<usize>,
+}
+
+impl RetentionPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.max_age_secs.is_some() || self.max_count.is_some()
+    }
+}
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct TableMetaData {
     pub(crate) name: String,
+    /// Empty after `force_unload_table`, read back from `table_file` on the next access.
     #[serde(skip)]
-    pub(crate) table: lib::Table,
+    pub(crate) table: OnceLock<lib::Table>,
     pub(crate) table_file: String,
+    #[serde(default)]
+    pub(crate) compression: CompressionProfile,
+    /// ID of the table this snapshot was taken of, `None` for every other table.
+    #[serde(default)]
+    pub(crate) snapshot_of: Option<String>,
+    /// Values filled into rows added without the column, by column name.
+    #[serde(default)]
+    pub(crate) column_defaults: HashMap<String, query::Literal>,
+    /// Column whose values have to be unique, used by upsert when the query names no column.
+    #[serde(default)]
+    pub(crate) key_column: Option<String>,
+    /// Kept in memory only, for as long as the query reading it. Has no file and is left out
+    /// of the saved metastore.
+    #[serde(skip)]
+    pub(crate) transient: bool,
+}
+
+impl TableMetaData {
+    /// Table data, reloaded from its file if it was unloaded. Fails when the file went missing
+    /// or got damaged since it was written on unloading.
+    pub(crate) fn table(&self) -> Result<&lib::Table, MetastoreError> {
+        if let Some(table) = self.table.get() {
+            return Ok(table);
+        }
+        let table = read_table_file(&self.table_file, self.compression)?;
+        Ok(self.table.get_or_init(|| table))
+    }
+
+    fn table_mut(&mut self) -> Result<&mut lib::Table, MetastoreError> {
+        self.table()?;
+        Ok(self.table.get_mut().expect("table was loaded above"))
+    }
+
+    /// Deep copy of the table stored under `table_id`, with a file of its own.
+    pub(crate) fn duplicate(
+        &self,
+        name: String,
+        table_id: &String,
+        snapshot_of: Option<String>,
+    ) -> Result<Self, MetastoreError> {
+        Ok(Self {
+            name,
+            table: self.table()?.clone().into(),
+            table_file: convert_to_table_file_table(table_id),
+            compression: self.compression,
+            snapshot_of,
+            column_defaults: self.column_defaults.clone(),
+            key_column: self.key_column.clone(),
+            transient: false,
+        })
+    }
+}
+
+/// Single file of a partitioned table. `range` of the partition column is taken from the
+/// file once, when the table is registered, `None` when the file has no rows.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Partition {
+    pub(crate) key: String,
+    pub(crate) file_path: String,
+    pub(crate) range: Option<(i64, i64)>,
+}
+
+/// One logical table stored as several ISDB files of the same schema, split by values of an
+/// INT64 partition column (e.g. a date as days since epoch). Files are not kept in memory,
+/// a SELECT reads only the partitions overlapping the range its filter allows.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PartitionedTable {
+    pub(crate) partition_column: String,
+    pub(crate) partitions: Vec<Partition>,
+    #[serde(default)]
+    pub(crate) compression: CompressionProfile,
+    /// Partition files read by queries since the server started.
+    #[serde(skip)]
+    pub(crate) files_read: usize,
+}
+
+impl PartitionedTable {
+    fn overlapping(&self, (min, max): (Option<i64>, Option<i64>)) -> Vec<&Partition> {
+        self.partitions
+            .iter()
+            .filter(|partition| {
+                partition.range.is_some_and(|(low, high)| {
+                    min.is_none_or(|min| high >= min) && max.is_none_or(|max| low <= max)
+                })
+            })
+            .collect()
+    }
+}
+
+/// Files of a partitioned table one SELECT needs, listed by `Metastore::partitions_for` under
+/// the lock and read by `read_partitions` after it is released.
+pub struct PartitionRead {
+    table_name: String,
+    compression: CompressionProfile,
+    /// Partitions overlapping the filter in key order, empty when only the schema is needed.
+    paths: Vec<String>,
+    schema_path: String,
+}
+
+impl PartitionRead {
+    /// Rows of all listed partitions, appended in partition key order.
+    fn read(&self) -> Result<lib::Table, String> {
+        let serializer = self
+            .compression
+            .serializer()
+            .unwrap_or_else(lib::Serializer::new);
+        let read = |path: &String| {
+            serializer
+                .deserialize(Path::new(path))
+                .map_err(|e| format!("Failed to read partition {}: {}", path, e))
+        };
+
+        let Some((first, rest)) = self.paths.split_first() else {
+            // Nothing overlaps, only the schema is needed.
+            let header = serializer
+                .read_schema(Path::new(&self.schema_path))
+                .map_err(|e| format!("Failed to read partition header: {}", e))?;
+            return Ok(lib::Table::with_capacity(0, &header.schema()));
+        };
+
+        let mut table = read(first)?;
+        for path in rest {
+            table
+                .append_table(read(path)?)
+                .map_err(|e| format!("Failed to read partition {}: {}", path, e))?;
+        }
+
+        Ok(table)
+    }
+}
+
+/// Table file rewrite planned by `Metastore::compaction_job` under the lock. The compacted copy
+/// is written by `write` without the lock and replaces the file in `Metastore::finish_compaction`.
+pub struct CompactionJob {
+    table_id: String,
+    path: PathBuf,
+    serializer: lib::Serializer,
+    /// File as it was when the job was planned, a file changed since is not replaced.
+    stamp: FileStamp,
+}
+
+impl CompactionJob {
+    fn compacted_path(&self) -> PathBuf {
+        self.path.with_extension("compact")
+    }
+
+    /// Writes the compacted copy next to the table file.
+    fn write(&self) -> Result<(), lib::SerializerError> {
+        let table = self.serializer.deserialize(&self.path)?;
+        self.serializer.serialize(&self.compacted_path(), &table)
+    }
+}
+
+/// Tables changed since the last save and the metastore file content, taken by
+/// `Metastore::persist_plan` under the lock. Tables are written by `write_tables` without the
+/// lock and swapped in by `Metastore::finish_persist`.
+pub struct PersistPlan {
+    tables: Vec<TableSave>,
+    metastore_file: String,
+}
+
+struct TableSave {
+    table_id: String,
+    path: PathBuf,
+    serializer: lib::Serializer,
+    /// Copy taken when planning, emptied once written.
+    table: lib::Table,
+    /// `Metastore::table_changes` of the table when planning.
+    changes: u64,
+}
+
+impl TableSave {
+    fn staged_path(&self) -> PathBuf {
+        let mut path = self.path.as_os_str().to_owned();
+        path.push(".saving");
+        PathBuf::from(path)
+    }
+}
+
+impl PersistPlan {
+    /// Writes every table next to its file.
+    fn write_tables(&mut self) -> Result<(), MetastoreFileError> {
+        for index in 0..self.tables.len() {
+            let save = &mut self.tables[index];
+            let table = std::mem::take(&mut save.table);
+            if let Err(e) = save.serializer.serialize(&save.staged_path(), &table) {
+                self.remove_staged();
+                return Err(MetastoreFileError::Table(e));
+            }
+        }
+        Ok(())
+    }
+
+    fn remove_staged(&self) {
+        for save in &self.tables {
+            let _ = fs::remove_file(save.staged_path());
+        }
+    }
+
+    /// Written next to the target and renamed over it, so a crash mid-write never leaves
+    /// a truncated metastore behind. The previous file is kept as a single backup generation.
+    fn write_metastore_file(&self, file_path: &str) -> Result<(), MetastoreFileError> {
+        let tmp_path = format!("{}.tmp", file_path);
+        fs::write(&tmp_path, &self.metastore_file).map_err(MetastoreFileError::IO)?;
+        if Path::new(file_path).exists() {
+            fs::copy(file_path, backup_path(file_path)).map_err(MetastoreFileError::IO)?;
+        }
+        fs::rename(&tmp_path, file_path).map_err(MetastoreFileError::IO)
+    }
+}
+
+/// Partitions read for one SELECT, handed to `Metastore::create_select_query`.
+pub struct PartitionData {
+    table_name: String,
+    table: lib::Table,
+    files_read: usize,
+}
+
+/// Counts describing the whole metastore, taken at one moment by `Metastore::stats`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MetastoreStats {
+    /// Tables visible to users, i.e. not scheduled for deletion.
+    pub table_count: usize,
+    pub scheduled_for_deletion_count: usize,
+    pub snapshot_table_count: usize,
+    pub partitioned_table_count: usize,
+    pub partition_files_read: usize,
+    /// Number of queries per status, every status listed even when zero.
+    pub query_counts: Vec<(query::QueryStatus, usize)>,
+    /// Rows and uncompressed value bytes of all stored tables, scheduled ones included.
+    pub total_rows: u64,
+    pub total_bytes: u64,
+    /// Bytes compressed by every codec since the server started, shared by all serializers.
+    pub codec_counters: Vec<(lib::compress::Codec, lib::compress::CodecCounters)>,
+}
+
+impl From<MetastoreStats> for models::SystemStatistics {
+    fn from(value: MetastoreStats) -> Self {
+        Self {
+            table_count: value.table_count as i64,
+            scheduled_for_deletion_count: value.scheduled_for_deletion_count as i64,
+            snapshot_table_count: value.snapshot_table_count as i64,
+            partitioned_table_count: value.partitioned_table_count as i64,
+            partition_files_read: value.partition_files_read as i64,
+            query_counts: value
+                .query_counts
+                .into_iter()
+                .map(|(status, count)| models::QueryStatusCount {
+                    status: status.into(),
+                    count: count as i64,
+                })
+                .collect(),
+            total_rows: value.total_rows as i64,
+            total_bytes: value.total_bytes as i64,
+            codec_statistics: value
+                .codec_counters
+                .into_iter()
+                .map(|(codec, counters)| models::CodecStatistics {
+                    codec: codec.name().to_string(),
+                    bytes_in: counters.bytes_in as i64,
+                    bytes_out: counters.bytes_out as i64,
+                })
+                .collect(),
+        }
+    }
+}
+
+fn tags_to_api(tags: &[String]) -> Option<Vec<String>> {
+    if tags.is_empty() {
+        None
+    } else {
+        Some(tags.to_vec())
+    }
+}
+
+pub fn logical_column_type(column_type: &models::LogicalColumnType) -> lib::LogicalColumnType {
+    match column_type {
+        models::LogicalColumnType::Int64 => lib::LogicalColumnType::Int64,
+        models::LogicalColumnType::Varchar => lib::LogicalColumnType::Varchar,
+    }
+}
+
+/// `len` rows of a column filled with `default`, or with 0, empty string or false without one.
+pub(crate) fn backfilled_column(
+    column_type: lib::LogicalColumnType,
+    default: Option<&query::Literal>,
+    len: usize,
+) -> lib::ColumnData {
+    match (column_type, default) {
+        (lib::LogicalColumnType::Int64, Some(query::Literal::I64(value))) => {
+            lib::ColumnData::INT64(vec![*value; len])
+        }
+        (lib::LogicalColumnType::Int64, _) => lib::ColumnData::INT64(vec![0; len]),
+        (lib::LogicalColumnType::Varchar, Some(query::Literal::String(value))) => {
+            lib::ColumnData::STR(vec![value.clone(); len])
+        }
+        (lib::LogicalColumnType::Varchar, _) => lib::ColumnData::STR(vec![String::new(); len]),
+        (lib::LogicalColumnType::Bool, Some(query::Literal::Bool(value))) => {
+            lib::ColumnData::BOOL(vec![*value; len])
+        }
+        (lib::LogicalColumnType::Bool, _) => lib::ColumnData::BOOL(vec![false; len]),
+    }
+}
+
+/// Default value of the column from the API, checked against the type of the column.
+fn column_default(column: &models::Column) -> Result<Option<query::Literal>, Error> {
+    let Some(default) = &column.default_value else {
+        return Ok(None);
+    };
+    let default = query::Literal::from(default.clone());
+    if lib::RowValue::from(default.clone()).logical_type() != logical_column_type(&column.r#type) {
+        return Err(Error::with_context(
+            "Default value doesn't match the type of the column",
+            column.name.clone(),
+        ));
+    }
+    Ok(Some(default))
 }
 
 #[derive(Debug)]
@@ -48,22 +442,137 @@ impl Error {
 
 #[derive(Debug)]
 pub enum MetastoreError {
-    TableAccessError(Error),
+    /// Table with given ID doesn't exist (or is already deleted).
+    TableNotFound(Error),
     TableCreationError(Vec<Error>),
-    TableDeletionError(Error),
-    QueryAccessError(Error),
+    /// Table exists, but the requested change of its column isn't possible.
+    ColumnChangeError(Error),
+    /// Query with given ID doesn't exist.
+    QueryNotFound(Error),
     QueryCreationError(Vec<Error>),
+    /// Query exists, but its result can't be returned.
     QueryResultAccessError(Error),
+    /// Query exists, but it has no errors to return.
     QueryErrorAccessError(Error),
+    /// Table exists, but its data can't be unloaded from memory.
+    TableUnloadError(Error),
+    /// Table exists, but its unloaded data can't be read back from its file.
+    TableLoadError(Error),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.context {
+            Some(context) => write!(f, "{}: {}", self.message, context),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl Display for MetastoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MetastoreError::TableCreationError(errors)
+            | MetastoreError::QueryCreationError(errors) => {
+                let messages = errors.iter().map(Error::to_string).collect::<Vec<_>>();
+                write!(f, "{}", messages.join(", "))
+            }
+            MetastoreError::TableNotFound(error)
+            | MetastoreError::ColumnChangeError(error)
+            | MetastoreError::QueryNotFound(error)
+            | MetastoreError::QueryResultAccessError(error)
+            | MetastoreError::QueryErrorAccessError(error)
+            | MetastoreError::TableUnloadError(error)
+            | MetastoreError::TableLoadError(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// Absolute `path` with its directory resolved, `None` when the directory doesn't exist. The
+/// file itself doesn't have to.
+fn resolved_path(path: &Path) -> Option<PathBuf> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    Some(fs::canonicalize(dir).ok()?.join(path.file_name()?))
+}
+
+/// Reads table data written by `force_unload_table` back.
+fn read_table_file(
+    table_file: &str,
+    compression: CompressionProfile,
+) -> Result<lib::Table, MetastoreError> {
+    let path = Path::new(table_file);
+    match compression.serializer() {
+        Some(table_serializer) => table_serializer.deserialize(path),
+        None => lib::Serializer::new().deserialize(path),
+    }
+    .map_err(|e| {
+        MetastoreError::TableLoadError(Error::with_context(
+            &format!("Failed to read table file: {}", e),
+            table_file.to_string(),
+        ))
+    })
+}
+
+/// Rejects names of columns added or renamed that a table file can't hold.
+fn check_column_name(name: &str) -> Result<(), MetastoreError> {
+    if name.is_empty() {
+        return Err(MetastoreError::ColumnChangeError(Error::new(
+            "Column has an empty name",
+        )));
+    }
+    if name.len() > lib::MAX_COLUMN_NAME_LEN {
+        return Err(MetastoreError::ColumnChangeError(Error::with_context(
+            &format!(
+                "Column name is longer than {} bytes",
+                lib::MAX_COLUMN_NAME_LEN
+            ),
+            name.to_string(),
+        )));
+    }
+    Ok(())
+}
+
+/// Context carries the ID, so clients can tell which of the tables named in a request is missing.
+fn table_not_found(id: &str) -> MetastoreError {
+    MetastoreError::TableNotFound(Error::with_context(
+        "Couldn't find a table of given ID",
+        id.to_string(),
+    ))
+}
+
+fn serialize_saved_tables<S: serde::Serializer>(
+    tables: &HashMap<String, TableMetaData>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(tables.iter().filter(|(_, metadata)| !metadata.transient))
 }
 
+/// Missing fields default to empty, so the `{}` file created by `make run` is a valid metastore.
 #[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Metastore {
     pub(crate) scheduled_for_deletion: HashSet<String>,
+    #[serde(serialize_with = "serialize_saved_tables")]
     pub(crate) tables: HashMap<String, TableMetaData>,
     pub(crate) tables_name_id: HashMap<String, String>,
     pub(crate) table_accesses: HashMap<String, HashSet<String>>,
     pub(crate) queries: HashMap<String, query::Query>,
+    /// Keyed by name, these share the namespace of regular tables.
+    pub(crate) partitioned_tables: HashMap<String, PartitionedTable>,
+    /// IDs of tables whose file matches the table in memory. Any table missing here is
+    /// written by the next save.
+    #[serde(skip)]
+    pub(crate) persisted_tables: HashSet<String>,
+    /// Rows and value bytes of tables unloaded from memory, so statistics don't reload them.
+    #[serde(skip)]
+    pub(crate) unloaded_sizes: HashMap<String, (u64, u64)>,
+    /// Number of times each table was changed, so a save can tell tables changed while it
+    /// was writing them.
+    #[serde(skip)]
+    pub(crate) table_changes: HashMap<String, u64>,
 }
 
 impl Metastore {
@@ -74,6 +583,10 @@ impl Metastore {
             tables_name_id: HashMap::new(),
             table_accesses: HashMap::new(),
             queries: HashMap::new(),
+            partitioned_tables: HashMap::new(),
+            persisted_tables: HashSet::new(),
+            unloaded_sizes: HashMap::new(),
+            table_changes: HashMap::new(),
         }
     }
 
@@ -88,17 +601,111 @@ impl Metastore {
             .collect()
     }
 
+    /// Everything is counted in one pass over `&self`, so a single read lock gives a
+    /// consistent picture.
+    pub fn stats(&self) -> MetastoreStats {
+        let mut query_counts = [
+            query::QueryStatus::Created,
+            query::QueryStatus::Planning,
+            query::QueryStatus::Running,
+            query::QueryStatus::Completed,
+            query::QueryStatus::Failed,
+        ]
+        .map(|status| (status, 0usize));
+        for query in self.queries.values() {
+            if let Some((_, count)) = query_counts
+                .iter_mut()
+                .find(|(status, _)| *status == query.status)
+            {
+                *count += 1;
+            }
+        }
+
+        MetastoreStats {
+            table_count: self
+                .tables
+                .keys()
+                .filter(|id| !self.scheduled_for_deletion.contains(*id))
+                .count(),
+            scheduled_for_deletion_count: self.scheduled_for_deletion.len(),
+            snapshot_table_count: self
+                .tables
+                .values()
+                .filter(|metadata| metadata.snapshot_of.is_some())
+                .count(),
+            partitioned_table_count: self.partitioned_tables.len(),
+            partition_files_read: self
+                .partitioned_tables
+                .values()
+                .map(|table| table.files_read)
+                .sum(),
+            query_counts: query_counts.into(),
+            total_rows: self
+                .tables
+                .iter()
+                .map(|(table_id, metadata)| match metadata.table.get() {
+                    Some(table) => table.get_num_rows(),
+                    None => self.unloaded_sizes.get(table_id).map_or(0, |size| size.0),
+                })
+                .sum(),
+            total_bytes: self
+                .tables
+                .iter()
+                .map(|(table_id, metadata)| match metadata.table.get() {
+                    Some(table) => table
+                        .iter_columns()
+                        .map(|column| column.data.byte_size() as u64)
+                        .sum(),
+                    None => self.unloaded_sizes.get(table_id).map_or(0, |size| size.1),
+                })
+                .sum(),
+            codec_counters: lib::compress::Codec::ALL
+                .map(|codec| (codec, codec.counters()))
+                .into(),
+        }
+    }
+
+    /// Whether writing `path` would replace a file the database keeps itself: the metastore
+    /// (or its temporary copy), anything in the tables directory or a partition file. Paths
+    /// are compared with their directory resolved, so other spellings of the same file match.
+    pub(crate) fn is_database_file(&self, path: &Path) -> bool {
+        let Some(path) = resolved_path(path) else {
+            return false;
+        };
+        let metastore_file = Path::new(METASTORE_FILE);
+        let tmp_file = format!("{}.tmp", METASTORE_FILE);
+
+        [metastore_file, Path::new(&tmp_file)]
+            .into_iter()
+            .chain(
+                self.partitioned_tables
+                    .values()
+                    .flat_map(|table| &table.partitions)
+                    .map(|partition| Path::new(&partition.file_path)),
+            )
+            .any(|file| resolved_path(file).is_some_and(|file| file == path))
+            || fs::canonicalize(TABLES_DIR)
+                .is_ok_and(|tables_dir| path.parent() == Some(tables_dir.as_path()))
+    }
+
+    pub fn table_exists(&self, id: &String) -> bool {
+        self.tables.contains_key(id) && !self.scheduled_for_deletion.contains(id)
+    }
+
+    pub fn query_exists(&self, id: &String) -> bool {
+        self.queries.contains_key(id)
+    }
+
     pub fn get_table(&self, id: &String) -> Result<models::TableSchema, MetastoreError> {
         if self.scheduled_for_deletion.contains(id) {
-            return Err(MetastoreError::TableAccessError(Error::new(
-                "Couldn't find a table of given ID",
-            )));
+            return Err(table_not_found(id));
         }
 
-        let table = self.tables.get(id).map(|metadata| models::TableSchema {
+        let metadata = self.tables.get(id).ok_or_else(|| table_not_found(id))?;
+        Ok(models::TableSchema {
             name: metadata.name.clone(),
             columns: metadata
-                .table
+                .table()?
                 .iter_columns()
                 .map(|column| models::Column {
                     name: column.name.clone(),
@@ -107,29 +714,38 @@ impl Metastore {
                         lib::ColumnData::STR(_) => models::LogicalColumnType::Varchar,
                         lib::ColumnData::BOOL(_) => models::LogicalColumnType::Int64,
                     },
+                    metadata: column.metadata.clone(),
+                    default_value: metadata
+                        .column_defaults
+                        .get(&column.name)
+                        .cloned()
+                        .map(Into::into),
                 })
                 .collect(),
-        });
+            key_column: metadata.key_column.clone(),
+        })
+    }
 
-        match table {
-            Some(existing_table) => Ok(existing_table),
-            None => Err(MetastoreError::TableAccessError(Error::new(
-                "Couldn't find a table of given ID",
-            ))),
+    /// IDs of queries keeping the table alive, sorted. Tables scheduled for deletion are
+    /// included, as those are the ones waiting for this list to get empty.
+    pub fn queries_accessing(&self, table_id: &String) -> Result<Vec<String>, MetastoreError> {
+        if !self.tables.contains_key(table_id) {
+            return Err(table_not_found(table_id));
         }
+
+        let mut query_ids = self
+            .table_accesses
+            .get(table_id)
+            .map(|accesses| accesses.iter().cloned().collect::<Vec<_>>())
+            .unwrap_or_default();
+        query_ids.sort();
+
+        Ok(query_ids)
     }
 
     pub fn delete_table(&mut self, table_id: &String) -> Result<(), MetastoreError> {
-        if self.scheduled_for_deletion.contains(table_id) {
-            return Err(MetastoreError::TableDeletionError(Error::new(
-                "Couldn't find a table of given ID",
-            )));
-        }
-
-        if !self.tables.contains_key(table_id) {
-            return Err(MetastoreError::TableDeletionError(Error::new(
-                "Couldn't find a table of given ID",
-            )));
+        if !self.table_exists(table_id) {
+            return Err(table_not_found(table_id));
         }
 
         self.scheduled_for_deletion.insert(table_id.clone());
@@ -138,6 +754,144 @@ impl Metastore {
         return Ok(());
     }
 
+    /// Renames a column of the stored table, its file is rewritten by the next save.
+    pub fn rename_column(
+        &mut self,
+        table_id: &String,
+        old: &str,
+        new: &str,
+    ) -> Result<(), MetastoreError> {
+        check_column_name(new)?;
+        if self.scheduled_for_deletion.contains(table_id) {
+            return Err(table_not_found(table_id));
+        }
+        let table = self
+            .get_table_internal_mut(table_id)?
+            .ok_or_else(|| table_not_found(table_id))?;
+
+        table
+            .rename_column(old, new)
+            .map_err(|e| MetastoreError::ColumnChangeError(Error::new(&e.to_string())))?;
+
+        let metadata = self.tables.get_mut(table_id).unwrap();
+        if let Some(default) = metadata.column_defaults.remove(old) {
+            metadata.column_defaults.insert(new.to_string(), default);
+        }
+        if metadata.key_column.as_deref() == Some(old) {
+            metadata.key_column = Some(new.to_string());
+        }
+        Ok(())
+    }
+
+    /// Converts values of column `name` to `new_type`, the file is rewritten on the next save.
+    pub fn change_column_type(
+        &mut self,
+        table_id: &String,
+        name: &str,
+        new_type: &models::LogicalColumnType,
+    ) -> Result<(), MetastoreError> {
+        if self.scheduled_for_deletion.contains(table_id) {
+            return Err(table_not_found(table_id));
+        }
+        let table = self
+            .get_table_internal_mut(table_id)?
+            .ok_or_else(|| table_not_found(table_id))?;
+
+        table
+            .change_column_type(name, logical_column_type(new_type))
+            .map_err(|e| MetastoreError::ColumnChangeError(Error::new(&e.to_string())))?;
+
+        // INT64 to VARCHAR is the only change of type, the default is formatted like the values.
+        let defaults = &mut self.tables.get_mut(table_id).unwrap().column_defaults;
+        if let Some(query::Literal::I64(value)) = defaults.get(name) {
+            let value = query::Literal::String(value.to_string());
+            defaults.insert(name.to_string(), value);
+        }
+        Ok(())
+    }
+
+    /// Applies type change and rename of column `name` together. Both are validated before
+    /// anything is changed, so a failing rename never leaves the type changed. Queries reading
+    /// the table keep seeing the column as it was.
+    pub fn update_column(
+        &mut self,
+        table_id: &String,
+        name: &str,
+        change: &models::ColumnChange,
+    ) -> Result<(), MetastoreError> {
+        if self.scheduled_for_deletion.contains(table_id) {
+            return Err(table_not_found(table_id));
+        }
+        let table = self
+            .get_table_internal(table_id)?
+            .ok_or_else(|| table_not_found(table_id))?;
+        let column = table.column(name).ok_or_else(|| {
+            MetastoreError::ColumnChangeError(Error::new(
+                &lib::SerializerError::ColumnNotFound(name.to_string()).to_string(),
+            ))
+        })?;
+        if let Some(new_type) = &change.new_type {
+            column
+                .data
+                .check_convert(logical_column_type(new_type))
+                .map_err(|e| MetastoreError::ColumnChangeError(Error::new(&e.to_string())))?;
+        }
+        if let Some(new_name) = &change.new_name {
+            check_column_name(new_name)?;
+            if new_name != name && table.column(new_name).is_some() {
+                return Err(MetastoreError::ColumnChangeError(Error::new(
+                    &lib::SerializerError::DuplicateColumn(new_name.clone()).to_string(),
+                )));
+            }
+        }
+
+        self.isolate_readers(table_id)?;
+        if let Some(new_type) = &change.new_type {
+            self.change_column_type(table_id, name, new_type)?;
+        }
+        if let Some(new_name) = &change.new_name {
+            self.rename_column(table_id, name, new_name)?;
+        }
+        Ok(())
+    }
+
+    /// Adds a column to the stored table, rows already in it get the default value of the column.
+    pub fn add_column(
+        &mut self,
+        table_id: &String,
+        column: &models::Column,
+    ) -> Result<(), MetastoreError> {
+        check_column_name(&column.name)?;
+        let default = column_default(column).map_err(MetastoreError::ColumnChangeError)?;
+        if self.scheduled_for_deletion.contains(table_id) {
+            return Err(table_not_found(table_id));
+        }
+        let table = self
+            .get_table_internal_mut(table_id)?
+            .ok_or_else(|| table_not_found(table_id))?;
+
+        let data = backfilled_column(
+            logical_column_type(&column.r#type),
+            default.as_ref(),
+            table.num_rows as usize,
+        );
+        table
+            .add_column(lib::Column {
+                name: column.name.clone(),
+                data,
+                metadata: column.metadata.clone(),
+            })
+            .map_err(|e| MetastoreError::ColumnChangeError(Error::new(&e.to_string())))?;
+
+        if let Some(default) = default {
+            let metadata = self.tables.get_mut(table_id).unwrap();
+            metadata
+                .column_defaults
+                .insert(column.name.clone(), default);
+        }
+        Ok(())
+    }
+
     pub fn create_table(
         &mut self,
         table_schema: models::TableSchema,
@@ -153,19 +907,44 @@ impl Metastore {
         if table_schema.columns.iter().any(|col| col.name.is_empty()) {
             errors.push(Error::new("One of the columns have empty name"));
         }
+        // Limits of the table file format, checked here rather than failing on first save.
+        if table_schema.columns.len() > lib::MAX_COLUMNS {
+            errors.push(Error::new(&format!(
+                "Table has more than {} columns",
+                lib::MAX_COLUMNS
+            )));
+        }
+        for column in &table_schema.columns {
+            if column.name.len() > lib::MAX_COLUMN_NAME_LEN {
+                errors.push(Error::with_context(
+                    &format!(
+                        "Column name is longer than {} bytes",
+                        lib::MAX_COLUMN_NAME_LEN
+                    ),
+                    column.name.clone(),
+                ));
+            }
+        }
 
         if !errors.is_empty() {
             return Err(MetastoreError::TableCreationError(errors));
         }
 
-        let existing_table_id = self.tables_name_id.get(&table_schema.name);
-
-        if let Some(id) = existing_table_id
-            && !self.scheduled_for_deletion.contains(id)
-        {
+        if self.table_name_taken(&table_schema.name) {
             errors.push(Error::new("Table with given name already exists"));
         }
 
+        let mut column_defaults = HashMap::new();
+        for column in table_schema.columns.iter() {
+            match column_default(column) {
+                Ok(Some(default)) => {
+                    column_defaults.insert(column.name.clone(), default);
+                }
+                Ok(None) => {}
+                Err(e) => errors.push(e),
+            }
+        }
+
         let mut columns_names_counts = HashMap::new();
         for column in table_schema.columns.iter() {
             let counter = columns_names_counts.entry(column.name.clone()).or_insert(0);
@@ -178,6 +957,15 @@ impl Metastore {
             }
         }
 
+        if let Some(key_column) = &table_schema.key_column
+            && !columns_names_counts.contains_key(key_column)
+        {
+            errors.push(Error::with_context(
+                "Key column doesn't exist in the table",
+                key_column.clone(),
+            ));
+        }
+
         if !errors.is_empty() {
             return Err(MetastoreError::TableCreationError(errors));
         }
@@ -185,21 +973,22 @@ impl Metastore {
         let columns = table_schema
             .columns
             .iter()
-            .map(|column| match column.r#type {
-                models::LogicalColumnType::Int64 => {
-                    lib::Column::new_int_col(column.name.clone(), vec![])
-                }
-                models::LogicalColumnType::Varchar => {
-                    lib::Column::new_str_col(column.name.clone(), vec![])
-                }
+            .map(|column| {
+                lib::Column::empty(column.name.clone(), logical_column_type(&column.r#type))
+                    .with_metadata(column.metadata.clone())
             })
             .collect();
         let table = lib::Table::new(0, columns);
         let table_id = Uuid::new_v4().to_string();
         let metadata = TableMetaData {
             name: table_schema.name.clone(),
-            table,
+            table: OnceLock::from(table),
             table_file: convert_to_table_file_table(&table_id),
+            compression: CompressionProfile::Default,
+            snapshot_of: None,
+            column_defaults,
+            key_column: table_schema.key_column.clone(),
+            transient: false,
         };
         self.tables.insert(table_id.clone(), metadata);
         self.tables_name_id
@@ -208,44 +997,371 @@ impl Metastore {
         Ok(table_id)
     }
 
-    pub fn get_queries(&self) -> Vec<models::ShallowQuery> {
-        self.queries
-            .iter()
-            .map(|(id, query)| models::ShallowQuery {
-                query_id: id.clone(),
-                status: query.status.clone().into(),
-            })
-            .collect()
+    /// Whether `name` is used by a table that is not being deleted, or by a partitioned table.
+    fn table_name_taken(&self, name: &str) -> bool {
+        self.tables_name_id
+            .get(name)
+            .is_some_and(|id| !self.scheduled_for_deletion.contains(id))
+            || self.partitioned_tables.contains_key(name)
     }
 
-    pub fn get_query(&self, id: &String) -> Result<models::Query, MetastoreError> {
-        let query = self.queries.get(id).map(|query| models::Query {
-            query_id: id.clone(),
-            status: query.status.clone().into(),
-            is_result_available: Some(query.result.is_some()),
-            query_definition: match &query.definition {
-                query::QueryDefinition::SelectAll(select_all) => {
-                    models::QueryQueryDefinition::from(OneOf3::A(models::SelectAllQuery {
-                        table_name: select_all.table_name.clone(),
-                    }))
-                }
-                query::QueryDefinition::Select(select) => {
-                    models::QueryQueryDefinition::from(OneOf3::B(models::SelectQuery {
-                        column_clauses: select
-                            .column_clauses
-                            .clone()
-                            .into_iter()
-                            .map(Into::into)
-                            .collect(),
-                        where_clause: select.where_clause.clone().map(Into::into),
-                        order_by_clause: if select.order_by_clause.len() == 0 {
-                            None
-                        } else {
-                            Some(
-                                select
-                                    .order_by_clause
-                                    .iter()
-                                    .map(|clause| models::OrderByExpression {
+    /// Registers a deep copy of the table under `new_name`. The copy gets its own ID and file
+    /// and is a regular table from then on, changes to either table don't affect the other.
+    pub fn duplicate_table(
+        &mut self,
+        source_id: &String,
+        new_name: &str,
+    ) -> Result<String, MetastoreError> {
+        if !self.table_exists(source_id) {
+            return Err(table_not_found(source_id));
+        }
+        if new_name.is_empty() {
+            return Err(MetastoreError::TableCreationError(vec![Error::new(
+                "Table has an empty name",
+            )]));
+        }
+        if self.table_name_taken(new_name) {
+            return Err(MetastoreError::TableCreationError(vec![Error::new(
+                "Table with given name already exists",
+            )]));
+        }
+
+        let table_id = Uuid::new_v4().to_string();
+        let metadata = self.tables[source_id].duplicate(new_name.to_string(), &table_id, None)?;
+        self.tables.insert(table_id.clone(), metadata);
+        self.tables_name_id
+            .insert(new_name.to_string(), table_id.clone());
+
+        Ok(table_id)
+    }
+
+    /// Registers existing ISDB files as partitions of one table. Files have to share a schema
+    /// holding INT64 `partition_column`, whose range is read from each file right away.
+    pub fn create_partitioned_table(
+        &mut self,
+        schema: models::PartitionedTableSchema,
+    ) -> Result<(), MetastoreError> {
+        let mut errors = vec![];
+
+        if schema.name.is_empty() {
+            errors.push(Error::new("Table has an empty name"));
+        }
+        if schema.partitions.is_empty() {
+            errors.push(Error::new("Table has no partitions"));
+        }
+        if self.table_name_taken(&schema.name) {
+            errors.push(Error::new("Table with given name already exists"));
+        }
+        if !errors.is_empty() {
+            return Err(MetastoreError::TableCreationError(errors));
+        }
+
+        let serializer = lib::Serializer::new();
+        let mut expected: Option<lib::Schema> = None;
+        let mut partitions = Vec::with_capacity(schema.partitions.len());
+        for partition in schema.partitions {
+            let path = Path::new(&partition.file_path);
+            let header = match serializer.read_schema(path) {
+                Ok(header) => header,
+                Err(e) => {
+                    errors.push(Error::with_context(
+                        "Can't read partition file",
+                        format!("{}: {}", partition.file_path, e),
+                    ));
+                    continue;
+                }
+            };
+
+            let file_schema = header.schema();
+            match &expected {
+                Some(expected) if *expected != file_schema => {
+                    errors.push(Error::with_context(
+                        "Partition has a different schema than the first one",
+                        format!(
+                            "{}: {}, expected {}",
+                            partition.file_path, file_schema, expected
+                        ),
+                    ));
+                    continue;
+                }
+                Some(_) => {}
+                None => expected = Some(file_schema.clone()),
+            }
+
+            let Some(column) = header
+                .columns
+                .iter()
+                .find(|column| column.name == schema.partition_column)
+            else {
+                errors.push(Error::with_context(
+                    "Partition column doesn't exist",
+                    schema.partition_column.clone(),
+                ));
+                continue;
+            };
+            if column.data.logical_type() != lib::LogicalColumnType::Int64 {
+                errors.push(Error::with_context(
+                    "Partition column has to be of type INT64",
+                    schema.partition_column.clone(),
+                ));
+                continue;
+            }
+
+            // Files written without stats are scanned once, so queries never have to.
+            let stats = if header.stats {
+                column.stats
+            } else {
+                match serializer.deserialize_columns(path, &[schema.partition_column.as_str()]) {
+                    Ok(table) => lib::ColumnStats::of(&table.columns[0].data),
+                    Err(e) => {
+                        errors.push(Error::with_context(
+                            "Can't read partition file",
+                            format!("{}: {}", partition.file_path, e),
+                        ));
+                        continue;
+                    }
+                }
+            };
+
+            partitions.push(Partition {
+                key: partition.key,
+                file_path: partition.file_path,
+                range: stats.map(|stats| (stats.min, stats.max)),
+            });
+        }
+
+        if !errors.is_empty() {
+            return Err(MetastoreError::TableCreationError(errors));
+        }
+
+        partitions.sort_by(|a, b| a.key.cmp(&b.key));
+        self.partitioned_tables.insert(
+            schema.name,
+            PartitionedTable {
+                partition_column: schema.partition_column,
+                partitions,
+                compression: CompressionProfile::Default,
+                files_read: 0,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Partitions of the partitioned table `query` selects from its filter may need, `None`
+    /// when it reads a regular table or no table at all. Clauses that don't parse are left
+    /// for `create_select_query` to report.
+    pub fn partitions_for(&self, query: &models::SelectQuery) -> Option<PartitionRead> {
+        let mut names = HashSet::new();
+        for clause in &query.column_clauses {
+            let expr: query::ColumnExpression = clause.clone().try_into().ok()?;
+            names.extend(expr.get_tables_names());
+        }
+        let filter: Option<query::ColumnExpression> = match &query.where_clause {
+            Some(clause) => Some(clause.clone().try_into().ok()?),
+            None => None,
+        };
+        names.extend(filter.iter().flat_map(|filter| filter.get_tables_names()));
+
+        let mut names = names.into_iter();
+        let (Some(table_name), None) = (names.next(), names.next()) else {
+            return None;
+        };
+        if self.tables_name_id.contains_key(&table_name) {
+            return None;
+        }
+        let partitioned_table = self.partitioned_tables.get(&table_name)?;
+        let range = filter.map_or((None, None), |filter| {
+            filter.column_range(&partitioned_table.partition_column)
+        });
+
+        Some(PartitionRead {
+            paths: partitioned_table
+                .overlapping(range)
+                .into_iter()
+                .map(|partition| partition.file_path.clone())
+                .collect(),
+            schema_path: partitioned_table.partitions[0].file_path.clone(),
+            compression: partitioned_table.compression,
+            table_name,
+        })
+    }
+
+    /// Registers partitions read for a SELECT as a transient table used by `query_id` only.
+    /// It is removed once the query stops reading it.
+    fn register_partitions(
+        &mut self,
+        table_name: &str,
+        partitions: Option<PartitionData>,
+        query_id: &String,
+    ) -> Option<Result<String, MetastoreError>> {
+        let partitioned_table = self.partitioned_tables.get_mut(table_name)?;
+        let Some(partitions) = partitions.filter(|data| data.table_name == table_name) else {
+            // Registered after `partitions_for` ran, the caller read nothing for it.
+            return Some(Err(MetastoreError::QueryCreationError(vec![
+                Error::with_context(
+                    "Partitioned table was created while the query was submitted",
+                    table_name.to_string(),
+                ),
+            ])));
+        };
+        partitioned_table.files_read += partitions.files_read;
+        info!(
+            "Query {} reads {} rows of partitioned table {}",
+            query_id,
+            partitions.table.get_num_rows(),
+            table_name
+        );
+
+        let table_id = Uuid::new_v4().to_string();
+        self.tables.insert(
+            table_id.clone(),
+            TableMetaData {
+                name: table_id.clone(),
+                table: OnceLock::from(partitions.table),
+                table_file: String::new(),
+                compression: partitioned_table.compression,
+                snapshot_of: None,
+                column_defaults: HashMap::new(),
+                key_column: None,
+                transient: true,
+            },
+        );
+        self.scheduled_for_deletion.insert(table_id.clone());
+
+        Some(Ok(table_id))
+    }
+
+    pub fn get_queries(&self, label: Option<&str>, tag: Option<&str>) -> Vec<models::ShallowQuery> {
+        self.queries
+            .iter()
+            .filter(|(_, query)| label.is_none_or(|label| query.label.as_deref() == Some(label)))
+            .filter(|(_, query)| tag.is_none_or(|tag| query.tags.iter().any(|t| t == tag)))
+            .map(|(id, query)| models::ShallowQuery {
+                query_id: id.clone(),
+                status: query.status.clone().into(),
+                label: query.label.clone(),
+                tags: tags_to_api(&query.tags),
+            })
+            .collect()
+    }
+
+    /// Attaches user supplied label and tags to an existing query.
+    pub fn label_query(&mut self, id: &String, label: Option<String>, tags: Vec<String>) {
+        if let Some(query) = self.queries.get_mut(id) {
+            query.label = label;
+            query.tags = tags;
+        }
+    }
+
+    /// Remembers the span ID of the request that submitted the query.
+    pub fn trace_query(&mut self, id: &String, span_id: String) {
+        if let Some(query) = self.queries.get_mut(id) {
+            query.span_id = Some(span_id);
+        }
+    }
+
+    pub fn query_span_id(&self, id: &String) -> Option<String> {
+        self.queries.get(id)?.span_id.clone()
+    }
+
+    /// Creates a new query with the definition, label and tags of a finished one. Tables are
+    /// referenced by ID, so the rerun fails when any of them was deleted in the meantime.
+    pub fn rerun_query(&mut self, id: &String) -> Result<String, MetastoreError> {
+        let query = self
+            .queries
+            .get(id)
+            .ok_or(MetastoreError::QueryNotFound(Error::new(
+                "Couldn't find a query of given ID",
+            )))?;
+        if !matches!(
+            query.status,
+            query::QueryStatus::Completed | query::QueryStatus::Failed
+        ) {
+            return Err(MetastoreError::QueryCreationError(vec![
+                Error::with_context("Query hasn't finished yet", id.clone()),
+            ]));
+        }
+
+        // Tables the query was submitted with, not snapshots it was moved onto.
+        let definition = query
+            .submitted_definition
+            .as_ref()
+            .unwrap_or(&query.definition);
+        let table_ids = definition.table_ids();
+        let errors = table_ids
+            .iter()
+            .filter(|table_id| {
+                !self.tables.contains_key(*table_id)
+                    || self.scheduled_for_deletion.contains(*table_id)
+            })
+            .map(|table_id| {
+                Error::with_context("Table used by the query no longer exists", table_id.clone())
+            })
+            .collect::<Vec<_>>();
+        if !errors.is_empty() {
+            return Err(MetastoreError::QueryCreationError(errors));
+        }
+
+        let mut rerun = query::Query::new(query::QueryStatus::Created, definition.clone());
+        rerun.label = query.label.clone();
+        rerun.tags = query.tags.clone();
+
+        let query_id = Uuid::new_v4().to_string();
+        for table_id in table_ids {
+            self.table_accesses
+                .entry(table_id)
+                .or_default()
+                .insert(query_id.clone());
+        }
+        self.queries.insert(query_id.clone(), rerun);
+
+        Ok(query_id)
+    }
+
+    /// Queue priority of the query, `None` when it doesn't exist.
+    pub fn query_priority(&self, id: &String) -> Option<query::QueryPriority> {
+        Some(self.queries.get(id)?.definition.priority())
+    }
+
+    pub fn get_query(&self, id: &String) -> Result<models::Query, MetastoreError> {
+        let query = self.queries.get(id).map(|query| models::Query {
+            query_id: id.clone(),
+            status: query.status.clone().into(),
+            is_result_available: Some(query.result.is_some()),
+            label: query.label.clone(),
+            tags: tags_to_api(&query.tags),
+            skipped_row_count: match &query.definition {
+                query::QueryDefinition::Copy(copy) if copy.best_effort => {
+                    Some(query.skipped_row_count() as i64)
+                }
+                _ => None,
+            },
+            validated_row_count: query.validated_row_count.map(|count| count as i64),
+            span_id: query.span_id.clone(),
+            query_definition: match &query.definition {
+                query::QueryDefinition::SelectAll(select_all) => {
+                    models::QueryQueryDefinition::from(OneOf5::A(models::SelectAllQuery {
+                        table_name: select_all.table_name.clone(),
+                    }))
+                }
+                query::QueryDefinition::Select(select) => {
+                    models::QueryQueryDefinition::from(OneOf5::B(models::SelectQuery {
+                        column_clauses: select
+                            .column_clauses
+                            .clone()
+                            .into_iter()
+                            .map(Into::into)
+                            .collect(),
+                        where_clause: select.where_clause.clone().map(Into::into),
+                        order_by_clause: if select.order_by_clause.len() == 0 {
+                            None
+                        } else {
+                            Some(
+                                select
+                                    .order_by_clause
+                                    .iter()
+                                    .map(|clause| models::OrderByExpression {
                                         column_index: clause.column_index as i32,
                                         ascending: Some(clause.asscending),
                                     })
@@ -256,14 +1372,43 @@ impl Metastore {
                         limit_clause: Some(models::LimitExpression {
                             limit: select.limit,
                         }),
+                        result_compression: Some(select.result_compression.into()),
+                        result_filepath: select.result_file.clone(),
+                        overwrite_result_file: Some(select.overwrite_result_file),
                     }))
                 }
                 query::QueryDefinition::Copy(copy) => {
-                    models::QueryQueryDefinition::from(OneOf3::C(models::CopyQuery {
+                    models::QueryQueryDefinition::from(OneOf5::C(models::CopyQuery {
                         source_filepath: copy.source_filepath.clone(),
                         destination_table_name: copy.table_name.clone(),
                         destination_columns: copy.destination_columns.clone(),
                         does_csv_contain_header: Some(copy.does_csv_contain_header),
+                        isolation_level: Some(copy.isolation_level.into()),
+                        max_field_size: copy.max_field_size.map(|size| size as i64),
+                        best_effort: Some(copy.best_effort),
+                        key_column: copy.key_column.clone(),
+                        expected_column_count: copy.expected_column_count.map(|c| c as i32),
+                        min_row_count: copy.min_row_count.map(|c| c as i64),
+                        max_row_count: copy.max_row_count.map(|c| c as i64),
+                        null_sentinel: copy.null_sentinel.clone(),
+                        null_int_value: Some(copy.null_int_value),
+                        validate_only: Some(copy.validate_only),
+                        source_format: Some(copy.source_format.into()),
+                        stringify_nested_values: Some(copy.stringify_nested),
+                        upsert: Some(copy.upsert),
+                    }))
+                }
+                query::QueryDefinition::Union(union) => {
+                    models::QueryQueryDefinition::from(OneOf5::D(models::UnionQuery {
+                        table_names: union.table_names.clone(),
+                        all: Some(union.all),
+                    }))
+                }
+                query::QueryDefinition::TableCopy(copy) => {
+                    models::QueryQueryDefinition::from(OneOf5::E(models::TableCopyQuery {
+                        source_table_name: copy.source_table_name.clone(),
+                        destination_table_name: copy.table_name.clone(),
+                        isolation_level: Some(copy.isolation_level.into()),
                     }))
                 }
             },
@@ -271,7 +1416,7 @@ impl Metastore {
 
         match query {
             Some(existing_query) => Ok(existing_query),
-            None => Err(MetastoreError::QueryAccessError(Error::new(
+            None => Err(MetastoreError::QueryNotFound(Error::new(
                 "Couldn't find a query of given ID",
             ))),
         }
@@ -307,9 +1452,12 @@ impl Metastore {
         Ok(query_id)
     }
 
+    /// `partitions` are the ones `read_partitions` read for the query, if it selects from a
+    /// partitioned table.
     pub fn create_select_query(
         &mut self,
         query: &models::SelectQuery,
+        partitions: Option<PartitionData>,
     ) -> Result<String, MetastoreError> {
         let mut errors = Vec::new();
         let mut parsed_column_clauses = Vec::<query::ColumnExpression>::new();
@@ -339,6 +1487,29 @@ impl Metastore {
             }
         }
 
+        if let Some(result_file) = &query.result_filepath {
+            let path = Path::new(result_file);
+            if !path
+                .parent()
+                .is_some_and(|dir| dir.as_os_str().is_empty() || dir.is_dir())
+            {
+                errors.push(Error::with_context(
+                    "Directory of result file does not exist",
+                    result_file.clone(),
+                ));
+            } else if self.is_database_file(path) {
+                errors.push(Error::with_context(
+                    "Result file would replace a database file",
+                    result_file.clone(),
+                ));
+            } else if path.exists() && !query.overwrite_result_file.unwrap_or(false) {
+                errors.push(Error::with_context(
+                    "Result file already exists",
+                    result_file.clone(),
+                ));
+            }
+        }
+
         if !errors.is_empty() {
             return Err(MetastoreError::QueryCreationError(errors));
         }
@@ -359,13 +1530,14 @@ impl Metastore {
 
         let query_id = Uuid::new_v4().to_string();
         let table_id = if let Some(table_name) = unique_tables.into_iter().next() {
-            let tid = self
-                .tables_name_id
-                .get(&table_name)
-                .ok_or(MetastoreError::QueryCreationError(vec![
-                    Error::with_context("There is no table with that name", table_name),
-                ]))?
-                .clone();
+            let tid = match self.tables_name_id.get(&table_name) {
+                Some(tid) => tid.clone(),
+                None => self
+                    .register_partitions(&table_name, partitions, &query_id)
+                    .ok_or(MetastoreError::QueryCreationError(vec![
+                        Error::with_context("There is no table with that name", table_name),
+                    ]))??,
+            };
             self.table_accesses
                 .entry(tid.clone())
                 .or_default()
@@ -385,6 +1557,12 @@ impl Metastore {
                     where_clause: parsed_where_clause,
                     order_by_clause: parsed_order_by_clauses,
                     limit: query.limit_clause.as_ref().and_then(|f| f.limit),
+                    result_compression: query
+                        .result_compression
+                        .map(Into::into)
+                        .unwrap_or_default(),
+                    result_file: query.result_filepath.clone(),
+                    overwrite_result_file: query.overwrite_result_file.unwrap_or(false),
                 }),
             ),
         );
@@ -403,6 +1581,14 @@ impl Metastore {
             ]));
         }
 
+        if let Some(size) = query.max_field_size
+            && size < 1
+        {
+            return Err(MetastoreError::QueryCreationError(vec![
+                Error::with_context("Maximum field size has to be positive", size.to_string()),
+            ]));
+        }
+
         let table_id = self
             .tables_name_id
             .get(&query.destination_table_name)
@@ -428,6 +1614,111 @@ impl Metastore {
                     source_filepath: query.source_filepath.clone(),
                     destination_columns: query.destination_columns.clone(),
                     does_csv_contain_header: query.does_csv_contain_header.unwrap_or(false),
+                    isolation_level: query.isolation_level.map(Into::into).unwrap_or_default(),
+                    max_field_size: query.max_field_size.map(|size| size as usize),
+                    best_effort: query.best_effort.unwrap_or(false),
+                    key_column: query.key_column.clone(),
+                    upsert: query.upsert.unwrap_or(false),
+                    expected_column_count: query.expected_column_count.map(|c| c as usize),
+                    min_row_count: query.min_row_count.map(|c| c as usize),
+                    max_row_count: query.max_row_count.map(|c| c as usize),
+                    null_sentinel: query.null_sentinel.clone(),
+                    null_int_value: query.null_int_value.unwrap_or(0),
+                    validate_only: query.validate_only.unwrap_or(false),
+                    source_format: query.source_format.map(Into::into).unwrap_or_default(),
+                    stringify_nested: query.stringify_nested_values.unwrap_or(false),
+                }),
+            ),
+        );
+
+        Ok(query_id)
+    }
+
+    pub fn create_union_query(
+        &mut self,
+        query: &models::UnionQuery,
+    ) -> Result<String, MetastoreError> {
+        if query.table_names.len() < 2 {
+            return Err(MetastoreError::QueryCreationError(vec![Error::new(
+                "UNION needs at least two tables",
+            )]));
+        }
+
+        let mut errors = Vec::new();
+        let mut table_ids = Vec::new();
+        for table_name in &query.table_names {
+            match self.tables_name_id.get(table_name) {
+                Some(table_id) => table_ids.push(table_id.clone()),
+                None => errors.push(Error::with_context(
+                    "There is no table with that name",
+                    table_name.clone(),
+                )),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(MetastoreError::QueryCreationError(errors));
+        }
+
+        let query_id = Uuid::new_v4().to_string();
+        for table_id in &table_ids {
+            self.table_accesses
+                .entry(table_id.clone())
+                .or_default()
+                .insert(query_id.clone());
+        }
+        self.queries.insert(
+            query_id.clone(),
+            query::Query::new(
+                query::QueryStatus::Created,
+                query::QueryDefinition::Union(query::UnionQuery {
+                    tables: table_ids,
+                    table_names: query.table_names.clone(),
+                    all: query.all.unwrap_or(false),
+                }),
+            ),
+        );
+
+        Ok(query_id)
+    }
+
+    pub fn create_table_copy_query(
+        &mut self,
+        query: &models::TableCopyQuery,
+    ) -> Result<String, MetastoreError> {
+        let mut errors = Vec::new();
+        let mut table_ids = Vec::new();
+        for table_name in [&query.source_table_name, &query.destination_table_name] {
+            match self.tables_name_id.get(table_name) {
+                Some(table_id) => table_ids.push(table_id.clone()),
+                None => errors.push(Error::with_context(
+                    "There is no table with that name",
+                    table_name.clone(),
+                )),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(MetastoreError::QueryCreationError(errors));
+        }
+
+        let query_id = Uuid::new_v4().to_string();
+        for table_id in &table_ids {
+            self.table_accesses
+                .entry(table_id.clone())
+                .or_default()
+                .insert(query_id.clone());
+        }
+        self.queries.insert(
+            query_id.clone(),
+            query::Query::new(
+                query::QueryStatus::Created,
+                query::QueryDefinition::TableCopy(query::TableCopyQuery {
+                    source_table_id: table_ids[0].clone(),
+                    source_table_name: query.source_table_name.clone(),
+                    table_id: table_ids[1].clone(),
+                    table_name: query.destination_table_name.clone(),
+                    isolation_level: query.isolation_level.map(Into::into).unwrap_or_default(),
                 }),
             ),
         );
@@ -435,20 +1726,22 @@ impl Metastore {
         Ok(query_id)
     }
 
+    /// Every result table is returned in parts of at most `batch_rows` rows, in row order.
     pub fn get_query_result(
         &self,
         query_id: &String,
         row_limit: Option<i32>,
+        predicate: Option<&models::ResultPredicate>,
+        batch_rows: Option<NonZeroUsize>,
     ) -> Result<Vec<models::QueryResultInner>, MetastoreError> {
         let result_table_ids = self.get_result_table_ids(query_id)?;
 
         let api_results = result_table_ids
             .iter()
-            .filter_map(|table_id| {
-                self.tables
-                    .get(table_id)
-                    .map(|metadata| self.build_single_table_result(&metadata.table, row_limit))
-            })
+            .map(|table_id| self.build_result_for_table(table_id, row_limit, predicate, batch_rows))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .flatten()
             .collect();
 
         Ok(api_results)
@@ -458,21 +1751,23 @@ impl Metastore {
         &mut self,
         query_id: &String,
         row_limit: Option<i32>,
+        predicate: Option<&models::ResultPredicate>,
+        batch_rows: Option<NonZeroUsize>,
     ) -> Result<Vec<models::QueryResultInner>, MetastoreError> {
         let result_table_ids = self.get_result_table_ids(query_id)?;
 
         let api_results = result_table_ids
             .iter()
-            .filter_map(|table_id| {
-                self.tables
-                    .get(table_id)
-                    .map(|metadata| self.build_single_table_result(&metadata.table, row_limit))
-            })
+            .map(|table_id| self.build_result_for_table(table_id, row_limit, predicate, batch_rows))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .flatten()
             .collect();
 
         for table_id in result_table_ids {
             self.flush_table_reference(&table_id, Some(query_id));
         }
+        self.collect_snapshots();
 
         Ok(api_results)
     }
@@ -481,7 +1776,7 @@ impl Metastore {
         let query = self
             .queries
             .get(query_id)
-            .ok_or(MetastoreError::QueryAccessError(Error::new(
+            .ok_or(MetastoreError::QueryNotFound(Error::new(
                 "Couldn't find a query of given ID",
             )))?;
 
@@ -495,41 +1790,437 @@ impl Metastore {
         Ok(result.iter().map(|res| res.table_id.clone()).collect())
     }
 
-    fn build_single_table_result(
+    fn build_result_for_table(
         &self,
-        table: &lib::Table,
+        table_id: &String,
         row_limit: Option<i32>,
-    ) -> models::QueryResultInner {
-        let total_rows = table.get_num_rows() as i32;
-        let limit = row_limit.unwrap_or(total_rows);
-        let row_count = min(total_rows, limit);
-
-        let columns = table
-            .iter_columns()
-            .map(|column| match &column.data {
-                lib::ColumnData::INT64(raw) => models::QueryResultInnerColumnsInner::from(
-                    OneOf3::A(raw.iter().take(row_count as usize).cloned().collect()),
-                ),
-                lib::ColumnData::STR(raw) => models::QueryResultInnerColumnsInner::from(OneOf3::B(
-                    raw.iter().take(row_count as usize).cloned().collect(),
-                )),
-                lib::ColumnData::BOOL(raw) => models::QueryResultInnerColumnsInner::from(
-                    OneOf3::C(raw.iter().take(row_count as usize).cloned().collect()),
-                ),
-            })
-            .collect::<Vec<_>>();
+        predicate: Option<&models::ResultPredicate>,
+        batch_rows: Option<NonZeroUsize>,
+    ) -> Result<Vec<models::QueryResultInner>, MetastoreError> {
+        let metadata = self
+            .tables
+            .get(table_id)
+            .ok_or(MetastoreError::QueryResultAccessError(Error::new(
+                "Result for this query is no longer available",
+            )))?;
+        self.build_single_table_result(metadata.table()?, row_limit, predicate, batch_rows)
+    }
 
-        if row_count == 0 {
-            models::QueryResultInner {
-                row_count: Some(row_count),
-                columns: None,
+    /// Single column of the result, searched in all result tables of the query.
+    pub fn get_query_result_column(
+        &self,
+        query_id: &String,
+        column_name: &str,
+        row_limit: Option<i32>,
+    ) -> Result<models::QueryResultInner, MetastoreError> {
+        let result_table_ids = self.get_result_table_ids(query_id)?;
+
+        for table_id in &result_table_ids {
+            let metadata =
+                self.tables
+                    .get(table_id)
+                    .ok_or(MetastoreError::QueryResultAccessError(Error::new(
+                        "Result for this query is no longer available",
+                    )))?;
+            let table = metadata.table()?;
+            if let Some(column) = table.column(column_name) {
+                // Without batching the result is always a single part.
+                return Ok(self
+                    .build_result_from_columns(
+                        &[column],
+                        table.get_num_rows(),
+                        row_limit,
+                        None,
+                        None,
+                    )
+                    .remove(0));
+            }
+        }
+
+        Err(MetastoreError::QueryResultAccessError(Error::new(
+            &format!(
+                "Column '{}' doesn't exist in result of this query",
+                column_name
+            ),
+        )))
+    }
+
+    fn build_single_table_result(
+        &self,
+        table: &lib::Table,
+        row_limit: Option<i32>,
+        predicate: Option<&models::ResultPredicate>,
+        batch_rows: Option<NonZeroUsize>,
+    ) -> Result<Vec<models::QueryResultInner>, MetastoreError> {
+        let columns = table.iter_columns().collect::<Vec<_>>();
+        let rows = match predicate {
+            Some(predicate) => Some(self.matching_rows(table, predicate, row_limit)?),
+            None => None,
+        };
+
+        Ok(self.build_result_from_columns(
+            &columns,
+            table.get_num_rows(),
+            row_limit,
+            rows.as_deref(),
+            batch_rows,
+        ))
+    }
+
+    /// Indexes of first `row_limit` rows satisfying `predicate`. Stored table is only read.
+    fn matching_rows(
+        &self,
+        table: &lib::Table,
+        predicate: &models::ResultPredicate,
+        row_limit: Option<i32>,
+    ) -> Result<Vec<usize>, MetastoreError> {
+        let column = table.column(&predicate.column_name).ok_or_else(|| {
+            MetastoreError::QueryResultAccessError(Error::new(&format!(
+                "Column '{}' doesn't exist in result of this query",
+                predicate.column_name
+            )))
+        })?;
+
+        let target = match &*predicate.value {
+            OneOf3::A(value) => lib::RowValue::Int(*value),
+            OneOf3::B(value) => lib::RowValue::Str(value.clone()),
+            OneOf3::C(value) => lib::RowValue::Bool(*value),
+        };
+        if !column.data.accepts(&target) {
+            return Err(MetastoreError::QueryResultAccessError(Error::new(
+                &format!(
+                    "Predicate value has different type than column '{}'",
+                    predicate.column_name
+                ),
+            )));
+        }
+
+        let limit = row_limit.map_or(usize::MAX, |limit| limit.max(0) as usize);
+        Ok((0..column.data.len())
+            .filter(|&row| {
+                let ordering = column.data.value(row).cmp(&target);
+                match predicate.operator {
+                    models::ResultPredicateOperator::Equal => ordering.is_eq(),
+                    models::ResultPredicateOperator::NotEqual => ordering.is_ne(),
+                    models::ResultPredicateOperator::LessThan => ordering.is_lt(),
+                    models::ResultPredicateOperator::LessEqual => ordering.is_le(),
+                    models::ResultPredicateOperator::GreaterThan => ordering.is_gt(),
+                    models::ResultPredicateOperator::GreaterEqual => ordering.is_ge(),
+                }
+            })
+            .take(limit)
+            .collect())
+    }
+
+    /// Builds the response from `rows` when given, otherwise from first `row_limit` rows. Rows
+    /// are split into parts of at most `batch_rows`, so a large result isn't copied into a
+    /// single part. Empty result is one part with no columns.
+    fn build_result_from_columns(
+        &self,
+        columns: &[&lib::Column],
+        num_rows: u64,
+        row_limit: Option<i32>,
+        rows: Option<&[usize]>,
+        batch_rows: Option<NonZeroUsize>,
+    ) -> Vec<models::QueryResultInner> {
+        let total_rows = num_rows as i32;
+        let limit = row_limit.unwrap_or(total_rows);
+        let row_count = match rows {
+            Some(rows) => rows.len(),
+            None => min(total_rows, limit).max(0) as usize,
+        };
+        let batch_rows = batch_rows.map_or(row_count, NonZeroUsize::get).max(1);
+
+        fn pick<T: Clone>(raw: &[T], part: Range<usize>, rows: Option<&[usize]>) -> Vec<T> {
+            match rows {
+                Some(rows) => rows[part].iter().map(|&row| raw[row].clone()).collect(),
+                None => raw
+                    .iter()
+                    .skip(part.start)
+                    .take(part.len())
+                    .cloned()
+                    .collect(),
             }
+        }
+
+        let parts = (0..row_count)
+            .step_by(batch_rows)
+            .map(|start| {
+                let part = start..min(start + batch_rows, row_count);
+                let columns = columns
+                    .iter()
+                    .map(|column| match &column.data {
+                        lib::ColumnData::INT64(raw) => models::QueryResultInnerColumnsInner::from(
+                            OneOf3::A(pick(raw, part.clone(), rows)),
+                        ),
+                        lib::ColumnData::STR(raw) => models::QueryResultInnerColumnsInner::from(
+                            OneOf3::B(pick(raw, part.clone(), rows)),
+                        ),
+                        lib::ColumnData::BOOL(raw) => models::QueryResultInnerColumnsInner::from(
+                            OneOf3::C(pick(raw, part.clone(), rows)),
+                        ),
+                    })
+                    .collect::<Vec<_>>();
+
+                models::QueryResultInner {
+                    row_count: Some(part.len() as i32),
+                    columns: Some(columns),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if parts.is_empty() {
+            vec![models::QueryResultInner {
+                row_count: Some(0),
+                columns: None,
+            }]
         } else {
-            models::QueryResultInner {
-                row_count: Some(row_count),
-                columns: Some(columns),
+            parts
+        }
+    }
+
+    /// Copies tables changed since the last save and the metastore file content. Unloaded
+    /// tables are left out, they were written on unloading.
+    pub fn persist_plan(
+        &self,
+        serializer: &lib::Serializer,
+    ) -> Result<PersistPlan, MetastoreFileError> {
+        let tables = self
+            .tables
+            .iter()
+            .filter(|(table_id, metadata)| {
+                !metadata.transient && !self.persisted_tables.contains(*table_id)
+            })
+            .filter_map(|(table_id, metadata)| {
+                Some(TableSave {
+                    table_id: table_id.clone(),
+                    path: PathBuf::from(&metadata.table_file),
+                    serializer: metadata
+                        .compression
+                        .serializer()
+                        .unwrap_or_else(|| serializer.clone()),
+                    table: metadata.table.get()?.clone(),
+                    changes: self.table_changes.get(table_id).copied().unwrap_or(0),
+                })
+            })
+            .collect();
+
+        let json = serde_json::to_string_pretty(self).map_err(MetastoreFileError::Json)?;
+        let metastore_file = format!(
+            "{}\n{}{:016x}\n",
+            json,
+            METASTORE_CHECKSUM_PREFIX,
+            lib::checksum(&[json.as_bytes()])
+        );
+
+        Ok(PersistPlan {
+            tables,
+            metastore_file,
+        })
+    }
+
+    /// Renames the tables written by `plan` over their files. Tables changed or deleted since
+    /// the plan was made keep their file and stay unsaved. Returns number of table files written.
+    pub fn finish_persist(&mut self, plan: &PersistPlan) -> Result<usize, MetastoreFileError> {
+        let mut written = 0;
+        for save in &plan.tables {
+            let changes = self.table_changes.get(&save.table_id).copied().unwrap_or(0);
+            if !self.tables.contains_key(&save.table_id) || changes != save.changes {
+                if let Err(e) = fs::remove_file(save.staged_path()) {
+                    warn!(
+                        "Failed to delete saved copy {}: {}",
+                        save.staged_path().display(),
+                        e
+                    );
+                }
+                continue;
+            }
+
+            if let Err(e) = fs::rename(save.staged_path(), &save.path) {
+                plan.remove_staged();
+                return Err(MetastoreFileError::IO(e));
+            }
+            self.persisted_tables.insert(save.table_id.clone());
+            written += 1;
+        }
+        Ok(written)
+    }
+
+    /// Plans rewriting the file of `table_id` with the codecs of the table. Skipped (`None`)
+    /// for tables a query reads right now, tables scheduled for deletion and tables whose file
+    /// is stale or missing, as the next save writes those anyway.
+    pub fn compaction_job(
+        &self,
+        table_id: &String,
+        serializer: &lib::Serializer,
+    ) -> Option<CompactionJob> {
+        if !self.can_compact(table_id) {
+            return None;
+        }
+        let metadata = self.tables.get(table_id)?;
+        let path = PathBuf::from(&metadata.table_file);
+        Some(CompactionJob {
+            table_id: table_id.clone(),
+            stamp: LocalFs.stamp(&path).ok()?,
+            serializer: metadata
+                .compression
+                .serializer()
+                .unwrap_or_else(|| serializer.clone()),
+            path,
+        })
+    }
+
+    /// Replaces the table file with the copy written by `job`, unless the table was written,
+    /// read or deleted in the meantime. The copy is removed then and `None` is returned.
+    pub fn finish_compaction(
+        &self,
+        job: &CompactionJob,
+    ) -> Result<Option<lib::CompactionReport>, MetastoreFileError> {
+        let compacted_path = job.compacted_path();
+        if !self.can_compact(&job.table_id) || LocalFs.stamp(&job.path).ok() != Some(job.stamp) {
+            if let Err(e) = fs::remove_file(&compacted_path) {
+                warn!(
+                    "Failed to delete compacted file {}: {}",
+                    compacted_path.display(),
+                    e
+                );
+            }
+            return Ok(None);
+        }
+
+        fs::rename(&compacted_path, &job.path).map_err(MetastoreFileError::IO)?;
+        Ok(Some(lib::CompactionReport {
+            size_before: job.stamp.len,
+            size_after: fs::metadata(&job.path)
+                .map_err(MetastoreFileError::IO)?
+                .len(),
+        }))
+    }
+
+    fn can_compact(&self, table_id: &String) -> bool {
+        self.tables.get(table_id).is_some_and(|metadata| {
+            !self.scheduled_for_deletion.contains(table_id)
+                && self.persisted_tables.contains(table_id)
+                && self
+                    .table_accesses
+                    .get(table_id)
+                    .is_none_or(HashSet::is_empty)
+                && Path::new(&metadata.table_file).exists()
+        })
+    }
+
+    /// Moves queries reading `table_id` onto a copy of its current content, so changes made to
+    /// the table next don't affect their results. Queries writing into the table are not
+    /// readers. Nothing is copied when nobody reads the table.
+    pub fn isolate_readers(&mut self, table_id: &String) -> Result<(), MetastoreError> {
+        let active_readers = self
+            .table_accesses
+            .get(table_id)
+            .into_iter()
+            .flatten()
+            .filter(
+                |id| match self.queries.get(*id).map(|query| &query.definition) {
+                    Some(query::QueryDefinition::Copy(_)) | None => false,
+                    Some(query::QueryDefinition::TableCopy(copy)) => copy.table_id != *table_id,
+                    Some(_) => true,
+                },
+            )
+            .cloned()
+            .collect::<Vec<_>>();
+        if active_readers.is_empty() {
+            return Ok(());
+        }
+
+        info!(
+            "Table {} has {} active readers. Creating snapshot.",
+            table_id,
+            active_readers.len()
+        );
+        let current_metadata = self
+            .tables
+            .get(table_id)
+            .ok_or_else(|| table_not_found(table_id))?;
+        let snapshot_id = Uuid::new_v4().to_string();
+        let snapshot_metadata = current_metadata.duplicate(
+            current_metadata.name.clone(),
+            &snapshot_id,
+            Some(table_id.clone()),
+        )?;
+        self.tables.insert(snapshot_id.clone(), snapshot_metadata);
+
+        for reader_query_id in active_readers {
+            if let Some(query) = self.queries.get_mut(&reader_query_id) {
+                query
+                    .submitted_definition
+                    .get_or_insert_with(|| query.definition.clone());
+                if let Some(results) = &mut query.result {
+                    for res in results {
+                        if res.table_id == *table_id {
+                            res.table_id = snapshot_id.clone();
+                        }
+                    }
+                }
+
+                match &mut query.definition {
+                    query::QueryDefinition::SelectAll(select_all) => {
+                        if select_all.table_id == *table_id {
+                            select_all.table_id = snapshot_id.clone();
+                        }
+                    }
+                    query::QueryDefinition::Select(select) => {
+                        if select.table_id.as_ref() == Some(table_id) {
+                            select.table_id = Some(snapshot_id.clone());
+                        }
+                    }
+                    query::QueryDefinition::Copy(copy) => {
+                        if copy.table_id == *table_id {
+                            copy.table_id = snapshot_id.clone();
+                        }
+                    }
+                    query::QueryDefinition::Union(union) => {
+                        for union_table_id in union.tables.iter_mut() {
+                            if union_table_id == table_id {
+                                *union_table_id = snapshot_id.clone();
+                            }
+                        }
+                    }
+                    query::QueryDefinition::TableCopy(copy) => {
+                        if copy.source_table_id == *table_id {
+                            copy.source_table_id = snapshot_id.clone();
+                        }
+                    }
+                }
             }
+
+            self.table_accesses
+                .entry(snapshot_id.clone())
+                .or_default()
+                .insert(reader_query_id);
         }
+
+        self.table_accesses.remove(table_id);
+        self.scheduled_for_deletion.insert(snapshot_id);
+        Ok(())
+    }
+
+    /// Removes snapshots no query reads anymore, together with their files. Tables they were
+    /// taken of are never touched. Returns number of removed snapshots.
+    pub fn collect_snapshots(&mut self) -> usize {
+        let unreferenced = self
+            .tables
+            .iter()
+            .filter(|(id, metadata)| {
+                metadata.snapshot_of.is_some()
+                    && self.table_accesses.get(*id).is_none_or(HashSet::is_empty)
+            })
+            .map(|(id, _)| id.clone())
+            .collect::<Vec<_>>();
+
+        for table_id in &unreferenced {
+            info!("Removing unreferenced snapshot {}", table_id);
+            self.flush_table_reference(table_id, None);
+        }
+        unreferenced.len()
     }
 
     pub fn flush_table_reference(&mut self, table_id: &String, query_id: Option<&String>) {
@@ -540,17 +2231,136 @@ impl Metastore {
 
         if access_set.is_empty() && self.scheduled_for_deletion.contains(table_id) {
             if let Some(metadata) = self.tables.remove(table_id) {
-                self.tables_name_id.remove(&metadata.name);
-                if let Err(e) = fs::remove_file(&metadata.table_file) {
+                if self.tables_name_id.get(&metadata.name) == Some(table_id) {
+                    self.tables_name_id.remove(&metadata.name);
+                }
+                if !metadata.transient
+                    && let Err(e) = fs::remove_file(&metadata.table_file)
+                {
                     warn!("Failed to delete table file {}: {}", metadata.table_file, e);
                 }
             }
 
             self.table_accesses.remove(table_id);
             self.scheduled_for_deletion.remove(table_id);
+            self.persisted_tables.remove(table_id);
+            self.unloaded_sizes.remove(table_id);
         }
     }
 
+    /// Removes finished queries exceeding the retention policy, oldest first.
+    /// Returns number of removed queries.
+    pub fn apply_retention(&mut self, policy: &RetentionPolicy, now: u64) -> usize {
+        let mut finished = self
+            .queries
+            .iter()
+            .filter_map(|(id, query)| query.finished_at.map(|at| (at, id.clone())))
+            .collect::<Vec<_>>();
+        finished.sort();
+
+        let expired_by_age = policy.max_age_secs.map_or(0, |max_age| {
+            finished
+                .iter()
+                .take_while(|(at, _)| now.saturating_sub(*at) >= max_age)
+                .count()
+        });
+        let expired_by_count = policy
+            .max_count
+            .map_or(0, |max_count| finished.len().saturating_sub(max_count));
+        let expired = max(expired_by_age, expired_by_count);
+
+        for (_, query_id) in finished.iter().take(expired) {
+            self.remove_query(query_id);
+        }
+
+        expired
+    }
+
+    fn remove_query(&mut self, query_id: &String) {
+        if self.queries.remove(query_id).is_none() {
+            return;
+        }
+
+        let accessed_tables = self
+            .table_accesses
+            .iter()
+            .filter(|(_, access_set)| access_set.contains(query_id))
+            .map(|(table_id, _)| table_id.clone())
+            .collect::<Vec<_>>();
+        for table_id in accessed_tables {
+            self.flush_table_reference(&table_id, Some(query_id));
+        }
+    }
+
+    /// Reads every table from its file. Tables whose file is gone are dropped, as a backup
+    /// can list tables deleted after it was written. Any other failure is an error.
+    fn load_table_files(&mut self, serializer: &lib::Serializer) -> Result<(), MetastoreFileError> {
+        let missing = self
+            .tables
+            .iter()
+            .filter(|(_, metadata)| !Path::new(&metadata.table_file).exists())
+            .map(|(table_id, _)| table_id.clone())
+            .collect::<Vec<_>>();
+        for table_id in missing {
+            let metadata = self.tables.remove(&table_id).expect("table listed above");
+            warn!(
+                "Dropped table {} ({}) whose file {} is missing",
+                metadata.name, table_id, metadata.table_file
+            );
+            if self.tables_name_id.get(&metadata.name) == Some(&table_id) {
+                self.tables_name_id.remove(&metadata.name);
+            }
+            self.table_accesses.remove(&table_id);
+            self.scheduled_for_deletion.remove(&table_id);
+        }
+
+        for metadata in self.tables.values_mut() {
+            let path = Path::new(&metadata.table_file);
+            let table = match metadata.compression.serializer() {
+                Some(table_serializer) => table_serializer.deserialize(path),
+                None => serializer.deserialize(path),
+            }
+            .map_err(MetastoreFileError::Table)?;
+            metadata.table = OnceLock::from(table);
+        }
+        self.persisted_tables = self.tables.keys().cloned().collect();
+
+        Ok(())
+    }
+
+    /// Drops references to tables that are not in the metastore anymore, e.g. result tables
+    /// removed before the metastore file was written. Queries left without any result table
+    /// report their result as unavailable instead of returning partial results.
+    fn prune_dangling_references(&mut self) {
+        let tables = &self.tables;
+
+        for (query_id, query) in self.queries.iter_mut() {
+            let Some(results) = query.result.as_mut() else {
+                continue;
+            };
+
+            let before = results.len();
+            results.retain(|result| tables.contains_key(&result.table_id));
+            if results.len() != before {
+                warn!(
+                    "Pruned {} dangling result reference(s) of query {}",
+                    before - results.len(),
+                    query_id
+                );
+            }
+            if results.is_empty() && before > 0 {
+                query.result = None;
+            }
+        }
+
+        self.tables_name_id
+            .retain(|_, table_id| tables.contains_key(table_id));
+        self.table_accesses
+            .retain(|table_id, _| tables.contains_key(table_id));
+        self.scheduled_for_deletion
+            .retain(|table_id| tables.contains_key(table_id));
+    }
+
     pub fn get_query_error(&self, id: &String) -> Result<Vec<query::QueryError>, MetastoreError> {
         let query = self.queries.get(id);
 
@@ -561,7 +2371,7 @@ impl Metastore {
                     "Error for this query is not available",
                 ))),
             },
-            None => Err(MetastoreError::QueryAccessError(Error::new(
+            None => Err(MetastoreError::QueryNotFound(Error::new(
                 "Couldn't find a query of given ID",
             ))),
         }
@@ -571,14 +2381,87 @@ impl Metastore {
         self.queries.get_mut(id)
     }
 
-    pub fn get_table_internal(&self, table_id: &String) -> Option<&lib::Table> {
-        self.tables.get(table_id).map(|metadata| &metadata.table)
+    /// `None` when the table doesn't exist, error when its unloaded data can't be read back.
+    pub fn get_table_internal(
+        &self,
+        table_id: &String,
+    ) -> Result<Option<&lib::Table>, MetastoreError> {
+        self.tables
+            .get(table_id)
+            .map(TableMetaData::table)
+            .transpose()
+    }
+
+    /// Marks the table as changed, so it is written by the next save.
+    pub fn get_table_internal_mut(
+        &mut self,
+        table_id: &String,
+    ) -> Result<Option<&mut lib::Table>, MetastoreError> {
+        let table = self
+            .tables
+            .get_mut(table_id)
+            .map(TableMetaData::table_mut)
+            .transpose()?;
+        if table.is_some() {
+            self.persisted_tables.remove(table_id);
+            *self.table_changes.entry(table_id.clone()).or_default() += 1;
+        }
+        Ok(table)
     }
 
-    pub fn get_table_internal_mut(&mut self, table_id: &String) -> Option<&mut lib::Table> {
-        self.tables
+    /// Frees the in-memory data of the table, keeping its file. Unsaved changes are written
+    /// first and the data is read back on the next access. Tables held by a query are kept.
+    pub fn force_unload_table(
+        &mut self,
+        table_id: &String,
+        serializer: &lib::Serializer,
+    ) -> Result<(), MetastoreError> {
+        if !self.table_exists(table_id) {
+            return Err(table_not_found(table_id));
+        }
+        if self
+            .table_accesses
+            .get(table_id)
+            .is_some_and(|accesses| !accesses.is_empty())
+        {
+            return Err(MetastoreError::TableUnloadError(Error::with_context(
+                "Table is accessed by a query",
+                table_id.clone(),
+            )));
+        }
+
+        let metadata = self
+            .tables
             .get_mut(table_id)
-            .map(|metadata| &mut metadata.table)
+            .expect("table existence checked");
+        let Some(table) = metadata.table.take() else {
+            return Ok(());
+        };
+
+        if !self.persisted_tables.contains(table_id) {
+            let path = Path::new(&metadata.table_file);
+            let written = match metadata.compression.serializer() {
+                Some(table_serializer) => table_serializer.serialize(path, &table),
+                None => serializer.serialize(path, &table),
+            };
+            if let Err(e) = written {
+                metadata.table = OnceLock::from(table);
+                return Err(MetastoreError::TableUnloadError(Error::with_context(
+                    &format!("Failed to write table file: {}", e),
+                    table_id.clone(),
+                )));
+            }
+            self.persisted_tables.insert(table_id.clone());
+        }
+
+        let bytes = table
+            .iter_columns()
+            .map(|column| column.data.byte_size() as u64)
+            .sum();
+        self.unloaded_sizes
+            .insert(table_id.clone(), (table.get_num_rows(), bytes));
+        info!("Table {} unloaded from memory", table_id);
+        Ok(())
     }
 
     pub fn create_query_result_table(
@@ -586,6 +2469,7 @@ impl Metastore {
         query_id: &String,
         columns_data: Vec<lib::ColumnData>,
         num_rows: usize,
+        compression: CompressionProfile,
     ) -> String {
         let table_id = Uuid::new_v4().to_string();
         let columns = columns_data
@@ -593,13 +2477,19 @@ impl Metastore {
             .map(|data| lib::Column {
                 name: Uuid::new_v4().to_string(),
                 data,
+                metadata: None,
             })
             .collect();
         let table = lib::Table::new(num_rows as u64, columns);
         let table_metadata = TableMetaData {
             name: table_id.clone(),
-            table: table,
+            table: OnceLock::from(table),
             table_file: convert_to_table_file_table(&table_id),
+            compression,
+            snapshot_of: None,
+            column_defaults: HashMap::new(),
+            key_column: None,
+            transient: false,
         };
         self.tables.insert(table_id.clone(), table_metadata);
         self.table_accesses
@@ -613,34 +2503,806 @@ impl Metastore {
 
 pub type SharedMetastore = Arc<RwLock<Metastore>>;
 
-pub async fn load_metastore(file_path: &str, serializer: &lib::Serializer) -> SharedMetastore {
-    let mut metastore = if let Ok(data) = fs::read_to_string(file_path) {
-        serde_json::from_str(&data).unwrap_or_default()
-    } else {
-        Metastore::new()
+#[derive(Debug)]
+pub enum MetastoreFileError {
+    /// Content doesn't match the checksum line, file was most likely truncated.
+    ChecksumMismatch {
+        expected: u64,
+        found: u64,
+    },
+    Json(serde_json::Error),
+    IO(std::io::Error),
+    Table(lib::SerializerError),
+}
+
+impl Display for MetastoreFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ChecksumMismatch { expected, found } => write!(
+                f,
+                "checksum mismatch: expected {:016x}, found {:016x}",
+                expected, found
+            ),
+            Self::Json(e) => write!(f, "invalid JSON: {}", e),
+            Self::IO(e) => write!(f, "IO error: {}", e),
+            Self::Table(e) => write!(f, "table file error: {}", e),
+        }
+    }
+}
+
+/// Files written before checksums were introduced have no checksum line and are only parsed.
+fn parse_metastore_file(data: &str) -> Result<Metastore, MetastoreFileError> {
+    let json = match data.trim_end().rsplit_once('\n') {
+        Some((json, last_line)) if last_line.starts_with(METASTORE_CHECKSUM_PREFIX) => {
+            let expected = u64::from_str_radix(&last_line[METASTORE_CHECKSUM_PREFIX.len()..], 16)
+                .unwrap_or_default();
+            let found = lib::checksum(&[json.as_bytes()]);
+            if expected != found {
+                return Err(MetastoreFileError::ChecksumMismatch { expected, found });
+            }
+            json
+        }
+        _ => data,
     };
 
-    for metadata in metastore.tables.values_mut() {
-        let path = Path::new(&metadata.table_file);
-        let table = serializer.deserialize(path).unwrap();
-        metadata.table = table;
+    serde_json::from_str(json).map_err(MetastoreFileError::Json)
+}
+
+fn backup_path(file_path: &str) -> String {
+    format!("{}.bak", file_path)
+}
+
+/// Previous generation written by `save_metastore`, used when the primary file is corrupt.
+fn load_backup(file_path: &str) -> Option<Metastore> {
+    let backup_path = backup_path(file_path);
+    let data = fs::read_to_string(&backup_path).ok()?;
+    match parse_metastore_file(&data) {
+        Ok(metastore) => Some(metastore),
+        Err(e) => {
+            error!(
+                "Metastore backup {} is corrupt as well ({})",
+                backup_path, e
+            );
+            None
+        }
     }
+}
 
-    Arc::new(RwLock::new(metastore))
+/// Only a missing file starts an empty metastore. Unreadable or corrupt file (with no usable
+/// backup) is an error, so it is never silently replaced by an empty metastore on the next save.
+pub async fn load_metastore(
+    file_path: &str,
+    serializer: &lib::Serializer,
+) -> Result<SharedMetastore, MetastoreFileError> {
+    let mut metastore = match fs::read_to_string(file_path) {
+        Ok(data) => match parse_metastore_file(&data) {
+            Ok(metastore) => metastore,
+            Err(e) => {
+                error!("Metastore file {} is corrupt ({})", file_path, e);
+                let backup = load_backup(file_path).ok_or(e)?;
+                warn!("Loaded metastore from backup {}", backup_path(file_path));
+                backup
+            }
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Metastore::new(),
+        Err(e) => return Err(MetastoreFileError::IO(e)),
+    };
+
+    metastore.load_table_files(serializer)?;
+    metastore.prune_dangling_references();
+
+    Ok(Arc::new(RwLock::new(metastore)))
+}
+
+/// Reads the partitions a SELECT from a partitioned table needs. The metastore is locked only
+/// while they are listed, so other requests go on while the files are deserialized.
+pub async fn read_partitions(
+    metastore: &SharedMetastore,
+    query: &models::SelectQuery,
+) -> Result<Option<PartitionData>, MetastoreError> {
+    let Some(read) = metastore.read().await.partitions_for(query) else {
+        return Ok(None);
+    };
+
+    let table_name = read.table_name.clone();
+    let files_read = read.paths.len();
+    let table = tokio::task::spawn_blocking(move || read.read())
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|result| result)
+        .map_err(|e| {
+            MetastoreError::QueryCreationError(vec![Error::with_context(
+                "Failed to read partitioned table",
+                e,
+            )])
+        })?;
+
+    Ok(Some(PartitionData {
+        table_name,
+        table,
+        files_read,
+    }))
 }
 
+/// Reads unloaded tables among `table_ids` back into memory. The metastore isn't locked while
+/// the files are deserialized, so other requests go on meanwhile. Tables of a submitted query
+/// can't be unloaded again until the query finishes, any other table unloaded again before
+/// the caller locks the metastore is read back under the lock.
+pub async fn load_tables(
+    metastore: &SharedMetastore,
+    table_ids: &[String],
+) -> Result<(), MetastoreError> {
+    let unloaded = {
+        let guard = metastore.read().await;
+        table_ids
+            .iter()
+            .filter_map(|table_id| Some((table_id, guard.tables.get(table_id)?)))
+            .filter(|(_, metadata)| metadata.table.get().is_none())
+            .map(|(table_id, metadata)| {
+                (
+                    table_id.clone(),
+                    metadata.table_file.clone(),
+                    metadata.compression,
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    if unloaded.is_empty() {
+        return Ok(());
+    }
+
+    let tables = tokio::task::spawn_blocking(move || {
+        unloaded
+            .into_iter()
+            .map(|(table_id, table_file, compression)| {
+                read_table_file(&table_file, compression).map(|table| (table_id, table))
+            })
+            .collect::<Result<Vec<_>, _>>()
+    })
+    .await
+    .map_err(|e| {
+        MetastoreError::TableLoadError(Error::with_context(
+            "Failed to read table file",
+            e.to_string(),
+        ))
+    })??;
+
+    let guard = metastore.read().await;
+    for (table_id, table) in tables {
+        // Table loaded under the lock in the meantime is kept, it may have changed since.
+        if let Some(metadata) = guard.tables.get(&table_id) {
+            let _ = metadata.table.set(table);
+        }
+    }
+
+    Ok(())
+}
+
+/// `load_tables` for the result tables of `query_id`. A missing query or result is left for
+/// the result request to report.
+pub async fn load_query_results(
+    metastore: &SharedMetastore,
+    query_id: &String,
+) -> Result<(), MetastoreError> {
+    let table_ids = metastore
+        .read()
+        .await
+        .get_result_table_ids(query_id)
+        .unwrap_or_default();
+    load_tables(metastore, &table_ids).await
+}
+
+pub async fn run_retention(metastore: SharedMetastore, policy: RetentionPolicy) {
+    let mut interval = tokio::time::interval(Duration::from_secs(RETENTION_CHECK_INTERVAL_SECS));
+    loop {
+        interval.tick().await;
+
+        let removed = metastore
+            .write()
+            .await
+            .apply_retention(&policy, unix_timestamp());
+        if removed > 0 {
+            info!("Retention removed {} finished queries", removed);
+        }
+    }
+}
+
+/// Writes tables changed since the last save, then the metastore file itself. The metastore
+/// is locked only to copy what has to be saved and to swap in the written table files, so
+/// requests are served while tables are compressed. Saves run one at a time, so those
+/// requested through the API never overlap the one made at shutdown. Returns number of table
+/// files written.
 pub async fn save_metastore(
     metastore: SharedMetastore,
     file_path: &str,
     serializer: &lib::Serializer,
-) {
-    let metastore_guard = metastore.read().await;
+) -> Result<usize, MetastoreFileError> {
+    static SAVES: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+    let _save = SAVES.lock().await;
+
+    let mut plan = metastore.read().await.persist_plan(serializer)?;
+    let plan = tokio::task::spawn_blocking(move || plan.write_tables().map(|()| plan))
+        .await
+        .map_err(|e| MetastoreFileError::IO(std::io::Error::other(e.to_string())))??;
+
+    let written = metastore.write().await.finish_persist(&plan)?;
+    let file_path = file_path.to_string();
+    tokio::task::spawn_blocking(move || plan.write_metastore_file(&file_path))
+        .await
+        .map_err(|e| MetastoreFileError::IO(std::io::Error::other(e.to_string())))??;
+
+    Ok(written)
+}
+
+/// Compacts table files one at a time. The lock is taken only to plan the rewrite of a table
+/// and to swap its file, the file is rewritten without it, so requests are served meanwhile.
+/// Returns total number of bytes reclaimed.
+pub async fn compact_all(
+    metastore: SharedMetastore,
+    serializer: &lib::Serializer,
+) -> Result<u64, MetastoreFileError> {
+    let table_ids = metastore
+        .read()
+        .await
+        .tables
+        .keys()
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let mut reclaimed = 0;
+    for table_id in table_ids {
+        let Some(job) = metastore.read().await.compaction_job(&table_id, serializer) else {
+            continue;
+        };
+        let job = tokio::task::spawn_blocking(move || job.write().map(|()| job))
+            .await
+            .map_err(|e| MetastoreFileError::IO(std::io::Error::other(e.to_string())))?
+            .map_err(MetastoreFileError::Table)?;
+
+        if let Some(report) = metastore.write().await.finish_compaction(&job)? {
+            info!(
+                "Table {} compacted from {} to {} bytes",
+                table_id, report.size_before, report.size_after
+            );
+            reclaimed += report.size_before.saturating_sub(report.size_after);
+        }
+    }
+    Ok(reclaimed)
+}
 
-    for metadata in metastore_guard.tables.values() {
-        let path = Path::new(&metadata.table_file);
-        serializer.serialize(path, &metadata.table).unwrap();
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn partitions_read_for_select_are_never_saved() {
+        let directory = env::temp_dir().join(format!("isdb_{}_partitions", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let mut partitions = vec![];
+        for day in [19723, 19724] {
+            let path = directory.join(format!("{}.isdb", day));
+            let table = lib::Table::new(
+                2,
+                vec![lib::Column::new_int_col("day".to_string(), vec![day; 2])],
+            );
+            lib::Serializer::new().serialize(&path, &table).unwrap();
+            partitions.push(models::PartitionFile::new(
+                day.to_string(),
+                path.display().to_string(),
+            ));
+        }
+
+        let metastore: SharedMetastore = Arc::new(RwLock::new(Metastore::new()));
+        metastore
+            .write()
+            .await
+            .create_partitioned_table(models::PartitionedTableSchema::new(
+                "days".to_string(),
+                "day".to_string(),
+                partitions,
+            ))
+            .unwrap();
+        let select = models::SelectQuery::new(vec![
+            query::ColumnExpression::Ref(query::ColumnReferenceExpression {
+                table_name: "days".to_string(),
+                column_name: "day".to_string(),
+            })
+            .into(),
+        ]);
+
+        let data = read_partitions(&metastore, &select).await.unwrap();
+        let mut guard = metastore.write().await;
+        let query_id = guard.create_select_query(&select, data).unwrap();
+        let query::QueryDefinition::Select(definition) = &guard.queries[&query_id].definition
+        else {
+            panic!("select query expected");
+        };
+        let metadata = &guard.tables[definition.table_id.as_ref().unwrap()];
+        assert!(metadata.transient);
+        assert_eq!(metadata.table().unwrap().get_num_rows(), 4);
+        assert_eq!(guard.partitioned_tables["days"].files_read, 2);
+        drop(guard);
+
+        let metastore_file = directory.join("metastore.json");
+        let written = save_metastore(
+            metastore,
+            metastore_file.to_str().unwrap(),
+            &lib::Serializer::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(written, 0);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn select_from_partitioned_table_needs_its_partitions() {
+        let mut metastore = Metastore::new();
+        metastore.partitioned_tables.insert(
+            "days".to_string(),
+            PartitionedTable {
+                partition_column: "day".to_string(),
+                ..Default::default()
+            },
+        );
+        let select = models::SelectQuery::new(vec![
+            query::ColumnExpression::Ref(query::ColumnReferenceExpression {
+                table_name: "days".to_string(),
+                column_name: "day".to_string(),
+            })
+            .into(),
+        ]);
+
+        assert!(matches!(
+            metastore.create_select_query(&select, None),
+            Err(MetastoreError::QueryCreationError(_))
+        ));
+        assert!(metastore.tables.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unloaded_tables_are_loaded_before_planning() {
+        let path = env::temp_dir().join(format!("isdb_{}_unloaded.isdb", std::process::id()));
+        let table = lib::Table::new(
+            2,
+            vec![lib::Column::new_int_col("id".to_string(), vec![1, 2])],
+        );
+        lib::Serializer::new().serialize(&path, &table).unwrap();
+        let metastore: SharedMetastore = Arc::new(RwLock::new(Metastore::new()));
+        let table_id = "unloaded".to_string();
+        metastore.write().await.tables.insert(
+            table_id.clone(),
+            TableMetaData {
+                name: "ids".to_string(),
+                table_file: path.display().to_string(),
+                ..Default::default()
+            },
+        );
+
+        load_tables(&metastore, std::slice::from_ref(&table_id))
+            .await
+            .unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let guard = metastore.read().await;
+        assert_eq!(guard.get_table_internal(&table_id).unwrap(), Some(&table));
+    }
+
+    #[tokio::test]
+    async fn unloaded_result_tables_are_loaded_before_reading_results() {
+        let path =
+            env::temp_dir().join(format!("isdb_{}_unloaded_result.isdb", std::process::id()));
+        let mut metastore = Metastore::new();
+        table_with_ids(&mut metastore);
+        let select_all = models::SelectAllQuery::new("ids".to_string());
+        let query_id = metastore.create_select_all_query(&select_all).unwrap();
+        let result_id = metastore.create_query_result_table(
+            &query_id,
+            vec![lib::ColumnData::INT64(vec![7])],
+            1,
+            CompressionProfile::Default,
+        );
+        metastore.queries.get_mut(&query_id).unwrap().result = Some(vec![query::QueryResult {
+            table_id: result_id.clone(),
+        }]);
+        let metadata = metastore.tables.get_mut(&result_id).unwrap();
+        let table = metadata.table.take().unwrap();
+        lib::Serializer::new().serialize(&path, &table).unwrap();
+        metadata.table_file = path.display().to_string();
+        let metastore: SharedMetastore = Arc::new(RwLock::new(metastore));
+
+        load_query_results(&metastore, &query_id).await.unwrap();
+        fs::remove_file(&path).unwrap();
+        // Unknown queries are reported by the result request itself.
+        load_query_results(&metastore, &"unknown".to_string())
+            .await
+            .unwrap();
+
+        let guard = metastore.read().await;
+        assert_eq!(guard.get_table_internal(&result_id).unwrap(), Some(&table));
     }
 
-    let json = serde_json::to_string_pretty(&*metastore_guard).unwrap();
-    fs::write(file_path, json).expect("Failed to write metastore file");
+    #[test]
+    fn missing_table_file_is_an_error() {
+        let mut metastore = Metastore::new();
+        let table_id = "missing".to_string();
+        metastore.tables.insert(
+            table_id.clone(),
+            TableMetaData {
+                name: "gone".to_string(),
+                table_file: env::temp_dir()
+                    .join(format!("isdb_{}_missing.isdb", std::process::id()))
+                    .display()
+                    .to_string(),
+                ..Default::default()
+            },
+        );
+
+        assert!(matches!(
+            metastore.get_table(&table_id),
+            Err(MetastoreError::TableLoadError(_))
+        ));
+        assert!(matches!(
+            metastore.get_table_internal_mut(&table_id),
+            Err(MetastoreError::TableLoadError(_))
+        ));
+        assert!(metastore.tables[&table_id].table.get().is_none());
+    }
+
+    #[test]
+    fn database_files_are_never_result_files() {
+        let partition = env::temp_dir().join(format!("isdb_{}_partition.isdb", std::process::id()));
+        let mut metastore = Metastore::new();
+        metastore.partitioned_tables.insert(
+            "days".to_string(),
+            PartitionedTable {
+                partition_column: "day".to_string(),
+                partitions: vec![Partition {
+                    key: "19723".to_string(),
+                    file_path: partition.display().to_string(),
+                    range: None,
+                }],
+                ..Default::default()
+            },
+        );
+
+        assert!(metastore.is_database_file(Path::new(METASTORE_FILE)));
+        assert!(metastore.is_database_file(&Path::new(".").join(METASTORE_FILE)));
+        assert!(metastore.is_database_file(Path::new("metastore.json.tmp")));
+        assert!(metastore.is_database_file(&partition));
+        assert!(!metastore.is_database_file(&partition.with_extension("out.isdb")));
+    }
+
+    fn persisted_table(name: &str, table: &lib::Table) -> (Metastore, String, PathBuf) {
+        let path = env::temp_dir().join(format!("isdb_{}_{}.isdb", std::process::id(), name));
+        lib::Serializer::new().serialize(&path, table).unwrap();
+        let mut metastore = Metastore::new();
+        let table_id = name.to_string();
+        metastore.tables.insert(
+            table_id.clone(),
+            TableMetaData {
+                name: name.to_string(),
+                table_file: path.display().to_string(),
+                ..Default::default()
+            },
+        );
+        metastore.persisted_tables.insert(table_id.clone());
+        (metastore, table_id, path)
+    }
+
+    #[tokio::test]
+    async fn tables_are_compacted_without_holding_the_lock() {
+        let table = lib::Table::new(
+            2,
+            vec![lib::Column::new_int_col("id".to_string(), vec![1, 2])],
+        );
+        let (metastore, _, path) = persisted_table("compacted", &table);
+        let metastore: SharedMetastore = Arc::new(RwLock::new(metastore));
+
+        compact_all(metastore.clone(), &lib::Serializer::new())
+            .await
+            .unwrap();
+        assert_eq!(lib::Serializer::new().deserialize(&path).unwrap(), table);
+        assert!(!path.with_extension("compact").exists());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn compaction_keeps_files_changed_while_it_ran() {
+        let table = lib::Table::new(
+            2,
+            vec![lib::Column::new_int_col("id".to_string(), vec![1, 2])],
+        );
+        let (mut metastore, table_id, path) = persisted_table("changed", &table);
+        let serializer = lib::Serializer::new();
+
+        // Table was modified and not saved yet.
+        let job = metastore.compaction_job(&table_id, &serializer).unwrap();
+        job.write().unwrap();
+        metastore.persisted_tables.remove(&table_id);
+        assert!(metastore.finish_compaction(&job).unwrap().is_none());
+        assert!(!job.compacted_path().exists());
+
+        // Table file was rewritten by a save.
+        metastore.persisted_tables.insert(table_id.clone());
+        let job = metastore.compaction_job(&table_id, &serializer).unwrap();
+        job.write().unwrap();
+        let saved = lib::Table::new(
+            3,
+            vec![lib::Column::new_int_col("id".to_string(), vec![1, 2, 3])],
+        );
+        serializer.serialize(&path, &saved).unwrap();
+        assert!(metastore.finish_compaction(&job).unwrap().is_none());
+        assert_eq!(serializer.deserialize(&path).unwrap(), saved);
+        fs::remove_file(&path).unwrap();
+    }
+
+    fn table_with_ids(metastore: &mut Metastore) -> String {
+        let table_id = metastore
+            .create_table(models::TableSchema::new(
+                "ids".to_string(),
+                vec![
+                    models::Column::new("id".to_string(), models::LogicalColumnType::Int64),
+                    models::Column::new("name".to_string(), models::LogicalColumnType::Varchar),
+                ],
+            ))
+            .unwrap();
+        metastore
+            .get_table_internal_mut(&table_id)
+            .unwrap()
+            .unwrap()
+            .append_table(lib::Table::new(
+                1,
+                vec![
+                    lib::Column::new_int_col("id".to_string(), vec![7]),
+                    lib::Column::new_str_col("name".to_string(), vec!["a".to_string()]),
+                ],
+            ))
+            .unwrap();
+        table_id
+    }
+
+    #[test]
+    fn failing_rename_leaves_column_type_unchanged() {
+        let mut metastore = Metastore::new();
+        let table_id = table_with_ids(&mut metastore);
+        let mut change = models::ColumnChange::new();
+        change.new_type = Some(models::LogicalColumnType::Varchar);
+        change.new_name = Some("name".to_string());
+
+        assert!(matches!(
+            metastore.update_column(&table_id, "id", &change),
+            Err(MetastoreError::ColumnChangeError(_))
+        ));
+        let table = metastore.get_table_internal(&table_id).unwrap().unwrap();
+        assert_eq!(
+            table.column("id").unwrap().data.logical_type(),
+            lib::LogicalColumnType::Int64
+        );
+    }
+
+    #[test]
+    fn column_change_keeps_readers_on_the_old_column() {
+        let mut metastore = Metastore::new();
+        let table_id = table_with_ids(&mut metastore);
+        let query_id = metastore
+            .create_select_all_query(&models::SelectAllQuery::new("ids".to_string()))
+            .unwrap();
+        let mut change = models::ColumnChange::new();
+        change.new_type = Some(models::LogicalColumnType::Varchar);
+        change.new_name = Some("code".to_string());
+
+        metastore.update_column(&table_id, "id", &change).unwrap();
+
+        let query::QueryDefinition::SelectAll(select_all) =
+            &metastore.queries[&query_id].definition
+        else {
+            panic!("select all query expected");
+        };
+        assert_ne!(select_all.table_id, table_id);
+        let snapshot = metastore
+            .get_table_internal(&select_all.table_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            snapshot.column("id").unwrap().data,
+            lib::ColumnData::INT64(vec![7])
+        );
+        let table = metastore.get_table_internal(&table_id).unwrap().unwrap();
+        assert_eq!(
+            table.column("code").unwrap().data,
+            lib::ColumnData::STR(vec!["7".to_string()])
+        );
+    }
+
+    #[test]
+    fn column_names_a_file_cant_hold_are_rejected() {
+        let long_name = "c".repeat(lib::MAX_COLUMN_NAME_LEN + 1);
+        let mut metastore = Metastore::new();
+        assert!(matches!(
+            metastore.create_table(models::TableSchema::new("empty".to_string(), vec![])),
+            Err(MetastoreError::TableCreationError(_))
+        ));
+        assert!(matches!(
+            metastore.create_table(models::TableSchema::new(
+                "long".to_string(),
+                vec![models::Column::new(
+                    long_name.clone(),
+                    models::LogicalColumnType::Int64,
+                )],
+            )),
+            Err(MetastoreError::TableCreationError(_))
+        ));
+
+        let table_id = table_with_ids(&mut metastore);
+        assert!(matches!(
+            metastore.rename_column(&table_id, "id", &long_name),
+            Err(MetastoreError::ColumnChangeError(_))
+        ));
+        assert!(matches!(
+            metastore.add_column(
+                &table_id,
+                &models::Column::new(long_name.clone(), models::LogicalColumnType::Int64),
+            ),
+            Err(MetastoreError::ColumnChangeError(_))
+        ));
+        let table = metastore.get_table_internal(&table_id).unwrap().unwrap();
+        assert_eq!(table.get_num_cols(), 2);
+        assert!(table.column("id").is_some());
+    }
+
+    #[test]
+    fn rerun_reads_the_live_table_not_a_snapshot() {
+        let mut metastore = Metastore::new();
+        let table_id = table_with_ids(&mut metastore);
+        let query_id = metastore
+            .create_select_all_query(&models::SelectAllQuery::new("ids".to_string()))
+            .unwrap();
+        metastore.isolate_readers(&table_id).unwrap();
+        let snapshot_id = metastore.queries[&query_id].definition.table_ids()[0].clone();
+        assert_ne!(snapshot_id, table_id);
+
+        // Query finished and its snapshot was removed.
+        metastore.queries.get_mut(&query_id).unwrap().status = query::QueryStatus::Completed;
+        metastore.flush_table_reference(&snapshot_id, Some(&query_id));
+        assert!(!metastore.tables.contains_key(&snapshot_id));
+
+        let rerun_id = metastore.rerun_query(&query_id).unwrap();
+        assert_eq!(
+            metastore.queries[&rerun_id].definition.table_ids(),
+            [table_id.clone()]
+        );
+        assert!(metastore.table_accesses[&table_id].contains(&rerun_id));
+    }
+
+    #[test]
+    fn retention_removes_old_queries_with_their_result_and_snapshot_tables() {
+        let mut metastore = Metastore::new();
+        let table_id = table_with_ids(&mut metastore);
+        let select_all = models::SelectAllQuery::new("ids".to_string());
+        let old_id = metastore.create_select_all_query(&select_all).unwrap();
+        metastore.isolate_readers(&table_id).unwrap();
+        let snapshot_id = metastore.queries[&old_id].definition.table_ids()[0].clone();
+        let result_id = metastore.create_query_result_table(
+            &old_id,
+            vec![lib::ColumnData::INT64(vec![7])],
+            1,
+            CompressionProfile::Default,
+        );
+        metastore.scheduled_for_deletion.insert(result_id.clone());
+        let new_id = metastore.create_select_all_query(&select_all).unwrap();
+        let running_id = metastore.create_select_all_query(&select_all).unwrap();
+        for (query_id, finished_at) in [(&old_id, 100), (&new_id, 190)] {
+            let query = metastore.queries.get_mut(query_id).unwrap();
+            query.status = query::QueryStatus::Completed;
+            query.finished_at = Some(finished_at);
+        }
+
+        let by_age = RetentionPolicy {
+            max_age_secs: Some(60),
+            max_count: None,
+        };
+        assert_eq!(metastore.apply_retention(&by_age, 200), 1);
+
+        assert!(!metastore.queries.contains_key(&old_id));
+        assert!(!metastore.tables.contains_key(&snapshot_id));
+        assert!(!metastore.tables.contains_key(&result_id));
+        assert!(metastore.scheduled_for_deletion.is_empty());
+        assert!(metastore.tables.contains_key(&table_id));
+
+        let by_count = RetentionPolicy {
+            max_age_secs: None,
+            max_count: Some(0),
+        };
+        assert_eq!(metastore.apply_retention(&by_count, 200), 1);
+
+        assert!(!metastore.queries.contains_key(&new_id));
+        assert!(metastore.queries.contains_key(&running_id));
+        assert_eq!(
+            metastore.table_accesses[&table_id],
+            HashSet::from([running_id])
+        );
+    }
+
+    #[test]
+    fn tables_whose_file_is_gone_are_dropped_on_load() {
+        let table = lib::Table::new(1, vec![lib::Column::new_int_col("id".to_string(), vec![1])]);
+        let (mut metastore, kept_id, path) = persisted_table("kept_on_load", &table);
+        metastore.tables.insert(
+            "gone".to_string(),
+            TableMetaData {
+                name: "gone".to_string(),
+                table_file: path.with_extension("gone").display().to_string(),
+                ..Default::default()
+            },
+        );
+        metastore
+            .tables_name_id
+            .insert("gone".to_string(), "gone".to_string());
+        metastore
+            .table_accesses
+            .insert("gone".to_string(), HashSet::from(["query".to_string()]));
+
+        metastore.load_table_files(&lib::Serializer::new()).unwrap();
+
+        assert!(!metastore.tables.contains_key("gone"));
+        assert!(!metastore.tables_name_id.contains_key("gone"));
+        assert!(!metastore.table_accesses.contains_key("gone"));
+        assert_eq!(metastore.persisted_tables, HashSet::from([kept_id.clone()]));
+        assert_eq!(metastore.tables[&kept_id].table.get(), Some(&table));
+
+        fs::write(&path, b"not a table").unwrap();
+        assert!(matches!(
+            metastore.load_table_files(&lib::Serializer::new()),
+            Err(MetastoreFileError::Table(_))
+        ));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn tables_changed_while_saving_stay_unsaved() {
+        let directory = env::temp_dir().join(format!("isdb_{}_saving", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let mut metastore = Metastore::new();
+        let table_id = table_with_ids(&mut metastore);
+        let path = directory.join("ids.isdb");
+        metastore.tables.get_mut(&table_id).unwrap().table_file = path.display().to_string();
+        let metastore_file = directory.join("metastore.json");
+        let metastore_file = metastore_file.to_str().unwrap();
+        let serializer = lib::Serializer::new();
+
+        // Row added after the table was copied for the save.
+        let mut plan = metastore.persist_plan(&serializer).unwrap();
+        plan.write_tables().unwrap();
+        metastore
+            .get_table_internal_mut(&table_id)
+            .unwrap()
+            .unwrap()
+            .append_table(lib::Table::new(
+                1,
+                vec![
+                    lib::Column::new_int_col("id".to_string(), vec![8]),
+                    lib::Column::new_str_col("name".to_string(), vec!["b".to_string()]),
+                ],
+            ))
+            .unwrap();
+        assert_eq!(metastore.finish_persist(&plan).unwrap(), 0);
+        plan.write_metastore_file(metastore_file).unwrap();
+        assert!(!path.exists());
+        assert!(!plan.tables[0].staged_path().exists());
+        assert!(!metastore.persisted_tables.contains(&table_id));
+
+        let metastore: SharedMetastore = Arc::new(RwLock::new(metastore));
+        assert_eq!(
+            save_metastore(metastore.clone(), metastore_file, &serializer)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(serializer.deserialize(&path).unwrap().get_num_rows(), 2);
+        assert!(metastore.read().await.persisted_tables.contains(&table_id));
+        fs::remove_dir_all(&directory).unwrap();
+    }
 }
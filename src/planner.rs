@@ -1,12 +1,15 @@
 use std::{
     collections::{HashMap, HashSet},
+    fs::File,
+    io::BufReader,
     mem::swap,
 };
 
-use log::error;
+use csv::ReaderBuilder;
+use log::{error, info};
 use serde::{Deserialize, Serialize};
 
-use crate::{metastore, query};
+use crate::{consts::MAX_CSV_FIELD_SIZE, metastore, query, utils::unix_timestamp};
 
 #[derive(Clone, Serialize, Deserialize, Hash, PartialEq, Eq)]
 pub enum FlatExpression {
@@ -29,20 +32,48 @@ pub struct SelectPlan {
     pub filter_expression: Option<usize>,
     pub sorts: Vec<query::OrderByExpression>,
     pub limit: Option<usize>,
+    pub result_compression: metastore::CompressionProfile,
+    pub result_file: Option<String>,
+    pub overwrite_result_file: bool,
+    /// Names of result columns in the result file.
+    pub result_names: Vec<String>,
 }
 
 pub struct CopyFromCsvPlan {
     pub table_id: String,
-    pub table_name: String,
     pub file_path: String,
     pub mapping: Option<Vec<String>>,
     pub has_headers: bool,
+    pub isolation_level: query::IsolationLevel,
+    pub max_field_size: usize,
+    pub best_effort: bool,
+    pub key_column: Option<String>,
+    pub min_row_count: Option<usize>,
+    pub max_row_count: Option<usize>,
+    pub null_sentinel: Option<String>,
+    pub null_int_value: i64,
+    pub validate_only: bool,
+    pub source_format: query::SourceFormat,
+    pub stringify_nested: bool,
+}
+
+pub struct UnionPlan {
+    pub tables: Vec<String>,
+    pub all: bool,
+}
+
+pub struct CopyFromTablePlan {
+    pub source_table_id: String,
+    pub table_id: String,
+    pub isolation_level: query::IsolationLevel,
 }
 
 pub enum PhysicalPlan {
     SelectAll(SelectAllPlan),
     Select(SelectPlan),
     CopyFromCsv(CopyFromCsvPlan),
+    Union(UnionPlan),
+    CopyFromTable(CopyFromTablePlan),
 }
 
 #[derive(Clone)]
@@ -61,6 +92,11 @@ impl Planner {
         let (query_def, status_update_result) = {
             let mut guard = metastore.write().await;
             match guard.get_query_internal_mut(query_id) {
+                // Same id delivered twice (e.g. a retry) must not run the query again.
+                Some(query) if !query.status.is_pending() => {
+                    info!("Query {} was already processed, skipping", query_id);
+                    return None;
+                }
                 Some(query) => {
                     query.status = query::QueryStatus::Planning;
                     (query.definition.clone(), Ok(()))
@@ -76,6 +112,11 @@ impl Planner {
             self.fail_query(query_id, e, metastore).await;
             return None;
         }
+        // Unloaded tables are read back here, not under the lock taken for planning.
+        if let Err(e) = metastore::load_tables(metastore, &query_def.table_ids()).await {
+            self.fail_query(query_id, e.to_string(), metastore).await;
+            return None;
+        }
 
         let result = match query_def {
             query::QueryDefinition::SelectAll(select_all) => {
@@ -83,6 +124,8 @@ impl Planner {
             }
             query::QueryDefinition::Select(select) => self.select(select, metastore).await,
             query::QueryDefinition::Copy(copy) => self.copy_from_csv(copy, metastore).await,
+            query::QueryDefinition::Union(union) => self.union(union, metastore).await,
+            query::QueryDefinition::TableCopy(copy) => self.copy_from_table(copy, metastore).await,
         };
 
         match result {
@@ -119,6 +162,7 @@ impl Planner {
             let metastore_guard = metastore.read().await;
             let table = metastore_guard
                 .get_table_internal(table_id)
+                .map_err(|e| e.to_string())?
                 .ok_or("Table was deleted before planning query".to_string())?;
 
             let mut indexes = HashMap::new();
@@ -170,6 +214,20 @@ impl Planner {
             self.flatten_expression(&expr, &mut flat_expressions, &mut seen_expression)
         });
 
+        let mut result_names = Vec::<String>::with_capacity(select.column_clauses.len());
+        for (i, expr) in select.column_clauses.iter().enumerate() {
+            let name = match expr {
+                query::ColumnExpression::Ref(column) => column.column_name.clone(),
+                _ => format!("col{}", i + 1),
+            };
+            // Positional name for repeated columns, ISDB files need unique names.
+            if result_names.contains(&name) {
+                result_names.push(format!("col{}", i + 1));
+            } else {
+                result_names.push(name);
+            }
+        }
+
         for clause in &select.order_by_clause {
             if clause.column_index >= select.column_clauses.len() {
                 return Err(format!(
@@ -187,6 +245,10 @@ impl Planner {
             filter_expression: filter_expression,
             sorts: select.order_by_clause,
             limit: select.limit.map(|limit| limit as usize),
+            result_compression: select.result_compression,
+            result_file: select.result_file,
+            overwrite_result_file: select.overwrite_result_file,
+            result_names,
         }))
     }
 
@@ -245,29 +307,150 @@ impl Planner {
         copy: query::CopyQuery,
         metastore: &metastore::SharedMetastore,
     ) -> Result<PhysicalPlan, String> {
-        {
+        let key_column = {
             let metastore_guard = metastore.read().await;
             let table = metastore_guard
                 .get_table_internal(&copy.table_id)
+                .map_err(|e| e.to_string())?
                 .ok_or("Table was deleted before planning query".to_string())?;
+            let key_column = match &copy.key_column {
+                Some(key_column) => Some(key_column.clone()),
+                None if copy.upsert => Some(
+                    metastore_guard.tables[&copy.table_id]
+                        .key_column
+                        .clone()
+                        .ok_or("Table has no key column to upsert by, set keyColumn".to_string())?,
+                ),
+                None => None,
+            };
             if let Some(m) = copy.destination_columns.as_ref()
+                && copy.source_format == query::SourceFormat::Csv
                 && table.get_num_cols() != m.len()
             {
                 return Err(
                     "Mapping have different number of rows then destination table".to_string(),
                 );
             }
+            if let Some(key_column) = &key_column
+                && !table.iter_columns().any(|col| col.name == *key_column)
+            {
+                return Err(format!(
+                    "Key column '{}' does not exist in table",
+                    key_column
+                ));
+            }
+            key_column
+        };
+
+        if let Some(expected) = copy.expected_column_count
+            && copy.source_format == query::SourceFormat::Csv
+        {
+            let csv_width = self.csv_width(&copy.source_filepath)?;
+            if csv_width != expected {
+                return Err(format!(
+                    "Expected {} columns in CSV, but file has {}",
+                    expected, csv_width
+                ));
+            }
         }
 
         Ok(PhysicalPlan::CopyFromCsv(CopyFromCsvPlan {
             table_id: copy.table_id,
-            table_name: copy.table_name,
             file_path: copy.source_filepath,
             mapping: copy.destination_columns,
             has_headers: copy.does_csv_contain_header,
+            isolation_level: copy.isolation_level,
+            max_field_size: copy.max_field_size.unwrap_or(MAX_CSV_FIELD_SIZE),
+            best_effort: copy.best_effort,
+            key_column,
+            min_row_count: copy.min_row_count,
+            max_row_count: copy.max_row_count,
+            null_sentinel: copy.null_sentinel,
+            null_int_value: copy.null_int_value,
+            validate_only: copy.validate_only,
+            source_format: copy.source_format,
+            stringify_nested: copy.stringify_nested,
+        }))
+    }
+
+    async fn union(
+        &self,
+        union: query::UnionQuery,
+        metastore: &metastore::SharedMetastore,
+    ) -> Result<PhysicalPlan, String> {
+        {
+            let metastore_guard = metastore.read().await;
+            let mut expected: Option<lib::Schema> = None;
+            for (table_id, table_name) in union.tables.iter().zip(&union.table_names) {
+                let schema = metastore_guard
+                    .get_table_internal(table_id)
+                    .map_err(|e| e.to_string())?
+                    .ok_or("Table was deleted before planning query".to_string())?
+                    .schema();
+                match &expected {
+                    Some(expected) if *expected != schema => {
+                        return Err(format!(
+                            "Table '{}' has schema {}, expected {}",
+                            table_name, schema, expected
+                        ));
+                    }
+                    Some(_) => {}
+                    None => expected = Some(schema),
+                }
+            }
+        }
+
+        Ok(PhysicalPlan::Union(UnionPlan {
+            tables: union.tables,
+            all: union.all,
+        }))
+    }
+
+    async fn copy_from_table(
+        &self,
+        copy: query::TableCopyQuery,
+        metastore: &metastore::SharedMetastore,
+    ) -> Result<PhysicalPlan, String> {
+        {
+            let metastore_guard = metastore.read().await;
+            let get_schema = |table_id: &String| {
+                metastore_guard
+                    .get_table_internal(table_id)
+                    .map_err(|e| e.to_string())?
+                    .map(|table| table.schema())
+                    .ok_or("Table was deleted before planning query".to_string())
+            };
+            let source_schema = get_schema(&copy.source_table_id)?;
+            let schema = get_schema(&copy.table_id)?;
+            if source_schema != schema {
+                return Err(format!(
+                    "Table '{}' has schema {}, but table '{}' has {}",
+                    copy.source_table_name, source_schema, copy.table_name, schema
+                ));
+            }
+        }
+
+        Ok(PhysicalPlan::CopyFromTable(CopyFromTablePlan {
+            source_table_id: copy.source_table_id,
+            table_id: copy.table_id,
+            isolation_level: copy.isolation_level,
         }))
     }
 
+    /// Number of fields in the first row of the file (header row included).
+    fn csv_width(&self, file_path: &str) -> Result<usize, String> {
+        let file = File::open(file_path)
+            .map_err(|e| format!("Failed to open file '{}': {}", file_path, e))?;
+        let mut file = BufReader::new(file);
+        lib::skip_utf8_bom(&mut file)
+            .map_err(|e| format!("Failed to read file '{}': {}", file_path, e))?;
+        let mut rdr = ReaderBuilder::new().has_headers(false).from_reader(file);
+        match rdr.records().next() {
+            Some(record) => Ok(record.map_err(|e| format!("CSV Parse Error: {}", e))?.len()),
+            None => Ok(0),
+        }
+    }
+
     async fn fail_query(
         &self,
         query_id: &String,
@@ -277,16 +460,13 @@ impl Planner {
         let mut metastore_guard = metastore.write().await;
         if let Some(q) = metastore_guard.get_query_internal_mut(query_id) {
             q.status = query::QueryStatus::Failed;
+            q.finished_at = Some(unix_timestamp());
             q.errors = Some(vec![query::QueryError {
                 message: error_msg.clone(),
                 context: None,
+                kind: query::QueryErrorKind::Error,
             }]);
-            let maybe_table_id = match &q.definition {
-                query::QueryDefinition::SelectAll(select_all) => Some(select_all.table_id.clone()),
-                query::QueryDefinition::Select(select) => select.table_id.clone(),
-                query::QueryDefinition::Copy(copy) => Some(copy.table_id.clone()),
-            };
-            if let Some(id) = maybe_table_id {
+            for id in q.definition.table_ids() {
                 if let Some(access_set) = metastore_guard.table_accesses.get_mut(&id) {
                     access_set.remove(query_id);
                 }
@@ -1,5 +1,5 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     fmt::Display,
 };
 
@@ -9,7 +9,12 @@ use serde::{Deserialize, Serialize};
 use swagger::{OneOf3, OneOf5};
 use tokio::sync::mpsc;
 
-use crate::{executor::Executor, metastore::SharedMetastore, planner::Planner};
+use crate::{
+    consts::MAX_INTERACTIVE_STREAK,
+    executor::Executor,
+    metastore::{CompressionProfile, SharedMetastore},
+    planner::Planner,
+};
 
 #[derive(Clone, Serialize, Deserialize, Hash, PartialEq, Eq)]
 pub struct ColumnReferenceExpression {
@@ -17,7 +22,7 @@ pub struct ColumnReferenceExpression {
     pub column_name: String,
 }
 
-#[derive(Clone, Serialize, Deserialize, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq, Eq)]
 pub enum Literal {
     I64(i64),
     String(String),
@@ -44,6 +49,36 @@ impl From<Literal> for models::LiteralValue {
     }
 }
 
+impl From<models::ColumnDefaultValue> for Literal {
+    fn from(value: models::ColumnDefaultValue) -> Self {
+        match value.into() {
+            OneOf3::A(val) => Self::I64(val),
+            OneOf3::B(val) => Self::String(val),
+            OneOf3::C(val) => Self::Bool(val),
+        }
+    }
+}
+
+impl From<Literal> for models::ColumnDefaultValue {
+    fn from(value: Literal) -> Self {
+        match value {
+            Literal::I64(val) => Self::from(OneOf3::A(val)),
+            Literal::String(val) => Self::from(OneOf3::B(val)),
+            Literal::Bool(val) => Self::from(OneOf3::C(val)),
+        }
+    }
+}
+
+impl From<Literal> for lib::RowValue {
+    fn from(value: Literal) -> Self {
+        match value {
+            Literal::I64(val) => Self::Int(val),
+            Literal::String(val) => Self::Str(val),
+            Literal::Bool(val) => Self::Bool(val),
+        }
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Hash, PartialEq, Eq)]
 pub enum FunctionName {
     Strlen,
@@ -388,6 +423,53 @@ impl ColumnExpression {
             }
         }
     }
+
+    /// Inclusive bounds this filter puts on INT64 column `column_name`, `None` where open.
+    /// Only comparisons with literals joined by AND narrow the range, anything else (OR, NOT,
+    /// functions) leaves it open, so rows outside the range never pass the filter.
+    pub fn column_range(&self, column_name: &str) -> (Option<i64>, Option<i64>) {
+        let ColumnExpression::Binary(binary) = self else {
+            return (None, None);
+        };
+
+        if binary.operator == BinOperator::And {
+            let (left_min, left_max) = binary.left_operand.column_range(column_name);
+            let (right_min, right_max) = binary.right_operand.column_range(column_name);
+            let max = match (left_max, right_max) {
+                (Some(left), Some(right)) => Some(left.min(right)),
+                (left, right) => left.or(right),
+            };
+            return (left_min.max(right_min), max);
+        }
+
+        let is_column = |expr: &ColumnExpression| matches!(expr, ColumnExpression::Ref(reference) if reference.column_name == column_name);
+        // Literal on the left is turned around, so `5 < col` reads as `col > 5`.
+        let (operator, value) = match (&*binary.left_operand, &*binary.right_operand) {
+            (column, ColumnExpression::Literal(Literal::I64(value))) if is_column(column) => {
+                (binary.operator.clone(), *value)
+            }
+            (ColumnExpression::Literal(Literal::I64(value)), column) if is_column(column) => {
+                let operator = match binary.operator {
+                    BinOperator::LessThan => BinOperator::GreaterThan,
+                    BinOperator::LessEqual => BinOperator::GreaterEqual,
+                    BinOperator::GreaterThan => BinOperator::LessThan,
+                    BinOperator::GreaterEqual => BinOperator::LessEqual,
+                    ref other => other.clone(),
+                };
+                (operator, *value)
+            }
+            _ => return (None, None),
+        };
+
+        match operator {
+            BinOperator::Equal => (Some(value), Some(value)),
+            BinOperator::LessThan => (None, Some(value.saturating_sub(1))),
+            BinOperator::LessEqual => (None, Some(value)),
+            BinOperator::GreaterThan => (Some(value.saturating_add(1)), None),
+            BinOperator::GreaterEqual => (Some(value), None),
+            _ => (None, None),
+        }
+    }
 }
 
 impl TryFrom<models::ColumnExpression> for ColumnExpression {
@@ -494,6 +576,13 @@ pub struct SelectQuery {
     pub where_clause: Option<ColumnExpression>,
     pub order_by_clause: Vec<OrderByExpression>,
     pub limit: Option<i32>,
+    #[serde(default)]
+    pub result_compression: CompressionProfile,
+    /// ISDB file the result is written to, next to the result table.
+    #[serde(default)]
+    pub result_file: Option<String>,
+    #[serde(default)]
+    pub overwrite_result_file: bool,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -503,9 +592,112 @@ pub struct CopyQuery {
     pub source_filepath: String,
     pub destination_columns: Option<Vec<String>>,
     pub does_csv_contain_header: bool,
+    #[serde(default)]
+    pub isolation_level: IsolationLevel,
+    #[serde(default)]
+    pub max_field_size: Option<usize>,
+    #[serde(default)]
+    pub best_effort: bool,
+    #[serde(default)]
+    pub key_column: Option<String>,
+    /// Upsert by the key column of the table when `key_column` isn't given.
+    #[serde(default)]
+    pub upsert: bool,
+    #[serde(default)]
+    pub expected_column_count: Option<usize>,
+    #[serde(default)]
+    pub min_row_count: Option<usize>,
+    #[serde(default)]
+    pub max_row_count: Option<usize>,
+    #[serde(default)]
+    pub null_sentinel: Option<String>,
+    #[serde(default)]
+    pub null_int_value: i64,
+    #[serde(default)]
+    pub validate_only: bool,
+    #[serde(default)]
+    pub source_format: SourceFormat,
+    #[serde(default)]
+    pub stringify_nested: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize, Default)]
+pub struct UnionQuery {
+    pub tables: Vec<String>,
+    pub table_names: Vec<String>,
+    pub all: bool,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
+pub struct TableCopyQuery {
+    pub source_table_id: String,
+    pub source_table_name: String,
+    pub table_id: String,
+    pub table_name: String,
+    #[serde(default)]
+    pub isolation_level: IsolationLevel,
+}
+
+/// Controls what queries reading the destination table of a COPY see once it finishes.
+///
+/// There is no cheaper "no isolation" level: COPY parses everything into shadow columns and
+/// appends them under a single metastore write lock, so readers never observe a partial append.
+#[derive(Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq, Debug)]
+pub enum IsolationLevel {
+    /// Active readers are moved to a copy of the table taken before the append, so their results
+    /// never change. Costs a full clone of the table whenever there is at least one reader.
+    #[default]
+    Snapshot,
+    /// Readers keep pointing at the live table and see appended rows as soon as COPY completes.
+    /// No clone is made, but results of already completed queries can grow.
+    ReadCommitted,
+}
+
+impl From<models::IsolationLevel> for IsolationLevel {
+    fn from(value: models::IsolationLevel) -> Self {
+        match value {
+            models::IsolationLevel::Snapshot => Self::Snapshot,
+            models::IsolationLevel::ReadCommitted => Self::ReadCommitted,
+        }
+    }
+}
+
+impl From<IsolationLevel> for models::IsolationLevel {
+    fn from(value: IsolationLevel) -> Self {
+        match value {
+            IsolationLevel::Snapshot => Self::Snapshot,
+            IsolationLevel::ReadCommitted => Self::ReadCommitted,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq, Debug)]
+pub enum SourceFormat {
+    #[default]
+    Csv,
+    /// One flat JSON object per line, read by `lib::Table::from_ndjson_reader`.
+    Ndjson,
+}
+
+impl From<models::SourceFormat> for SourceFormat {
+    fn from(value: models::SourceFormat) -> Self {
+        match value {
+            models::SourceFormat::Csv => Self::Csv,
+            models::SourceFormat::Ndjson => Self::Ndjson,
+        }
+    }
+}
+
+impl From<SourceFormat> for models::SourceFormat {
+    fn from(value: SourceFormat) -> Self {
+        match value {
+            SourceFormat::Csv => Self::Csv,
+            SourceFormat::Ndjson => Self::Ndjson,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum QueryStatus {
     Created,
     Planning,
@@ -514,6 +706,13 @@ pub enum QueryStatus {
     Failed,
 }
 
+impl QueryStatus {
+    /// Whether the engine may still pick the query up, anything later means it already ran.
+    pub fn is_pending(&self) -> bool {
+        matches!(self, Self::Created | Self::Planning)
+    }
+}
+
 impl From<QueryStatus> for models::QueryStatus {
     fn from(value: QueryStatus) -> Self {
         match value {
@@ -531,12 +730,48 @@ pub enum QueryDefinition {
     SelectAll(SelectAllQuery),
     Select(SelectQuery),
     Copy(CopyQuery),
+    Union(UnionQuery),
+    TableCopy(TableCopyQuery),
+}
+
+impl QueryDefinition {
+    /// Queue the query waits in, loads of data go behind everything else.
+    pub fn priority(&self) -> QueryPriority {
+        match self {
+            QueryDefinition::Copy(_) | QueryDefinition::TableCopy(_) => QueryPriority::Bulk,
+            _ => QueryPriority::Interactive,
+        }
+    }
+
+    /// IDs of tables the query reads or writes.
+    pub fn table_ids(&self) -> Vec<String> {
+        match self {
+            QueryDefinition::SelectAll(select_all) => vec![select_all.table_id.clone()],
+            QueryDefinition::Select(select) => select.table_id.iter().cloned().collect(),
+            QueryDefinition::Copy(copy) => vec![copy.table_id.clone()],
+            QueryDefinition::Union(union) => union.tables.clone(),
+            QueryDefinition::TableCopy(copy) => {
+                vec![copy.source_table_id.clone(), copy.table_id.clone()]
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub enum QueryErrorKind {
+    /// Query failed because of this error.
+    #[default]
+    Error,
+    /// Row was dropped by best effort COPY, query itself still completed.
+    Skipped,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct QueryError {
     pub message: String,
     pub context: Option<String>,
+    #[serde(default)]
+    pub kind: QueryErrorKind,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -550,6 +785,23 @@ pub struct Query {
     pub(crate) definition: QueryDefinition,
     pub(crate) result: Option<Vec<QueryResult>>,
     pub(crate) errors: Option<Vec<QueryError>>,
+    /// Unix timestamp (seconds) of the moment query reached COMPLETED or FAILED state.
+    #[serde(default)]
+    pub(crate) finished_at: Option<u64>,
+    /// Number of rows that passed validation of a COPY run in validate-only mode.
+    #[serde(default)]
+    pub(crate) validated_row_count: Option<usize>,
+    #[serde(default)]
+    pub(crate) label: Option<String>,
+    #[serde(default)]
+    pub(crate) tags: Vec<String>,
+    /// Span ID of the API request that submitted the query, for correlating engine logs.
+    #[serde(default)]
+    pub(crate) span_id: Option<String>,
+    /// Definition as submitted, set once readers of a table are moved onto its snapshot, so
+    /// the query is rerun against the live table.
+    #[serde(default)]
+    pub(crate) submitted_definition: Option<QueryDefinition>,
 }
 
 impl Query {
@@ -559,8 +811,22 @@ impl Query {
             definition,
             result: None,
             errors: None,
+            finished_at: None,
+            validated_row_count: None,
+            label: None,
+            tags: vec![],
+            span_id: None,
+            submitted_definition: None,
         }
     }
+
+    pub fn skipped_row_count(&self) -> usize {
+        self.errors
+            .iter()
+            .flatten()
+            .filter(|error| error.kind == QueryErrorKind::Skipped)
+            .count()
+    }
 }
 
 pub struct QueryEngine {
@@ -578,11 +844,17 @@ impl QueryEngine {
         }
     }
 
-    pub async fn run(self, mut receiver: mpsc::Receiver<String>) {
+    pub async fn run(self, receiver: mpsc::Receiver<(String, QueryPriority)>) {
         info!("Query Engine started and waiting for jobs...");
 
-        while let Some(query_id) = receiver.recv().await {
-            info!("Engine received query: {}", query_id);
+        let mut queue = QueryQueue::new(receiver);
+        while let Some(query_id) = queue.next().await {
+            let span_id = self.metastore.read().await.query_span_id(&query_id);
+            info!(
+                "Engine received query: {} | SpanID: {}",
+                query_id,
+                span_id.as_deref().unwrap_or("-")
+            );
             self.process_query(&query_id).await;
         }
 
@@ -598,3 +870,143 @@ impl QueryEngine {
         }
     }
 }
+
+/// Which queue a submitted query waits in. Interactive queries go ahead of bulk loads.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QueryPriority {
+    Interactive,
+    Bulk,
+}
+
+/// Orders submitted queries by priority, keeping submission order within each class. After
+/// `MAX_INTERACTIVE_STREAK` interactive queries in a row a waiting bulk query is let through,
+/// so a steady stream of SELECTs doesn't hold a COPY back forever. Only interactive queries
+/// run while a bulk query waits count towards the streak.
+struct QueryQueue {
+    receiver: mpsc::Receiver<(String, QueryPriority)>,
+    interactive: VecDeque<String>,
+    bulk: VecDeque<String>,
+    interactive_streak: usize,
+}
+
+impl QueryQueue {
+    fn new(receiver: mpsc::Receiver<(String, QueryPriority)>) -> Self {
+        Self {
+            receiver,
+            interactive: VecDeque::new(),
+            bulk: VecDeque::new(),
+            interactive_streak: 0,
+        }
+    }
+
+    /// Next query to run, `None` once the channel is closed and nothing is left waiting.
+    async fn next(&mut self) -> Option<String> {
+        while let Ok((query_id, priority)) = self.receiver.try_recv() {
+            self.push(query_id, priority);
+        }
+        if self.interactive.is_empty() && self.bulk.is_empty() {
+            let (query_id, priority) = self.receiver.recv().await?;
+            self.push(query_id, priority);
+        }
+
+        if self.bulk.is_empty() {
+            self.interactive_streak = 0;
+            return self.interactive.pop_front();
+        }
+        if self.interactive_streak >= MAX_INTERACTIVE_STREAK || self.interactive.is_empty() {
+            self.interactive_streak = 0;
+            return self.bulk.pop_front();
+        }
+        self.interactive_streak += 1;
+        self.interactive.pop_front()
+    }
+
+    fn push(&mut self, query_id: String, priority: QueryPriority) {
+        match priority {
+            QueryPriority::Interactive => self.interactive.push_back(query_id),
+            QueryPriority::Bulk => self.bulk.push_back(query_id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn drain(queue: &mut QueryQueue) -> Vec<String> {
+        let mut order = vec![];
+        while let Some(query_id) = queue.next().await {
+            order.push(query_id);
+        }
+        order
+    }
+
+    fn interactive(count: usize, prefix: &str) -> Vec<(String, QueryPriority)> {
+        (0..count)
+            .map(|i| (format!("{}{}", prefix, i), QueryPriority::Interactive))
+            .collect()
+    }
+
+    async fn queue_of(queries: Vec<(String, QueryPriority)>) -> QueryQueue {
+        let (sender, receiver) = mpsc::channel(100);
+        for query in queries {
+            sender.send(query).await.unwrap();
+        }
+        QueryQueue::new(receiver)
+    }
+
+    #[tokio::test]
+    async fn interactive_queries_overtake_waiting_bulk_queries() {
+        let mut queue = queue_of(vec![
+            ("copy".to_string(), QueryPriority::Bulk),
+            ("select".to_string(), QueryPriority::Interactive),
+            ("union".to_string(), QueryPriority::Interactive),
+            ("table_copy".to_string(), QueryPriority::Bulk),
+        ])
+        .await;
+
+        assert_eq!(
+            drain(&mut queue).await,
+            ["select", "union", "copy", "table_copy"]
+        );
+    }
+
+    #[tokio::test]
+    async fn bulk_query_runs_after_a_full_interactive_streak() {
+        let mut queries = vec![("copy".to_string(), QueryPriority::Bulk)];
+        queries.extend(interactive(MAX_INTERACTIVE_STREAK + 2, "select"));
+        let mut queue = queue_of(queries).await;
+
+        let order = drain(&mut queue).await;
+        assert_eq!(order[MAX_INTERACTIVE_STREAK], "copy");
+        assert_eq!(order.len(), MAX_INTERACTIVE_STREAK + 3);
+        assert!(
+            order[..MAX_INTERACTIVE_STREAK]
+                .iter()
+                .all(|id| id.starts_with("select"))
+        );
+    }
+
+    #[tokio::test]
+    async fn streak_counts_only_while_bulk_queries_wait() {
+        let (sender, receiver) = mpsc::channel(100);
+        let mut queue = QueryQueue::new(receiver);
+        for query in interactive(MAX_INTERACTIVE_STREAK, "early") {
+            sender.send(query).await.unwrap();
+        }
+        for _ in 0..MAX_INTERACTIVE_STREAK {
+            queue.next().await.unwrap();
+        }
+
+        sender
+            .send(("copy".to_string(), QueryPriority::Bulk))
+            .await
+            .unwrap();
+        for query in interactive(2, "select") {
+            sender.send(query).await.unwrap();
+        }
+        drop(sender);
+
+        assert_eq!(drain(&mut queue).await, ["select0", "select1", "copy"]);
+    }
+}
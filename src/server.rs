@@ -1,37 +1,81 @@
 use async_trait::async_trait;
+use bytes::Bytes;
+use http_body_util::{BodyExt, Either, Full, LengthLimitError, Limited};
 use tokio::sync::mpsc;
 use tokio::time::Instant;
 
-use crate::consts::{AUTHOR, INTERFACE_VERSION, SERVER_VERSION};
+use crate::consts::{AUTHOR, INTERFACE_VERSION, METASTORE_FILE, SERVER_VERSION};
 use crate::metastore::{self, Metastore, MetastoreError, SharedMetastore};
-use crate::query::QueryEngine;
+use crate::query::{QueryEngine, QueryPriority};
+use hyper::body::Incoming;
+use hyper::header::{CONTENT_LENGTH, CONTENT_TYPE, HeaderValue};
 use hyper::server::conn::http1;
 use hyper::service::Service;
+use hyper::{Request, Response, StatusCode};
 use hyper_util::rt::TokioIo;
-use log::{info, warn};
+use log::{error, info, warn};
 use openapi_client::models::{
     ExecuteQueryRequest, MultipleProblemsError, MultipleProblemsErrorProblemsInner, QueryResult,
     SystemInformation, TableSchema,
 };
 use openapi_client::server::MakeService;
 use openapi_client::{
-    Api, CreateTableResponse, DeleteTableResponse, GetQueriesResponse, GetQueryByIdResponse,
-    GetQueryErrorResponse, GetQueryResultResponse, GetSystemInfoResponse, GetTableByIdResponse,
-    GetTablesResponse, SubmitQueryResponse, models,
+    AddColumnResponse, Api, CompactTablesResponse, CreatePartitionedTableResponse,
+    CreateTableResponse, DeleteTableResponse, DuplicateTableResponse, GetQueriesResponse,
+    GetQueryByIdResponse, GetQueryErrorResponse, GetQueryResultColumnResponse,
+    GetQueryResultResponse, GetSystemInfoResponse, GetSystemStatsResponse,
+    GetTableAccessesResponse, GetTableByIdResponse, GetTablesResponse, PersistMetastoreResponse,
+    QueryExistsResponse, RerunQueryResponse, SubmitQueryResponse, TableExistsResponse,
+    UnloadTableResponse, UpdateColumnResponse, models,
 };
+use std::future::Future;
 use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::pin::Pin;
 use std::sync::Arc;
 use swagger::auth::MakeAllowAllAuthenticator;
-use swagger::{ApiError, EmptyContext, Has, OneOf3, XSpanIdString};
+use swagger::{ApiError, EmptyContext, Has, OneOf5, XSpanIdString};
 use tokio::net::TcpListener;
 use tokio::sync::RwLock;
 
 #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "ios")))]
 use openssl::ssl::{Ssl, SslAcceptor, SslFiletype, SslMethod};
 
-pub async fn create(addr: &str, https: bool, metastore: SharedMetastore) {
-    let addr: SocketAddr = addr.parse().expect("Failed to parse bind address");
-    let listener = TcpListener::bind(&addr).await.unwrap();
+/// PEM files used by the HTTPS listener.
+#[derive(Clone)]
+pub struct TlsConfig {
+    pub cert_path: String,
+    pub key_path: String,
+}
+
+/// HTTPS is never served on these platforms, so there is no acceptor to build.
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "ios"))]
+type SslAcceptor = std::convert::Infallible;
+
+/// Serves plain HTTP on `http_addr` and HTTPS on `https_addr`, whichever are given. Both
+/// listeners share one `Server`, so they see the same metastore and query engine.
+pub async fn create(
+    http_addr: Option<String>,
+    https_addr: Option<String>,
+    tls: TlsConfig,
+    max_body_size: u64,
+    result_batch_rows: Option<NonZeroUsize>,
+    metastore: SharedMetastore,
+) -> Result<(), String> {
+    // Certificate problems are reported before anything starts listening.
+    let tls_acceptor = match &https_addr {
+        Some(_) => Some(build_tls_acceptor(&tls)?),
+        None => None,
+    };
+
+    let http_listener = match http_addr {
+        Some(addr) => Some(bind(&addr).await?),
+        None => None,
+    };
+    let https_listener = match https_addr {
+        Some(addr) => Some(bind(&addr).await?),
+        None => None,
+    };
 
     let (sender, receiver) = mpsc::channel(100);
 
@@ -41,8 +85,63 @@ pub async fn create(addr: &str, https: bool, metastore: SharedMetastore) {
         engine.run(receiver).await;
     });
 
-    let server = Server::new(metastore, sender);
+    let server = Server::new(metastore, sender, result_batch_rows);
+
+    tokio::join!(
+        async {
+            if let Some(listener) = http_listener {
+                serve_http(listener, server.clone(), max_body_size).await;
+            }
+        },
+        async {
+            if let (Some(listener), Some(tls_acceptor)) = (https_listener, tls_acceptor) {
+                serve_https(listener, server.clone(), tls_acceptor, max_body_size).await;
+            }
+        },
+    );
+
+    Ok(())
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "ios"))]
+fn build_tls_acceptor(_: &TlsConfig) -> Result<SslAcceptor, String> {
+    Err("SSL is not implemented for MacOS, Windows or iOS".to_string())
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "ios")))]
+fn build_tls_acceptor(tls: &TlsConfig) -> Result<SslAcceptor, String> {
+    for path in [&tls.cert_path, &tls.key_path] {
+        std::fs::metadata(path).map_err(|e| format!("Can't read TLS file {}: {}", path, e))?;
+    }
+
+    let mut ssl = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls())
+        .map_err(|e| format!("Failed to create SSL Acceptor: {}", e))?;
+
+    // Server authentication
+    ssl.set_private_key_file(&tls.key_path, SslFiletype::PEM)
+        .map_err(|e| format!("Failed to load TLS key {}: {}", tls.key_path, e))?;
+    ssl.set_certificate_chain_file(&tls.cert_path)
+        .map_err(|e| format!("Failed to load TLS certificate {}: {}", tls.cert_path, e))?;
+    ssl.check_private_key().map_err(|_| {
+        format!(
+            "TLS key {} doesn't match certificate {}",
+            tls.key_path, tls.cert_path
+        )
+    })?;
 
+    Ok(ssl.build())
+}
+
+async fn bind(addr: &str) -> Result<TcpListener, String> {
+    let socket_addr: SocketAddr = addr
+        .parse()
+        .map_err(|e| format!("Invalid bind address {}: {}", addr, e))?;
+    TcpListener::bind(&socket_addr)
+        .await
+        .map_err(|e| format!("Failed to bind {}: {}", addr, e))
+}
+
+async fn serve_http(listener: TcpListener, server: Server, max_body_size: u64) {
     let service = MakeService::new(server);
     let service = MakeAllowAllAuthenticator::new(service, "cosmo");
 
@@ -50,80 +149,186 @@ pub async fn create(addr: &str, https: bool, metastore: SharedMetastore) {
     let mut service =
         openapi_client::server::context::MakeAddContext::<_, EmptyContext>::new(service);
 
-    if https {
-        #[cfg(any(target_os = "macos", target_os = "windows", target_os = "ios"))]
-        {
-            unimplemented!("SSL is not implemented for the examples on MacOS, Windows or iOS");
-        }
+    info!("Starting a server (over http, so no TLS)");
+    println!(
+        "Listening on http://{}",
+        listener.local_addr().expect("Failed to get bound address")
+    );
 
-        #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "ios")))]
-        {
-            let mut ssl = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls())
-                .expect("Failed to create SSL Acceptor");
-
-            // Server authentication
-            ssl.set_private_key_file("examples/server-key.pem", SslFiletype::PEM)
-                .expect("Failed to set private key");
-            ssl.set_certificate_chain_file("examples/server-chain.pem")
-                .expect("Failed to set certificate chain");
-            ssl.check_private_key()
-                .expect("Failed to check private key");
-
-            let tls_acceptor = ssl.build();
-
-            info!("Starting a server (with https)");
-            loop {
-                if let Ok((tcp, addr)) = listener.accept().await {
-                    let ssl = Ssl::new(tls_acceptor.context()).unwrap();
-                    let service = service.call(addr);
-
-                    tokio::spawn(async move {
-                        let tls = tokio_openssl::SslStream::new(ssl, tcp).map_err(|_| ())?;
-                        let service = service.await.map_err(|_| ())?;
-
-                        http1::Builder::new()
-                            .serve_connection(TokioIo::new(tls), service)
-                            .await
-                            .map_err(|_| ())
-                    });
-                }
+    loop {
+        // When an incoming TCP connection is received grab a TCP stream for
+        // client<->server communication.
+        //
+        // Note, this is a .await point, this loop will loop forever but is not a busy loop. The
+        // .await point allows the Tokio runtime to pull the task off of the thread until the task
+        // has work to do. In this case, a connection arrives on the port we are listening on and
+        // the task is woken up, at which point the task is then put back on a thread, and is
+        // driven forward by the runtime, eventually yielding a TCP stream.
+        let (tcp_stream, addr) = listener
+            .accept()
+            .await
+            .expect("Failed to accept connection");
+
+        let service = BodyLimit::new(service.call(addr).await.unwrap(), max_body_size);
+        let io = TokioIo::new(tcp_stream);
+        // Spin up a new task in Tokio so we can continue to listen for new TCP connection on the
+        // current task without waiting for the processing of the HTTP1 connection we just received
+        // to finish
+        tokio::task::spawn(async move {
+            // Handle the connection from the client using HTTP1 and pass any
+            // HTTP requests received on that connection to the `hello` function
+            let result = http1::Builder::new().serve_connection(io, service).await;
+            if let Err(err) = result {
+                println!("Error serving connection: {err:?}");
             }
-        }
-    } else {
-        info!("Starting a server (over http, so no TLS)");
-        println!("Listening on http://{}", addr);
+        });
+    }
+}
+
+#[allow(unused_variables)]
+async fn serve_https(
+    listener: TcpListener,
+    server: Server,
+    tls_acceptor: SslAcceptor,
+    max_body_size: u64,
+) {
+    #[cfg(any(target_os = "macos", target_os = "windows", target_os = "ios"))]
+    {
+        match tls_acceptor {}
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "ios")))]
+    {
+        let service = MakeService::new(server);
+        let service = MakeAllowAllAuthenticator::new(service, "cosmo");
 
+        #[allow(unused_mut)]
+        let mut service =
+            openapi_client::server::context::MakeAddContext::<_, EmptyContext>::new(service);
+
+        info!("Starting a server (with https)");
+        println!(
+            "Listening on https://{}",
+            listener.local_addr().expect("Failed to get bound address")
+        );
         loop {
-            // When an incoming TCP connection is received grab a TCP stream for
-            // client<->server communication.
-            //
-            // Note, this is a .await point, this loop will loop forever but is not a busy loop. The
-            // .await point allows the Tokio runtime to pull the task off of the thread until the task
-            // has work to do. In this case, a connection arrives on the port we are listening on and
-            // the task is woken up, at which point the task is then put back on a thread, and is
-            // driven forward by the runtime, eventually yielding a TCP stream.
-            let (tcp_stream, addr) = listener
-                .accept()
-                .await
-                .expect("Failed to accept connection");
-
-            let service = service.call(addr).await.unwrap();
-            let io = TokioIo::new(tcp_stream);
-            // Spin up a new task in Tokio so we can continue to listen for new TCP connection on the
-            // current task without waiting for the processing of the HTTP1 connection we just received
-            // to finish
-            tokio::task::spawn(async move {
-                // Handle the connection from the client using HTTP1 and pass any
-                // HTTP requests received on that connection to the `hello` function
-                let result = http1::Builder::new().serve_connection(io, service).await;
-                if let Err(err) = result {
-                    println!("Error serving connection: {err:?}");
-                }
-            });
+            if let Ok((tcp, addr)) = listener.accept().await {
+                let ssl = Ssl::new(tls_acceptor.context()).unwrap();
+                let service = service.call(addr);
+
+                tokio::spawn(async move {
+                    let mut tls = tokio_openssl::SslStream::new(ssl, tcp).map_err(|_| ())?;
+                    std::pin::Pin::new(&mut tls)
+                        .accept()
+                        .await
+                        .map_err(|_| ())?;
+                    let service = BodyLimit::new(service.await.map_err(|_| ())?, max_body_size);
+
+                    http1::Builder::new()
+                        .serve_connection(TokioIo::new(tls), service)
+                        .await
+                        .map_err(|_| ())
+                });
+            }
         }
     }
 }
 
+/// Rejects requests with a body over `max_body_size` bytes with 413 and a JSON error. A
+/// Content-Length over the limit is refused before reading anything, any other body, chunked
+/// ones included, is read through `Limited` and refused once it grows past the limit. Accepted
+/// bodies are handed on already read, the generated handlers buffer them whole anyway.
+struct BodyLimit<S> {
+    inner: Arc<S>,
+    max_body_size: u64,
+}
+
+impl<S> BodyLimit<S> {
+    fn new(inner: S, max_body_size: u64) -> Self {
+        Self {
+            inner: Arc::new(inner),
+            max_body_size,
+        }
+    }
+
+    fn declared_too_large(&self, request: &Request<Incoming>) -> bool {
+        request
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok())
+            .is_some_and(|length| length > self.max_body_size)
+    }
+}
+
+fn json_error<B>(status: StatusCode, message: String) -> Response<Either<B, Full<Bytes>>> {
+    let body = serde_json::to_string(&models::Error {
+        message,
+        context: None,
+    })
+    .unwrap_or_default();
+    let mut response = Response::new(Either::Right(Full::new(Bytes::from(body))));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    response
+}
+
+impl<S, B> Service<Request<Incoming>> for BodyLimit<S>
+where
+    S: Service<Request<Full<Bytes>>, Response = Response<B>> + Send + Sync + 'static,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+    B: Send + 'static,
+{
+    type Response = Response<Either<B, Full<Bytes>>>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, S::Error>> + Send>>;
+
+    fn call(&self, request: Request<Incoming>) -> Self::Future {
+        let max_body_size = self.max_body_size;
+        let too_large = move || {
+            json_error(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("Request body is larger than {} bytes", max_body_size),
+            )
+        };
+        if self.declared_too_large(&request) {
+            warn!(
+                "Rejected request {} {}: body over {} bytes",
+                request.method(),
+                request.uri(),
+                max_body_size
+            );
+            return Box::pin(async move { Ok(too_large()) });
+        }
+
+        let inner = self.inner.clone();
+        Box::pin(async move {
+            let (parts, body) = request.into_parts();
+            let limit = usize::try_from(max_body_size).unwrap_or(usize::MAX);
+            let body = match Limited::new(body, limit).collect().await {
+                Ok(collected) => collected.to_bytes(),
+                Err(e) if e.is::<LengthLimitError>() => {
+                    warn!("Rejected request: body over {} bytes", max_body_size);
+                    return Ok(too_large());
+                }
+                Err(e) => {
+                    return Ok(json_error(
+                        StatusCode::BAD_REQUEST,
+                        format!("Failed to read request body: {}", e),
+                    ));
+                }
+            };
+            let response = inner
+                .call(Request::from_parts(parts, Full::new(body)))
+                .await?;
+            Ok(response.map(Either::Left))
+        })
+    }
+}
+
 #[derive(Clone)]
 pub struct Server {
     version: String,
@@ -131,11 +336,17 @@ pub struct Server {
     author: String,
     start_time: Instant,
     metastore: Arc<RwLock<Metastore>>,
-    query_queue: mpsc::Sender<String>,
+    query_queue: mpsc::Sender<(String, QueryPriority)>,
+    /// Most rows returned in one part of a query result, unlimited when `None`.
+    result_batch_rows: Option<NonZeroUsize>,
 }
 
 impl Server {
-    pub fn new(metastore: SharedMetastore, query_queue: mpsc::Sender<String>) -> Self {
+    pub fn new(
+        metastore: SharedMetastore,
+        query_queue: mpsc::Sender<(String, QueryPriority)>,
+        result_batch_rows: Option<NonZeroUsize>,
+    ) -> Self {
         Server {
             version: SERVER_VERSION.to_string(),
             interface_version: INTERFACE_VERSION.to_string(),
@@ -143,6 +354,7 @@ impl Server {
             start_time: Instant::now(),
             metastore,
             query_queue,
+            result_batch_rows,
         }
     }
 }
@@ -151,10 +363,29 @@ impl From<metastore::Error> for models::Error {
     fn from(value: metastore::Error) -> Self {
         Self {
             message: value.message,
+            context: value.context,
         }
     }
 }
 
+fn multiple_problems(errors: Vec<metastore::Error>) -> MultipleProblemsError {
+    MultipleProblemsError {
+        problems: errors
+            .into_iter()
+            .map(|error| MultipleProblemsErrorProblemsInner {
+                error: error.message,
+                context: error.context,
+            })
+            .collect(),
+    }
+}
+
+/// Errors that handler doesn't expect from the metastore are a bug on the server side.
+fn unexpected_error(endpoint: &str, error: MetastoreError) -> ApiError {
+    error!("API: {} | Unexpected error: {:?}", endpoint, error);
+    ApiError("Internal server error".to_string())
+}
+
 #[async_trait]
 impl<C> Api<C> for Server
 where
@@ -177,20 +408,82 @@ where
     ) -> Result<GetTableByIdResponse, ApiError> {
         info!("API: get_table_by_id | Starting processing");
 
+        metastore::load_tables(&self.metastore, std::slice::from_ref(&table_id))
+            .await
+            .map_err(|e| unexpected_error("get_table_by_id", e))?;
+
         let table = self.metastore.read().await.get_table(&table_id);
         match table {
             Ok(table) => {
                 info!("API: get_table_by_id | Success | TableID: {}", table_id);
                 Ok(GetTableByIdResponse::DetailedTableDescription(table))
             }
-            Err(MetastoreError::TableAccessError(error)) => {
+            Err(MetastoreError::TableNotFound(error)) => {
                 warn!(
                     "API: get_table_by_id | Failed | TableID: {} | Error: {:?}",
                     table_id, error
                 );
                 Ok(GetTableByIdResponse::GenericError(error.into()))
             }
-            _ => Err(ApiError("Internal server error".to_string())),
+            Err(error) => Err(unexpected_error("get_table_by_id", error)),
+        }
+    }
+
+    /// Get IDs of queries holding selected table, a table scheduled for deletion is removed only when none are left
+    async fn get_table_accesses(
+        &self,
+        table_id: String,
+        _: &C,
+    ) -> Result<GetTableAccessesResponse, ApiError> {
+        info!("API: get_table_accesses | Starting processing");
+
+        match self.metastore.read().await.queries_accessing(&table_id) {
+            Ok(query_ids) => {
+                info!("API: get_table_accesses | Success | TableID: {}", table_id);
+                Ok(GetTableAccessesResponse::ListOfQueriesAccessingTheTable(
+                    query_ids,
+                ))
+            }
+            Err(MetastoreError::TableNotFound(error)) => {
+                warn!(
+                    "API: get_table_accesses | Failed | TableID: {} | Error: {:?}",
+                    table_id, error
+                );
+                Ok(GetTableAccessesResponse::GenericError(error.into()))
+            }
+            Err(error) => Err(unexpected_error("get_table_accesses", error)),
+        }
+    }
+
+    /// Free memory held by selected table, its data is written to disk if needed and read back on next access
+    async fn unload_table(&self, table_id: String, _: &C) -> Result<UnloadTableResponse, ApiError> {
+        info!("API: unload_table | Starting processing");
+
+        let result = self
+            .metastore
+            .write()
+            .await
+            .force_unload_table(&table_id, &lib::Serializer::new());
+        match result {
+            Ok(()) => {
+                info!("API: unload_table | Success | TableID: {}", table_id);
+                Ok(UnloadTableResponse::TableHasBeenUnloadedFromMemory)
+            }
+            Err(MetastoreError::TableNotFound(error)) => {
+                warn!(
+                    "API: unload_table | Failed | TableID: {} | Error: {:?}",
+                    table_id, error
+                );
+                Ok(UnloadTableResponse::GenericError(error.into()))
+            }
+            Err(MetastoreError::TableUnloadError(error)) => {
+                warn!(
+                    "API: unload_table | Failed | TableID: {} | Error: {:?}",
+                    table_id, error
+                );
+                Ok(UnloadTableResponse::GenericError_2(error.into()))
+            }
+            Err(error) => Err(unexpected_error("unload_table", error)),
         }
     }
 
@@ -203,14 +496,14 @@ where
                 info!("API: delete_table | Success | TableID: {}", table_id);
                 Ok(DeleteTableResponse::TableHasBeenDeletedSuccessfully)
             }
-            Err(MetastoreError::TableDeletionError(error)) => {
+            Err(MetastoreError::TableNotFound(error)) => {
                 warn!(
                     "API: delete_table | Failed | TableID: {} | Error: {:?}",
                     table_id, error
                 );
                 Ok(DeleteTableResponse::GenericError(error.into()))
             }
-            _ => Err(ApiError("Internal server error".to_string())),
+            Err(error) => Err(unexpected_error("delete_table", error)),
         }
     }
 
@@ -228,28 +521,184 @@ where
                 Ok(CreateTableResponse::TableCreatedSuccessfully(id))
             }
             Err(MetastoreError::TableCreationError(errors)) => {
-                let problems = errors
-                    .iter()
-                    .map(|error| MultipleProblemsErrorProblemsInner {
-                        error: error.message.clone(),
-                        context: error.context.clone(),
-                    })
-                    .collect();
-                let e = MultipleProblemsError { problems };
+                let e = multiple_problems(errors);
                 warn!("API: create_table | Failed | Error: {:?}", e);
                 Ok(CreateTableResponse::ResponseUsedWhenMoreProblemsCanOccurInTheSystemWhenProcessingRequest(e))
             }
-            _ => Err(ApiError("Internal server error".to_string())),
+            Err(error) => Err(unexpected_error("create_table", error)),
+        }
+    }
+
+    /// Create a new table holding a copy of selected table, later changes to either of them don't affect the other
+    async fn duplicate_table(
+        &self,
+        table_id: String,
+        table_duplicate: models::TableDuplicate,
+        _: &C,
+    ) -> Result<DuplicateTableResponse, ApiError> {
+        info!("API: duplicate_table | Starting processing");
+
+        metastore::load_tables(&self.metastore, std::slice::from_ref(&table_id))
+            .await
+            .map_err(|e| unexpected_error("duplicate_table", e))?;
+
+        match self
+            .metastore
+            .write()
+            .await
+            .duplicate_table(&table_id, &table_duplicate.name)
+        {
+            Ok(id) => {
+                info!(
+                    "API: duplicate_table | Success | TableID: {} | NewTableID: {}",
+                    table_id, id
+                );
+                Ok(DuplicateTableResponse::TableCreatedSuccessfully(id))
+            }
+            Err(MetastoreError::TableNotFound(error)) => {
+                warn!(
+                    "API: duplicate_table | Failed | TableID: {} | Error: {:?}",
+                    table_id, error
+                );
+                Ok(DuplicateTableResponse::GenericError(error.into()))
+            }
+            Err(MetastoreError::TableCreationError(errors)) => {
+                let e = multiple_problems(errors);
+                warn!("API: duplicate_table | Failed | Error: {:?}", e);
+                Ok(DuplicateTableResponse::ResponseUsedWhenMoreProblemsCanOccurInTheSystemWhenProcessingRequest(e))
+            }
+            Err(error) => Err(unexpected_error("duplicate_table", error)),
+        }
+    }
+
+    /// Add a column to selected table, existing rows get its default value
+    async fn add_column(
+        &self,
+        table_id: String,
+        column: models::Column,
+        _: &C,
+    ) -> Result<AddColumnResponse, ApiError> {
+        info!("API: add_column | Starting processing");
+
+        metastore::load_tables(&self.metastore, std::slice::from_ref(&table_id))
+            .await
+            .map_err(|e| unexpected_error("add_column", e))?;
+
+        match self.metastore.write().await.add_column(&table_id, &column) {
+            Ok(()) => {
+                info!(
+                    "API: add_column | Success | TableID: {} | Column: {}",
+                    table_id, column.name
+                );
+                Ok(AddColumnResponse::ColumnHasBeenAddedSuccessfully)
+            }
+            Err(MetastoreError::TableNotFound(error)) => {
+                warn!(
+                    "API: add_column | Failed | TableID: {} | Error: {:?}",
+                    table_id, error
+                );
+                Ok(AddColumnResponse::GenericError(error.into()))
+            }
+            Err(MetastoreError::ColumnChangeError(error)) => {
+                warn!(
+                    "API: add_column | Failed | TableID: {} | Error: {:?}",
+                    table_id, error
+                );
+                Ok(AddColumnResponse::GenericError_2(error.into()))
+            }
+            Err(error) => Err(unexpected_error("add_column", error)),
+        }
+    }
+
+    /// Change a single column of selected table, data of the column is kept
+    async fn update_column(
+        &self,
+        table_id: String,
+        column_name: String,
+        column_change: models::ColumnChange,
+        _: &C,
+    ) -> Result<UpdateColumnResponse, ApiError> {
+        info!("API: update_column | Starting processing");
+
+        metastore::load_tables(&self.metastore, std::slice::from_ref(&table_id))
+            .await
+            .map_err(|e| unexpected_error("update_column", e))?;
+
+        let result =
+            self.metastore
+                .write()
+                .await
+                .update_column(&table_id, &column_name, &column_change);
+
+        match result {
+            Ok(()) => {
+                info!(
+                    "API: update_column | Success | TableID: {} | Column: {}",
+                    table_id, column_name
+                );
+                Ok(UpdateColumnResponse::ColumnHasBeenChangedSuccessfully)
+            }
+            Err(MetastoreError::TableNotFound(error)) => {
+                warn!(
+                    "API: update_column | Failed | TableID: {} | Error: {:?}",
+                    table_id, error
+                );
+                Ok(UpdateColumnResponse::GenericError(error.into()))
+            }
+            Err(MetastoreError::ColumnChangeError(error)) => {
+                warn!(
+                    "API: update_column | Failed | TableID: {} | Error: {:?}",
+                    table_id, error
+                );
+                Ok(UpdateColumnResponse::GenericError_2(error.into()))
+            }
+            Err(error) => Err(unexpected_error("update_column", error)),
+        }
+    }
+
+    /// Register existing ISDB files as partitions of a single table, split by an INT64 column
+    async fn create_partitioned_table(
+        &self,
+        partitioned_table_schema: models::PartitionedTableSchema,
+        _: &C,
+    ) -> Result<CreatePartitionedTableResponse, ApiError> {
+        info!("API: create_partitioned_table | Starting processing");
+
+        let name = partitioned_table_schema.name.clone();
+        match self
+            .metastore
+            .write()
+            .await
+            .create_partitioned_table(partitioned_table_schema)
+        {
+            Ok(()) => {
+                info!("API: create_partitioned_table | Success | Name: {}", name);
+                Ok(CreatePartitionedTableResponse::PartitionedTableRegisteredSuccessfully)
+            }
+            Err(MetastoreError::TableCreationError(errors)) => {
+                let e = multiple_problems(errors);
+                warn!("API: create_partitioned_table | Failed | Error: {:?}", e);
+                Ok(CreatePartitionedTableResponse::ResponseUsedWhenMoreProblemsCanOccurInTheSystemWhenProcessingRequest(e))
+            }
+            Err(error) => Err(unexpected_error("create_partitioned_table", error)),
         }
     }
 
     /// Get list of queries (optional in project 3, but useful). Use those IDs to get details by calling /query endpoint.
-    async fn get_queries(&self, _: &C) -> Result<GetQueriesResponse, ApiError> {
+    async fn get_queries(
+        &self,
+        label: Option<String>,
+        tag: Option<String>,
+        _: &C,
+    ) -> Result<GetQueriesResponse, ApiError> {
         info!("API: get_queries | Starting processing");
 
         info!("API: get_queries  | Success");
         Ok(GetQueriesResponse::ArrayOfQueriesSubmittedToTheSystem(
-            self.metastore.read().await.get_queries(),
+            self.metastore
+                .read()
+                .await
+                .get_queries(label.as_deref(), tag.as_deref()),
         ))
     }
 
@@ -266,11 +715,11 @@ where
                 info!("API: get_query_by_id | Success | QueryID {:}", query_id);
                 Ok(GetQueryByIdResponse::DetailedQueryDescription(query))
             }
-            Err(MetastoreError::QueryAccessError(error)) => {
+            Err(MetastoreError::QueryNotFound(error)) => {
                 warn!("API: get_query_by_id | Failed | Error: {:?}", error);
                 Ok(GetQueryByIdResponse::GenericError(error.into()))
             }
-            _ => Err(ApiError("Internal server error".to_string())),
+            Err(error) => Err(unexpected_error("get_query_by_id", error)),
         }
     }
 
@@ -278,37 +727,95 @@ where
     async fn submit_query(
         &self,
         execute_query_request: ExecuteQueryRequest,
-        _: &C,
+        context: &C,
     ) -> Result<SubmitQueryResponse, ApiError> {
         info!("API: submit_query | Starting processing");
 
-        let mut metastore_guard = self.metastore.write().await;
+        let span_id = Has::<XSpanIdString>::get(context).0.clone();
+
         let query_def = execute_query_request.query_definition;
-        let result = match &*query_def {
-            OneOf3::A(select_all) => metastore_guard.create_select_all_query(select_all),
-            OneOf3::B(select) => metastore_guard.create_select_query(select),
-            OneOf3::C(copy) => metastore_guard.create_copy_query(copy),
+        // Partition files are read before taking the write lock.
+        let partitions = match &*query_def {
+            OneOf5::B(select) => metastore::read_partitions(&self.metastore, select).await,
+            _ => Ok(None),
+        };
+
+        let mut metastore_guard = self.metastore.write().await;
+        let result = match (&*query_def, partitions) {
+            (_, Err(e)) => Err(e),
+            (OneOf5::A(select_all), _) => metastore_guard.create_select_all_query(select_all),
+            (OneOf5::B(select), Ok(partitions)) => {
+                metastore_guard.create_select_query(select, partitions)
+            }
+            (OneOf5::C(copy), _) => metastore_guard.create_copy_query(copy),
+            (OneOf5::D(union), _) => metastore_guard.create_union_query(union),
+            (OneOf5::E(copy), _) => metastore_guard.create_table_copy_query(copy),
         };
 
         match result {
             Ok(id) => {
-                let _ = self.query_queue.send(id.clone()).await;
-                info!("API: submit_query | Success | QueryID: {}", id);
+                metastore_guard.label_query(
+                    &id,
+                    execute_query_request.label,
+                    execute_query_request.tags.unwrap_or_default(),
+                );
+                metastore_guard.trace_query(&id, span_id.clone());
+                let priority = metastore_guard
+                    .query_priority(&id)
+                    .expect("query was just created");
+                let _ = self.query_queue.send((id.clone(), priority)).await;
+                info!(
+                    "API: submit_query | Success | QueryID: {} | SpanID: {}",
+                    id, span_id
+                );
                 Ok(SubmitQueryResponse::QueryHasBeenCreatedSuccessfully(id))
             }
             Err(MetastoreError::QueryCreationError(errors)) => {
-                let problems = errors
-                    .iter()
-                    .map(|error| MultipleProblemsErrorProblemsInner {
-                        error: error.message.clone(),
-                        context: error.context.clone(),
-                    })
-                    .collect();
-                let e = MultipleProblemsError { problems };
+                let e = multiple_problems(errors);
                 warn!("API: submit_query | Failed | Error: {:?}", e);
                 Ok(SubmitQueryResponse::ResponseUsedWhenMoreProblemsCanOccurInTheSystemWhenProcessingRequest(e))
             }
-            _ => Err(ApiError("Internal server error".to_string())),
+            Err(error) => Err(unexpected_error("submit_query", error)),
+        }
+    }
+
+    /// Submit a new query with the definition of selected completed or failed query, the original query is left untouched
+    async fn rerun_query(
+        &self,
+        query_id: String,
+        context: &C,
+    ) -> Result<RerunQueryResponse, ApiError> {
+        info!("API: rerun_query | Starting processing");
+
+        let span_id = Has::<XSpanIdString>::get(context).0.clone();
+
+        let mut metastore_guard = self.metastore.write().await;
+        match metastore_guard.rerun_query(&query_id) {
+            Ok(id) => {
+                metastore_guard.trace_query(&id, span_id.clone());
+                let priority = metastore_guard
+                    .query_priority(&id)
+                    .expect("query was just created");
+                let _ = self.query_queue.send((id.clone(), priority)).await;
+                info!(
+                    "API: rerun_query | Success | QueryID: {} | NewQueryID: {} | SpanID: {}",
+                    query_id, id, span_id
+                );
+                Ok(RerunQueryResponse::QueryHasBeenCreatedSuccessfully(id))
+            }
+            Err(MetastoreError::QueryNotFound(error)) => {
+                warn!(
+                    "API: rerun_query | Failed | QueryID: {} | Error: {:?}",
+                    query_id, error
+                );
+                Ok(RerunQueryResponse::GenericError(error.into()))
+            }
+            Err(MetastoreError::QueryCreationError(errors)) => {
+                let e = multiple_problems(errors);
+                warn!("API: rerun_query | Failed | Error: {:?}", e);
+                Ok(RerunQueryResponse::ResponseUsedWhenMoreProblemsCanOccurInTheSystemWhenProcessingRequest(e))
+            }
+            Err(error) => Err(unexpected_error("rerun_query", error)),
         }
     }
 
@@ -325,21 +832,29 @@ where
             Some(r) => r.row_limit,
             None => None,
         };
-        let flush_result = match get_query_result_request {
+        let flush_result = match get_query_result_request.as_ref() {
             Some(r) => r.flush_result.unwrap_or(false),
             None => false,
         };
+        let predicate = get_query_result_request.and_then(|r| r.predicate);
 
+        metastore::load_query_results(&self.metastore, &query_id)
+            .await
+            .map_err(|e| unexpected_error("get_query_result", e))?;
         let result = if flush_result {
-            self.metastore
-                .write()
-                .await
-                .get_query_result_flush(&query_id, row_limit)
+            self.metastore.write().await.get_query_result_flush(
+                &query_id,
+                row_limit,
+                predicate.as_ref(),
+                self.result_batch_rows,
+            )
         } else {
-            self.metastore
-                .read()
-                .await
-                .get_query_result(&query_id, row_limit)
+            self.metastore.read().await.get_query_result(
+                &query_id,
+                row_limit,
+                predicate.as_ref(),
+                self.result_batch_rows,
+            )
         };
 
         match result {
@@ -349,7 +864,7 @@ where
                     QueryResult::from(res),
                 ))
             }
-            Err(MetastoreError::QueryAccessError(error)) => {
+            Err(MetastoreError::QueryNotFound(error)) => {
                 warn!("API: get_query_result | Failed | Error: {:?}", error);
                 Ok(GetQueryResultResponse::GenericError(error.into()))
             }
@@ -357,7 +872,48 @@ where
                 warn!("API: get_query_result | Failed | Error: {:?}", error);
                 Ok(GetQueryResultResponse::GenericError_2(error.into()))
             }
-            _ => Err(ApiError("Internal server error".to_string())),
+            Err(error) => Err(unexpected_error("get_query_result", error)),
+        }
+    }
+
+    /// Get a single column of the result of selected query. Returned result contains one column.
+    async fn get_query_result_column(
+        &self,
+        query_id: String,
+        column_name: String,
+        row_limit: Option<i32>,
+        _: &C,
+    ) -> Result<GetQueryResultColumnResponse, ApiError> {
+        info!("API: get_query_result_column | Starting processing");
+
+        metastore::load_query_results(&self.metastore, &query_id)
+            .await
+            .map_err(|e| unexpected_error("get_query_result_column", e))?;
+        let result =
+            self.metastore
+                .read()
+                .await
+                .get_query_result_column(&query_id, &column_name, row_limit);
+
+        match result {
+            Ok(res) => {
+                info!(
+                    "API: get_query_result_column | Success | QueryID: {} | Column: {}",
+                    query_id, column_name
+                );
+                Ok(GetQueryResultColumnResponse::ResultOfSelectedQuery(
+                    QueryResult::from(vec![res]),
+                ))
+            }
+            Err(MetastoreError::QueryNotFound(error)) => {
+                warn!("API: get_query_result_column | Failed | Error: {:?}", error);
+                Ok(GetQueryResultColumnResponse::GenericError(error.into()))
+            }
+            Err(MetastoreError::QueryResultAccessError(error)) => {
+                warn!("API: get_query_result_column | Failed | Error: {:?}", error);
+                Ok(GetQueryResultColumnResponse::GenericError_2(error.into()))
+            }
+            Err(error) => Err(unexpected_error("get_query_result_column", error)),
         }
     }
 
@@ -383,7 +939,7 @@ where
                 info!("API: get_query_error | Success | QueryID: {}", query_id);
                 Ok(GetQueryErrorResponse::ResponseUsedWhenMoreProblemsCanOccurInTheSystemWhenProcessingRequest(e))
             }
-            Err(MetastoreError::QueryAccessError(error)) => {
+            Err(MetastoreError::QueryNotFound(error)) => {
                 warn!("API: get_query_error | Failed | Error: {:?}", error);
                 Ok(GetQueryErrorResponse::GenericError(error.into()))
             }
@@ -391,7 +947,7 @@ where
                 warn!("API: get_query_error | Success | Error: {:?}", error);
                 Ok(GetQueryErrorResponse::GenericError_2(error.into()))
             }
-            _ => Err(ApiError("Internal server error".to_string())),
+            Err(error) => Err(unexpected_error("get_query_error", error)),
         }
     }
 
@@ -409,4 +965,96 @@ where
             },
         ))
     }
+
+    /// Get counts of tables and queries currently kept by the system
+    async fn get_system_stats(&self, _: &C) -> Result<GetSystemStatsResponse, ApiError> {
+        info!("API: get_system_stats | Starting processing");
+
+        let stats = self.metastore.read().await.stats();
+
+        info!("API: get_system_stats | Success");
+        Ok(GetSystemStatsResponse::CountsOfTablesAndQueries(
+            stats.into(),
+        ))
+    }
+
+    /// Write tables changed since the last save and the metastore file right away, instead of waiting for shutdown
+    async fn persist_metastore(&self, _: &C) -> Result<PersistMetastoreResponse, ApiError> {
+        info!("API: persist_metastore | Starting processing");
+
+        match metastore::save_metastore(
+            self.metastore.clone(),
+            METASTORE_FILE,
+            &lib::Serializer::new(),
+        )
+        .await
+        {
+            Ok(written) => {
+                info!(
+                    "API: persist_metastore | Success | Tables written: {}",
+                    written
+                );
+                Ok(PersistMetastoreResponse::NumberOfTableFilesWritten(
+                    written as i64,
+                ))
+            }
+            Err(e) => {
+                error!("API: persist_metastore | Failed | Error: {}", e);
+                Ok(PersistMetastoreResponse::GenericError(models::Error {
+                    message: format!("Failed to save metastore: {}", e),
+                    context: None,
+                }))
+            }
+        }
+    }
+
+    /// Rewrite table files one at a time to drop space left by overwrites. Tables read by a running query are skipped
+    async fn compact_tables(&self, _: &C) -> Result<CompactTablesResponse, ApiError> {
+        info!("API: compact_tables | Starting processing");
+
+        match metastore::compact_all(self.metastore.clone(), &lib::Serializer::new()).await {
+            Ok(reclaimed) => {
+                info!(
+                    "API: compact_tables | Success | Bytes reclaimed: {}",
+                    reclaimed
+                );
+                Ok(CompactTablesResponse::NumberOfBytesReclaimed(
+                    reclaimed as i64,
+                ))
+            }
+            Err(e) => {
+                error!("API: compact_tables | Failed | Error: {}", e);
+                Ok(CompactTablesResponse::GenericError(models::Error {
+                    message: format!("Failed to compact tables: {}", e),
+                    context: None,
+                }))
+            }
+        }
+    }
+
+    /// Check whether selected table exists without fetching its description
+    async fn table_exists(&self, table_id: String, _: &C) -> Result<TableExistsResponse, ApiError> {
+        info!("API: table_exists | Starting processing");
+
+        if self.metastore.read().await.table_exists(&table_id) {
+            info!("API: table_exists | Success | TableID: {}", table_id);
+            Ok(TableExistsResponse::TableExists)
+        } else {
+            info!("API: table_exists | Not found | TableID: {}", table_id);
+            Ok(TableExistsResponse::TableNotFound)
+        }
+    }
+
+    /// Check whether selected query exists without fetching its description
+    async fn query_exists(&self, query_id: String, _: &C) -> Result<QueryExistsResponse, ApiError> {
+        info!("API: query_exists | Starting processing");
+
+        if self.metastore.read().await.query_exists(&query_id) {
+            info!("API: query_exists | Success | QueryID: {}", query_id);
+            Ok(QueryExistsResponse::QueryExists)
+        } else {
+            info!("API: query_exists | Not found | QueryID: {}", query_id);
+            Ok(QueryExistsResponse::QueryNotFound)
+        }
+    }
 }
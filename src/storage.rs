@@ -0,0 +1,96 @@
+use std::{
+    fmt::Debug,
+    fs::{self, File, OpenOptions},
+    io::{Error, ErrorKind, Read, Seek, Write},
+    path::Path,
+    time::SystemTime,
+};
+
+pub trait ReadSeek: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send + ?Sized> ReadSeek for T {}
+
+pub trait WriteSeek: Write + Seek + Send {}
+
+impl<T: Write + Seek + Send + ?Sized> WriteSeek for T {}
+
+/// Where a `Serializer` keeps its files. Paths are only passed through, so a backend is free
+/// to treat them as keys. Written data has to be readable once the writer is flushed.
+pub trait StorageBackend: Debug + Send + Sync {
+    fn open_read(&self, path: &Path) -> Result<Box<dyn ReadSeek>, Error>;
+
+    /// Unless `overwrite` is set, an existing file is left alone and `AlreadyExists` returned.
+    fn create_write(&self, path: &Path, overwrite: bool) -> Result<Box<dyn WriteSeek>, Error>;
+
+    fn exists(&self, path: &Path) -> bool;
+
+    fn remove(&self, path: &Path) -> Result<(), Error>;
+
+    /// Replaces `to` with `from` in a single step.
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), Error>;
+
+    /// Identifies the current contents of the file, cached headers of a file are dropped when it
+    /// changes. Backends without one never serve headers from the cache.
+    fn stamp(&self, _path: &Path) -> Result<FileStamp, Error> {
+        Err(Error::from(ErrorKind::Unsupported))
+    }
+}
+
+/// Modification time and size of a file. A rewrite within the mtime granularity of the
+/// filesystem keeps the time, but a different header changes the size in practice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStamp {
+    pub modified: SystemTime,
+    pub len: u64,
+}
+
+/// Files on the local filesystem, the default backend.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalFs;
+
+impl StorageBackend for LocalFs {
+    fn open_read(&self, path: &Path) -> Result<Box<dyn ReadSeek>, Error> {
+        Ok(Box::new(File::open(path)?))
+    }
+
+    /// Creates `path` along with any missing parent directories, so a fresh deployment without
+    /// a tables directory can still be written to. Errors name the path that could not be
+    /// created.
+    fn create_write(&self, path: &Path, overwrite: bool) -> Result<Box<dyn WriteSeek>, Error> {
+        let with_path = |e: Error| Error::new(e.kind(), format!("{}: {}", path.display(), e));
+
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent).map_err(with_path)?;
+        }
+        let file = OpenOptions::new()
+            .write(true)
+            .create(overwrite)
+            .truncate(overwrite)
+            .create_new(!overwrite)
+            .open(path)
+            .map_err(with_path)?;
+        Ok(Box::new(file))
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn remove(&self, path: &Path) -> Result<(), Error> {
+        fs::remove_file(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        fs::rename(from, to)
+    }
+
+    fn stamp(&self, path: &Path) -> Result<FileStamp, Error> {
+        let metadata = fs::metadata(path)?;
+        Ok(FileStamp {
+            modified: metadata.modified()?,
+            len: metadata.len(),
+        })
+    }
+}
@@ -1,5 +1,14 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use crate::consts::{FILE_EXTENSION, TABLES_DIR};
 
 pub fn convert_to_table_file_table(table_id: &String) -> String {
     format!("{}/{}.{}", TABLES_DIR, table_id, FILE_EXTENSION)
 }
+
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}